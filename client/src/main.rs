@@ -145,6 +145,26 @@ pub async fn main() -> Result<(), Box<dyn StdError>> {
                         .arg(&required_property_name_arg),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("index")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("indexes a property")
+                        .arg(&required_property_name_arg),
+                )
+                .subcommand(
+                    SubCommand::with_name("remove")
+                        .about("stops indexing a property")
+                        .arg(&required_property_name_arg),
+                )
+                .subcommand(SubCommand::with_name("list").about("lists the indexed properties")),
+        )
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("runs a query written in the text syntax, e.g. 'V(type=person).out(follows).limit(10)'")
+                .arg(Arg::with_name("query").help("the query text").required(true)),
+        )
         .get_matches();
 
     run(matches).await
@@ -252,6 +272,22 @@ async fn run(matches: clap::ArgMatches<'_>) -> Result<(), Box<dyn StdError>> {
                 .name(property_name);
             client.delete(q).await?;
         }
+    } else if let Some(matches) = matches.subcommand_matches("index") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            let property_name = Identifier::new(matches.value_of("name").unwrap())?;
+            client.index_property(property_name).await?;
+        } else if let Some(matches) = matches.subcommand_matches("remove") {
+            let property_name = Identifier::new(matches.value_of("name").unwrap())?;
+            client.unindex_property(property_name).await?;
+        } else if matches.subcommand_matches("list").is_some() {
+            for property_name in client.indexed_properties().await? {
+                println!("{}", property_name.as_str());
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("query") {
+        for value in client.get_text(matches.value_of("query").unwrap()).await? {
+            println!("{value:?}");
+        }
     }
 
     Ok(())
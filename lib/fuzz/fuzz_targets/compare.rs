@@ -16,6 +16,7 @@ pub enum Op {
     CreateEdge(Edge),
     SetProperties(Query, Identifier, Json),
     IndexProperty(Identifier),
+    UnindexProperty(Identifier),
 }
 
 #[derive(Arbitrary, Clone, Debug, PartialEq)]
@@ -573,6 +574,11 @@ fuzz_target!(|ops: Vec<Op>| {
                 let v2 = d2.index_property(t.into());
                 cmp!(v1, v2);
             }
+            Op::UnindexProperty(t) => {
+                let v1 = d1.unindex_property(t.clone().into());
+                let v2 = d2.unindex_property(t.into());
+                cmp!(v1, v2);
+            }
         }
     }
 });
@@ -0,0 +1,308 @@
+use std::collections::{HashSet, VecDeque};
+use std::slice;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{BulkInsertItem, Edge, Identifier, Json, SchemaKind, Vertex};
+
+use uuid::Uuid;
+
+/// A change made through a `Database`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeEvent {
+    /// A vertex was created.
+    VertexCreated(Vertex),
+    /// A vertex was deleted. The properties and edges deleted along with it
+    /// have their own events, which come before this one.
+    VertexDeleted(Vertex),
+    /// An edge was created.
+    EdgeCreated(Edge),
+    /// An edge was deleted. The properties deleted along with it have their
+    /// own events, which come before this one.
+    EdgeDeleted(Edge),
+    /// A vertex property was set.
+    VertexPropertySet(Uuid, Identifier, Json),
+    /// A vertex property was deleted.
+    VertexPropertyDeleted(Uuid, Identifier),
+    /// An edge property was set.
+    EdgePropertySet(Edge, Identifier, Json),
+    /// An edge property was deleted.
+    EdgePropertyDeleted(Edge, Identifier),
+    /// A property was indexed.
+    PropertyIndexed(Identifier),
+    /// A property was indexed with a unique index.
+    UniquePropertyIndexed(Identifier),
+    /// A composite index was added over an ordered list of properties.
+    CompositePropertyIndexed(Vec<Identifier>),
+    /// A property's index was removed.
+    PropertyUnindexed(Identifier),
+    /// The schema for vertices or edges of a type was set or removed.
+    SchemaChanged(SchemaKind, Identifier),
+}
+
+impl From<&BulkInsertItem> for ChangeEvent {
+    fn from(item: &BulkInsertItem) -> Self {
+        match item {
+            BulkInsertItem::Vertex(vertex) | BulkInsertItem::ExpiringVertex(vertex, _) => {
+                ChangeEvent::VertexCreated(vertex.clone())
+            }
+            BulkInsertItem::Edge(edge) | BulkInsertItem::ExpiringEdge(edge, _) => {
+                ChangeEvent::EdgeCreated(edge.clone())
+            }
+            BulkInsertItem::VertexProperty(id, name, value) => {
+                ChangeEvent::VertexPropertySet(*id, *name, value.clone())
+            }
+            BulkInsertItem::EdgeProperty(edge, name, value) => {
+                ChangeEvent::EdgePropertySet(edge.clone(), *name, value.clone())
+            }
+        }
+    }
+}
+
+/// Selects a subset of changes, e.g. for watching them over gRPC. Each
+/// non-empty set narrows down the matching changes, and changes that don't
+/// carry the information a set filters on don't match it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChangeFilter {
+    /// Matches changes to these vertices and their properties, as well as
+    /// changes to edges going to or from them and to those edges' properties.
+    pub vertex_ids: HashSet<Uuid>,
+    /// Matches vertices of these types being created or deleted, and changes
+    /// to the schemas for them.
+    pub vertex_types: HashSet<Identifier>,
+    /// Matches changes to edges of these types, to their properties, and to
+    /// the schemas for them.
+    pub edge_types: HashSet<Identifier>,
+    /// Matches changes to properties with these names, and to their indexes.
+    pub property_names: HashSet<Identifier>,
+}
+
+impl ChangeFilter {
+    /// Returns whether a change matches the filter.
+    ///
+    /// # Arguments
+    /// * `event`: The change.
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        let (ids, vertex_t, edge_t, names) = match event {
+            ChangeEvent::VertexCreated(vertex) | ChangeEvent::VertexDeleted(vertex) => {
+                (vec![vertex.id], Some(vertex.t), None, &[][..])
+            }
+            ChangeEvent::EdgeCreated(edge) | ChangeEvent::EdgeDeleted(edge) => {
+                (vec![edge.outbound_id, edge.inbound_id], None, Some(edge.t), &[][..])
+            }
+            ChangeEvent::VertexPropertySet(id, name, _) | ChangeEvent::VertexPropertyDeleted(id, name) => {
+                (vec![*id], None, None, slice::from_ref(name))
+            }
+            ChangeEvent::EdgePropertySet(edge, name, _) | ChangeEvent::EdgePropertyDeleted(edge, name) => (
+                vec![edge.outbound_id, edge.inbound_id],
+                None,
+                Some(edge.t),
+                slice::from_ref(name),
+            ),
+            ChangeEvent::PropertyIndexed(name)
+            | ChangeEvent::UniquePropertyIndexed(name)
+            | ChangeEvent::PropertyUnindexed(name) => (Vec::new(), None, None, slice::from_ref(name)),
+            ChangeEvent::CompositePropertyIndexed(names) => (Vec::new(), None, None, names.as_slice()),
+            ChangeEvent::SchemaChanged(SchemaKind::Vertex, t) => (Vec::new(), Some(*t), None, &[][..]),
+            ChangeEvent::SchemaChanged(SchemaKind::Edge, t) => (Vec::new(), None, Some(*t), &[][..]),
+        };
+
+        (self.vertex_ids.is_empty() || ids.iter().any(|id| self.vertex_ids.contains(id)))
+            && (self.vertex_types.is_empty() || matches!(vertex_t, Some(t) if self.vertex_types.contains(&t)))
+            && (self.edge_types.is_empty() || matches!(edge_t, Some(t) if self.edge_types.contains(&t)))
+            && (self.property_names.is_empty() || names.iter().any(|name| self.property_names.contains(name)))
+    }
+}
+
+/// A change event, along with its sequence number. Sequence numbers start
+/// at zero, and increase by one with each published event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeRecord {
+    /// The sequence number.
+    pub seq: u64,
+    /// The change event.
+    pub event: ChangeEvent,
+}
+
+/// Receives the changes made through a `Database`.
+pub trait ChangeSink: Send + Sync {
+    /// Receives the changes made by an operation, or by a committed
+    /// transaction. This is called in sequence order, and writers wait on
+    /// it, so it shouldn't block for long.
+    ///
+    /// # Arguments
+    /// * `records`: The changes.
+    fn receive(&self, records: &[ChangeRecord]);
+}
+
+/// A change sink that keeps the most recent changes in memory, so that
+/// consumers can read them from a given sequence number.
+pub struct RingBufferSink {
+    capacity: usize,
+    records: Mutex<VecDeque<ChangeRecord>>,
+}
+
+impl RingBufferSink {
+    /// Creates a new ring buffer sink.
+    ///
+    /// # Arguments
+    /// * `capacity`: The maximum number of changes to keep. Once it's
+    ///   reached, the oldest changes are evicted to make room for new ones.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Gets the buffered changes with sequence numbers at or after `seq`, in
+    /// order. If some of those changes have already been evicted, the
+    /// returned changes start at a later sequence number, which consumers can
+    /// check for to detect that they've missed changes.
+    ///
+    /// # Arguments
+    /// * `seq`: The sequence number to read from.
+    pub fn read_from(&self, seq: u64) -> Vec<ChangeRecord> {
+        let records = self.records.lock().unwrap();
+        match records.front() {
+            // Sequence numbers are contiguous, so there's no need to search.
+            Some(first) => {
+                let skipped = seq.saturating_sub(first.seq).try_into().unwrap_or(usize::MAX);
+                records.iter().skip(skipped).cloned().collect()
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+impl ChangeSink for RingBufferSink {
+    fn receive(&self, records: &[ChangeRecord]) {
+        let mut buffer = self.records.lock().unwrap();
+        buffer.extend(records.iter().cloned());
+        while buffer.len() > self.capacity {
+            buffer.pop_front();
+        }
+    }
+}
+
+// Assigns sequence numbers to change events, and publishes them to sinks.
+#[derive(Default)]
+pub(crate) struct ChangeFeed {
+    sinks: RwLock<Vec<Arc<dyn ChangeSink>>>,
+    next_seq: Mutex<u64>,
+}
+
+impl ChangeFeed {
+    pub(crate) fn add_sink(&self, sink: Arc<dyn ChangeSink>) {
+        self.sinks.write().unwrap().push(sink);
+    }
+
+    // Whether there are any sinks, so that callers can skip building events
+    // that nothing would receive.
+    pub(crate) fn is_active(&self) -> bool {
+        !self.sinks.read().unwrap().is_empty()
+    }
+
+    pub(crate) fn publish(&self, events: Vec<ChangeEvent>) {
+        if events.is_empty() {
+            return;
+        }
+
+        let sinks = self.sinks.read().unwrap();
+        // The lock is held while sinks receive the records, so that they're
+        // received in sequence order.
+        let mut next_seq = self.next_seq.lock().unwrap();
+        let records: Vec<ChangeRecord> = events
+            .into_iter()
+            .map(|event| {
+                let seq = *next_seq;
+                *next_seq += 1;
+                ChangeRecord { seq, event }
+            })
+            .collect();
+        for sink in sinks.iter() {
+            sink.receive(&records);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChangeEvent, ChangeFeed, ChangeFilter, RingBufferSink};
+    use crate::{ijson, Edge, Identifier, SchemaKind, Vertex};
+    use std::sync::Arc;
+
+    fn indexed(name: &str) -> ChangeEvent {
+        ChangeEvent::PropertyIndexed(Identifier::new(name).unwrap())
+    }
+
+    #[test]
+    fn should_read_from_sequence_number() {
+        let feed = ChangeFeed::default();
+        let sink = Arc::new(RingBufferSink::new(10));
+        feed.add_sink(sink.clone());
+        feed.publish(vec![indexed("a"), indexed("b")]);
+        feed.publish(vec![indexed("c")]);
+
+        let records = sink.read_from(1);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].seq, 1);
+        assert_eq!(records[0].event, indexed("b"));
+        assert_eq!(records[1].seq, 2);
+        assert_eq!(records[1].event, indexed("c"));
+        assert!(sink.read_from(3).is_empty());
+    }
+
+    #[test]
+    fn should_evict_oldest_changes() {
+        let feed = ChangeFeed::default();
+        let sink = Arc::new(RingBufferSink::new(2));
+        feed.add_sink(sink.clone());
+        feed.publish(vec![indexed("a"), indexed("b"), indexed("c")]);
+
+        let records = sink.read_from(0);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].seq, 1);
+        assert_eq!(records[1].seq, 2);
+    }
+
+    #[test]
+    fn should_filter_changes() -> Result<(), crate::Error> {
+        let person_t = Identifier::new("person")?;
+        let follows_t = Identifier::new("follows")?;
+        let name = Identifier::new("name")?;
+        let alice = Vertex::new(person_t);
+        let bob = Vertex::new(person_t);
+        let follows = Edge::new(alice.id, follows_t, bob.id);
+
+        let filter = ChangeFilter {
+            vertex_ids: [bob.id].into_iter().collect(),
+            edge_types: [follows_t].into_iter().collect(),
+            ..ChangeFilter::default()
+        };
+        assert!(filter.matches(&ChangeEvent::EdgeCreated(follows.clone())));
+        assert!(filter.matches(&ChangeEvent::EdgePropertySet(follows.clone(), name, ijson!(1))));
+        assert!(!filter.matches(&ChangeEvent::EdgeCreated(Edge::new(alice.id, follows_t, alice.id))));
+        assert!(!filter.matches(&ChangeEvent::VertexCreated(bob.clone())));
+
+        let filter = ChangeFilter {
+            vertex_types: [person_t].into_iter().collect(),
+            ..ChangeFilter::default()
+        };
+        assert!(filter.matches(&ChangeEvent::VertexDeleted(alice.clone())));
+        assert!(filter.matches(&ChangeEvent::SchemaChanged(SchemaKind::Vertex, person_t)));
+        assert!(!filter.matches(&ChangeEvent::SchemaChanged(SchemaKind::Edge, person_t)));
+        assert!(!filter.matches(&ChangeEvent::VertexPropertyDeleted(alice.id, name)));
+
+        let filter = ChangeFilter {
+            property_names: [name].into_iter().collect(),
+            ..ChangeFilter::default()
+        };
+        assert!(filter.matches(&ChangeEvent::VertexPropertyDeleted(alice.id, name)));
+        assert!(filter.matches(&ChangeEvent::CompositePropertyIndexed(vec![follows_t, name])));
+        assert!(!filter.matches(&ChangeEvent::PropertyIndexed(follows_t)));
+        assert!(!filter.matches(&ChangeEvent::EdgeDeleted(follows)));
+
+        assert!(ChangeFilter::default().matches(&ChangeEvent::VertexCreated(alice)));
+        Ok(())
+    }
+}
@@ -1,9 +1,19 @@
-use crate::errors::{Error, Result};
+use crate::changes::{ChangeEvent, ChangeFeed, ChangeSink};
+use crate::errors::{Error, QueryLimit, Result, ValidationError};
 use crate::models::{
-    BulkInsertItem, Edge, EdgeDirection, EdgeProperties, Identifier, Json, NamedProperty, Query, QueryOutputValue,
-    Vertex, VertexProperties,
+    BulkInsertItem, Edge, EdgeDirection, EdgeProperties, EdgeWeight, Identifier, InvalidWeight, Json, JsonRange,
+    NamedProperty, NullsOrder, Path, PathHop, PipePath, Query, QueryOutputValue, Schema, SchemaKind, SortOrder, Vertex,
+    VertexProperties,
 };
-use std::collections::HashSet;
+use crate::plan::{self, IndexPredicate, PlanOp, QueryPlan};
+use crate::util;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::iter;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use std::vec::Vec;
 use uuid::Uuid;
 
@@ -32,6 +42,20 @@ pub trait Transaction<'a> {
     /// * `offset` - Only fetch vertices with an offset greater than or equal
     /// to this value.
     fn range_vertices(&'a self, offset: Uuid) -> Result<DynIter<'a, Vertex>>;
+    /// Returns all vertices of a given type with `id >= offset`, ordered by
+    /// id. By default, this filters `range_vertices`, but this can be
+    /// overridden in datastores that index vertices by type.
+    ///
+    /// # Arguments
+    /// * `t` - The type of vertices to fetch.
+    /// * `offset` - Only fetch vertices with an offset greater than or equal
+    ///   to this value.
+    fn range_vertices_of_type(&'a self, t: Identifier, offset: Uuid) -> Result<DynIter<'a, Vertex>> {
+        let iter = self.range_vertices(offset)?;
+        Ok(Box::new(
+            iter.filter(move |r| !matches!(r, Ok(vertex) if vertex.t != t)),
+        ))
+    }
     /// Gets a specific set of vertices with the given IDs.
     fn specific_vertices(&'a self, ids: Vec<Uuid>) -> Result<DynIter<'a, Vertex>>;
     /// Get all vertices with a given property.
@@ -45,6 +69,36 @@ pub trait Transaction<'a> {
     /// * `name` - The property name.
     /// * `value` - The property value.
     fn vertex_ids_with_property_value(&'a self, name: Identifier, value: &Json) -> Result<Option<DynIter<'a, Uuid>>>;
+    /// Get all vertices with a property value in a given range, ordered by
+    /// property value. By default, this errors out, but this can be
+    /// overridden in datastores that support range queries.
+    ///
+    /// # Arguments
+    /// * `name` - The property name.
+    /// * `range` - The range of property values.
+    fn vertex_ids_with_property_range(
+        &'a self,
+        _name: Identifier,
+        _range: &JsonRange,
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        Err(Error::Unsupported)
+    }
+    /// Get all vertices whose values for a prefix of a composite index's
+    /// properties are equal to the given ones. Returns `None` if there is no
+    /// composite index over exactly `names`. By default, this errors out,
+    /// but this can be overridden in datastores that support composite
+    /// indexes.
+    ///
+    /// # Arguments
+    /// * `names` - The property names of the composite index.
+    /// * `values` - The values of the first `values.len()` properties.
+    fn vertex_ids_with_composite_property_values(
+        &'a self,
+        _names: &[Identifier],
+        _values: &[Json],
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        Err(Error::Unsupported)
+    }
 
     /// Gets the number of edges.
     fn edge_count(&self) -> u64;
@@ -62,6 +116,22 @@ pub trait Transaction<'a> {
     /// # Arguments
     /// * `offset` - Only fetch edges greater than or equal to this value.
     fn range_reversed_edges(&'a self, offset: Edge) -> Result<DynIter<'a, Edge>>;
+    /// Returns all edges of a given type that are greater than or equal to
+    /// `offset`, in the same order as `range_edges`. By default, this filters
+    /// `range_edges`, but this can be overridden in datastores that index
+    /// edges by type.
+    ///
+    /// # Arguments
+    /// * `t` - The type of edges to fetch.
+    /// * `offset` - Only fetch edges greater than or equal to this value. If
+    ///   unset, all edges of the type are fetched.
+    fn range_edges_of_type(&'a self, t: Identifier, offset: Option<Edge>) -> Result<DynIter<'a, Edge>> {
+        let iter = match offset {
+            Some(offset) => self.range_edges(offset)?,
+            None => self.all_edges()?,
+        };
+        Ok(Box::new(iter.filter(move |r| !matches!(r, Ok(edge) if edge.t != t))))
+    }
     /// Gets a specific set of edges.
     ///
     /// # Arguments
@@ -78,6 +148,16 @@ pub trait Transaction<'a> {
     /// * `name` - The property name.
     /// * `value` - The property value.
     fn edges_with_property_value(&'a self, name: Identifier, value: &Json) -> Result<Option<DynIter<'a, Edge>>>;
+    /// Get all edges with a property value in a given range, ordered by
+    /// property value. By default, this errors out, but this can be
+    /// overridden in datastores that support range queries.
+    ///
+    /// # Arguments
+    /// * `name` - The property name.
+    /// * `range` - The range of property values.
+    fn edges_with_property_range(&'a self, _name: Identifier, _range: &JsonRange) -> Result<Option<DynIter<'a, Edge>>> {
+        Err(Error::Unsupported)
+    }
 
     /// Gets the value of a vertex property if it exists, or `None` otherwise.
     ///
@@ -103,6 +183,46 @@ pub trait Transaction<'a> {
     /// * `edge` - The edge.
     fn all_edge_properties_for_edge(&'a self, edge: &Edge) -> Result<DynIter<'a, (Identifier, Json)>>;
 
+    /// Returns the vertices that expire at or before a given time, ordered
+    /// by when they expire. By default, this returns nothing, but this can be
+    /// overridden in datastores that support expiry.
+    ///
+    /// # Arguments
+    /// * `now`: The time to check expiry against.
+    fn expired_vertices(&'a self, _now: SystemTime) -> Result<DynIter<'a, Vertex>> {
+        Ok(Box::new(iter::empty()))
+    }
+
+    /// Returns the edges that expire at or before a given time, ordered by
+    /// when they expire. By default, this returns nothing, but this can be
+    /// overridden in datastores that support expiry.
+    ///
+    /// # Arguments
+    /// * `now`: The time to check expiry against.
+    fn expired_edges(&'a self, _now: SystemTime) -> Result<DynIter<'a, Edge>> {
+        Ok(Box::new(iter::empty()))
+    }
+
+    /// Gets when a vertex expires, if it was created with an expiry. By
+    /// default, this returns `None`, but this can be overridden in
+    /// datastores that support expiry.
+    ///
+    /// # Arguments
+    /// * `id`: The id of the vertex.
+    fn vertex_expiry(&self, _id: Uuid) -> Result<Option<SystemTime>> {
+        Ok(None)
+    }
+
+    /// Gets when an edge expires, if it was created with an expiry. By
+    /// default, this returns `None`, but this can be overridden in
+    /// datastores that support expiry.
+    ///
+    /// # Arguments
+    /// * `edge`: The edge.
+    fn edge_expiry(&self, _edge: &Edge) -> Result<Option<SystemTime>> {
+        Ok(None)
+    }
+
     /// Deletes the given vertices.
     ///
     /// # Arguments
@@ -130,6 +250,30 @@ pub trait Transaction<'a> {
         Err(Error::Unsupported)
     }
 
+    /// Commits any writes buffered by this transaction. Transactions returned
+    /// by `Datastore::transaction` apply writes immediately, so by default
+    /// this is a no-op.
+    fn commit(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Discards any writes buffered by this transaction. By default, this
+    /// errors out, but this can be overridden in datastores that support
+    /// buffered transactions.
+    fn rollback(&mut self) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Pins subsequent reads made through this transaction to a consistent,
+    /// point-in-time view of the datastore, so that e.g. a multi-hop query
+    /// doesn't observe writes that land between hops. Writes made through an
+    /// unbuffered transaction afterwards aren't observed by its reads. By
+    /// default, this errors out, but this can be overridden in datastores
+    /// that support snapshots.
+    fn snapshot(&mut self) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
     /// Creates a new vertex. Returns whether the vertex was successfully
     /// created - if this is false, it's because a vertex with the same UUID
     /// already exists.
@@ -145,6 +289,32 @@ pub trait Transaction<'a> {
     /// * `edge`: The edge to create.
     fn create_edge(&mut self, edge: &Edge) -> Result<bool>;
 
+    /// Creates a new vertex that expires at a given time. Once it has
+    /// expired, the vertex must be returned by `expired_vertices` until it's
+    /// deleted. Returns whether the vertex was successfully created. By
+    /// default, this errors out, but this can be overridden in datastores
+    /// that support expiry.
+    ///
+    /// # Arguments
+    /// * `vertex`: The vertex to create.
+    /// * `expires_at`: When the vertex expires.
+    fn create_vertex_with_expiry(&mut self, _vertex: &Vertex, _expires_at: SystemTime) -> Result<bool> {
+        Err(Error::Unsupported)
+    }
+
+    /// Creates a new edge that expires at a given time. Once it has expired,
+    /// the edge must be returned by `expired_edges` until it's deleted.
+    /// Returns whether the edge was successfully created. By default, this
+    /// errors out, but this can be overridden in datastores that support
+    /// expiry.
+    ///
+    /// # Arguments
+    /// * `edge`: The edge to create.
+    /// * `expires_at`: When the edge expires.
+    fn create_edge_with_expiry(&mut self, _edge: &Edge, _expires_at: SystemTime) -> Result<bool> {
+        Err(Error::Unsupported)
+    }
+
     /// Bulk inserts many vertices, edges, and/or properties. By default, this
     /// makes the underlying calls to insert the values, but can be overridden
     /// to offer a more efficient implementation.
@@ -152,6 +322,7 @@ pub trait Transaction<'a> {
     /// # Arguments
     /// * `items`: The items to insert.
     fn bulk_insert(&mut self, items: Vec<BulkInsertItem>) -> Result<()> {
+        let now = SystemTime::now();
         for item in items {
             match item {
                 BulkInsertItem::Vertex(vertex) => {
@@ -166,6 +337,12 @@ pub trait Transaction<'a> {
                 BulkInsertItem::EdgeProperty(edge, name, value) => {
                     self.set_edge_properties(vec![edge], name, &value)?;
                 }
+                BulkInsertItem::ExpiringVertex(vertex, ttl) => {
+                    self.create_vertex_with_expiry(&vertex, util::expiry(now, ttl)?)?;
+                }
+                BulkInsertItem::ExpiringEdge(edge, ttl) => {
+                    self.create_edge_with_expiry(&edge, util::expiry(now, ttl)?)?;
+                }
             }
         }
 
@@ -179,6 +356,79 @@ pub trait Transaction<'a> {
     /// * `name`: The name of the property to index.
     fn index_property(&mut self, name: Identifier) -> Result<()>;
 
+    /// Enables a unique index on a specified property. This works like
+    /// `index_property`, but additionally, `set_vertex_properties` and
+    /// `bulk_insert` must fail with `Error::UniqueViolation` - without
+    /// writing anything - if they'd give two vertices the same value for the
+    /// property. Edge property values aren't constrained.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to index.
+    ///
+    /// # Errors
+    /// Returns `Error::UniqueViolation` if vertices already share a value for
+    /// the property. By default, this errors out with `Error::Unsupported`,
+    /// but this can be overridden in datastores that support unique indexes.
+    fn index_unique_property(&mut self, _name: Identifier) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Enables a composite index over an ordered list of vertex properties.
+    /// Vertices are only included in the index if they have all of the
+    /// properties. Names are validated by the caller to be non-empty and
+    /// distinct. By default, this errors out, but this can be overridden in
+    /// datastores that support composite indexes.
+    ///
+    /// # Arguments
+    /// * `names`: The names of the properties to index, in order.
+    fn index_composite_property(&mut self, _names: Vec<Identifier>) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Disables indexing on a specified property, and removes its existing
+    /// index entries. This is a no-op if the property isn't indexed. By
+    /// default, this errors out, but this can be overridden in datastores
+    /// that support removing indexes.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to stop indexing.
+    fn unindex_property(&mut self, _name: Identifier) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Gets the names of the properties with single-property indexes, in
+    /// sorted order. By default, this errors out, but this can be overridden
+    /// in datastores that keep track of their indexes.
+    fn indexed_properties(&self) -> Result<Vec<Identifier>> {
+        Err(Error::Unsupported)
+    }
+
+    /// Sets or removes the schema for vertices or edges of a given type.
+    /// Once set, `set_vertex_properties`, `set_edge_properties` and
+    /// `bulk_insert` must fail with `Error::SchemaViolation` - without
+    /// writing anything - if any of their properties aren't allowed by it.
+    /// By default, this errors out, but this can be overridden in datastores
+    /// that support schemas.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    /// * `schema`: The schema, or `None` to remove it.
+    fn set_schema(&mut self, _kind: SchemaKind, _t: Identifier, _schema: Option<Schema>) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Gets the schema for vertices or edges of a given type, if any. By
+    /// default, this returns nothing, but this can be overridden in
+    /// datastores that support schemas.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    fn schema(&self, _kind: SchemaKind, _t: Identifier) -> Result<Option<Schema>> {
+        Ok(None)
+    }
+
     /// Sets vertex properties.
     ///
     /// # Arguments
@@ -204,6 +454,50 @@ pub trait Datastore {
         Self: 'a;
     /// Creates a new transaction.
     fn transaction(&self) -> Self::Transaction<'_>;
+    /// Creates a new transaction that buffers its writes until
+    /// `Transaction::commit` is called. If the transaction is dropped
+    /// without being committed, its writes are discarded. By default, this
+    /// errors out, but this can be overridden in datastores that support
+    /// buffered transactions.
+    fn begin(&self) -> Result<Self::Transaction<'_>> {
+        Err(Error::Unsupported)
+    }
+    /// Creates a new transaction that's only used for reads, so that
+    /// datastores can run read transactions concurrently with each other.
+    /// Writes through it may fail with `Error::ReadOnlyTransaction`. By
+    /// default, this is the same as `transaction`.
+    fn read_transaction(&self) -> Self::Transaction<'_> {
+        self.transaction()
+    }
+}
+
+/// Options for running a query with `Database::get_with_options`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GetOptions {
+    /// Whether to run the query against a consistent snapshot of the
+    /// datastore. Datastores that don't support snapshots return
+    /// `Error::Unsupported`.
+    pub snapshot: bool,
+    /// Limits on the resources the query can use. If this is `None`, the
+    /// database's default budget is used.
+    pub budget: Option<QueryBudget>,
+}
+
+/// Limits on the resources used by a query. Queries that go over any of
+/// these fail with `Error::QueryBudgetExceeded`. Limits that are `None` are
+/// unbounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryBudget {
+    /// How long the query can run for, including the time spent pulling
+    /// values from lazily evaluated output.
+    pub timeout: Option<Duration>,
+    /// The maximum number of rows output by all of the query's steps
+    /// combined, e.g. a query that gets the outbound edges of ten vertices
+    /// uses ten rows, plus one for each edge.
+    pub max_rows: Option<u64>,
+    /// The maximum number of edges read from the datastore, including those
+    /// that are filtered out before they're output.
+    pub max_edges_scanned: Option<u64>,
 }
 
 /// The IndraDB database.
@@ -216,6 +510,8 @@ pub trait Datastore {
 /// datastores.
 pub struct Database<D: Datastore> {
     pub datastore: D,
+    changes: ChangeFeed,
+    budget: QueryBudget,
 }
 
 impl<D: Datastore> Database<D> {
@@ -224,7 +520,80 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `datastore`: The underlying datastore to use.
     pub fn new(datastore: D) -> Database<D> {
-        Self { datastore }
+        Self {
+            datastore,
+            changes: ChangeFeed::default(),
+            budget: QueryBudget::default(),
+        }
+    }
+
+    /// Sets the budget for queries that don't specify their own, which is
+    /// unbounded by default.
+    ///
+    /// # Arguments
+    /// * `budget`: The query budget.
+    pub fn set_query_budget(&mut self, budget: QueryBudget) {
+        self.budget = budget;
+    }
+
+    /// Gets the budget for queries that don't specify their own.
+    pub fn query_budget(&self) -> QueryBudget {
+        self.budget
+    }
+
+    /// Adds a sink that receives the changes made through this database from
+    /// now on, including those made through transactions once they're
+    /// committed. Writes made directly through the datastore bypass the
+    /// database, and aren't published.
+    ///
+    /// # Arguments
+    /// * `sink`: The sink to add.
+    pub fn add_change_sink(&self, sink: Arc<dyn ChangeSink>) {
+        self.changes.add_sink(sink);
+    }
+
+    /// Begins a multi-operation read-write transaction. Writes made through
+    /// the returned transaction are visible to reads made through it, but
+    /// are only persisted once it's committed. In the bundled datastores,
+    /// other writes wait until the transaction is dropped.
+    pub fn begin(&self) -> Result<DatabaseTransaction<'_, D>> {
+        Ok(DatabaseTransaction {
+            txn: self.datastore.begin()?,
+            changes: &self.changes,
+            pending_changes: Some(Vec::new()),
+            budget: self.budget,
+        })
+    }
+
+    fn autocommit(&self) -> DatabaseTransaction<'_, D> {
+        DatabaseTransaction {
+            txn: self.datastore.transaction(),
+            changes: &self.changes,
+            pending_changes: None,
+            budget: self.budget,
+        }
+    }
+
+    // Like `autocommit`, but for methods that only read.
+    fn reader(&self) -> DatabaseTransaction<'_, D> {
+        DatabaseTransaction {
+            txn: self.datastore.read_transaction(),
+            changes: &self.changes,
+            pending_changes: None,
+            budget: self.budget,
+        }
+    }
+
+    // Like `reader`, but with the snapshot and budget from a get's options.
+    fn reader_with_options(&self, options: &GetOptions) -> Result<DatabaseTransaction<'_, D>> {
+        let mut txn = self.reader();
+        if options.snapshot {
+            txn.txn.snapshot()?;
+        }
+        if let Some(budget) = options.budget {
+            txn.budget = budget;
+        }
+        Ok(txn)
     }
 
     /// Syncs persisted content. Depending on the datastore implementation,
@@ -241,8 +610,7 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `vertex`: The vertex to create.
     pub fn create_vertex(&self, vertex: &Vertex) -> Result<bool> {
-        let mut txn = self.datastore.transaction();
-        txn.create_vertex(vertex)
+        self.autocommit().create_vertex(vertex)
     }
 
     /// Creates a new vertex with just a type specification. As opposed to
@@ -252,13 +620,7 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `t`: The type of the vertex to create.
     pub fn create_vertex_from_type(&self, t: Identifier) -> Result<Uuid> {
-        let v = Vertex::new(t);
-
-        if !self.create_vertex(&v)? {
-            Err(Error::UuidTaken)
-        } else {
-            Ok(v.id)
-        }
+        self.autocommit().create_vertex_from_type(t)
     }
 
     /// Creates a new edge. Returns whether the edge was successfully
@@ -268,8 +630,31 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `edge`: The edge to create.
     pub fn create_edge(&self, edge: &Edge) -> Result<bool> {
-        let mut txn = self.datastore.transaction();
-        txn.create_edge(edge)
+        self.autocommit().create_edge(edge)
+    }
+
+    /// Creates a new vertex that expires after a time-to-live. Once it has
+    /// expired, the vertex is hidden from queries, and it's deleted by the
+    /// next call to `expire_now`. Returns whether the vertex was successfully
+    /// created - if this is false, it's because a vertex with the same UUID
+    /// already exists.
+    ///
+    /// # Arguments
+    /// * `vertex`: The vertex to create.
+    /// * `ttl`: How long until the vertex expires.
+    pub fn create_vertex_with_ttl(&self, vertex: &Vertex, ttl: Duration) -> Result<bool> {
+        self.autocommit().create_vertex_with_ttl(vertex, ttl)
+    }
+
+    /// Creates a new edge that expires after a time-to-live. Returns whether
+    /// the edge was successfully created - if this is false, it's because one
+    /// of the specified vertices is missing.
+    ///
+    /// # Arguments
+    /// * `edge`: The edge to create.
+    /// * `ttl`: How long until the edge expires.
+    pub fn create_edge_with_ttl(&self, edge: &Edge, ttl: Duration) -> Result<bool> {
+        self.autocommit().create_edge_with_ttl(edge, ttl)
     }
 
     /// Gets values specified by a query.
@@ -277,13 +662,51 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `q`: The query to run.
     pub fn get<Q: Into<Query>>(&self, q: Q) -> Result<Vec<QueryOutputValue>> {
-        let q = q.into();
-        let txn = self.datastore.transaction();
-        let mut output = Vec::with_capacity(q.output_len());
-        unsafe {
-            query(&txn as *const D::Transaction<'_>, &q, &mut output)?;
-        }
-        Ok(output)
+        self.reader().get(q)
+    }
+
+    /// Gets values specified by a query, with additional options.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    /// * `options`: Options controlling how the query is run.
+    pub fn get_with_options<Q: Into<Query>>(&self, q: Q, options: GetOptions) -> Result<Vec<QueryOutputValue>> {
+        self.reader_with_options(&options)?.get(q)
+    }
+
+    /// Gets values specified by a query, pulling them lazily from the
+    /// datastore as the returned iterators are advanced. This avoids
+    /// materializing large results all at once, but depending on the
+    /// datastore, may hold locks until the iterators are dropped.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    pub fn get_iter<Q: Into<Query>>(&self, q: Q) -> Result<Vec<QueryOutputIter<'_>>> {
+        self.get_iter_with_options(q, GetOptions::default())
+    }
+
+    /// Gets values specified by a query lazily, with additional options. A
+    /// budget's deadline keeps applying while the returned iterators are
+    /// advanced.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    /// * `options`: Options controlling how the query is run.
+    pub fn get_iter_with_options<Q: Into<Query>>(&self, q: Q, options: GetOptions) -> Result<Vec<QueryOutputIter<'_>>> {
+        let txn = Rc::new(self.reader_with_options(&options)?);
+        let output = unsafe { txn.query(&q.into())? };
+        Ok(output.into_iter().map(|value| value.owned_by(&txn)).collect())
+    }
+
+    /// Plans a query without running it, returning the plan along with
+    /// estimates of how many values each step outputs. This is useful for
+    /// debugging slow queries. Estimating counts the entries of the type and
+    /// property indexes the plan reads.
+    ///
+    /// # Arguments
+    /// * `q`: The query to plan.
+    pub fn explain<Q: Into<Query>>(&self, q: Q) -> Result<QueryPlan> {
+        self.reader().explain(q)
     }
 
     /// Deletes values specified by a query.
@@ -291,44 +714,14 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `q`: The query to run.
     pub fn delete<Q: Into<Query>>(&self, q: Q) -> Result<()> {
-        let q = q.into();
-        let mut txn = self.datastore.transaction();
-        let mut output = Vec::with_capacity(q.output_len());
-        unsafe {
-            query(&txn as *const D::Transaction<'_>, &q, &mut output)?;
-        }
-        match output.pop().unwrap() {
-            QueryOutputValue::Vertices(vertices) => {
-                txn.delete_vertices(vertices)?;
-            }
-            QueryOutputValue::Edges(edges) => {
-                txn.delete_edges(edges)?;
-            }
-            QueryOutputValue::VertexProperties(vertex_properties) => {
-                txn.delete_vertex_properties(
-                    vertex_properties
-                        .into_iter()
-                        .flat_map(|vps| {
-                            let iter = vps.props.iter().map(move |vp| (vps.vertex.id, vp.name));
-                            iter.collect::<Vec<(Uuid, Identifier)>>()
-                        })
-                        .collect(),
-                )?;
-            }
-            QueryOutputValue::EdgeProperties(edge_properties) => {
-                txn.delete_edge_properties(
-                    edge_properties
-                        .into_iter()
-                        .flat_map(|eps| {
-                            let iter = eps.props.iter().map(move |ep| (eps.edge.clone(), ep.name));
-                            iter.collect::<Vec<(Edge, Identifier)>>()
-                        })
-                        .collect(),
-                )?;
-            }
-            QueryOutputValue::Count(_) => return Err(Error::OperationOnQuery),
-        }
-        Ok(())
+        self.autocommit().delete(q)
+    }
+
+    /// Deletes the vertices and edges that have expired, along with their
+    /// properties and the edges of the deleted vertices. Returns the number
+    /// of expired vertices and edges that were deleted.
+    pub fn expire_now(&self) -> Result<u64> {
+        self.autocommit().expire_now()
     }
 
     /// Sets properties.
@@ -338,23 +731,7 @@ impl<D: Datastore> Database<D> {
     /// * `name`: The property name.
     /// * `value`: The property value.
     pub fn set_properties<Q: Into<Query>>(&self, q: Q, name: Identifier, value: &Json) -> Result<()> {
-        let q = q.into();
-        let mut txn = self.datastore.transaction();
-        let mut output = Vec::with_capacity(q.output_len());
-        unsafe {
-            query(&txn as *const D::Transaction<'_>, &q, &mut output)?;
-        }
-
-        match output.pop().unwrap() {
-            QueryOutputValue::Vertices(vertices) => {
-                txn.set_vertex_properties(vertices.into_iter().map(|v| v.id).collect(), name, value)?;
-            }
-            QueryOutputValue::Edges(edges) => {
-                txn.set_edge_properties(edges, name, value)?;
-            }
-            _ => return Err(Error::OperationOnQuery),
-        }
-        Ok(())
+        self.autocommit().set_properties(q, name, value)
     }
 
     /// Bulk inserts many vertices, edges, and/or properties.
@@ -362,8 +739,7 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `items`: The items to insert.
     pub fn bulk_insert(&self, items: Vec<BulkInsertItem>) -> Result<()> {
-        let mut txn = self.datastore.transaction();
-        txn.bulk_insert(items)
+        self.autocommit().bulk_insert(items)
     }
 
     /// Enables indexing on a specified property. When indexing is enabled on a
@@ -372,327 +748,1730 @@ impl<D: Datastore> Database<D> {
     /// # Arguments
     /// * `name`: The name of the property to index.
     pub fn index_property(&self, name: Identifier) -> Result<()> {
-        let mut txn = self.datastore.transaction();
-        txn.index_property(name)
+        self.autocommit().index_property(name)
     }
-}
-
-unsafe fn query<'a, T: Transaction<'a> + 'a>(
-    txn: *const T,
-    q: &Query,
-    output: &mut Vec<QueryOutputValue>,
-) -> Result<()> {
-    let value = match q {
-        Query::AllVertex => {
-            let iter = (*txn).all_vertices()?;
-            QueryOutputValue::Vertices(iter.collect::<Result<Vec<Vertex>>>()?)
-        }
-        Query::RangeVertex(ref q) => {
-            let mut iter: DynIter<Vertex> = if let Some(start_id) = q.start_id {
-                (*txn).range_vertices(start_id)?
-            } else {
-                (*txn).all_vertices()?
-            };
-
-            if let Some(ref t) = q.t {
-                iter = Box::new(iter.filter(move |r| match r {
-                    Ok(v) => &v.t == t,
-                    Err(_) => true,
-                }));
-            }
 
-            iter = Box::new(iter.take(q.limit as usize));
-            QueryOutputValue::Vertices(iter.collect::<Result<Vec<Vertex>>>()?)
-        }
-        Query::SpecificVertex(ref q) => {
-            let iter = (*txn).specific_vertices(q.ids.clone())?;
-            QueryOutputValue::Vertices(iter.collect::<Result<Vec<Vertex>>>()?)
-        }
-        Query::Pipe(ref q) => {
-            query(txn, &q.inner, output)?;
-            let piped_values = output.pop().unwrap();
+    /// Enables a unique index on a specified property. Besides supporting
+    /// the same queries as `index_property`, this guarantees that no two
+    /// vertices have the same value for the property: writes that would
+    /// break this fail with `Error::UniqueViolation`. Calling this on a
+    /// property that's already indexed makes its index unique.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to index.
+    ///
+    /// # Errors
+    /// Returns `Error::UniqueViolation` if vertices already share a value for
+    /// the property.
+    pub fn index_unique_property(&self, name: Identifier) -> Result<()> {
+        self.autocommit().index_unique_property(name)
+    }
 
-            let values = match piped_values {
-                QueryOutputValue::Edges(ref piped_edges) => {
-                    let iter: Box<dyn Iterator<Item = Uuid>> = match q.direction {
-                        EdgeDirection::Outbound => Box::new(piped_edges.iter().map(|e| e.outbound_id)),
-                        EdgeDirection::Inbound => Box::new(piped_edges.iter().map(|e| e.inbound_id)),
-                    };
+    /// Enables a composite index over an ordered list of vertex properties.
+    /// Composite indexes can be queried with a
+    /// `VertexWithCompositePropertyQuery` on any prefix of the properties.
+    ///
+    /// # Arguments
+    /// * `names`: The names of the properties to index, in order.
+    ///
+    /// # Errors
+    /// Returns a `ValidationError::InvalidValue` if `names` is empty or
+    /// contains duplicates.
+    pub fn index_composite_property(&self, names: Vec<Identifier>) -> Result<()> {
+        self.autocommit().index_composite_property(names)
+    }
 
-                    let mut iter: DynIter<Vertex> = (*txn).specific_vertices(iter.collect())?;
+    /// Disables indexing on a specified property, removing its index
+    /// entries. Queries that require the index will fail with
+    /// `Error::NotIndexed` afterwards.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to stop indexing.
+    pub fn unindex_property(&self, name: Identifier) -> Result<()> {
+        self.autocommit().unindex_property(name)
+    }
 
-                    if let Some(ref t) = q.t {
-                        iter = Box::new(iter.filter(move |r| match r {
-                            Ok(v) => &v.t == t,
-                            Err(_) => true,
-                        }));
-                    }
+    /// Gets the names of the indexed properties, in sorted order. This does
+    /// not include composite indexes.
+    pub fn indexed_properties(&self) -> Result<Vec<Identifier>> {
+        self.reader().indexed_properties()
+    }
 
-                    iter = Box::new(iter.take(q.limit as usize));
+    /// Sets the schema for vertices or edges of a given type. Subsequent
+    /// property writes on that type fail with `Error::SchemaViolation` if the
+    /// schema doesn't allow them. Existing properties aren't checked.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    /// * `schema`: The schema.
+    pub fn set_schema(&self, kind: SchemaKind, t: Identifier, schema: Schema) -> Result<()> {
+        self.autocommit().set_schema(kind, t, schema)
+    }
 
-                    QueryOutputValue::Vertices(iter.collect::<Result<Vec<Vertex>>>()?)
-                }
-                QueryOutputValue::Vertices(ref piped_vertices) => {
-                    let mut edges = Vec::new();
-
-                    for vertex in piped_vertices {
-                        let lower_bound = match &q.t {
-                            Some(t) => Edge::new(vertex.id, *t, Uuid::default()),
-                            None => Edge::new(vertex.id, Identifier::default(), Uuid::default()),
-                        };
-
-                        let mut iter = if q.direction == EdgeDirection::Outbound {
-                            (*txn).range_edges(lower_bound)?
-                        } else {
-                            (*txn).range_reversed_edges(lower_bound)?
-                        };
-
-                        iter = Box::new(iter.take_while(move |r| match r {
-                            Ok(e) => e.outbound_id == vertex.id,
-                            Err(_) => true,
-                        }));
-
-                        if let Some(ref t) = q.t {
-                            iter = Box::new(iter.filter(move |r| match r {
-                                Ok(e) => &e.t == t,
-                                Err(_) => true,
-                            }));
-                        }
-
-                        if q.direction == EdgeDirection::Inbound {
-                            iter = Box::new(iter.map(move |r| Ok(r?.reversed())));
-                        }
-
-                        iter = Box::new(iter.take((q.limit as usize) - edges.len()));
-
-                        for result in iter {
-                            edges.push(result?);
-                        }
-
-                        if edges.len() >= (q.limit as usize) {
-                            break;
-                        }
-                    }
+    /// Removes the schema for vertices or edges of a given type, if any.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    pub fn delete_schema(&self, kind: SchemaKind, t: Identifier) -> Result<()> {
+        self.autocommit().delete_schema(kind, t)
+    }
 
-                    QueryOutputValue::Edges(edges)
-                }
-                _ => {
-                    return Err(Error::OperationOnQuery);
-                }
-            };
+    /// Gets the schema for vertices or edges of a given type, if any.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    pub fn schema(&self, kind: SchemaKind, t: Identifier) -> Result<Option<Schema>> {
+        self.reader().schema(kind, t)
+    }
+}
 
-            if let Query::Include(_) = *q.inner {
-                // keep the value exported
-                output.push(piped_values);
-            }
+/// A multi-operation read-write transaction, created via `Database::begin`.
+///
+/// Nothing written through the transaction is persisted until `commit` is
+/// called. Dropping the transaction without committing it is equivalent to
+/// calling `rollback`.
+pub struct DatabaseTransaction<'a, D: Datastore + 'a> {
+    txn: D::Transaction<'a>,
+    changes: &'a ChangeFeed,
+    // Changes are held here until commit, except for the autocommit
+    // transactions backing `Database` methods, which publish them right away.
+    pending_changes: Option<Vec<ChangeEvent>>,
+    budget: QueryBudget,
+}
 
-            values
-        }
-        Query::PipeProperty(ref q) => {
-            query(txn, &q.inner, output)?;
-            let piped_values = output.pop().unwrap();
-
-            let values = match piped_values {
-                QueryOutputValue::Edges(ref piped_edges) => {
-                    let mut edge_properties = Vec::with_capacity(piped_edges.len());
-                    for edge in piped_edges {
-                        let mut props = Vec::new();
-                        if let Some(name) = &q.name {
-                            if let Some(value) = (*txn).edge_property(edge, *name)? {
-                                props.push(NamedProperty::new(*name, value.clone()));
-                            }
-                        } else {
-                            for result in (*txn).all_edge_properties_for_edge(edge)? {
-                                let (name, value) = result?;
-                                props.push(NamedProperty::new(name, value.clone()));
-                            }
-                        }
-                        if !props.is_empty() {
-                            edge_properties.push(EdgeProperties::new(edge.clone(), props));
-                        }
-                    }
+impl<'a, D: Datastore + 'a> DatabaseTransaction<'a, D> {
+    /// Creates a new vertex. Returns whether the vertex was successfully
+    /// created - if this is false, it's because a vertex with the same UUID
+    /// already exists.
+    ///
+    /// # Arguments
+    /// * `vertex`: The vertex to create.
+    pub fn create_vertex(&mut self, vertex: &Vertex) -> Result<bool> {
+        let created = self.txn.create_vertex(vertex)?;
+        if created {
+            let events = self.capture_changes(|| vec![ChangeEvent::VertexCreated(vertex.clone())]);
+            self.record_changes(events);
+        }
+        Ok(created)
+    }
 
-                    QueryOutputValue::EdgeProperties(edge_properties)
-                }
-                QueryOutputValue::Vertices(ref piped_vertices) => {
-                    let mut vertex_properties = Vec::with_capacity(piped_vertices.len());
-                    for vertex in piped_vertices {
-                        let mut props = Vec::new();
-                        if let Some(name) = &q.name {
-                            if let Some(value) = (*txn).vertex_property(vertex, *name)? {
-                                props.push(NamedProperty::new(*name, value.clone()));
-                            }
-                        } else {
-                            for result in (*txn).all_vertex_properties_for_vertex(vertex)? {
-                                let (name, value) = result?;
-                                props.push(NamedProperty::new(name, value.clone()));
-                            }
-                        }
-                        if !props.is_empty() {
-                            vertex_properties.push(VertexProperties::new(vertex.clone(), props));
-                        }
-                    }
+    /// Creates a new vertex with just a type specification. Returns the new
+    /// vertex's UUID.
+    ///
+    /// # Arguments
+    /// * `t`: The type of the vertex to create.
+    pub fn create_vertex_from_type(&mut self, t: Identifier) -> Result<Uuid> {
+        let v = Vertex::new(t);
 
-                    QueryOutputValue::VertexProperties(vertex_properties)
-                }
-                _ => {
-                    return Err(Error::OperationOnQuery);
-                }
-            };
+        if !self.create_vertex(&v)? {
+            Err(Error::UuidTaken)
+        } else {
+            Ok(v.id)
+        }
+    }
 
-            if let Query::Include(_) = *q.inner {
-                // keep the value exported
-                output.push(piped_values);
+    /// Creates a new edge. Returns whether the edge was successfully
+    /// created - if this is false, it's because one of the specified vertices
+    /// is missing.
+    ///
+    /// # Arguments
+    /// * `edge`: The edge to create.
+    pub fn create_edge(&mut self, edge: &Edge) -> Result<bool> {
+        let created = self.txn.create_edge(edge)?;
+        if created {
+            let events = self.capture_changes(|| vec![ChangeEvent::EdgeCreated(edge.clone())]);
+            self.record_changes(events);
+        }
+        Ok(created)
+    }
+
+    /// Creates a new vertex that expires after a time-to-live. Returns
+    /// whether the vertex was successfully created - if this is false, it's
+    /// because a vertex with the same UUID already exists.
+    ///
+    /// # Arguments
+    /// * `vertex`: The vertex to create.
+    /// * `ttl`: How long until the vertex expires.
+    pub fn create_vertex_with_ttl(&mut self, vertex: &Vertex, ttl: Duration) -> Result<bool> {
+        let expires_at = util::expiry(SystemTime::now(), ttl)?;
+        let created = self.txn.create_vertex_with_expiry(vertex, expires_at)?;
+        if created {
+            let events = self.capture_changes(|| vec![ChangeEvent::VertexCreated(vertex.clone())]);
+            self.record_changes(events);
+        }
+        Ok(created)
+    }
+
+    /// Creates a new edge that expires after a time-to-live. Returns whether
+    /// the edge was successfully created - if this is false, it's because one
+    /// of the specified vertices is missing.
+    ///
+    /// # Arguments
+    /// * `edge`: The edge to create.
+    /// * `ttl`: How long until the edge expires.
+    pub fn create_edge_with_ttl(&mut self, edge: &Edge, ttl: Duration) -> Result<bool> {
+        let expires_at = util::expiry(SystemTime::now(), ttl)?;
+        let created = self.txn.create_edge_with_expiry(edge, expires_at)?;
+        if created {
+            let events = self.capture_changes(|| vec![ChangeEvent::EdgeCreated(edge.clone())]);
+            self.record_changes(events);
+        }
+        Ok(created)
+    }
+
+    /// Gets values specified by a query. Writes made earlier in this
+    /// transaction are visible.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    pub fn get<Q: Into<Query>>(&self, q: Q) -> Result<Vec<QueryOutputValue>> {
+        let output = unsafe { self.query(&q.into())? };
+        output.into_iter().map(|value| value.into_value()).collect()
+    }
+
+    /// Gets values specified by a query, pulling them lazily from the
+    /// datastore as the returned iterators are advanced.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    pub fn get_iter<Q: Into<Query>>(&self, q: Q) -> Result<Vec<QueryOutputIter<'_>>> {
+        unsafe { self.query(&q.into()) }
+    }
+
+    /// Plans a query without running it, returning the plan along with
+    /// estimates of how many values each step outputs.
+    ///
+    /// # Arguments
+    /// * `q`: The query to plan.
+    pub fn explain<Q: Into<Query>>(&self, q: Q) -> Result<QueryPlan> {
+        // Nothing borrowed for `'a` outlives this call, since the expiry
+        // checks are only used to build the plan, and the index reads made
+        // for estimates are only counted.
+        let txn = unsafe { &*(&self.txn as *const D::Transaction<'a>) };
+        let expired = Expired::load(txn, SystemTime::now())?;
+        let mut plan = plan::build(&q.into(), expired.is_some())?;
+        plan::estimate(txn, &mut plan)?;
+        Ok(plan)
+    }
+
+    /// Deletes values specified by a query.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    pub fn delete<Q: Into<Query>>(&mut self, q: Q) -> Result<()> {
+        let value = unsafe { self.query(&q.into())? }.pop().unwrap().into_value()?;
+        match value {
+            QueryOutputValue::Vertices(vertices) => {
+                let events = self.capture_deletions(&vertices, &[])?;
+                self.txn.delete_vertices(vertices)?;
+                self.record_changes(events);
+            }
+            QueryOutputValue::Edges(edges) => {
+                let events = self.capture_deletions(&[], &edges)?;
+                self.txn.delete_edges(edges)?;
+                self.record_changes(events);
+            }
+            QueryOutputValue::VertexProperties(vertex_properties) => {
+                let props: Vec<(Uuid, Identifier)> = vertex_properties
+                    .into_iter()
+                    .flat_map(|vps| {
+                        let iter = vps.props.iter().map(move |vp| (vps.vertex.id, vp.name));
+                        iter.collect::<Vec<(Uuid, Identifier)>>()
+                    })
+                    .collect();
+                let events = self.capture_changes(|| {
+                    props
+                        .iter()
+                        .map(|(id, name)| ChangeEvent::VertexPropertyDeleted(*id, *name))
+                        .collect()
+                });
+                self.txn.delete_vertex_properties(props)?;
+                self.record_changes(events);
+            }
+            QueryOutputValue::EdgeProperties(edge_properties) => {
+                let props: Vec<(Edge, Identifier)> = edge_properties
+                    .into_iter()
+                    .flat_map(|eps| {
+                        let iter = eps.props.iter().map(move |ep| (eps.edge.clone(), ep.name));
+                        iter.collect::<Vec<(Edge, Identifier)>>()
+                    })
+                    .collect();
+                let events = self.capture_changes(|| {
+                    props
+                        .iter()
+                        .map(|(edge, name)| ChangeEvent::EdgePropertyDeleted(edge.clone(), *name))
+                        .collect()
+                });
+                self.txn.delete_edge_properties(props)?;
+                self.record_changes(events);
+            }
+            QueryOutputValue::Count(_) | QueryOutputValue::Paths(_) | QueryOutputValue::PipePaths(_) => {
+                return Err(Error::OperationOnQuery)
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the vertices and edges that have expired, along with their
+    /// properties and the edges of the deleted vertices. Returns the number
+    /// of expired vertices and edges that were deleted.
+    pub fn expire_now(&mut self) -> Result<u64> {
+        let now = SystemTime::now();
+        let (vertices, edges) = {
+            // The iterators are drained before the transaction is mutated.
+            let txn = unsafe { &*(&self.txn as *const D::Transaction<'a>) };
+            let vertices = txn.expired_vertices(now)?.collect::<Result<Vec<Vertex>>>()?;
+            let edges = txn.expired_edges(now)?.collect::<Result<Vec<Edge>>>()?;
+            (vertices, edges)
+        };
+        let count = (vertices.len() + edges.len()) as u64;
+        let events = self.capture_deletions(&vertices, &edges)?;
+
+        // Edges go first, since some of them may be deleted along with the
+        // vertices.
+        self.txn.delete_edges(edges)?;
+        self.txn.delete_vertices(vertices)?;
+        self.record_changes(events);
+        Ok(count)
+    }
+
+    /// Sets properties.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    /// * `name`: The property name.
+    /// * `value`: The property value.
+    pub fn set_properties<Q: Into<Query>>(&mut self, q: Q, name: Identifier, value: &Json) -> Result<()> {
+        let piped_values = unsafe { self.query(&q.into())? }.pop().unwrap().into_value()?;
+
+        match piped_values {
+            QueryOutputValue::Vertices(vertices) => {
+                let ids: Vec<Uuid> = vertices.into_iter().map(|v| v.id).collect();
+                let events = self.capture_changes(|| {
+                    ids.iter()
+                        .map(|id| ChangeEvent::VertexPropertySet(*id, name, value.clone()))
+                        .collect()
+                });
+                self.txn.set_vertex_properties(ids, name, value)?;
+                self.record_changes(events);
+            }
+            QueryOutputValue::Edges(edges) => {
+                let events = self.capture_changes(|| {
+                    edges
+                        .iter()
+                        .map(|edge| ChangeEvent::EdgePropertySet(edge.clone(), name, value.clone()))
+                        .collect()
+                });
+                self.txn.set_edge_properties(edges, name, value)?;
+                self.record_changes(events);
             }
+            _ => return Err(Error::OperationOnQuery),
+        }
+        Ok(())
+    }
+
+    /// Bulk inserts many vertices, edges, and/or properties.
+    ///
+    /// # Arguments
+    /// * `items`: The items to insert.
+    pub fn bulk_insert(&mut self, items: Vec<BulkInsertItem>) -> Result<()> {
+        let events = self.capture_bulk_insert(&items)?;
+        self.txn.bulk_insert(items)?;
+        self.record_changes(events);
+        Ok(())
+    }
 
-            values
+    /// Enables indexing on a specified property.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to index.
+    pub fn index_property(&mut self, name: Identifier) -> Result<()> {
+        self.txn.index_property(name)?;
+        let events = self.capture_changes(|| vec![ChangeEvent::PropertyIndexed(name)]);
+        self.record_changes(events);
+        Ok(())
+    }
+
+    /// Enables a unique index on a specified property.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to index.
+    pub fn index_unique_property(&mut self, name: Identifier) -> Result<()> {
+        self.txn.index_unique_property(name)?;
+        let events = self.capture_changes(|| vec![ChangeEvent::UniquePropertyIndexed(name)]);
+        self.record_changes(events);
+        Ok(())
+    }
+
+    /// Enables a composite index over an ordered list of vertex properties.
+    ///
+    /// # Arguments
+    /// * `names`: The names of the properties to index, in order.
+    pub fn index_composite_property(&mut self, names: Vec<Identifier>) -> Result<()> {
+        let unique_names: HashSet<&Identifier> = names.iter().collect();
+        if names.is_empty() || unique_names.len() != names.len() {
+            return Err(ValidationError::InvalidValue.into());
         }
-        Query::VertexWithPropertyPresence(ref q) => {
-            if let Some(iter) = (*txn).vertex_ids_with_property(q.name)? {
-                let iter = (*txn).specific_vertices(iter.collect::<Result<Vec<Uuid>>>()?)?;
-                QueryOutputValue::Vertices(iter.collect::<Result<Vec<Vertex>>>()?)
-            } else {
-                return Err(Error::NotIndexed);
+        let events = self.capture_changes(|| vec![ChangeEvent::CompositePropertyIndexed(names.clone())]);
+        self.txn.index_composite_property(names)?;
+        self.record_changes(events);
+        Ok(())
+    }
+
+    /// Disables indexing on a specified property.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to stop indexing.
+    pub fn unindex_property(&mut self, name: Identifier) -> Result<()> {
+        self.txn.unindex_property(name)?;
+        let events = self.capture_changes(|| vec![ChangeEvent::PropertyUnindexed(name)]);
+        self.record_changes(events);
+        Ok(())
+    }
+
+    /// Gets the names of the indexed properties, in sorted order.
+    pub fn indexed_properties(&self) -> Result<Vec<Identifier>> {
+        self.txn.indexed_properties()
+    }
+
+    /// Sets the schema for vertices or edges of a given type.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    /// * `schema`: The schema.
+    pub fn set_schema(&mut self, kind: SchemaKind, t: Identifier, schema: Schema) -> Result<()> {
+        self.txn.set_schema(kind, t, Some(schema))?;
+        let events = self.capture_changes(|| vec![ChangeEvent::SchemaChanged(kind, t)]);
+        self.record_changes(events);
+        Ok(())
+    }
+
+    /// Removes the schema for vertices or edges of a given type, if any.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    pub fn delete_schema(&mut self, kind: SchemaKind, t: Identifier) -> Result<()> {
+        self.txn.set_schema(kind, t, None)?;
+        let events = self.capture_changes(|| vec![ChangeEvent::SchemaChanged(kind, t)]);
+        self.record_changes(events);
+        Ok(())
+    }
+
+    /// Gets the schema for vertices or edges of a given type, if any.
+    ///
+    /// # Arguments
+    /// * `kind`: Whether the schema applies to vertices or edges.
+    /// * `t`: The vertex or edge type.
+    pub fn schema(&self, kind: SchemaKind, t: Identifier) -> Result<Option<Schema>> {
+        self.txn.schema(kind, t)
+    }
+
+    // Queries need the transaction to be borrowed for `'a`, which outlives
+    // the borrow of `self`. Callers must make sure that the output is dropped
+    // before the transaction is mutated or dropped.
+    unsafe fn query(&self, q: &Query) -> Result<Vec<QueryOutputIter<'a>>> {
+        let txn = &*(&self.txn as *const D::Transaction<'a>);
+        let expired = Expired::load(txn, SystemTime::now())?;
+        let plan = plan::build(q, expired.is_some())?;
+        let meter = Meter::start(self.budget);
+        let mut output = Vec::with_capacity(q.output_len());
+        execute(txn, plan, &expired, &meter, &mut output)?;
+        Ok(output)
+    }
+
+    // Builds change events for a write, unless nothing would receive them.
+    // This is called before the write, since writes consume their inputs.
+    fn capture_changes<F: FnOnce() -> Vec<ChangeEvent>>(&self, events: F) -> Vec<ChangeEvent> {
+        if self.changes.is_active() {
+            events()
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Builds change events for deleting vertices and edges, unless nothing
+    // would receive them. The properties and edges deleted along with them
+    // get their own events, so this is called while they can still be read.
+    fn capture_deletions(&self, vertices: &[Vertex], edges: &[Edge]) -> Result<Vec<ChangeEvent>> {
+        fn edge_events<'a, T: Transaction<'a>>(txn: &'a T, edge: Edge, events: &mut Vec<ChangeEvent>) -> Result<()> {
+            for item in txn.all_edge_properties_for_edge(&edge)? {
+                let (name, _) = item?;
+                events.push(ChangeEvent::EdgePropertyDeleted(edge.clone(), name));
             }
+            events.push(ChangeEvent::EdgeDeleted(edge));
+            Ok(())
+        }
+
+        let mut events = Vec::new();
+        if !self.changes.is_active() {
+            return Ok(events);
         }
-        Query::VertexWithPropertyValue(ref q) => {
-            if let Some(iter) = (*txn).vertex_ids_with_property_value(q.name, &q.value)? {
-                let iter = (*txn).specific_vertices(iter.collect::<Result<Vec<Uuid>>>()?)?;
-                QueryOutputValue::Vertices(iter.collect::<Result<Vec<Vertex>>>()?)
-            } else {
-                return Err(Error::NotIndexed);
+
+        // The iterators are drained before this returns.
+        let txn = unsafe { &*(&self.txn as *const D::Transaction<'a>) };
+        let mut deleted_edges = HashSet::new();
+        for edge in edges {
+            if deleted_edges.insert(edge.clone()) {
+                edge_events(txn, edge.clone(), &mut events)?;
             }
         }
-        Query::EdgeWithPropertyPresence(ref q) => {
-            if let Some(iter) = (*txn).edges_with_property(q.name)? {
-                QueryOutputValue::Edges(iter.collect::<Result<Vec<Edge>>>()?)
-            } else {
-                return Err(Error::NotIndexed);
+        for vertex in vertices {
+            let outbound = vertex_edges(txn, vertex.id, EdgeDirection::Outbound, None, &None);
+            let inbound = vertex_edges(txn, vertex.id, EdgeDirection::Inbound, None, &None)
+                .map(|item| item.map(|edge| edge.reversed()));
+            for item in outbound.chain(inbound).collect::<Result<Vec<Edge>>>()? {
+                if deleted_edges.insert(item.clone()) {
+                    edge_events(txn, item, &mut events)?;
+                }
             }
+            for item in txn.all_vertex_properties_for_vertex(vertex)? {
+                let (name, _) = item?;
+                events.push(ChangeEvent::VertexPropertyDeleted(vertex.id, name));
+            }
+            events.push(ChangeEvent::VertexDeleted(vertex.clone()));
         }
-        Query::EdgeWithPropertyValue(ref q) => {
-            if let Some(iter) = (*txn).edges_with_property_value(q.name, &q.value)? {
-                QueryOutputValue::Edges(iter.collect::<Result<Vec<Edge>>>()?)
-            } else {
-                return Err(Error::NotIndexed);
+        Ok(events)
+    }
+
+    // Builds change events for a bulk insert, unless nothing would receive
+    // them. Vertices and edges that already exist are overwritten rather
+    // than created, so they don't get creation events.
+    fn capture_bulk_insert(&self, items: &[BulkInsertItem]) -> Result<Vec<ChangeEvent>> {
+        if !self.changes.is_active() {
+            return Ok(Vec::new());
+        }
+
+        // The iterators are drained before this returns.
+        let txn = unsafe { &*(&self.txn as *const D::Transaction<'a>) };
+        let mut vertex_ids = Vec::new();
+        let mut edges = Vec::new();
+        for item in items {
+            match item {
+                BulkInsertItem::Vertex(vertex) | BulkInsertItem::ExpiringVertex(vertex, _) => {
+                    vertex_ids.push(vertex.id)
+                }
+                BulkInsertItem::Edge(edge) | BulkInsertItem::ExpiringEdge(edge, _) => edges.push(edge.clone()),
+                _ => {}
             }
         }
-        Query::PipeWithPropertyPresence(ref q) => {
-            query(txn, &q.inner, output)?;
-            let piped_values = output.pop().unwrap();
-
-            let values = match piped_values {
-                QueryOutputValue::Edges(ref piped_edges) => {
-                    let edges_with_property = match (*txn).edges_with_property(q.name)? {
-                        Some(iter) => iter.collect::<Result<HashSet<Edge>>>()?,
-                        None => return Err(Error::NotIndexed),
-                    };
-                    let iter = piped_edges.iter().filter(move |e| {
-                        let contains = edges_with_property.contains(e);
-                        (q.exists && contains) || (!q.exists && !contains)
-                    });
-                    QueryOutputValue::Edges(iter.cloned().collect())
+        let mut existing_vertex_ids = txn
+            .specific_vertices(vertex_ids)?
+            .map(|item| item.map(|vertex| vertex.id))
+            .collect::<Result<HashSet<Uuid>>>()?;
+        let mut existing_edges = txn.specific_edges(edges)?.collect::<Result<HashSet<Edge>>>()?;
+
+        Ok(items
+            .iter()
+            .filter(|item| match item {
+                BulkInsertItem::Vertex(vertex) | BulkInsertItem::ExpiringVertex(vertex, _) => {
+                    existing_vertex_ids.insert(vertex.id)
+                }
+                BulkInsertItem::Edge(edge) | BulkInsertItem::ExpiringEdge(edge, _) => {
+                    existing_edges.insert(edge.clone())
+                }
+                _ => true,
+            })
+            .map(ChangeEvent::from)
+            .collect())
+    }
+
+    // Records the change events for a successful write.
+    fn record_changes(&mut self, events: Vec<ChangeEvent>) {
+        match self.pending_changes {
+            Some(ref mut pending) => pending.extend(events),
+            None => self.changes.publish(events),
+        }
+    }
+
+    /// Commits the transaction, atomically persisting all of its writes.
+    pub fn commit(mut self) -> Result<()> {
+        self.txn.commit()?;
+        if let Some(pending) = self.pending_changes.take() {
+            self.changes.publish(pending);
+        }
+        Ok(())
+    }
+
+    /// Rolls back the transaction, discarding all of its writes.
+    pub fn rollback(mut self) -> Result<()> {
+        self.txn.rollback()
+    }
+}
+
+/// Value(s) returned from a query, pulled lazily from the datastore.
+pub enum QueryOutputIter<'a> {
+    /// Vertices.
+    Vertices(DynIter<'a, Vertex>),
+    /// Edges.
+    Edges(DynIter<'a, Edge>),
+    /// A Count.
+    Count(u64),
+    /// Vertex properties.
+    VertexProperties(DynIter<'a, VertexProperties>),
+    /// Edge properties.
+    EdgeProperties(DynIter<'a, EdgeProperties>),
+    /// Paths.
+    Paths(DynIter<'a, Path>),
+    /// Paths through pipes.
+    PipePaths(DynIter<'a, PipePath>),
+}
+
+impl<'a> QueryOutputIter<'a> {
+    /// Pulls all of the remaining values, materializing them.
+    pub fn into_value(self) -> Result<QueryOutputValue> {
+        Ok(match self {
+            QueryOutputIter::Vertices(iter) => QueryOutputValue::Vertices(iter.collect::<Result<Vec<Vertex>>>()?),
+            QueryOutputIter::Edges(iter) => QueryOutputValue::Edges(iter.collect::<Result<Vec<Edge>>>()?),
+            QueryOutputIter::Count(count) => QueryOutputValue::Count(count),
+            QueryOutputIter::VertexProperties(iter) => {
+                QueryOutputValue::VertexProperties(iter.collect::<Result<Vec<VertexProperties>>>()?)
+            }
+            QueryOutputIter::EdgeProperties(iter) => {
+                QueryOutputValue::EdgeProperties(iter.collect::<Result<Vec<EdgeProperties>>>()?)
+            }
+            QueryOutputIter::Paths(iter) => QueryOutputValue::Paths(iter.collect::<Result<Vec<Path>>>()?),
+            QueryOutputIter::PipePaths(iter) => QueryOutputValue::PipePaths(iter.collect::<Result<Vec<PipePath>>>()?),
+        })
+    }
+
+    fn count(self) -> Result<u64> {
+        fn count_iter<T>(iter: DynIter<T>) -> Result<u64> {
+            let mut count = 0;
+            for result in iter {
+                result?;
+                count += 1;
+            }
+            Ok(count)
+        }
+
+        match self {
+            QueryOutputIter::Vertices(iter) => count_iter(iter),
+            QueryOutputIter::Edges(iter) => count_iter(iter),
+            QueryOutputIter::VertexProperties(iter) => count_iter(iter),
+            QueryOutputIter::EdgeProperties(iter) => count_iter(iter),
+            QueryOutputIter::Paths(iter) => count_iter(iter),
+            QueryOutputIter::PipePaths(iter) => count_iter(iter),
+            QueryOutputIter::Count(_) => Err(Error::OperationOnQuery),
+        }
+    }
+
+    // Ties the iterators to `owner`, so that it's kept alive until they're
+    // dropped.
+    fn owned_by<O: 'a>(self, owner: &Rc<O>) -> Self {
+        fn own<'a, T: 'a, O: 'a>(iter: DynIter<'a, T>, owner: &Rc<O>) -> DynIter<'a, T> {
+            Box::new(OwnedIter {
+                iter,
+                _owner: owner.clone(),
+            })
+        }
+
+        match self {
+            QueryOutputIter::Vertices(iter) => QueryOutputIter::Vertices(own(iter, owner)),
+            QueryOutputIter::Edges(iter) => QueryOutputIter::Edges(own(iter, owner)),
+            QueryOutputIter::Count(count) => QueryOutputIter::Count(count),
+            QueryOutputIter::VertexProperties(iter) => QueryOutputIter::VertexProperties(own(iter, owner)),
+            QueryOutputIter::EdgeProperties(iter) => QueryOutputIter::EdgeProperties(own(iter, owner)),
+            QueryOutputIter::Paths(iter) => QueryOutputIter::Paths(own(iter, owner)),
+            QueryOutputIter::PipePaths(iter) => QueryOutputIter::PipePaths(own(iter, owner)),
+        }
+    }
+}
+
+impl<'a> From<QueryOutputValue> for QueryOutputIter<'a> {
+    fn from(value: QueryOutputValue) -> Self {
+        match value {
+            QueryOutputValue::Vertices(vertices) => QueryOutputIter::Vertices(Box::new(vertices.into_iter().map(Ok))),
+            QueryOutputValue::Edges(edges) => QueryOutputIter::Edges(Box::new(edges.into_iter().map(Ok))),
+            QueryOutputValue::Count(count) => QueryOutputIter::Count(count),
+            QueryOutputValue::VertexProperties(props) => {
+                QueryOutputIter::VertexProperties(Box::new(props.into_iter().map(Ok)))
+            }
+            QueryOutputValue::EdgeProperties(props) => {
+                QueryOutputIter::EdgeProperties(Box::new(props.into_iter().map(Ok)))
+            }
+            QueryOutputValue::Paths(paths) => QueryOutputIter::Paths(Box::new(paths.into_iter().map(Ok))),
+            QueryOutputValue::PipePaths(paths) => QueryOutputIter::PipePaths(Box::new(paths.into_iter().map(Ok))),
+        }
+    }
+}
+
+// An iterator that keeps whatever it borrows from alive. Fields are dropped
+// in declaration order, so the iterator goes before its owner.
+struct OwnedIter<'a, T, O> {
+    iter: DynIter<'a, T>,
+    _owner: Rc<O>,
+}
+
+impl<'a, T, O> Iterator for OwnedIter<'a, T, O> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+// Flattens the result of a fallible transaction call into an iterator, so
+// that it can be used from within a lazy iterator chain.
+fn flatten_result<'a, T: 'a>(result: Result<DynIter<'a, T>>) -> DynIter<'a, T> {
+    match result {
+        Ok(iter) => iter,
+        Err(err) => Box::new(iter::once(Err(err))),
+    }
+}
+
+// Checks for vertices and edges that have expired, but haven't been deleted
+// by `expire_now` yet. Queries hide these, along with the edges of expired
+// vertices. Expiries are looked up as values are read, rather than loaded up
+// front, since there may be many more of them than a query touches.
+type VertexExpiryLookup<'a> = Box<dyn Fn(Uuid) -> Result<Option<SystemTime>> + 'a>;
+type EdgeExpiryLookup<'a> = Box<dyn Fn(&Edge) -> Result<Option<SystemTime>> + 'a>;
+
+struct Expired<'a> {
+    now: SystemTime,
+    vertex_expiry: VertexExpiryLookup<'a>,
+    edge_expiry: EdgeExpiryLookup<'a>,
+    // Vertices are checked once for each of their edges, so the results are
+    // kept.
+    hidden_vertices: RefCell<HashMap<Uuid, bool>>,
+}
+
+impl<'a> Expired<'a> {
+    // Returns `None` if nothing has expired, so that queries can skip
+    // filtering altogether.
+    fn load<T: Transaction<'a> + 'a>(txn: &'a T, now: SystemTime) -> Result<Option<Rc<Self>>> {
+        let nothing_expired = txn.expired_vertices(now)?.next().transpose()?.is_none()
+            && txn.expired_edges(now)?.next().transpose()?.is_none();
+        if nothing_expired {
+            Ok(None)
+        } else {
+            Ok(Some(Rc::new(Self {
+                now,
+                vertex_expiry: Box::new(move |id| txn.vertex_expiry(id)),
+                edge_expiry: Box::new(move |edge| txn.edge_expiry(edge)),
+                hidden_vertices: RefCell::new(HashMap::new()),
+            })))
+        }
+    }
+
+    fn has_expired(&self, expires_at: Option<SystemTime>) -> bool {
+        matches!(expires_at, Some(expires_at) if expires_at <= self.now)
+    }
+
+    fn hides_vertex(&self, id: Uuid) -> Result<bool> {
+        if let Some(hidden) = self.hidden_vertices.borrow().get(&id) {
+            return Ok(*hidden);
+        }
+        let hidden = self.has_expired((self.vertex_expiry)(id)?);
+        self.hidden_vertices.borrow_mut().insert(id, hidden);
+        Ok(hidden)
+    }
+
+    fn hides_edge(&self, edge: &Edge) -> Result<bool> {
+        Ok(self.has_expired((self.edge_expiry)(edge)?)
+            || self.hides_vertex(edge.outbound_id)?
+            || self.hides_vertex(edge.inbound_id)?)
+    }
+}
+
+// Keeps a value unless it's hidden, passing along errors from either.
+fn unless_hidden<T>(r: Result<T>, hidden: impl FnOnce(&T) -> Result<bool>) -> Option<Result<T>> {
+    match r {
+        Ok(value) => match hidden(&value) {
+            Ok(true) => None,
+            Ok(false) => Some(Ok(value)),
+            Err(err) => Some(Err(err)),
+        },
+        Err(err) => Some(Err(err)),
+    }
+}
+
+// Tracks a query's resource usage against its budget. Iterators hold on to
+// the meter, so that lazily pulled values are counted too.
+struct Meter {
+    budget: QueryBudget,
+    deadline: Option<Instant>,
+    rows: Cell<u64>,
+    edges_scanned: Cell<u64>,
+}
+
+impl Meter {
+    // Returns `None` if the budget is unbounded, so that queries can skip
+    // metering altogether.
+    fn start(budget: QueryBudget) -> Option<Rc<Self>> {
+        if budget == QueryBudget::default() {
+            return None;
+        }
+        Some(Rc::new(Self {
+            budget,
+            // A timeout too far in the future to represent is unbounded.
+            deadline: budget.timeout.and_then(|timeout| Instant::now().checked_add(timeout)),
+            rows: Cell::new(0),
+            edges_scanned: Cell::new(0),
+        }))
+    }
+
+    fn check_deadline(&self) -> Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(Error::QueryBudgetExceeded(QueryLimit::Deadline)),
+            _ => Ok(()),
+        }
+    }
+
+    // Counts one unit of usage against `limit`, which is either `Rows` or
+    // `EdgesScanned`.
+    fn add(&self, limit: QueryLimit) -> Result<()> {
+        self.check_deadline()?;
+        let (counter, max) = match limit {
+            QueryLimit::Rows => (&self.rows, self.budget.max_rows),
+            QueryLimit::EdgesScanned => (&self.edges_scanned, self.budget.max_edges_scanned),
+            QueryLimit::Deadline => unreachable!(),
+        };
+        let count = counter.get() + 1;
+        counter.set(count);
+        match max {
+            Some(max) if count > max => Err(Error::QueryBudgetExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// An iterator that counts the values it yields against a meter. Once the
+// budget is exceeded, it yields the error and then stops.
+struct MeteredIter<'a, T> {
+    iter: DynIter<'a, T>,
+    meter: Rc<Meter>,
+    limit: QueryLimit,
+    exceeded: bool,
+}
+
+impl<'a, T> Iterator for MeteredIter<'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exceeded {
+            return None;
+        }
+        match self.iter.next()? {
+            Ok(value) => match self.meter.add(self.limit) {
+                Ok(()) => Some(Ok(value)),
+                Err(err) => {
+                    self.exceeded = true;
+                    Some(Err(err))
                 }
-                QueryOutputValue::Vertices(ref piped_vertices) => {
-                    let vertices_with_property = match (*txn).vertex_ids_with_property(q.name)? {
-                        Some(iter) => iter.collect::<Result<HashSet<Uuid>>>()?,
-                        None => return Err(Error::NotIndexed),
-                    };
-                    let iter = piped_vertices.iter().filter(move |v| {
-                        let contains = vertices_with_property.contains(&v.id);
-                        (q.exists && contains) || (!q.exists && !contains)
-                    });
-                    QueryOutputValue::Vertices(iter.cloned().collect())
+            },
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+fn metered<'a, T: 'a>(iter: DynIter<'a, T>, meter: &Option<Rc<Meter>>, limit: QueryLimit) -> DynIter<'a, T> {
+    match meter {
+        Some(meter) => Box::new(MeteredIter {
+            iter,
+            meter: meter.clone(),
+            limit,
+            exceeded: false,
+        }),
+        None => iter,
+    }
+}
+
+// Counts the values output by a plan node as rows.
+fn metered_rows<'a>(value: QueryOutputIter<'a>, meter: &Option<Rc<Meter>>) -> QueryOutputIter<'a> {
+    match value {
+        QueryOutputIter::Vertices(iter) => QueryOutputIter::Vertices(metered(iter, meter, QueryLimit::Rows)),
+        QueryOutputIter::Edges(iter) => QueryOutputIter::Edges(metered(iter, meter, QueryLimit::Rows)),
+        QueryOutputIter::Count(count) => QueryOutputIter::Count(count),
+        QueryOutputIter::VertexProperties(iter) => {
+            QueryOutputIter::VertexProperties(metered(iter, meter, QueryLimit::Rows))
+        }
+        QueryOutputIter::EdgeProperties(iter) => {
+            QueryOutputIter::EdgeProperties(metered(iter, meter, QueryLimit::Rows))
+        }
+        QueryOutputIter::Paths(iter) => QueryOutputIter::Paths(metered(iter, meter, QueryLimit::Rows)),
+        QueryOutputIter::PipePaths(iter) => QueryOutputIter::PipePaths(metered(iter, meter, QueryLimit::Rows)),
+    }
+}
+
+fn scanned_edges<'a>(iter: DynIter<'a, Edge>, meter: &Option<Rc<Meter>>) -> DynIter<'a, Edge> {
+    metered(iter, meter, QueryLimit::EdgesScanned)
+}
+
+fn live_vertices<'a>(iter: DynIter<'a, Vertex>, expired: &Option<Rc<Expired<'a>>>) -> DynIter<'a, Vertex> {
+    match expired {
+        Some(expired) => {
+            let expired = expired.clone();
+            Box::new(iter.filter_map(move |r| unless_hidden(r, |v| expired.hides_vertex(v.id))))
+        }
+        None => iter,
+    }
+}
+
+fn live_edges<'a>(iter: DynIter<'a, Edge>, expired: &Option<Rc<Expired<'a>>>) -> DynIter<'a, Edge> {
+    match expired {
+        Some(expired) => {
+            let expired = expired.clone();
+            Box::new(iter.filter_map(move |r| unless_hidden(r, |e| expired.hides_edge(e))))
+        }
+        None => iter,
+    }
+}
+
+fn vertices_with_ids<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    ids: impl Iterator<Item = Result<Uuid>> + 'a,
+) -> DynIter<'a, Vertex> {
+    Box::new(ids.flat_map(move |id| flatten_result(id.and_then(|id| txn.specific_vertices(vec![id])))))
+}
+
+fn vertex_properties<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    vertex: &Vertex,
+    name: Option<Identifier>,
+) -> Result<Vec<NamedProperty>> {
+    let mut props = Vec::new();
+    if let Some(name) = name {
+        if let Some(value) = txn.vertex_property(vertex, name)? {
+            props.push(NamedProperty::new(name, value));
+        }
+    } else {
+        for result in txn.all_vertex_properties_for_vertex(vertex)? {
+            let (name, value) = result?;
+            props.push(NamedProperty::new(name, value));
+        }
+    }
+    Ok(props)
+}
+
+fn edge_properties<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    edge: &Edge,
+    name: Option<Identifier>,
+) -> Result<Vec<NamedProperty>> {
+    let mut props = Vec::new();
+    if let Some(name) = name {
+        if let Some(value) = txn.edge_property(edge, name)? {
+            props.push(NamedProperty::new(name, value));
+        }
+    } else {
+        for result in txn.all_edge_properties_for_edge(edge)? {
+            let (name, value) = result?;
+            props.push(NamedProperty::new(name, value));
+        }
+    }
+    Ok(props)
+}
+
+// Gets the edges of a vertex in a direction, as they're stored. Inbound
+// edges are reversed, so their `outbound_id` is the vertex's id either way.
+fn vertex_edges<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    id: Uuid,
+    direction: EdgeDirection,
+    t: Option<Identifier>,
+    meter: &Option<Rc<Meter>>,
+) -> DynIter<'a, Edge> {
+    let lower_bound = Edge::new(id, t.unwrap_or_default(), Uuid::default());
+    let iter = flatten_result(if direction == EdgeDirection::Outbound {
+        txn.range_edges(lower_bound)
+    } else {
+        txn.range_reversed_edges(lower_bound)
+    });
+    let mut iter = scanned_edges(iter, meter);
+
+    iter = Box::new(iter.take_while(move |r| match r {
+        Ok(e) => e.outbound_id == id,
+        Err(_) => true,
+    }));
+
+    if let Some(t) = t {
+        iter = Box::new(iter.filter(move |r| match r {
+            Ok(e) => e.t == t,
+            Err(_) => true,
+        }));
+    }
+
+    iter
+}
+
+// Walks breadth-first from a set of vertices. Each depth is read in full
+// before its vertices are output, so that vertices reachable through several
+// others are only output once.
+struct Traversal<'a, T: Transaction<'a> + 'a> {
+    txn: &'a T,
+    direction: EdgeDirection,
+    t: Option<Identifier>,
+    min_depth: u32,
+    max_depth: u32,
+    limit: u32,
+    expired: Option<Rc<Expired<'a>>>,
+    meter: Option<Rc<Meter>>,
+    visited: HashSet<Uuid>,
+    depth: u32,
+    level: Vec<Vertex>,
+    pos: usize,
+    done: bool,
+}
+
+impl<'a, T: Transaction<'a> + 'a> Traversal<'a, T> {
+    // Reads the vertices one edge away from the current depth that haven't
+    // been visited yet.
+    fn next_level(&mut self) -> Result<Vec<Vertex>> {
+        let mut ids = Vec::new();
+        'level: for vertex in &self.level {
+            let edges = vertex_edges(self.txn, vertex.id, self.direction, self.t, &self.meter);
+            for edge in live_edges(edges, &self.expired) {
+                if ids.len() >= self.limit as usize {
+                    break 'level;
                 }
-                _ => {
-                    return Err(Error::OperationOnQuery);
+                let id = edge?.inbound_id;
+                if self.visited.insert(id) {
+                    ids.push(id);
                 }
-            };
+            }
+        }
 
-            if let Query::Include(_) = *q.inner {
-                // keep the value exported
-                output.push(piped_values);
+        // Edges to expired vertices are hidden, so these are all live
+        self.txn.specific_vertices(ids)?.collect()
+    }
+}
+
+impl<'a, T: Transaction<'a> + 'a> Iterator for Traversal<'a, T> {
+    type Item = Result<Vertex>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.depth >= self.min_depth {
+                if let Some(vertex) = self.level.get(self.pos) {
+                    self.pos += 1;
+                    return Some(Ok(vertex.clone()));
+                }
+            }
+            if self.depth >= self.max_depth || self.level.is_empty() {
+                break;
             }
+            match self.next_level() {
+                Ok(level) => {
+                    self.level = level;
+                    self.pos = 0;
+                    self.depth += 1;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        self.done = true;
+        None
+    }
+}
 
-            values
-        }
-        Query::PipeWithPropertyValue(ref q) => {
-            query(txn, &q.inner, output)?;
-            let piped_values = output.pop().unwrap();
-
-            let values = match piped_values {
-                QueryOutputValue::Edges(ref piped_edges) => {
-                    let edges = match (*txn).edges_with_property_value(q.name, &q.value)? {
-                        Some(iter) => iter.collect::<Result<HashSet<Edge>>>()?,
-                        None => return Err(Error::NotIndexed),
-                    };
-                    let iter = piped_edges.iter().filter(move |e| {
-                        let contains = edges.contains(e);
-                        (q.equal && contains) || (!q.equal && !contains)
-                    });
-                    QueryOutputValue::Edges(iter.cloned().collect())
+// Finds the shortest path with a breadth-first search from both ends, one
+// depth at a time. Whichever end has fewer vertices at its current depth is
+// expanded next, which keeps the search small when one end has far more
+// edges than the other.
+fn shortest_path<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    source_id: Uuid,
+    target_id: Uuid,
+    t: Option<Identifier>,
+    max_depth: u32,
+    expired: &Option<Rc<Expired<'a>>>,
+    meter: &Option<Rc<Meter>>,
+) -> Result<Option<Path>> {
+    let endpoints = live_vertices(txn.specific_vertices(vec![source_id, target_id])?, expired)
+        .map(|r| r.map(|v| v.id))
+        .collect::<Result<HashSet<Uuid>>>()?;
+    if !endpoints.contains(&source_id) || !endpoints.contains(&target_id) {
+        return Ok(None);
+    }
+
+    // The edge that each visited vertex was reached through, from each end
+    let mut forward: HashMap<Uuid, Option<Edge>> = HashMap::from([(source_id, None)]);
+    let mut backward: HashMap<Uuid, Option<Edge>> = HashMap::from([(target_id, None)]);
+    let mut forward_frontier = vec![source_id];
+    let mut backward_frontier = vec![target_id];
+    let mut meeting_id = if source_id == target_id { Some(source_id) } else { None };
+    let mut depth = 0;
+
+    while meeting_id.is_none() && depth < max_depth && !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+        let is_forward = forward_frontier.len() <= backward_frontier.len();
+        let (frontier, visited, other, direction) = if is_forward {
+            (&mut forward_frontier, &mut forward, &backward, EdgeDirection::Outbound)
+        } else {
+            (&mut backward_frontier, &mut backward, &forward, EdgeDirection::Inbound)
+        };
+
+        let mut next_frontier = Vec::new();
+        'level: for id in frontier.iter() {
+            for edge in live_edges(vertex_edges(txn, *id, direction, t, meter), expired) {
+                let edge = edge?;
+                let neighbor_id = edge.inbound_id;
+                if visited.contains_key(&neighbor_id) {
+                    continue;
                 }
-                QueryOutputValue::Vertices(ref piped_vertices) => {
-                    let vertex_ids = match (*txn).vertex_ids_with_property_value(q.name, &q.value)? {
-                        Some(iter) => iter.collect::<Result<HashSet<Uuid>>>()?,
-                        None => return Err(Error::NotIndexed),
-                    };
-                    let iter = piped_vertices.iter().filter(move |v| {
-                        let contains = vertex_ids.contains(&v.id);
-                        (q.equal && contains) || (!q.equal && !contains)
-                    });
-                    QueryOutputValue::Vertices(iter.cloned().collect())
+                let edge = if is_forward { edge } else { edge.reversed() };
+                visited.insert(neighbor_id, Some(edge));
+                // The first meeting is always on a shortest path, since
+                // every shorter path would've met at an earlier depth
+                if other.contains_key(&neighbor_id) {
+                    meeting_id = Some(neighbor_id);
+                    break 'level;
                 }
-                _ => {
-                    return Err(Error::OperationOnQuery);
+                next_frontier.push(neighbor_id);
+            }
+        }
+        *frontier = next_frontier;
+        depth += 1;
+    }
+
+    let meeting_id = match meeting_id {
+        Some(meeting_id) => meeting_id,
+        None => return Ok(None),
+    };
+
+    let mut edges = Vec::new();
+    let mut id = meeting_id;
+    while let Some(Some(edge)) = forward.get(&id) {
+        id = edge.outbound_id;
+        edges.push(edge.clone());
+    }
+    edges.reverse();
+    let mut id = meeting_id;
+    while let Some(Some(edge)) = backward.get(&id) {
+        id = edge.inbound_id;
+        edges.push(edge.clone());
+    }
+
+    let ids = iter::once(source_id)
+        .chain(edges.iter().map(|e| e.inbound_id))
+        .collect();
+    let vertices = txn.specific_vertices(ids)?.collect::<Result<Vec<Vertex>>>()?;
+    Ok(Some(Path::new(vertices, edges)))
+}
+
+// A path reached by a weighted search. Paths are kept in an arena, and each
+// one extends an earlier path by an edge.
+struct WeightedStep {
+    id: Uuid,
+    cost: f64,
+    prev: Option<(usize, Edge)>,
+}
+
+// An index into the arena of paths, ordered so that a `BinaryHeap` pops the
+// cheapest path first, breaking ties by the fewest edges.
+struct WeightedEntry {
+    cost: f64,
+    depth: u32,
+    index: usize,
+}
+
+impl PartialEq for WeightedEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for WeightedEntry {}
+
+impl PartialOrd for WeightedEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for WeightedEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.depth.cmp(&self.depth))
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+// Finds the cheapest path with Dijkstra's algorithm. Because of the maximum
+// depth, a vertex can be expanded more than once, but only again by a path
+// with fewer edges than the cheaper ones that already expanded it.
+#[allow(clippy::too_many_arguments)]
+fn weighted_shortest_path<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    source_id: Uuid,
+    target_id: Uuid,
+    t: Option<Identifier>,
+    max_depth: u32,
+    weight: &EdgeWeight,
+    expired: &Option<Rc<Expired<'a>>>,
+    meter: &Option<Rc<Meter>>,
+) -> Result<Option<Path>> {
+    let endpoints = live_vertices(txn.specific_vertices(vec![source_id, target_id])?, expired)
+        .map(|r| r.map(|v| v.id))
+        .collect::<Result<HashSet<Uuid>>>()?;
+    if !endpoints.contains(&source_id) || !endpoints.contains(&target_id) {
+        return Ok(None);
+    }
+
+    let default_weight = match weight.invalid {
+        InvalidWeight::Default(ref value) => {
+            Some(EdgeWeight::parse(value).ok_or(Error::Invalid(ValidationError::InvalidValue))?)
+        }
+        _ => None,
+    };
+
+    let mut steps = vec![WeightedStep {
+        id: source_id,
+        cost: 0.0,
+        prev: None,
+    }];
+    let mut heap = BinaryHeap::from([WeightedEntry {
+        cost: 0.0,
+        depth: 0,
+        index: 0,
+    }]);
+    // The fewest edges of the paths that each vertex was expanded from
+    let mut expanded: HashMap<Uuid, u32> = HashMap::new();
+
+    let found = loop {
+        let entry = match heap.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let id = steps[entry.index].id;
+        if id == target_id {
+            break entry.index;
+        }
+        if expanded.get(&id).is_some_and(|depth| *depth <= entry.depth) {
+            continue;
+        }
+        expanded.insert(id, entry.depth);
+        if entry.depth >= max_depth {
+            continue;
+        }
+
+        for edge in live_edges(vertex_edges(txn, id, EdgeDirection::Outbound, t, meter), expired) {
+            let edge = edge?;
+            let value = txn.edge_property(&edge, weight.name)?;
+            let edge_weight = match value.as_ref().and_then(EdgeWeight::parse).or(default_weight) {
+                Some(edge_weight) => edge_weight,
+                None if weight.invalid == InvalidWeight::Skip => continue,
+                None => {
+                    return Err(Error::InvalidWeight {
+                        edge,
+                        name: weight.name,
+                    })
                 }
             };
 
-            if let Query::Include(_) = *q.inner {
-                // keep the value exported
-                output.push(piped_values);
-            }
+            let cost = entry.cost + edge_weight;
+            heap.push(WeightedEntry {
+                cost,
+                depth: entry.depth + 1,
+                index: steps.len(),
+            });
+            steps.push(WeightedStep {
+                id: edge.inbound_id,
+                cost,
+                prev: Some((entry.index, edge)),
+            });
+        }
+    };
+
+    let cost = steps[found].cost;
+    let mut edges = Vec::new();
+    let mut index = found;
+    while let Some((prev, ref edge)) = steps[index].prev {
+        edges.push(edge.clone());
+        index = prev;
+    }
+    edges.reverse();
+
+    let ids = iter::once(source_id)
+        .chain(edges.iter().map(|e| e.inbound_id))
+        .collect();
+    let vertices = txn.specific_vertices(ids)?.collect::<Result<Vec<Vertex>>>()?;
+    Ok(Some(Path::with_cost(vertices, edges, cost)))
+}
+
+// Sorts values by their property values for an order by, then skips and
+// limits them. Values without a property value are nulls. The sort is
+// stable, so ties keep their input order.
+fn sorted<'a, V: 'a>(
+    mut keyed: Vec<(Option<Json>, V)>,
+    order: SortOrder,
+    nulls: NullsOrder,
+    offset: u32,
+    limit: u32,
+) -> DynIter<'a, V> {
+    let null_ordering = match nulls {
+        NullsOrder::First => Ordering::Less,
+        NullsOrder::Last => Ordering::Greater,
+    };
+    keyed.sort_by(|(first, _), (second, _)| match (first, second) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => null_ordering,
+        (Some(_), None) => null_ordering.reverse(),
+        (Some(first), Some(second)) => match order {
+            SortOrder::Ascending => first.total_cmp(second),
+            SortOrder::Descending => second.total_cmp(first),
+        },
+    });
+    Box::new(
+        keyed
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|(_, value)| Ok(value)),
+    )
+}
 
-            values
-        }
-        Query::AllEdge => {
-            let iter = (*txn).all_edges()?;
-            QueryOutputValue::Edges(iter.collect::<Result<Vec<Edge>>>()?)
-        }
-        Query::SpecificEdge(ref q) => {
-            let iter = (*txn).specific_edges(q.edges.clone())?;
-            QueryOutputValue::Edges(iter.collect::<Result<Vec<Edge>>>()?)
-        }
-        Query::Include(ref q) => {
-            query(txn, &q.inner, output)?;
-            output.pop().unwrap()
-        }
-        Query::Count(ref q) => {
-            let count = match &*q.inner {
-                // These paths are optimized
-                Query::AllVertex => (*txn).vertex_count(),
-                Query::AllEdge => (*txn).edge_count(),
-                q => {
-                    query(txn, q, output)?;
-                    let piped_values = output.pop().unwrap();
-                    let len = match piped_values {
-                        QueryOutputValue::Vertices(ref v) => v.len(),
-                        QueryOutputValue::Edges(ref e) => e.len(),
-                        QueryOutputValue::VertexProperties(ref p) => p.len(),
-                        QueryOutputValue::EdgeProperties(ref p) => p.len(),
-                        _ => return Err(Error::OperationOnQuery),
-                    };
-                    if let Query::Include(_) = q {
-                        // keep the value exported
-                        output.push(piped_values);
+// Reads the ids of the vertices that match a property index.
+fn indexed_vertex_ids<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    name: Identifier,
+    predicate: &IndexPredicate,
+) -> Result<HashSet<Uuid>> {
+    let ids = match predicate {
+        IndexPredicate::Presence => txn.vertex_ids_with_property(name)?,
+        IndexPredicate::Value(value) => txn.vertex_ids_with_property_value(name, value)?,
+        IndexPredicate::Range(range) => txn.vertex_ids_with_property_range(name, range)?,
+    };
+    ids.ok_or(Error::NotIndexed)?.collect()
+}
+
+// Reads the edges that match a property index.
+fn indexed_edges<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    name: Identifier,
+    predicate: &IndexPredicate,
+    meter: &Option<Rc<Meter>>,
+) -> Result<HashSet<Edge>> {
+    let edges = match predicate {
+        IndexPredicate::Presence => txn.edges_with_property(name)?,
+        IndexPredicate::Value(value) => txn.edges_with_property_value(name, value)?,
+        IndexPredicate::Range(range) => txn.edges_with_property_range(name, range)?,
+    };
+    scanned_edges(edges.ok_or(Error::NotIndexed)?, meter).collect()
+}
+
+// Runs a plan node whose output is consumed by another one. If the node's
+// output is exported, it's materialized so that it can be both exported and
+// consumed.
+fn piped<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    plan: QueryPlan,
+    expired: &Option<Rc<Expired<'a>>>,
+    meter: &Option<Rc<Meter>>,
+    output: &mut Vec<QueryOutputIter<'a>>,
+) -> Result<QueryOutputIter<'a>> {
+    let exported = plan.exported;
+    execute(txn, plan, expired, meter, output)?;
+    let piped_values = output.pop().unwrap();
+
+    if exported {
+        // keep the value exported
+        let value = piped_values.into_value()?;
+        output.push(value.clone().into());
+        Ok(value.into())
+    } else {
+        Ok(piped_values)
+    }
+}
+
+fn execute<'a, T: Transaction<'a> + 'a>(
+    txn: &'a T,
+    plan: QueryPlan,
+    expired: &Option<Rc<Expired<'a>>>,
+    meter: &Option<Rc<Meter>>,
+    output: &mut Vec<QueryOutputIter<'a>>,
+) -> Result<()> {
+    let input = match plan.input {
+        Some(input) => Some(piped(txn, *input, expired, meter, output)?),
+        None => None,
+    };
+    if let Some(meter) = meter {
+        meter.check_deadline()?;
+    }
+
+    let value = match (plan.op, input) {
+        (PlanOp::VertexScan { start_id }, None) => {
+            let iter = match start_id {
+                Some(start_id) => txn.range_vertices(start_id)?,
+                None => txn.all_vertices()?,
+            };
+            QueryOutputIter::Vertices(live_vertices(iter, expired))
+        }
+        (PlanOp::VertexTypeScan { t, start_id }, None) => {
+            let iter = txn.range_vertices_of_type(t, start_id.unwrap_or_default())?;
+            QueryOutputIter::Vertices(live_vertices(iter, expired))
+        }
+        (PlanOp::VertexLookup { ids }, None) => {
+            QueryOutputIter::Vertices(live_vertices(txn.specific_vertices(ids)?, expired))
+        }
+        (PlanOp::VertexIndexScan { name, predicate }, None) => {
+            let ids = match predicate {
+                IndexPredicate::Presence => txn.vertex_ids_with_property(name)?,
+                IndexPredicate::Value(ref value) => txn.vertex_ids_with_property_value(name, value)?,
+                IndexPredicate::Range(ref range) => txn.vertex_ids_with_property_range(name, range)?,
+            };
+            let ids = ids.ok_or(Error::NotIndexed)?;
+            QueryOutputIter::Vertices(live_vertices(vertices_with_ids(txn, ids), expired))
+        }
+        (PlanOp::VertexCompositeIndexScan { names, values }, None) => {
+            let ids = txn
+                .vertex_ids_with_composite_property_values(&names, &values)?
+                .ok_or(Error::NotIndexed)?;
+            QueryOutputIter::Vertices(live_vertices(vertices_with_ids(txn, ids), expired))
+        }
+        (PlanOp::EdgeScan { start }, None) => {
+            let iter = match start {
+                Some(start) => txn.range_edges(start)?,
+                None => txn.all_edges()?,
+            };
+            QueryOutputIter::Edges(live_edges(scanned_edges(iter, meter), expired))
+        }
+        (PlanOp::EdgeTypeScan { t, start }, None) => {
+            let iter = scanned_edges(txn.range_edges_of_type(t, start)?, meter);
+            QueryOutputIter::Edges(live_edges(iter, expired))
+        }
+        (PlanOp::EdgeLookup { edges }, None) => {
+            let iter = scanned_edges(txn.specific_edges(edges)?, meter);
+            QueryOutputIter::Edges(live_edges(iter, expired))
+        }
+        (PlanOp::EdgeIndexScan { name, predicate }, None) => {
+            let iter = match predicate {
+                IndexPredicate::Presence => txn.edges_with_property(name)?,
+                IndexPredicate::Value(ref value) => txn.edges_with_property_value(name, value)?,
+                IndexPredicate::Range(ref range) => txn.edges_with_property_range(name, range)?,
+            };
+            let iter = scanned_edges(iter.ok_or(Error::NotIndexed)?, meter);
+            QueryOutputIter::Edges(live_edges(iter, expired))
+        }
+        (
+            PlanOp::ShortestPath {
+                source_id,
+                target_id,
+                t,
+                max_depth,
+                weight,
+            },
+            None,
+        ) => {
+            let expired = expired.clone();
+            let meter = meter.clone();
+            let iter = iter::once_with(move || match weight {
+                Some(weight) => {
+                    weighted_shortest_path(txn, source_id, target_id, t, max_depth, &weight, &expired, &meter)
+                }
+                None => shortest_path(txn, source_id, target_id, t, max_depth, &expired, &meter),
+            })
+            .filter_map(Result::transpose);
+            QueryOutputIter::Paths(Box::new(iter))
+        }
+        (PlanOp::PathStart, Some(QueryOutputIter::Vertices(piped_vertices))) => {
+            let iter = piped_vertices.map(|r| r.map(|v| PipePath::new(vec![PathHop::Vertex(v.id)])));
+            QueryOutputIter::PipePaths(Box::new(iter))
+        }
+        (PlanOp::PathStart, Some(QueryOutputIter::Edges(piped_edges))) => {
+            let iter = piped_edges.map(|r| r.map(|e| PipePath::new(vec![PathHop::Edge(e)])));
+            QueryOutputIter::PipePaths(Box::new(iter))
+        }
+        (PlanOp::EdgeExpand { direction, t }, Some(QueryOutputIter::PipePaths(piped_paths))) => {
+            let expired = expired.clone();
+            let meter = meter.clone();
+            let iter = piped_paths.flat_map(move |r| -> DynIter<PipePath> {
+                let path = match r {
+                    Ok(path) => path,
+                    Err(err) => return Box::new(iter::once(Err(err))),
+                };
+                let id = match path.last() {
+                    Some(PathHop::Vertex(id)) => *id,
+                    _ => return Box::new(iter::once(Err(Error::OperationOnQuery))),
+                };
+
+                let mut iter = vertex_edges(txn, id, direction, t, &meter);
+                if direction == EdgeDirection::Inbound {
+                    iter = Box::new(iter.map(move |r| Ok(r?.reversed())));
+                }
+
+                Box::new(live_edges(iter, &expired).map(move |r| {
+                    let mut hops = path.hops.clone();
+                    hops.push(PathHop::Edge(r?));
+                    Ok(PipePath::new(hops))
+                }))
+            });
+
+            QueryOutputIter::PipePaths(Box::new(iter))
+        }
+        (PlanOp::VertexExpand { direction, t }, Some(QueryOutputIter::PipePaths(piped_paths))) => {
+            let expired = expired.clone();
+            let iter = piped_paths.flat_map(move |r| -> DynIter<PipePath> {
+                let path = match r {
+                    Ok(path) => path,
+                    Err(err) => return Box::new(iter::once(Err(err))),
+                };
+                let id = match (path.last(), direction) {
+                    (Some(PathHop::Edge(e)), EdgeDirection::Outbound) => e.outbound_id,
+                    (Some(PathHop::Edge(e)), EdgeDirection::Inbound) => e.inbound_id,
+                    _ => return Box::new(iter::once(Err(Error::OperationOnQuery))),
+                };
+
+                let vertices = live_vertices(flatten_result(txn.specific_vertices(vec![id])), &expired);
+                Box::new(vertices.filter_map(move |r| match r {
+                    Ok(v) if t.is_some_and(|t| v.t != t) => None,
+                    Ok(v) => {
+                        let mut hops = path.hops.clone();
+                        hops.push(PathHop::Vertex(v.id));
+                        Some(Ok(PipePath::new(hops)))
                     }
-                    len as u64
+                    Err(err) => Some(Err(err)),
+                }))
+            });
+
+            QueryOutputIter::PipePaths(Box::new(iter))
+        }
+        (PlanOp::EdgeExpand { direction, t }, Some(QueryOutputIter::Vertices(piped_vertices))) => {
+            let meter = meter.clone();
+            let iter = piped_vertices.flat_map(move |r| -> DynIter<Edge> {
+                let vertex = match r {
+                    Ok(vertex) => vertex,
+                    Err(err) => return Box::new(iter::once(Err(err))),
+                };
+
+                let mut iter = vertex_edges(txn, vertex.id, direction, t, &meter);
+                if direction == EdgeDirection::Inbound {
+                    iter = Box::new(iter.map(move |r| Ok(r?.reversed())));
                 }
+
+                iter
+            });
+
+            QueryOutputIter::Edges(live_edges(Box::new(iter), expired))
+        }
+        (PlanOp::VertexExpand { direction, t }, Some(QueryOutputIter::Edges(piped_edges))) => {
+            let ids = piped_edges.map(move |r| {
+                r.map(|e| match direction {
+                    EdgeDirection::Outbound => e.outbound_id,
+                    EdgeDirection::Inbound => e.inbound_id,
+                })
+            });
+
+            let mut iter = live_vertices(vertices_with_ids(txn, ids), expired);
+
+            if let Some(t) = t {
+                iter = Box::new(iter.filter(move |r| match r {
+                    Ok(v) => v.t == t,
+                    Err(_) => true,
+                }));
+            }
+
+            QueryOutputIter::Vertices(iter)
+        }
+        (
+            PlanOp::Traverse {
+                direction,
+                t,
+                min_depth,
+                max_depth,
+                limit,
+            },
+            Some(QueryOutputIter::Vertices(piped_vertices)),
+        ) => {
+            let mut visited = HashSet::new();
+            let mut level = Vec::new();
+            for r in piped_vertices {
+                let vertex = r?;
+                if visited.insert(vertex.id) {
+                    level.push(vertex);
+                }
+            }
+
+            QueryOutputIter::Vertices(Box::new(Traversal {
+                txn,
+                direction,
+                t,
+                min_depth,
+                max_depth,
+                limit,
+                expired: expired.clone(),
+                meter: meter.clone(),
+                visited,
+                depth: 0,
+                level,
+                pos: 0,
+                done: false,
+            }))
+        }
+        (
+            PlanOp::IndexFilter {
+                name,
+                predicate,
+                negated,
+            },
+            Some(QueryOutputIter::Vertices(piped_vertices)),
+        ) => {
+            let ids = indexed_vertex_ids(txn, name, &predicate)?;
+            let iter = piped_vertices.filter(move |r| match r {
+                Ok(v) => ids.contains(&v.id) != negated,
+                Err(_) => true,
+            });
+            QueryOutputIter::Vertices(Box::new(iter))
+        }
+        (
+            PlanOp::IndexFilter {
+                name,
+                predicate,
+                negated,
+            },
+            Some(QueryOutputIter::Edges(piped_edges)),
+        ) => {
+            let edges = indexed_edges(txn, name, &predicate, meter)?;
+            let iter = piped_edges.filter(move |r| match r {
+                Ok(e) => edges.contains(e) != negated,
+                Err(_) => true,
+            });
+            QueryOutputIter::Edges(Box::new(iter))
+        }
+        (
+            PlanOp::IndexFilter {
+                name,
+                predicate,
+                negated,
+            },
+            Some(QueryOutputIter::PipePaths(piped_paths)),
+        ) => {
+            // Every path ends with the same kind of value, so the first one
+            // decides which index is read
+            let mut piped_paths = piped_paths.peekable();
+            let hops: HashSet<PathHop> = match piped_paths.peek() {
+                Some(Ok(path)) => match path.last() {
+                    Some(PathHop::Vertex(_)) => indexed_vertex_ids(txn, name, &predicate)?
+                        .into_iter()
+                        .map(PathHop::Vertex)
+                        .collect(),
+                    Some(PathHop::Edge(_)) => indexed_edges(txn, name, &predicate, meter)?
+                        .into_iter()
+                        .map(PathHop::Edge)
+                        .collect(),
+                    None => HashSet::new(),
+                },
+                _ => HashSet::new(),
             };
-            QueryOutputValue::Count(count)
+            let iter = piped_paths.filter(move |r| match r {
+                Ok(path) => path.last().is_some_and(|hop| hops.contains(hop)) != negated,
+                Err(_) => true,
+            });
+            QueryOutputIter::PipePaths(Box::new(iter))
+        }
+        (PlanOp::Dedup, Some(QueryOutputIter::Vertices(piped_vertices))) => {
+            let mut seen = HashSet::new();
+            let iter = piped_vertices.filter(move |r| match r {
+                Ok(v) => seen.insert(v.id),
+                Err(_) => true,
+            });
+            QueryOutputIter::Vertices(Box::new(iter))
+        }
+        (PlanOp::Dedup, Some(QueryOutputIter::Edges(piped_edges))) => {
+            let mut seen = HashSet::new();
+            let iter = piped_edges.filter(move |r| match r {
+                Ok(e) => seen.insert(e.clone()),
+                Err(_) => true,
+            });
+            QueryOutputIter::Edges(Box::new(iter))
+        }
+        (
+            PlanOp::OrderBy {
+                name,
+                order,
+                nulls,
+                offset,
+                limit,
+            },
+            Some(QueryOutputIter::Vertices(piped_vertices)),
+        ) => {
+            let keyed = piped_vertices
+                .map(|r| {
+                    let vertex = r?;
+                    let key = txn.vertex_property(&vertex, name)?.filter(|value| !value.is_null());
+                    Ok((key, vertex))
+                })
+                .collect::<Result<Vec<(Option<Json>, Vertex)>>>()?;
+            QueryOutputIter::Vertices(sorted(keyed, order, nulls, offset, limit))
+        }
+        (
+            PlanOp::OrderBy {
+                name,
+                order,
+                nulls,
+                offset,
+                limit,
+            },
+            Some(QueryOutputIter::Edges(piped_edges)),
+        ) => {
+            let keyed = piped_edges
+                .map(|r| {
+                    let edge = r?;
+                    let key = txn.edge_property(&edge, name)?.filter(|value| !value.is_null());
+                    Ok((key, edge))
+                })
+                .collect::<Result<Vec<(Option<Json>, Edge)>>>()?;
+            QueryOutputIter::Edges(sorted(keyed, order, nulls, offset, limit))
+        }
+        (PlanOp::PropertyFetch { name }, Some(QueryOutputIter::Vertices(piped_vertices))) => {
+            let iter = piped_vertices.filter_map(move |r| {
+                let vertex = match r {
+                    Ok(vertex) => vertex,
+                    Err(err) => return Some(Err(err)),
+                };
+                match vertex_properties(txn, &vertex, name) {
+                    Ok(props) if props.is_empty() => None,
+                    Ok(props) => Some(Ok(VertexProperties::new(vertex, props))),
+                    Err(err) => Some(Err(err)),
+                }
+            });
+
+            QueryOutputIter::VertexProperties(Box::new(iter))
+        }
+        (PlanOp::PropertyFetch { name }, Some(QueryOutputIter::Edges(piped_edges))) => {
+            let iter = piped_edges.filter_map(move |r| {
+                let edge = match r {
+                    Ok(edge) => edge,
+                    Err(err) => return Some(Err(err)),
+                };
+                match edge_properties(txn, &edge, name) {
+                    Ok(props) if props.is_empty() => None,
+                    Ok(props) => Some(Ok(EdgeProperties::new(edge, props))),
+                    Err(err) => Some(Err(err)),
+                }
+            });
+
+            QueryOutputIter::EdgeProperties(Box::new(iter))
+        }
+        (PlanOp::Limit(limit), Some(piped_values)) => {
+            let limit = limit as usize;
+            match piped_values {
+                QueryOutputIter::Vertices(iter) => QueryOutputIter::Vertices(Box::new(iter.take(limit))),
+                QueryOutputIter::Edges(iter) => QueryOutputIter::Edges(Box::new(iter.take(limit))),
+                QueryOutputIter::VertexProperties(iter) => {
+                    QueryOutputIter::VertexProperties(Box::new(iter.take(limit)))
+                }
+                QueryOutputIter::EdgeProperties(iter) => QueryOutputIter::EdgeProperties(Box::new(iter.take(limit))),
+                QueryOutputIter::Paths(iter) => QueryOutputIter::Paths(Box::new(iter.take(limit))),
+                QueryOutputIter::PipePaths(iter) => QueryOutputIter::PipePaths(Box::new(iter.take(limit))),
+                QueryOutputIter::Count(_) => return Err(Error::OperationOnQuery),
+            }
         }
+        (PlanOp::Count, Some(piped_values)) => QueryOutputIter::Count(piped_values.count()?),
+        (PlanOp::VertexCount, None) => QueryOutputIter::Count(txn.vertex_count()),
+        (PlanOp::EdgeCount, None) => QueryOutputIter::Count(txn.edge_count()),
+        _ => return Err(Error::OperationOnQuery),
     };
 
-    output.push(value);
+    output.push(metered_rows(value, meter));
     Ok(())
 }
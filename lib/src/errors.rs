@@ -2,6 +2,8 @@ use std::error::Error as StdError;
 use std::fmt;
 use std::result::Result as StdResult;
 
+use crate::{Edge, Identifier, Json};
+
 #[cfg(feature = "rocksdb-datastore")]
 use bincode::Error as BincodeError;
 use rmp_serde::encode::Error as RmpEncodeError;
@@ -31,6 +33,39 @@ pub enum Error {
     /// The operation cannot work with the given query, based off it's output
     /// type (e.g. attempting to delete using a query that outputs a count.)
     OperationOnQuery,
+
+    /// A property write isn't allowed by the schema for the vertex or edge
+    /// type.
+    SchemaViolation {
+        /// The type of the vertex or edge.
+        t: Identifier,
+        /// The name of the property.
+        name: Identifier,
+    },
+
+    /// A property write would give two vertices the same value for a
+    /// property with a unique index.
+    UniqueViolation {
+        /// The name of the property.
+        name: Identifier,
+        /// The duplicated value.
+        value: Json,
+    },
+
+    /// A write was attempted through a read-only transaction.
+    ReadOnlyTransaction,
+
+    /// A query went over one of the limits in its `QueryBudget`.
+    QueryBudgetExceeded(QueryLimit),
+
+    /// A weighted shortest path query followed an edge whose weight is
+    /// missing or isn't a non-negative number.
+    InvalidWeight {
+        /// The edge.
+        edge: Edge,
+        /// The name of the weight property.
+        name: Identifier,
+    },
 }
 
 impl StdError for Error {
@@ -52,6 +87,34 @@ impl fmt::Display for Error {
             Error::Unsupported => write!(f, "functionality not supported"),
             Error::Invalid(ref err) => write!(f, "{err}"),
             Error::OperationOnQuery => write!(f, "the operation cannot work with the given query"),
+            Error::SchemaViolation { t, name } => write!(
+                f,
+                "property `{}` isn't allowed by the schema for type `{}`",
+                name.as_str(),
+                t.as_str()
+            ),
+            Error::UniqueViolation { name, ref value } => write!(
+                f,
+                "value {} for unique property `{}` is already taken",
+                **value,
+                name.as_str()
+            ),
+            Error::ReadOnlyTransaction => write!(f, "cannot write through a read-only transaction"),
+            Error::QueryBudgetExceeded(QueryLimit::Deadline) => write!(f, "query ran past its deadline"),
+            Error::QueryBudgetExceeded(QueryLimit::Rows) => {
+                write!(f, "query output more intermediate rows than its budget allows")
+            }
+            Error::QueryBudgetExceeded(QueryLimit::EdgesScanned) => {
+                write!(f, "query scanned more edges than its budget allows")
+            }
+            Error::InvalidWeight { ref edge, name } => write!(
+                f,
+                "edge ({}, {}, {}) has a missing or invalid weight in property `{}`",
+                edge.outbound_id,
+                edge.t.as_str(),
+                edge.inbound_id,
+                name.as_str()
+            ),
         }
     }
 }
@@ -88,6 +151,17 @@ impl From<ValidationError> for Error {
     }
 }
 
+/// A limit in a `QueryBudget`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryLimit {
+    /// The wall-clock deadline.
+    Deadline,
+    /// The maximum number of intermediate rows.
+    Rows,
+    /// The maximum number of edges scanned.
+    EdgesScanned,
+}
+
 /// A result that might be an `Error`.
 pub type Result<T> = StdResult<T, Error>;
 
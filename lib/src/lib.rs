@@ -19,16 +19,22 @@ pub mod tests;
 #[macro_use]
 pub mod benches;
 
+mod changes;
 mod database;
 mod errors;
 mod memory;
 mod models;
+mod plan;
+mod text;
 pub mod util;
 
+pub use crate::changes::*;
 pub use crate::database::*;
 pub use crate::errors::*;
 pub use crate::memory::*;
 pub use crate::models::*;
+pub use crate::plan::*;
+pub use crate::text::*;
 
 #[cfg(feature = "rocksdb-datastore")]
 mod rdb;
@@ -1,13 +1,21 @@
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::iter;
+use std::ops::{Bound, Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::SystemTime;
 
+use super::wal::{self, FsyncPolicy, Wal};
 use crate::errors::{Error, Result};
 use crate::util;
-use crate::{Database, Datastore, DynIter, Edge, Identifier, Json, Transaction, Vertex};
+use crate::{
+    BulkInsertItem, Database, Datastore, DynIter, Edge, Identifier, Json, JsonRange, Schema, SchemaKind, Transaction,
+    Vertex,
+};
 
 use rmp_serde::decode::Error as RmpDecodeError;
 use serde::{Deserialize, Serialize};
@@ -20,6 +28,65 @@ enum IndexedPropertyMember {
     Edge(Edge),
 }
 
+// Wraps indexed property values so that they're ordered the same way the
+// rocksdb datastore orders its encoded keys, which is what makes range
+// queries possible.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+struct OrderedJson(Json);
+
+impl PartialEq for OrderedJson {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrderedJson {}
+
+impl PartialOrd for OrderedJson {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedJson {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// Iterates over the indexed values that fall within `range`. `BTreeMap::range`
+// panics on inverted bounds, so those are short-circuited to an empty
+// iterator.
+fn ordered_range<'a, V>(
+    container: &'a BTreeMap<OrderedJson, V>,
+    range: &JsonRange,
+) -> Box<dyn Iterator<Item = (&'a OrderedJson, &'a V)> + 'a> {
+    if range.is_empty() {
+        return Box::new(iter::empty());
+    }
+    let start = range.start.as_ref().map(|v| OrderedJson(v.clone()));
+    let end = range.end.as_ref().map(|v| OrderedJson(v.clone()));
+    Box::new(container.range((start, end)))
+}
+
+// Gets the key of a vertex in a composite index, or `None` if the vertex is
+// missing any of the index's properties.
+fn composite_key(
+    vertex_properties: &BTreeMap<(Uuid, Identifier), Json>,
+    vertex_id: Uuid,
+    names: &[Identifier],
+) -> Option<Vec<OrderedJson>> {
+    names
+        .iter()
+        .map(|name| {
+            vertex_properties
+                .get(&(vertex_id, *name))
+                .map(|value| OrderedJson(value.clone()))
+        })
+        .collect()
+}
+
 // All of the data is actually stored in this struct, which is stored
 // internally to the datastore itself. This way, we can wrap a mutex around
 // the entire datastore, rather than on a per-data structure basis, as the
@@ -27,16 +94,583 @@ enum IndexedPropertyMember {
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct InternalMemory {
     vertices: BTreeMap<Uuid, Identifier>,
+    // Derived from `vertices`, so it's rebuilt rather than persisted.
+    #[serde(skip)]
+    vertices_by_type: BTreeMap<Identifier, BTreeSet<Uuid>>,
     edges: BTreeSet<Edge>,
     reversed_edges: BTreeSet<Edge>,
+    // Derived from `edges`, so it's rebuilt rather than persisted.
+    #[serde(skip)]
+    edges_by_type: BTreeMap<Identifier, BTreeSet<Edge>>,
     vertex_properties: BTreeMap<(Uuid, Identifier), Json>,
     edge_properties: BTreeMap<(Edge, Identifier), Json>,
-    property_values: HashMap<Identifier, HashMap<Json, HashSet<IndexedPropertyMember>>>,
+    property_values: HashMap<Identifier, BTreeMap<OrderedJson, HashSet<IndexedPropertyMember>>>,
+    #[serde(default)]
+    composite_property_values: HashMap<Vec<Identifier>, BTreeSet<(Vec<OrderedJson>, Uuid)>>,
+    #[serde(default)]
+    unique_properties: HashSet<Identifier>,
+    #[serde(default)]
+    vertex_schemas: HashMap<Identifier, Schema>,
+    #[serde(default)]
+    edge_schemas: HashMap<Identifier, Schema>,
+    #[serde(default)]
+    vertex_expiries: BTreeMap<Uuid, SystemTime>,
+    #[serde(default)]
+    edge_expiries: BTreeMap<Edge, SystemTime>,
+    // Derived from the expiries, so they're rebuilt rather than persisted.
+    #[serde(skip)]
+    vertices_by_expiry: BTreeSet<(SystemTime, Uuid)>,
+    #[serde(skip)]
+    edges_by_expiry: BTreeSet<(SystemTime, Edge)>,
+}
+
+impl InternalMemory {
+    fn insert_vertex(&mut self, id: Uuid, t: Identifier) {
+        self.vertices.insert(id, t);
+        self.vertices_by_type.entry(t).or_default().insert(id);
+    }
+
+    fn remove_vertex(&mut self, id: Uuid) -> Option<Identifier> {
+        let t = self.vertices.remove(&id)?;
+        if let Some(ids) = self.vertices_by_type.get_mut(&t) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.vertices_by_type.remove(&t);
+            }
+        }
+        Some(t)
+    }
+
+    fn insert_edge(&mut self, edge: Edge) -> bool {
+        if !self.edges.insert(edge.clone()) {
+            return false;
+        }
+        self.reversed_edges.insert(edge.reversed());
+        self.edges_by_type.entry(edge.t).or_default().insert(edge);
+        true
+    }
+
+    fn remove_edge(&mut self, edge: &Edge) -> bool {
+        if !self.edges.remove(edge) {
+            return false;
+        }
+        self.reversed_edges.remove(&edge.reversed());
+        if let Some(edges) = self.edges_by_type.get_mut(&edge.t) {
+            edges.remove(edge);
+            if edges.is_empty() {
+                self.edges_by_type.remove(&edge.t);
+            }
+        }
+        true
+    }
+
+    fn rebuild_derived_indexes(&mut self) {
+        self.vertices_by_type.clear();
+        for (id, t) in self.vertices.iter() {
+            self.vertices_by_type.entry(*t).or_default().insert(*id);
+        }
+        self.edges_by_type.clear();
+        for edge in self.edges.iter() {
+            self.edges_by_type.entry(edge.t).or_default().insert(edge.clone());
+        }
+        self.vertices_by_expiry = self.vertex_expiries.iter().map(|(id, at)| (*at, *id)).collect();
+        self.edges_by_expiry = self
+            .edge_expiries
+            .iter()
+            .map(|(edge, at)| (*at, edge.clone()))
+            .collect();
+    }
+
+    // Sets or clears when a vertex expires, returning the previous expiry.
+    fn set_vertex_expiry(&mut self, id: Uuid, expires_at: Option<SystemTime>) -> Option<SystemTime> {
+        let previous = match expires_at {
+            Some(expires_at) => self.vertex_expiries.insert(id, expires_at),
+            None => self.vertex_expiries.remove(&id),
+        };
+        if let Some(previous) = previous {
+            self.vertices_by_expiry.remove(&(previous, id));
+        }
+        if let Some(expires_at) = expires_at {
+            self.vertices_by_expiry.insert((expires_at, id));
+        }
+        previous
+    }
+
+    // Sets or clears when an edge expires, returning the previous expiry.
+    fn set_edge_expiry(&mut self, edge: &Edge, expires_at: Option<SystemTime>) -> Option<SystemTime> {
+        let previous = match expires_at {
+            Some(expires_at) => self.edge_expiries.insert(edge.clone(), expires_at),
+            None => self.edge_expiries.remove(edge),
+        };
+        if let Some(previous) = previous {
+            self.edges_by_expiry.remove(&(previous, edge.clone()));
+        }
+        if let Some(expires_at) = expires_at {
+            self.edges_by_expiry.insert((expires_at, edge.clone()));
+        }
+        previous
+    }
+
+    fn insert_vertex_property(&mut self, vertex_id: Uuid, name: Identifier, value: Json) {
+        if let Some(property_container) = self.property_values.get_mut(&name) {
+            property_container
+                .entry(OrderedJson(value.clone()))
+                .or_insert_with(HashSet::new)
+                .insert(IndexedPropertyMember::Vertex(vertex_id));
+        }
+        self.vertex_properties.insert((vertex_id, name), value);
+        self.update_composite_property_values(vertex_id, name, true);
+    }
+
+    fn remove_vertex_property(&mut self, vertex_id: Uuid, name: Identifier) -> Option<Json> {
+        self.update_composite_property_values(vertex_id, name, false);
+        let property_value = self.vertex_properties.remove(&(vertex_id, name))?;
+        if let Some(property_container) = self.property_values.get_mut(&name) {
+            let removed = property_container
+                .get_mut(&OrderedJson(property_value.clone()))
+                .unwrap()
+                .remove(&IndexedPropertyMember::Vertex(vertex_id));
+            debug_assert!(removed);
+        }
+        Some(property_value)
+    }
+
+    // Adds or removes a vertex from the composite indexes that include a
+    // given property, based on the vertex's current property values.
+    fn update_composite_property_values(&mut self, vertex_id: Uuid, name: Identifier, insert: bool) {
+        for (names, members) in self.composite_property_values.iter_mut() {
+            if !names.contains(&name) {
+                continue;
+            }
+            if let Some(key) = composite_key(&self.vertex_properties, vertex_id, names) {
+                if insert {
+                    members.insert((key, vertex_id));
+                } else {
+                    members.remove(&(key, vertex_id));
+                }
+            }
+        }
+    }
+
+    fn insert_edge_property(&mut self, edge: Edge, name: Identifier, value: Json) {
+        if let Some(property_container) = self.property_values.get_mut(&name) {
+            property_container
+                .entry(OrderedJson(value.clone()))
+                .or_insert_with(HashSet::new)
+                .insert(IndexedPropertyMember::Edge(edge.clone()));
+        }
+        self.edge_properties.insert((edge, name), value);
+    }
+
+    fn remove_edge_property(&mut self, edge: Edge, name: Identifier) -> Option<Json> {
+        let property_value = self.edge_properties.remove(&(edge.clone(), name))?;
+        if let Some(property_container) = self.property_values.get_mut(&name) {
+            let removed = property_container
+                .get_mut(&OrderedJson(property_value.clone()))
+                .unwrap()
+                .remove(&IndexedPropertyMember::Edge(edge));
+            debug_assert!(removed);
+        }
+        Some(property_value)
+    }
+
+    fn schemas_mut(&mut self, kind: SchemaKind) -> &mut HashMap<Identifier, Schema> {
+        match kind {
+            SchemaKind::Vertex => &mut self.vertex_schemas,
+            SchemaKind::Edge => &mut self.edge_schemas,
+        }
+    }
+
+    fn validate_vertex_property(&self, t: Identifier, name: Identifier, value: &Json) -> Result<()> {
+        match self.vertex_schemas.get(&t) {
+            Some(schema) => schema.validate(t, name, value),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_edge_property(&self, edge: &Edge, name: Identifier, value: &Json) -> Result<()> {
+        match self.edge_schemas.get(&edge.t) {
+            Some(schema) => schema.validate(edge.t, name, value),
+            None => Ok(()),
+        }
+    }
+
+    fn check_unique_vertex_properties<'b, I>(&self, writes: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (Uuid, Identifier, &'b Json)>,
+    {
+        util::check_unique_vertex_properties(&self.unique_properties, writes, |name, value| {
+            let members = self
+                .property_values
+                .get(&name)
+                .and_then(|container| container.get(&OrderedJson(value.clone())));
+            Ok(members
+                .into_iter()
+                .flatten()
+                .filter_map(|member| match member {
+                    IndexedPropertyMember::Vertex(id) => Some(*id),
+                    IndexedPropertyMember::Edge(_) => None,
+                })
+                .collect())
+        })
+    }
+
+    // Reverts a change recorded by a buffered transaction.
+    fn undo(&mut self, change: Change) {
+        match change {
+            Change::CreateVertex(id) => {
+                self.remove_vertex(id);
+            }
+            Change::DeleteVertex(id, t) => {
+                self.insert_vertex(id, t);
+            }
+            Change::CreateEdge(edge) => {
+                self.remove_edge(&edge);
+            }
+            Change::DeleteEdge(edge) => {
+                self.insert_edge(edge);
+            }
+            Change::SetVertexProperty(id, name) => {
+                self.remove_vertex_property(id, name);
+            }
+            Change::DeleteVertexProperty(id, name, value) => {
+                self.insert_vertex_property(id, name, value);
+            }
+            Change::SetEdgeProperty(edge, name) => {
+                self.remove_edge_property(edge, name);
+            }
+            Change::DeleteEdgeProperty(edge, name, value) => {
+                self.insert_edge_property(edge, name, value);
+            }
+            Change::IndexProperty(name) => {
+                self.property_values.remove(&name);
+            }
+            Change::UnindexProperty(name, property_container, was_unique) => {
+                self.property_values.insert(name, property_container);
+                if was_unique {
+                    self.unique_properties.insert(name);
+                }
+            }
+            Change::IndexUniqueProperty(name) => {
+                self.unique_properties.remove(&name);
+            }
+            Change::IndexCompositeProperty(names) => {
+                self.composite_property_values.remove(&names);
+            }
+            Change::SetSchema(kind, t, previous) => {
+                let schemas = self.schemas_mut(kind);
+                match previous {
+                    Some(schema) => schemas.insert(t, schema),
+                    None => schemas.remove(&t),
+                };
+            }
+            Change::SetVertexExpiry(id, previous) => {
+                self.set_vertex_expiry(id, previous);
+            }
+            Change::SetEdgeExpiry(edge, previous) => {
+                self.set_edge_expiry(&edge, previous);
+            }
+        }
+    }
+}
+
+// A change made by a buffered transaction, with enough information to revert
+// it on rollback.
+enum Change {
+    CreateVertex(Uuid),
+    DeleteVertex(Uuid, Identifier),
+    CreateEdge(Edge),
+    DeleteEdge(Edge),
+    SetVertexProperty(Uuid, Identifier),
+    DeleteVertexProperty(Uuid, Identifier, Json),
+    SetEdgeProperty(Edge, Identifier),
+    DeleteEdgeProperty(Edge, Identifier, Json),
+    IndexProperty(Identifier),
+    UnindexProperty(Identifier, BTreeMap<OrderedJson, HashSet<IndexedPropertyMember>>, bool),
+    IndexUniqueProperty(Identifier),
+    IndexCompositeProperty(Vec<Identifier>),
+    SetSchema(SchemaKind, Identifier, Option<Schema>),
+    SetVertexExpiry(Uuid, Option<SystemTime>),
+    SetEdgeExpiry(Edge, Option<SystemTime>),
+}
+
+// A change written to the write-ahead log. As opposed to `Change`, this
+// holds the state after the change, so that it can be replayed.
+#[derive(Serialize, Deserialize)]
+enum LogEntry {
+    CreateVertex(Uuid, Identifier),
+    DeleteVertex(Uuid),
+    CreateEdge(Edge),
+    DeleteEdge(Edge),
+    SetVertexProperty(Uuid, Identifier, Json),
+    DeleteVertexProperty(Uuid, Identifier),
+    SetEdgeProperty(Edge, Identifier, Json),
+    DeleteEdgeProperty(Edge, Identifier),
+    IndexProperty(Identifier),
+    UnindexProperty(Identifier),
+    IndexUniqueProperty(Identifier),
+    IndexCompositeProperty(Vec<Identifier>),
+    SetSchema(SchemaKind, Identifier, Option<Schema>),
+    SetVertexExpiry(Uuid, Option<SystemTime>),
+    SetEdgeExpiry(Edge, Option<SystemTime>),
+}
+
+impl LogEntry {
+    // Creates the log entry for a change that was just made.
+    fn new(internal: &InternalMemory, change: &Change) -> Self {
+        match change {
+            Change::CreateVertex(id) => LogEntry::CreateVertex(*id, internal.vertices[id]),
+            Change::DeleteVertex(id, _) => LogEntry::DeleteVertex(*id),
+            Change::CreateEdge(edge) => LogEntry::CreateEdge(edge.clone()),
+            Change::DeleteEdge(edge) => LogEntry::DeleteEdge(edge.clone()),
+            Change::SetVertexProperty(id, name) => {
+                LogEntry::SetVertexProperty(*id, *name, internal.vertex_properties[&(*id, *name)].clone())
+            }
+            Change::DeleteVertexProperty(id, name, _) => LogEntry::DeleteVertexProperty(*id, *name),
+            Change::SetEdgeProperty(edge, name) => LogEntry::SetEdgeProperty(
+                edge.clone(),
+                *name,
+                internal.edge_properties[&(edge.clone(), *name)].clone(),
+            ),
+            Change::DeleteEdgeProperty(edge, name, _) => LogEntry::DeleteEdgeProperty(edge.clone(), *name),
+            Change::IndexProperty(name) => LogEntry::IndexProperty(*name),
+            Change::UnindexProperty(name, _, _) => LogEntry::UnindexProperty(*name),
+            Change::IndexUniqueProperty(name) => LogEntry::IndexUniqueProperty(*name),
+            Change::IndexCompositeProperty(names) => LogEntry::IndexCompositeProperty(names.clone()),
+            Change::SetSchema(kind, t, _) => {
+                let schemas = match kind {
+                    SchemaKind::Vertex => &internal.vertex_schemas,
+                    SchemaKind::Edge => &internal.edge_schemas,
+                };
+                LogEntry::SetSchema(*kind, *t, schemas.get(t).cloned())
+            }
+            Change::SetVertexExpiry(id, _) => LogEntry::SetVertexExpiry(*id, internal.vertex_expiries.get(id).copied()),
+            Change::SetEdgeExpiry(edge, _) => {
+                LogEntry::SetEdgeExpiry(edge.clone(), internal.edge_expiries.get(edge).copied())
+            }
+        }
+    }
+
+    // Reapplies the change through a transaction that isn't logged.
+    fn replay(self, txn: &mut MemoryTransaction) -> Result<()> {
+        match self {
+            LogEntry::CreateVertex(id, t) => txn.internal.insert_vertex(id, t),
+            LogEntry::DeleteVertex(id) => {
+                txn.internal.remove_vertex(id);
+            }
+            LogEntry::CreateEdge(edge) => {
+                txn.internal.insert_edge(edge);
+            }
+            LogEntry::DeleteEdge(edge) => {
+                txn.internal.remove_edge(&edge);
+            }
+            LogEntry::SetVertexProperty(id, name, value) => txn.internal.insert_vertex_property(id, name, value),
+            LogEntry::DeleteVertexProperty(id, name) => {
+                txn.internal.remove_vertex_property(id, name);
+            }
+            LogEntry::SetEdgeProperty(edge, name, value) => txn.internal.insert_edge_property(edge, name, value),
+            LogEntry::DeleteEdgeProperty(edge, name) => {
+                txn.internal.remove_edge_property(edge, name);
+            }
+            LogEntry::IndexProperty(name) => txn.index_property(name)?,
+            LogEntry::UnindexProperty(name) => txn.unindex_property(name)?,
+            LogEntry::IndexUniqueProperty(name) => {
+                txn.internal.unique_properties.insert(name);
+            }
+            LogEntry::IndexCompositeProperty(names) => txn.index_composite_property(names)?,
+            LogEntry::SetSchema(kind, t, schema) => txn.set_schema(kind, t, schema)?,
+            LogEntry::SetVertexExpiry(id, expires_at) => {
+                txn.internal.set_vertex_expiry(id, expires_at);
+            }
+            LogEntry::SetEdgeExpiry(edge, expires_at) => {
+                txn.internal.set_edge_expiry(&edge, expires_at);
+            }
+        }
+        Ok(())
+    }
+}
+
+// The datastore lock held by a transaction. Read transactions share it, so
+// they can run concurrently.
+enum InternalGuard<'a> {
+    Read(RwLockReadGuard<'a, InternalMemory>),
+    Write(RwLockWriteGuard<'a, InternalMemory>),
+}
+
+impl<'a> Deref for InternalGuard<'a> {
+    type Target = InternalMemory;
+
+    fn deref(&self) -> &InternalMemory {
+        match self {
+            InternalGuard::Read(guard) => guard,
+            InternalGuard::Write(guard) => guard,
+        }
+    }
+}
+
+impl<'a> DerefMut for InternalGuard<'a> {
+    fn deref_mut(&mut self) -> &mut InternalMemory {
+        match self {
+            // Writes check `MemoryTransaction::writable` before mutating.
+            InternalGuard::Read(_) => unreachable!("mutable access through a read-only transaction"),
+            InternalGuard::Write(guard) => guard,
+        }
+    }
+}
+
+// Overwrites the persisted image at a given path.
+fn write_image(path: &Path, internal: &InternalMemory) -> Result<()> {
+    let temp_path = NamedTempFile::new().map_err(|err| Error::Datastore(Box::new(err)))?;
+    {
+        let mut buf = BufWriter::new(temp_path.as_file());
+        rmp_serde::encode::write(&mut buf, internal)?;
+    }
+    temp_path.persist(path).map_err(|err| Error::Datastore(Box::new(err)))?;
+    Ok(())
+}
+
+// Appends entries to the write-ahead log. The first write to a new datastore
+// also writes an empty image, since the log can't be replayed without one,
+// and mustn't be replayed on top of a stale one left at the path.
+fn append_log(wal: &Mutex<Wal>, image_path: Option<&Path>, entries: &[LogEntry]) -> Result<()> {
+    let mut wal = wal.lock().unwrap();
+    if let Some(image_path) = image_path {
+        if wal.open_if_new()? {
+            write_image(image_path, &InternalMemory::default())?;
+        }
+    }
+    wal.append(entries)
 }
 
 pub struct MemoryTransaction<'a> {
-    internal: MutexGuard<'a, InternalMemory>,
+    internal: InternalGuard<'a>,
     path: Option<PathBuf>,
+    wal: Option<&'a Mutex<Wal>>,
+    // Changes made since the last commit. This is only set for buffered
+    // transactions, which hold the datastore lock until they're dropped, so
+    // other transactions never observe uncommitted changes.
+    changes: Option<Vec<Change>>,
+    // Log entries that are held back rather than written right away, which
+    // is the case for buffered transactions until they're committed.
+    pending_log: Option<Vec<LogEntry>>,
+}
+
+impl<'a> MemoryTransaction<'a> {
+    fn writable(&self) -> Result<()> {
+        match self.internal {
+            InternalGuard::Read(_) => Err(Error::ReadOnlyTransaction),
+            InternalGuard::Write(_) => Ok(()),
+        }
+    }
+
+    fn record(&mut self, change: Change) -> Result<()> {
+        if let Some(wal) = self.wal {
+            let entry = LogEntry::new(&self.internal, &change);
+            match self.pending_log {
+                Some(ref mut pending_log) => pending_log.push(entry),
+                None => append_log(wal, self.path.as_deref(), &[entry])?,
+            }
+        }
+        if let Some(ref mut changes) = self.changes {
+            changes.push(change);
+        }
+        Ok(())
+    }
+
+    // Writes the held back log entries.
+    fn write_pending_log(&mut self) -> Result<()> {
+        if let (Some(wal), Some(pending_log)) = (self.wal, self.pending_log.as_mut()) {
+            append_log(wal, self.path.as_deref(), pending_log)?;
+            pending_log.clear();
+        }
+        Ok(())
+    }
+
+    // Inserts bulk items once they've been validated.
+    fn insert_items(&mut self, items: Vec<BulkInsertItem>, now: SystemTime) -> Result<()> {
+        for item in items {
+            match item {
+                BulkInsertItem::Vertex(vertex) => {
+                    self.create_vertex(&vertex)?;
+                }
+                BulkInsertItem::Edge(edge) => {
+                    self.create_edge(&edge)?;
+                }
+                BulkInsertItem::VertexProperty(id, name, value) => {
+                    self.write_vertex_properties(vec![id], name, &value)?;
+                }
+                BulkInsertItem::EdgeProperty(edge, name, value) => {
+                    self.write_edge_properties(vec![edge], name, &value)?;
+                }
+                BulkInsertItem::ExpiringVertex(vertex, ttl) => {
+                    self.create_vertex_with_expiry(&vertex, util::expiry(now, ttl)?)?;
+                }
+                BulkInsertItem::ExpiringEdge(edge, ttl) => {
+                    self.create_edge_with_expiry(&edge, util::expiry(now, ttl)?)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Sets vertex properties without validating them.
+    fn write_vertex_properties(&mut self, vertex_ids: Vec<Uuid>, name: Identifier, value: &Json) -> Result<()> {
+        let mut deletable_vertex_properties = Vec::new();
+        for vertex_id in &vertex_ids {
+            deletable_vertex_properties.push((*vertex_id, name));
+        }
+        self.delete_vertex_properties(deletable_vertex_properties)?;
+
+        for vertex_id in vertex_ids.into_iter() {
+            self.internal.insert_vertex_property(vertex_id, name, value.clone());
+            self.record(Change::SetVertexProperty(vertex_id, name))?;
+        }
+
+        Ok(())
+    }
+
+    fn set_vertex_expiry(&mut self, id: Uuid, expires_at: Option<SystemTime>) -> Result<()> {
+        let previous = self.internal.set_vertex_expiry(id, expires_at);
+        if previous != expires_at {
+            self.record(Change::SetVertexExpiry(id, previous))?;
+        }
+        Ok(())
+    }
+
+    fn set_edge_expiry(&mut self, edge: &Edge, expires_at: Option<SystemTime>) -> Result<()> {
+        let previous = self.internal.set_edge_expiry(edge, expires_at);
+        if previous != expires_at {
+            self.record(Change::SetEdgeExpiry(edge.clone(), previous))?;
+        }
+        Ok(())
+    }
+
+    // Sets edge properties without validating them.
+    fn write_edge_properties(&mut self, edges: Vec<Edge>, name: Identifier, value: &Json) -> Result<()> {
+        let mut deletable_edge_properties = Vec::new();
+        for edge in &edges {
+            deletable_edge_properties.push((edge.clone(), name));
+        }
+        self.delete_edge_properties(deletable_edge_properties)?;
+
+        for edge in edges.into_iter() {
+            self.internal.insert_edge_property(edge.clone(), name, value.clone());
+            self.record(Change::SetEdgeProperty(edge, name))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Drop for MemoryTransaction<'a> {
+    fn drop(&mut self) {
+        if let Some(changes) = self.changes.take() {
+            for change in changes.into_iter().rev() {
+                self.internal.undo(change);
+            }
+        }
+    }
 }
 
 impl<'a> Transaction<'a> for MemoryTransaction<'a> {
@@ -62,6 +696,14 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
         Ok(Box::new(iter))
     }
 
+    fn range_vertices_of_type(&'a self, t: Identifier, offset: Uuid) -> Result<DynIter<'a, Vertex>> {
+        let iter: DynIter<'a, Vertex> = match self.internal.vertices_by_type.get(&t) {
+            Some(ids) => Box::new(ids.range(offset..).map(move |id| Ok(Vertex::with_id(*id, t)))),
+            None => Box::new(iter::empty()),
+        };
+        Ok(iter)
+    }
+
     fn specific_vertices(&'a self, ids: Vec<Uuid>) -> Result<DynIter<'a, Vertex>> {
         let iter = ids.into_iter().filter_map(move |id| {
             self.internal
@@ -90,7 +732,7 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
 
     fn vertex_ids_with_property_value(&'a self, name: Identifier, value: &Json) -> Result<Option<DynIter<'a, Uuid>>> {
         if let Some(container) = self.internal.property_values.get(&name) {
-            if let Some(sub_container) = container.get(value) {
+            if let Some(sub_container) = container.get(&OrderedJson(value.clone())) {
                 let iter = Box::new(sub_container.iter().filter_map(move |member| match member {
                     IndexedPropertyMember::Vertex(id) => Some(Ok(*id)),
                     _ => None,
@@ -105,6 +747,42 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
         }
     }
 
+    fn vertex_ids_with_property_range(
+        &'a self,
+        name: Identifier,
+        range: &JsonRange,
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        if let Some(container) = self.internal.property_values.get(&name) {
+            let iter = ordered_range(container, range).flat_map(|(_, sub_container)| {
+                sub_container.iter().filter_map(|member| match member {
+                    IndexedPropertyMember::Vertex(id) => Some(Ok(*id)),
+                    _ => None,
+                })
+            });
+            Ok(Some(Box::new(iter)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn vertex_ids_with_composite_property_values(
+        &'a self,
+        names: &[Identifier],
+        values: &[Json],
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        if let Some(members) = self.internal.composite_property_values.get(names) {
+            let prefix: Vec<OrderedJson> = values.iter().map(|value| OrderedJson(value.clone())).collect();
+            let start = (prefix.clone(), Uuid::nil());
+            let iter = members
+                .range((Bound::Included(start), Bound::Unbounded))
+                .take_while(move |(key, _)| key.starts_with(&prefix))
+                .map(|(_, id)| Ok(*id));
+            Ok(Some(Box::new(iter)))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn edge_count(&self) -> u64 {
         self.internal.edges.len() as u64
     }
@@ -124,6 +802,15 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
         Ok(Box::new(iter))
     }
 
+    fn range_edges_of_type(&'a self, t: Identifier, offset: Option<Edge>) -> Result<DynIter<'a, Edge>> {
+        let iter: DynIter<'a, Edge> = match (self.internal.edges_by_type.get(&t), offset) {
+            (Some(edges), Some(offset)) => Box::new(edges.range(offset..).map(|e| Ok(e.clone()))),
+            (Some(edges), None) => Box::new(edges.iter().map(|e| Ok(e.clone()))),
+            (None, _) => Box::new(iter::empty()),
+        };
+        Ok(iter)
+    }
+
     fn specific_edges(&'a self, edges: Vec<Edge>) -> Result<DynIter<'a, Edge>> {
         let iter = edges
             .into_iter()
@@ -150,7 +837,7 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
 
     fn edges_with_property_value(&'a self, name: Identifier, value: &Json) -> Result<Option<DynIter<'a, Edge>>> {
         if let Some(container) = self.internal.property_values.get(&name) {
-            if let Some(sub_container) = container.get(value) {
+            if let Some(sub_container) = container.get(&OrderedJson(value.clone())) {
                 let iter = Box::new(sub_container.iter().filter_map(move |member| match member {
                     IndexedPropertyMember::Edge(edge) if self.internal.edges.contains(edge) => Some(edge),
                     _ => None,
@@ -165,6 +852,20 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
         }
     }
 
+    fn edges_with_property_range(&'a self, name: Identifier, range: &JsonRange) -> Result<Option<DynIter<'a, Edge>>> {
+        if let Some(container) = self.internal.property_values.get(&name) {
+            let iter = ordered_range(container, range).flat_map(move |(_, sub_container)| {
+                sub_container.iter().filter_map(move |member| match member {
+                    IndexedPropertyMember::Edge(edge) if self.internal.edges.contains(edge) => Some(Ok(edge.clone())),
+                    _ => None,
+                })
+            });
+            Ok(Some(Box::new(iter)))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn vertex_property(&self, vertex: &Vertex, name: Identifier) -> Result<Option<Json>> {
         if let Some(value) = self.internal.vertex_properties.get(&(vertex.id, name)) {
             Ok(Some(value.clone()))
@@ -203,9 +904,41 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
         Ok(Box::new(edge_properties.into_iter().map(Ok)))
     }
 
+    fn expired_vertices(&'a self, now: SystemTime) -> Result<DynIter<'a, Vertex>> {
+        let iter = self
+            .internal
+            .vertices_by_expiry
+            .iter()
+            .take_while(move |(expires_at, _)| *expires_at <= now)
+            .filter_map(move |(_, id)| self.internal.vertices.get(id).map(|t| Ok(Vertex::with_id(*id, *t))));
+        Ok(Box::new(iter))
+    }
+
+    fn expired_edges(&'a self, now: SystemTime) -> Result<DynIter<'a, Edge>> {
+        let iter = self
+            .internal
+            .edges_by_expiry
+            .iter()
+            .take_while(move |(expires_at, _)| *expires_at <= now)
+            .map(|(_, edge)| Ok(edge.clone()));
+        Ok(Box::new(iter))
+    }
+
+    fn vertex_expiry(&self, id: Uuid) -> Result<Option<SystemTime>> {
+        Ok(self.internal.vertex_expiries.get(&id).copied())
+    }
+
+    fn edge_expiry(&self, edge: &Edge) -> Result<Option<SystemTime>> {
+        Ok(self.internal.edge_expiries.get(edge).copied())
+    }
+
     fn delete_vertices(&mut self, vertices: Vec<Vertex>) -> Result<()> {
+        self.writable()?;
         for vertex in vertices {
-            self.internal.vertices.remove(&vertex.id);
+            if let Some(t) = self.internal.remove_vertex(vertex.id) {
+                self.record(Change::DeleteVertex(vertex.id, t))?;
+            }
+            self.set_vertex_expiry(vertex.id, None)?;
 
             let mut deletable_vertex_properties: Vec<(Uuid, Identifier)> = Vec::new();
             for (property_key, _) in self
@@ -235,9 +968,12 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
     }
 
     fn delete_edges(&mut self, edges: Vec<Edge>) -> Result<()> {
+        self.writable()?;
         for edge in edges {
-            self.internal.edges.remove(&edge);
-            self.internal.reversed_edges.remove(&edge.reversed());
+            if self.internal.remove_edge(&edge) {
+                self.record(Change::DeleteEdge(edge.clone()))?;
+            }
+            self.set_edge_expiry(&edge, None)?;
 
             let mut deletable_edge_properties: Vec<(Edge, Identifier)> = Vec::new();
             for (property_key, _) in self
@@ -259,78 +995,123 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
     }
 
     fn delete_vertex_properties(&mut self, props: Vec<(Uuid, Identifier)>) -> Result<()> {
-        for prop in props {
-            if let Some(property_value) = self.internal.vertex_properties.remove(&prop) {
-                let (property_vertex_id, property_name) = prop;
-                if let Some(property_container) = self.internal.property_values.get_mut(&property_name) {
-                    debug_assert!(property_container
-                        .get_mut(&property_value)
-                        .unwrap()
-                        .remove(&IndexedPropertyMember::Vertex(property_vertex_id)));
-                }
+        self.writable()?;
+        for (property_vertex_id, property_name) in props {
+            if let Some(property_value) = self.internal.remove_vertex_property(property_vertex_id, property_name) {
+                self.record(Change::DeleteVertexProperty(
+                    property_vertex_id,
+                    property_name,
+                    property_value,
+                ))?;
             }
         }
         Ok(())
     }
 
     fn delete_edge_properties(&mut self, props: Vec<(Edge, Identifier)>) -> Result<()> {
-        for prop in props {
-            if let Some(property_value) = self.internal.edge_properties.remove(&prop) {
-                let (property_edge, property_name) = prop;
-                if let Some(property_container) = self.internal.property_values.get_mut(&property_name) {
-                    debug_assert!(property_container
-                        .get_mut(&property_value)
-                        .unwrap()
-                        .remove(&IndexedPropertyMember::Edge(property_edge)));
-                }
+        self.writable()?;
+        for (property_edge, property_name) in props {
+            if let Some(property_value) = self.internal.remove_edge_property(property_edge.clone(), property_name) {
+                self.record(Change::DeleteEdgeProperty(property_edge, property_name, property_value))?;
             }
         }
         Ok(())
     }
 
     fn sync(&self) -> Result<()> {
+        // The image would include uncommitted changes, which would then be
+        // kept even if the transaction is rolled back.
+        if matches!(self.changes, Some(ref changes) if !changes.is_empty()) {
+            return Err(Error::Unsupported);
+        }
         if let Some(ref persist_path) = self.path {
-            let temp_path = NamedTempFile::new().map_err(|err| Error::Datastore(Box::new(err)))?;
-            {
-                let mut buf = BufWriter::new(temp_path.as_file());
-                rmp_serde::encode::write(&mut buf, &*self.internal)?;
+            write_image(persist_path, &self.internal)?;
+            if let Some(wal) = self.wal {
+                wal.lock().unwrap().truncate()?;
             }
-            temp_path
-                .persist(persist_path)
-                .map_err(|err| Error::Datastore(Box::new(err)))?;
         }
         Ok(())
     }
 
-    fn create_vertex(&mut self, vertex: &Vertex) -> Result<bool> {
-        let mut inserted = false;
+    fn commit(&mut self) -> Result<()> {
+        self.write_pending_log()?;
+        if let Some(ref mut changes) = self.changes {
+            changes.clear();
+        }
+        Ok(())
+    }
 
-        self.internal.vertices.entry(vertex.id).or_insert_with(|| {
-            inserted = true;
-            vertex.t
-        });
+    fn rollback(&mut self) -> Result<()> {
+        match self.changes {
+            Some(ref mut changes) => {
+                for change in changes.drain(..).rev() {
+                    self.internal.undo(change);
+                }
+                if let Some(ref mut pending_log) = self.pending_log {
+                    pending_log.clear();
+                }
+                Ok(())
+            }
+            None => Err(Error::Unsupported),
+        }
+    }
+
+    fn snapshot(&mut self) -> Result<()> {
+        // The transaction holds the datastore lock for its whole lifetime, so
+        // no other writes can land while it's reading.
+        Ok(())
+    }
 
-        Ok(inserted)
+    fn create_vertex(&mut self, vertex: &Vertex) -> Result<bool> {
+        self.writable()?;
+        if self.internal.vertices.contains_key(&vertex.id) {
+            return Ok(false);
+        }
+
+        self.internal.insert_vertex(vertex.id, vertex.t);
+        self.record(Change::CreateVertex(vertex.id))?;
+        Ok(true)
     }
 
     fn create_edge(&mut self, edge: &Edge) -> Result<bool> {
+        self.writable()?;
         if !self.internal.vertices.contains_key(&edge.outbound_id)
             || !self.internal.vertices.contains_key(&edge.inbound_id)
         {
             return Ok(false);
         }
 
-        self.internal.edges.insert(edge.clone());
-        self.internal.reversed_edges.insert(edge.reversed());
+        if self.internal.insert_edge(edge.clone()) {
+            self.record(Change::CreateEdge(edge.clone()))?;
+        }
+        Ok(true)
+    }
+
+    fn create_vertex_with_expiry(&mut self, vertex: &Vertex, expires_at: SystemTime) -> Result<bool> {
+        if !self.create_vertex(vertex)? {
+            return Ok(false);
+        }
+
+        self.set_vertex_expiry(vertex.id, Some(expires_at))?;
+        Ok(true)
+    }
+
+    fn create_edge_with_expiry(&mut self, edge: &Edge, expires_at: SystemTime) -> Result<bool> {
+        if !self.create_edge(edge)? {
+            return Ok(false);
+        }
+
+        self.set_edge_expiry(edge, Some(expires_at))?;
         Ok(true)
     }
 
     fn index_property(&mut self, name: Identifier) -> Result<()> {
-        let mut property_container: HashMap<Json, HashSet<IndexedPropertyMember>> = HashMap::new();
+        self.writable()?;
+        let mut property_container: BTreeMap<OrderedJson, HashSet<IndexedPropertyMember>> = BTreeMap::new();
         for id in self.internal.vertices.keys() {
             if let Some(value) = self.internal.vertex_properties.get(&(*id, name)) {
                 property_container
-                    .entry(value.clone())
+                    .entry(OrderedJson(value.clone()))
                     .or_insert_with(HashSet::new)
                     .insert(IndexedPropertyMember::Vertex(*id));
             }
@@ -338,13 +1119,17 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
         for edge in self.internal.edges.iter() {
             if let Some(value) = self.internal.edge_properties.get(&(edge.clone(), name)) {
                 property_container
-                    .entry(value.clone())
+                    .entry(OrderedJson(value.clone()))
                     .or_insert_with(HashSet::new)
                     .insert(IndexedPropertyMember::Edge(edge.clone()));
             }
         }
 
-        let existing_property_container = self.internal.property_values.entry(name).or_insert_with(HashMap::new);
+        if !self.internal.property_values.contains_key(&name) {
+            self.record(Change::IndexProperty(name))?;
+        }
+
+        let existing_property_container = self.internal.property_values.entry(name).or_insert_with(BTreeMap::new);
         for (value, members) in property_container.into_iter() {
             let existing_members = existing_property_container.entry(value).or_insert_with(HashSet::new);
             for member in members {
@@ -355,95 +1140,256 @@ impl<'a> Transaction<'a> for MemoryTransaction<'a> {
         Ok(())
     }
 
-    fn set_vertex_properties(&mut self, vertex_ids: Vec<Uuid>, name: Identifier, value: &Json) -> Result<()> {
-        let mut deletable_vertex_properties = Vec::new();
-        for vertex_id in &vertex_ids {
-            deletable_vertex_properties.push((*vertex_id, name));
+    fn index_unique_property(&mut self, name: Identifier) -> Result<()> {
+        self.writable()?;
+        if self.internal.unique_properties.contains(&name) {
+            return Ok(());
         }
-        self.delete_vertex_properties(deletable_vertex_properties)?;
 
-        for vertex_id in &vertex_ids {
-            self.internal
-                .vertex_properties
-                .insert((*vertex_id, name), value.clone());
+        let mut values = BTreeSet::new();
+        for ((_, property_name), value) in self.internal.vertex_properties.iter() {
+            if *property_name == name && !values.insert(OrderedJson(value.clone())) {
+                return Err(Error::UniqueViolation {
+                    name,
+                    value: value.clone(),
+                });
+            }
+        }
+
+        self.index_property(name)?;
+        self.internal.unique_properties.insert(name);
+        self.record(Change::IndexUniqueProperty(name))?;
+        Ok(())
+    }
+
+    fn index_composite_property(&mut self, names: Vec<Identifier>) -> Result<()> {
+        self.writable()?;
+        if self.internal.composite_property_values.contains_key(&names) {
+            return Ok(());
         }
 
-        if let Some(property_container) = self.internal.property_values.get_mut(&name) {
-            let property_container = property_container.entry(value.clone()).or_insert_with(HashSet::new);
-            for vertex_id in vertex_ids.into_iter() {
-                property_container.insert(IndexedPropertyMember::Vertex(vertex_id));
+        let mut members = BTreeSet::new();
+        for id in self.internal.vertices.keys() {
+            if let Some(key) = composite_key(&self.internal.vertex_properties, *id, &names) {
+                members.insert((key, *id));
             }
         }
 
+        self.internal.composite_property_values.insert(names.clone(), members);
+        self.record(Change::IndexCompositeProperty(names))?;
         Ok(())
     }
 
-    fn set_edge_properties(&mut self, edges: Vec<Edge>, name: Identifier, value: &Json) -> Result<()> {
-        let mut deletable_edge_properties = Vec::new();
-        for edge in &edges {
-            deletable_edge_properties.push((edge.clone(), name));
+    fn unindex_property(&mut self, name: Identifier) -> Result<()> {
+        self.writable()?;
+        if let Some(property_container) = self.internal.property_values.remove(&name) {
+            let was_unique = self.internal.unique_properties.remove(&name);
+            self.record(Change::UnindexProperty(name, property_container, was_unique))?;
         }
-        self.delete_edge_properties(deletable_edge_properties)?;
+        Ok(())
+    }
 
-        for edge in &edges {
-            self.internal
-                .edge_properties
-                .insert((edge.clone(), name), value.clone());
-        }
+    fn indexed_properties(&self) -> Result<Vec<Identifier>> {
+        let mut names: Vec<Identifier> = self.internal.property_values.keys().copied().collect();
+        names.sort();
+        Ok(names)
+    }
 
-        if let Some(property_container) = self.internal.property_values.get_mut(&name) {
-            let property_container = property_container.entry(value.clone()).or_insert_with(HashSet::new);
-            for edge in edges.into_iter() {
-                property_container.insert(IndexedPropertyMember::Edge(edge));
+    fn bulk_insert(&mut self, items: Vec<BulkInsertItem>) -> Result<()> {
+        self.writable()?;
+        // Properties and expiries are validated up front, so that a violation
+        // doesn't leave the insert half-applied.
+        let now = SystemTime::now();
+        let mut vertex_types = HashMap::new();
+        for item in &items {
+            if let BulkInsertItem::Vertex(vertex) | BulkInsertItem::ExpiringVertex(vertex, _) = item {
+                vertex_types.entry(vertex.id).or_insert(vertex.t);
             }
         }
+        for item in &items {
+            match item {
+                BulkInsertItem::VertexProperty(id, name, value) => {
+                    let t = self.internal.vertices.get(id).or_else(|| vertex_types.get(id));
+                    if let Some(t) = t {
+                        self.internal.validate_vertex_property(*t, *name, value)?;
+                    }
+                }
+                BulkInsertItem::EdgeProperty(edge, name, value) => {
+                    self.internal.validate_edge_property(edge, *name, value)?;
+                }
+                BulkInsertItem::ExpiringVertex(_, ttl) | BulkInsertItem::ExpiringEdge(_, ttl) => {
+                    util::expiry(now, *ttl)?;
+                }
+                _ => {}
+            }
+        }
+        self.internal
+            .check_unique_vertex_properties(items.iter().filter_map(|item| match item {
+                BulkInsertItem::VertexProperty(id, name, value) => Some((*id, *name, value)),
+                _ => None,
+            }))?;
+
+        // Log entries are written all at once at the end, rather than item
+        // by item.
+        if self.pending_log.is_some() {
+            return self.insert_items(items, now);
+        }
+        self.pending_log = Some(Vec::new());
+        let result = self.insert_items(items, now);
+        let log_result = self.write_pending_log();
+        self.pending_log = None;
+        result.and(log_result)
+    }
 
+    fn set_schema(&mut self, kind: SchemaKind, t: Identifier, schema: Option<Schema>) -> Result<()> {
+        self.writable()?;
+        let schemas = self.internal.schemas_mut(kind);
+        let previous = match schema {
+            Some(schema) => schemas.insert(t, schema),
+            None => schemas.remove(&t),
+        };
+        self.record(Change::SetSchema(kind, t, previous))?;
         Ok(())
     }
+
+    fn schema(&self, kind: SchemaKind, t: Identifier) -> Result<Option<Schema>> {
+        let schemas = match kind {
+            SchemaKind::Vertex => &self.internal.vertex_schemas,
+            SchemaKind::Edge => &self.internal.edge_schemas,
+        };
+        Ok(schemas.get(&t).cloned())
+    }
+
+    fn set_vertex_properties(&mut self, vertex_ids: Vec<Uuid>, name: Identifier, value: &Json) -> Result<()> {
+        self.writable()?;
+        for vertex_id in &vertex_ids {
+            if let Some(t) = self.internal.vertices.get(vertex_id) {
+                self.internal.validate_vertex_property(*t, name, value)?;
+            }
+        }
+        self.internal
+            .check_unique_vertex_properties(vertex_ids.iter().map(|id| (*id, name, value)))?;
+
+        self.write_vertex_properties(vertex_ids, name, value)
+    }
+
+    fn set_edge_properties(&mut self, edges: Vec<Edge>, name: Identifier, value: &Json) -> Result<()> {
+        self.writable()?;
+        for edge in &edges {
+            self.internal.validate_edge_property(edge, name, value)?;
+        }
+
+        self.write_edge_properties(edges, name, value)
+    }
 }
 
 /// An in-memory datastore.
+///
+/// Datastores persisted to a msgpack image also keep a write-ahead log next
+/// to it, at the image's path with a `.wal` suffix. Writes are appended to
+/// the log as they're made (or when buffered transactions are committed),
+/// and the log is truncated whenever the image is rewritten by a sync.
+///
+/// Reads through `Database` methods share the datastore lock, so they run
+/// concurrently, whereas writes and transactions hold it exclusively.
 #[derive(Debug, Clone)]
 pub struct MemoryDatastore {
-    internal: Arc<Mutex<InternalMemory>>,
+    internal: Arc<RwLock<InternalMemory>>,
     path: Option<PathBuf>,
+    wal: Option<Arc<Mutex<Wal>>>,
 }
 
 impl MemoryDatastore {
     /// Creates a new in-memory database with no persistence.
     pub fn new_db() -> Database<MemoryDatastore> {
         Database::new(MemoryDatastore {
-            internal: Arc::new(Mutex::new(InternalMemory::default())),
+            internal: Arc::new(RwLock::new(InternalMemory::default())),
             path: None,
+            wal: None,
         })
     }
 
-    /// Reads a persisted image from disk. Calls to sync will overwrite the
-    /// file at the specified path.
+    /// Reads a persisted image from disk, and replays the write-ahead log on
+    /// top of it. Calls to sync will overwrite the file at the specified
+    /// path. The write-ahead log is fsynced with the default policy.
     ///
     /// # Arguments
     /// * `path`: The path to the persisted image.
     pub fn read_msgpack_db<P: Into<PathBuf>>(path: P) -> StdResult<Database<MemoryDatastore>, RmpDecodeError> {
+        Self::read_msgpack_db_with_fsync_policy(path, FsyncPolicy::default())
+    }
+
+    /// Reads a persisted image from disk, and replays the write-ahead log on
+    /// top of it. Calls to sync will overwrite the file at the specified
+    /// path.
+    ///
+    /// # Arguments
+    /// * `path`: The path to the persisted image.
+    /// * `policy`: When to fsync the write-ahead log.
+    pub fn read_msgpack_db_with_fsync_policy<P: Into<PathBuf>>(
+        path: P,
+        policy: FsyncPolicy,
+    ) -> StdResult<Database<MemoryDatastore>, RmpDecodeError> {
         let path = path.into();
         let f = File::open(&path).map_err(RmpDecodeError::InvalidDataRead)?;
         let buf = BufReader::new(f);
-        let internal: InternalMemory = rmp_serde::from_read(buf)?;
+        let mut internal: InternalMemory = rmp_serde::from_read(buf)?;
+        internal.rebuild_derived_indexes();
+        let internal = RwLock::new(internal);
+
+        let (wal, entries) = Wal::open::<LogEntry>(wal::wal_path(&path), policy)?;
+        {
+            let mut txn = MemoryTransaction {
+                internal: InternalGuard::Write(internal.write().unwrap()),
+                path: None,
+                wal: None,
+                changes: None,
+                pending_log: None,
+            };
+            for entry in entries {
+                entry
+                    .replay(&mut txn)
+                    .map_err(|err| RmpDecodeError::Syntax(err.to_string()))?;
+            }
+        }
+
         Ok(Database::new(MemoryDatastore {
-            internal: Arc::new(Mutex::new(internal)),
+            internal: Arc::new(internal),
             path: Some(path),
+            wal: Some(Arc::new(Mutex::new(wal))),
         }))
     }
 
     /// Creates a new datastore. Calls to sync will overwrite the file at the
     /// specified path, but as opposed to `read`, this will not read the file
-    /// first.
+    /// first. The first write replaces it with an empty image, and discards
+    /// any write-ahead log left next to it. The write-ahead log is fsynced
+    /// with the default policy.
     ///
     /// # Arguments
     /// * `path`: The path to the persisted image.
     pub fn create_msgpack_db<P: Into<PathBuf>>(path: P) -> Database<MemoryDatastore> {
+        Self::create_msgpack_db_with_fsync_policy(path, FsyncPolicy::default())
+    }
+
+    /// Creates a new datastore. Calls to sync will overwrite the file at the
+    /// specified path, but as opposed to `read`, this will not read the file
+    /// first. The first write replaces it with an empty image, and discards
+    /// any write-ahead log left next to it.
+    ///
+    /// # Arguments
+    /// * `path`: The path to the persisted image.
+    /// * `policy`: When to fsync the write-ahead log.
+    pub fn create_msgpack_db_with_fsync_policy<P: Into<PathBuf>>(
+        path: P,
+        policy: FsyncPolicy,
+    ) -> Database<MemoryDatastore> {
+        let path = path.into();
+        let wal = Wal::create(wal::wal_path(&path), policy);
         Database::new(MemoryDatastore {
-            internal: Arc::new(Mutex::new(InternalMemory::default())),
-            path: Some(path.into()),
+            internal: Arc::new(RwLock::new(InternalMemory::default())),
+            path: Some(path),
+            wal: Some(Arc::new(Mutex::new(wal))),
         })
     }
 }
@@ -452,8 +1398,31 @@ impl Datastore for MemoryDatastore {
     type Transaction<'a> = MemoryTransaction<'a>;
     fn transaction(&'_ self) -> Self::Transaction<'_> {
         MemoryTransaction {
-            internal: self.internal.lock().unwrap(),
+            internal: InternalGuard::Write(self.internal.write().unwrap()),
+            path: self.path.clone(),
+            wal: self.wal.as_deref(),
+            changes: None,
+            pending_log: None,
+        }
+    }
+
+    fn begin(&'_ self) -> Result<Self::Transaction<'_>> {
+        Ok(MemoryTransaction {
+            internal: InternalGuard::Write(self.internal.write().unwrap()),
+            path: self.path.clone(),
+            wal: self.wal.as_deref(),
+            changes: Some(Vec::new()),
+            pending_log: Some(Vec::new()),
+        })
+    }
+
+    fn read_transaction(&'_ self) -> Self::Transaction<'_> {
+        MemoryTransaction {
+            internal: InternalGuard::Read(self.internal.read().unwrap()),
             path: self.path.clone(),
+            wal: self.wal.as_deref(),
+            changes: None,
+            pending_log: None,
         }
     }
 }
@@ -1,11 +1,14 @@
 //! The in-memory datastore implementation. This is the simplest and generally
 //! fastest implementation, but there's no support for graphs larger than what
-//! can fit in-memory, and data is only persisted to disk when explicitly
+//! can fit in-memory. Persisted datastores write changes to a write-ahead
+//! log as they're made, and only rewrite the full image when explicitly
 //! requested.
 
 mod datastore;
+mod wal;
 
 pub use datastore::MemoryDatastore;
+pub use wal::FsyncPolicy;
 
 #[cfg(feature = "bench-suite")]
 full_bench_impl!(MemoryDatastore::new_db());
@@ -13,11 +16,18 @@ full_bench_impl!(MemoryDatastore::new_db());
 #[cfg(feature = "test-suite")]
 #[cfg(test)]
 mod tests {
-    use super::MemoryDatastore;
+    use super::{FsyncPolicy, MemoryDatastore};
     use crate::util::{extract_count, extract_vertices};
-    use crate::{ijson, AllVertexQuery, CountQueryExt, Database, Identifier, SpecificVertexQuery};
+    use crate::{
+        ijson, AllVertexQuery, CountQueryExt, Database, Datastore, Error, Identifier, JsonType, Schema, SchemaKind,
+        SpecificVertexQuery, Transaction, Vertex,
+    };
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::thread;
+    use std::time::Duration;
 
-    use tempfile::NamedTempFile;
+    use tempfile::{tempdir, NamedTempFile};
     use uuid::Uuid;
 
     full_test_impl!(MemoryDatastore::new_db());
@@ -46,4 +56,168 @@ mod tests {
         let db = MemoryDatastore::read_msgpack_db(path.path()).unwrap();
         expect_vertex(&db, id);
     }
+
+    #[test]
+    fn should_serialize_schemas_in_msgpack() {
+        let path = NamedTempFile::new().unwrap();
+        let db = MemoryDatastore::create_msgpack_db(path.path());
+        let schema = Schema::new().property(Identifier::new("name").unwrap(), vec![JsonType::String]);
+        db.set_schema(SchemaKind::Vertex, Identifier::default(), schema.clone())
+            .unwrap();
+        db.sync().unwrap();
+        let db = MemoryDatastore::read_msgpack_db(path.path()).unwrap();
+        assert_eq!(
+            db.schema(SchemaKind::Vertex, Identifier::default()).unwrap(),
+            Some(schema)
+        );
+        assert_eq!(db.schema(SchemaKind::Edge, Identifier::default()).unwrap(), None);
+    }
+
+    #[test]
+    fn should_serialize_expiries_in_msgpack() {
+        let path = NamedTempFile::new().unwrap();
+        let db = MemoryDatastore::create_msgpack_db(path.path());
+        let expired = Vertex::new(Identifier::default());
+        db.create_vertex_with_ttl(&expired, Duration::ZERO).unwrap();
+        let id = create_vertex_with_property(&db);
+        db.sync().unwrap();
+        let db = MemoryDatastore::read_msgpack_db(path.path()).unwrap();
+        expect_vertex(&db, id);
+        assert_eq!(db.expire_now().unwrap(), 1);
+    }
+
+    #[test]
+    fn should_replay_wal() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.msgpack");
+        let db = MemoryDatastore::create_msgpack_db_with_fsync_policy(&path, FsyncPolicy::Always);
+        db.sync().unwrap();
+        let id = create_vertex_with_property(&db);
+        let deleted_id = create_vertex_with_property(&db);
+        db.delete(SpecificVertexQuery::single(deleted_id)).unwrap();
+        db.index_property(Identifier::default()).unwrap();
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        expect_vertex(&db, id);
+        assert_eq!(db.indexed_properties().unwrap(), vec![Identifier::default()]);
+    }
+
+    #[test]
+    fn should_replay_wal_without_sync() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.msgpack");
+        let db = MemoryDatastore::create_msgpack_db_with_fsync_policy(&path, FsyncPolicy::Always);
+        let id = create_vertex_with_property(&db);
+        drop(db);
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        expect_vertex(&db, id);
+    }
+
+    #[test]
+    fn should_not_replay_wal_on_stale_image() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.msgpack");
+        let db = MemoryDatastore::create_msgpack_db(&path);
+        create_vertex_with_property(&db);
+        db.sync().unwrap();
+        let db = MemoryDatastore::create_msgpack_db(&path);
+        let id = create_vertex_with_property(&db);
+        drop(db);
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        expect_vertex(&db, id);
+    }
+
+    #[test]
+    fn should_truncate_wal_on_sync() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.msgpack");
+        let wal_path = dir.path().join("image.msgpack.wal");
+        let db = MemoryDatastore::create_msgpack_db_with_fsync_policy(&path, FsyncPolicy::Never);
+        let id = create_vertex_with_property(&db);
+        assert!(fs::metadata(&wal_path).unwrap().len() > 0);
+        db.sync().unwrap();
+        assert_eq!(fs::metadata(&wal_path).unwrap().len(), 0);
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        expect_vertex(&db, id);
+    }
+
+    #[test]
+    fn should_discard_torn_wal_entry() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.msgpack");
+        let db = MemoryDatastore::create_msgpack_db(&path);
+        db.sync().unwrap();
+        let id = create_vertex_with_property(&db);
+        drop(db);
+
+        // A map marker promising more entries than follow, as a crash in the
+        // middle of a write would leave behind
+        let wal_path = dir.path().join("image.msgpack.wal");
+        let mut wal = OpenOptions::new().append(true).open(&wal_path).unwrap();
+        wal.write_all(&[0x81]).unwrap();
+        drop(wal);
+
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        expect_vertex(&db, id);
+        db.create_vertex_from_type(Identifier::default()).unwrap();
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        assert_eq!(extract_count(db.get(AllVertexQuery.count().unwrap()).unwrap()), Some(2));
+    }
+
+    #[test]
+    fn should_not_log_rolled_back_transactions() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.msgpack");
+        let db = MemoryDatastore::create_msgpack_db(&path);
+        db.sync().unwrap();
+        let mut txn = db.begin().unwrap();
+        txn.create_vertex_from_type(Identifier::default()).unwrap();
+        txn.rollback().unwrap();
+        let mut txn = db.begin().unwrap();
+        let id = txn.create_vertex_from_type(Identifier::default()).unwrap();
+        txn.set_properties(SpecificVertexQuery::single(id), Identifier::default(), &ijson!(true))
+            .unwrap();
+        txn.commit().unwrap();
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        expect_vertex(&db, id);
+    }
+
+    #[test]
+    fn should_not_sync_uncommitted_changes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.msgpack");
+        let db = MemoryDatastore::create_msgpack_db(&path);
+        let id = create_vertex_with_property(&db);
+        let mut txn = db.datastore.begin().unwrap();
+        txn.create_vertex(&Vertex::new(Identifier::default())).unwrap();
+        assert!(matches!(txn.sync(), Err(Error::Unsupported)));
+        txn.rollback().unwrap();
+        txn.sync().unwrap();
+        drop(txn);
+        let db = MemoryDatastore::read_msgpack_db(&path).unwrap();
+        expect_vertex(&db, id);
+    }
+
+    #[test]
+    fn should_share_lock_between_reads() {
+        let db = MemoryDatastore::new_db();
+        let id = create_vertex_with_property(&db);
+        let txn = db.datastore.read_transaction();
+        assert_eq!(txn.vertex_count(), 1);
+        // This would block forever if reads took the lock exclusively.
+        thread::scope(|s| {
+            s.spawn(|| expect_vertex(&db, id)).join().unwrap();
+        });
+    }
+
+    #[test]
+    fn should_reject_writes_in_read_transactions() {
+        let db = MemoryDatastore::new_db();
+        let mut txn = db.datastore.read_transaction();
+        let result = txn.create_vertex(&Vertex::new(Identifier::default()));
+        assert!(matches!(result, Err(Error::ReadOnlyTransaction)));
+        let result = txn.delete_vertices(Vec::new());
+        assert!(matches!(result, Err(Error::ReadOnlyTransaction)));
+        drop(txn);
+        assert_eq!(extract_count(db.get(AllVertexQuery.count().unwrap()).unwrap()), Some(0));
+    }
 }
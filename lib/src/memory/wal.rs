@@ -0,0 +1,177 @@
+//! An append-only write-ahead log, which holds the changes made to a memory
+//! datastore since its msgpack image was last synced.
+
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use std::time::{Duration, Instant};
+
+use crate::errors::{Error, Result};
+
+use rmp_serde::decode::Error as RmpDecodeError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Specifies when the memory datastore's write-ahead log is fsynced.
+/// Regardless of the policy, writes are handed to the operating system right
+/// away, so they survive the process crashing; the policy controls how many
+/// of them can be lost if the machine crashes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Fsync after every write.
+    Always,
+    /// Fsync after a write, if it's been at least this long since the last
+    /// fsync.
+    Interval(Duration),
+    /// Never fsync, leaving it up to the operating system.
+    Never,
+}
+
+impl Default for FsyncPolicy {
+    fn default() -> Self {
+        FsyncPolicy::Interval(Duration::from_secs(1))
+    }
+}
+
+// Gets the path of the log for the image at a given path.
+pub(super) fn wal_path(image_path: &Path) -> PathBuf {
+    let mut path = OsString::from(image_path);
+    path.push(".wal");
+    PathBuf::from(path)
+}
+
+fn map_io_err(err: std::io::Error) -> Error {
+    Error::Datastore(Box::new(err))
+}
+
+#[derive(Debug)]
+pub(super) struct Wal {
+    path: PathBuf,
+    // This is opened lazily for new datastores, so that creating them can't
+    // fail. Opening it truncates whatever stale log was left at the path.
+    file: Option<File>,
+    policy: FsyncPolicy,
+    last_fsync: Instant,
+}
+
+impl Wal {
+    pub(super) fn create(path: PathBuf, policy: FsyncPolicy) -> Self {
+        Self {
+            path,
+            file: None,
+            policy,
+            last_fsync: Instant::now(),
+        }
+    }
+
+    // Opens the log at a given path, creating it if it doesn't exist, and
+    // reads its entries. An incomplete entry at the end, which is what a
+    // crash in the middle of a write leaves behind, is discarded.
+    pub(super) fn open<T: DeserializeOwned>(
+        path: PathBuf,
+        policy: FsyncPolicy,
+    ) -> StdResult<(Self, Vec<T>), RmpDecodeError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(RmpDecodeError::InvalidDataRead)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(RmpDecodeError::InvalidDataRead)?;
+
+        let mut entries = Vec::new();
+        let mut valid_len = 0;
+        while valid_len < bytes.len() {
+            let mut remaining = &bytes[valid_len..];
+            match rmp_serde::from_read(&mut remaining) {
+                Ok(entry) => {
+                    entries.push(entry);
+                    valid_len = bytes.len() - remaining.len();
+                }
+                Err(RmpDecodeError::InvalidMarkerRead(ref err) | RmpDecodeError::InvalidDataRead(ref err))
+                    if err.kind() == ErrorKind::UnexpectedEof =>
+                {
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        if valid_len < bytes.len() {
+            file.set_len(valid_len as u64)
+                .map_err(RmpDecodeError::InvalidDataRead)?;
+            file.seek(SeekFrom::Start(valid_len as u64))
+                .map_err(RmpDecodeError::InvalidDataRead)?;
+        }
+
+        let wal = Self {
+            path,
+            file: Some(file),
+            policy,
+            last_fsync: Instant::now(),
+        };
+        Ok((wal, entries))
+    }
+
+    fn file(&mut self) -> Result<&mut File> {
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&self.path)
+                .map_err(map_io_err)?;
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    // Opens the log if it hasn't been opened yet, truncating whatever stale
+    // log was left at the path. Returns whether this call opened it.
+    pub(super) fn open_if_new(&mut self) -> Result<bool> {
+        let is_new = self.file.is_none();
+        self.file()?;
+        Ok(is_new)
+    }
+
+    pub(super) fn append<T: Serialize>(&mut self, entries: &[T]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let file = self.file()?;
+        {
+            let mut buf = BufWriter::new(&*file);
+            for entry in entries {
+                rmp_serde::encode::write(&mut buf, entry)?;
+            }
+            buf.flush().map_err(map_io_err)?;
+        }
+
+        let now = Instant::now();
+        let fsync = match self.policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Interval(interval) => now.duration_since(self.last_fsync) >= interval,
+            FsyncPolicy::Never => false,
+        };
+        if fsync {
+            self.file()?.sync_data().map_err(map_io_err)?;
+            self.last_fsync = now;
+        }
+        Ok(())
+    }
+
+    // Empties the log, once its entries have been checkpointed into the
+    // image. This isn't fsynced: if the truncation is lost, the entries are
+    // replayed on top of an image that already includes them, which is
+    // harmless since they set state rather than modify it.
+    pub(super) fn truncate(&mut self) -> Result<()> {
+        let file = self.file()?;
+        file.set_len(0).map_err(map_io_err)?;
+        file.seek(SeekFrom::Start(0)).map_err(map_io_err)?;
+        Ok(())
+    }
+}
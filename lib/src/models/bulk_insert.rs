@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::{Edge, Identifier, Json, Vertex};
 
 use uuid::Uuid;
@@ -13,4 +15,8 @@ pub enum BulkInsertItem {
     VertexProperty(Uuid, Identifier, Json),
     /// An edge property to insert.
     EdgeProperty(Edge, Identifier, Json),
+    /// A vertex to insert, which expires once its time-to-live has elapsed.
+    ExpiringVertex(Vertex, Duration),
+    /// An edge to insert, which expires once its time-to-live has elapsed.
+    ExpiringEdge(Edge, Duration),
 }
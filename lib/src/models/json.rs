@@ -5,6 +5,8 @@ use std::ops::Deref;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::util;
+
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Similar to `serde_json::json!`, this allows you to create JSON literals.
@@ -143,6 +145,42 @@ where
     }
 }
 
+// The position of each type of JSON value in the total order.
+fn type_rank(value: &serde_json::Value) -> u8 {
+    match value {
+        serde_json::Value::Null => 0,
+        serde_json::Value::Bool(_) => 1,
+        serde_json::Value::Number(_) => 2,
+        serde_json::Value::String(_) => 3,
+        serde_json::Value::Array(_) => 4,
+        serde_json::Value::Object(_) => 5,
+    }
+}
+
+fn total_cmp(first: &serde_json::Value, second: &serde_json::Value) -> Ordering {
+    match (first, second) {
+        (serde_json::Value::Number(v1), serde_json::Value::Number(v2)) => {
+            let (f1, i1) = util::number_key(v1);
+            let (f2, i2) = util::number_key(v2);
+            // JSON numbers are always finite, so this can't fail
+            f1.partial_cmp(&f2).unwrap().then(i1.cmp(&i2))
+        }
+        (serde_json::Value::Array(v1), serde_json::Value::Array(v2)) => {
+            partial_cmp_by(v1.iter(), v2.iter(), |v1, v2| Some(total_cmp(v1, v2))).unwrap()
+        }
+        (serde_json::Value::Object(v1), serde_json::Value::Object(v2)) => {
+            partial_cmp_by(v1.iter(), v2.iter(), |(v1_key, v1_value), (v2_key, v2_value)| {
+                Some(v1_key.cmp(v2_key).then_with(|| total_cmp(v1_value, v2_value)))
+            })
+            .unwrap()
+        }
+        _ => match type_rank(first).cmp(&type_rank(second)) {
+            Ordering::Equal => partial_cmp(first, second).unwrap(),
+            non_eq => non_eq,
+        },
+    }
+}
+
 /// Wraps `serde_json::Value` in an `Arc` to make it more cheaply cloneable, as
 /// well as implements extra traits useful for datastore storage and querying.
 #[derive(Clone, Eq, Debug)]
@@ -156,6 +194,18 @@ impl Json {
     pub fn new(value: serde_json::Value) -> Self {
         Self(Arc::new(value))
     }
+
+    /// Compares against another JSON value under a total order, which is
+    /// what property range queries use. Values of different types are
+    /// ordered null, bools, numbers, strings, arrays, then objects. Values
+    /// of the same type are ordered as with `partial_cmp`, except that
+    /// arrays and objects compare their contents with this total order too.
+    ///
+    /// # Arguments
+    /// * `other`: The value to compare against.
+    pub fn total_cmp(&self, other: &Json) -> Ordering {
+        total_cmp(&self.0, &other.0)
+    }
 }
 
 impl From<serde_json::Value> for Json {
@@ -226,6 +276,7 @@ impl FromStr for Json {
 #[cfg(test)]
 mod tests {
     use super::Json;
+    use std::cmp::Ordering;
     use std::collections::HashSet;
 
     fn json_u64() -> Json {
@@ -280,4 +331,36 @@ mod tests {
         assert!(ijson!({}) < ijson!({"key": "value"}));
         assert!(ijson!({"key": "value"}) > ijson!({}));
     }
+
+    #[test]
+    fn should_total_cmp() {
+        let values = vec![
+            ijson!(null),
+            ijson!(false),
+            ijson!(true),
+            json_i64(),
+            ijson!(-1.5),
+            ijson!(0),
+            ijson!(3),
+            ijson!(3.5),
+            json_u64(),
+            ijson!(""),
+            ijson!("foo"),
+            ijson!([]),
+            ijson!([1, "foo"]),
+            ijson!(["foo", 1]),
+            ijson!({}),
+            ijson!({"key": 1}),
+            ijson!({"key": "value"}),
+        ];
+
+        for (i, first) in values.iter().enumerate() {
+            for (j, second) in values.iter().enumerate() {
+                assert_eq!(first.total_cmp(second), i.cmp(&j), "{first:?} vs {second:?}");
+            }
+        }
+
+        assert_eq!(ijson!(4).total_cmp(&ijson!(4.0)), Ordering::Equal);
+        assert_eq!(ijson!(0.0).total_cmp(&ijson!(-0.0)), Ordering::Equal);
+    }
 }
@@ -2,14 +2,18 @@ mod bulk_insert;
 mod edges;
 mod identifiers;
 mod json;
+mod paths;
 mod properties;
 mod queries;
+mod schemas;
 mod vertices;
 
 pub use self::bulk_insert::BulkInsertItem;
 pub use self::edges::Edge;
 pub use self::identifiers::Identifier;
 pub use self::json::Json;
+pub use self::paths::{Path, PathHop, PipePath};
 pub use self::properties::{EdgeProperties, EdgeProperty, NamedProperty, VertexProperties, VertexProperty};
 pub use self::queries::*;
+pub use self::schemas::{JsonType, Schema, SchemaKind};
 pub use self::vertices::Vertex;
@@ -0,0 +1,87 @@
+use super::{Edge, Vertex};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A path through the graph, from one vertex to another.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Path {
+    /// The vertices along the path, in order. The first is where the path
+    /// starts, and the last is where it ends.
+    pub vertices: Vec<Vertex>,
+
+    /// The edges between each consecutive pair of vertices, so there's one
+    /// fewer of them than there are vertices.
+    pub edges: Vec<Edge>,
+
+    /// The total weight of the edges. Unless the path was found by a
+    /// weighted query, each edge has a weight of 1.
+    pub cost: f64,
+}
+
+impl Path {
+    /// Creates a new path, where each edge has a weight of 1.
+    ///
+    /// # Arguments
+    /// * `vertices`: The vertices along the path.
+    /// * `edges`: The edges between the vertices.
+    pub fn new(vertices: Vec<Vertex>, edges: Vec<Edge>) -> Self {
+        let cost = edges.len() as f64;
+        Self { vertices, edges, cost }
+    }
+
+    /// Creates a new path with a given cost.
+    ///
+    /// # Arguments
+    /// * `vertices`: The vertices along the path.
+    /// * `edges`: The edges between the vertices.
+    /// * `cost`: The total weight of the edges.
+    pub fn with_cost(vertices: Vec<Vertex>, edges: Vec<Edge>, cost: f64) -> Self {
+        Self { vertices, edges, cost }
+    }
+
+    /// The number of edges along the path.
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Whether the path has no edges, i.e. it starts and ends at the same
+    /// vertex.
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+}
+
+/// A value that a pipe path passes through.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathHop {
+    /// A vertex, by its id.
+    Vertex(Uuid),
+    /// An edge.
+    Edge(Edge),
+}
+
+/// The values that a pipe query went through to reach one of its outputs,
+/// as returned by pipe queries in path mode.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipePath {
+    /// The hops, in order. The first is the value the pipes started from,
+    /// and the last is the output value.
+    pub hops: Vec<PathHop>,
+}
+
+impl PipePath {
+    /// Creates a new pipe path.
+    ///
+    /// # Arguments
+    /// * `hops`: The hops, in order.
+    pub fn new(hops: Vec<PathHop>) -> Self {
+        Self { hops }
+    }
+
+    /// The last hop of the path, which is the output value.
+    pub fn last(&self) -> Option<&PathHop> {
+        self.hops.last()
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{Edge, Identifier, Json, Vertex};
 
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 /// Represents a vertex property.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct VertexProperty {
     /// The id of the vertex.
     pub id: Uuid,
@@ -24,7 +25,7 @@ impl VertexProperty {
 }
 
 /// A property.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct NamedProperty {
     /// The property name.
     pub name: Identifier,
@@ -45,7 +46,7 @@ impl NamedProperty {
 }
 
 /// A vertex with properties.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct VertexProperties {
     /// The vertex.
     pub vertex: Vertex,
@@ -65,7 +66,7 @@ impl VertexProperties {
 }
 
 /// An edge with properties.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct EdgeProperties {
     /// The edge.
     pub edge: Edge,
@@ -85,7 +86,7 @@ impl EdgeProperties {
 }
 
 /// Represents an edge property.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct EdgeProperty {
     /// The edge.
     pub edge: Edge,
@@ -1,8 +1,11 @@
+use std::cmp::Ordering;
+use std::ops::Bound;
 use std::str::FromStr;
 use std::u32;
 
 use crate::{errors, Edge, Identifier, Json};
 
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 macro_rules! into_query {
@@ -33,7 +36,8 @@ macro_rules! nestable_query {
 /// query to an edge query. `EdgeDirection`s are used to specify which
 /// end of things you want to pipe - either the outbound items or the inbound
 /// items.
-#[derive(Eq, PartialEq, Clone, Debug, Hash, Copy)]
+#[derive(Eq, PartialEq, Clone, Debug, Hash, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EdgeDirection {
     /// Outbound direction.
     Outbound,
@@ -63,7 +67,14 @@ impl From<EdgeDirection> for String {
 }
 
 /// A query to get a set of values from the database.
-#[derive(Eq, PartialEq, Clone, Debug)]
+///
+/// With serde, queries are represented as maps with a `type` field naming
+/// the variant in snake case, alongside the fields of the variant's struct,
+/// e.g. `{"type": "pipe", "inner": {"type": "all_vertex"}, ...}`. Queries
+/// aren't validated when they're deserialized, so invalid combinations are
+/// only rejected once they're run.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Query {
     /// Gets all vertices.
     AllVertex,
@@ -75,15 +86,26 @@ pub enum Query {
     VertexWithPropertyPresence(VertexWithPropertyPresenceQuery),
     /// Gets vertices with a property equal to a given value.
     VertexWithPropertyValue(VertexWithPropertyValueQuery),
+    /// Gets vertices with a property value in a given range.
+    VertexWithPropertyRange(VertexWithPropertyRangeQuery),
+    /// Gets vertices by the values of a prefix of a composite index.
+    VertexWithCompositeProperty(VertexWithCompositePropertyQuery),
 
     /// Gets all edges.
     AllEdge,
+    /// Gets a range of edges.
+    RangeEdge(RangeEdgeQuery),
     /// Gets a specific set of edges.
     SpecificEdge(SpecificEdgeQuery),
     /// Gets edges with or without a given property.
     EdgeWithPropertyPresence(EdgeWithPropertyPresenceQuery),
     /// Gets edges with a property equal to a given value.
     EdgeWithPropertyValue(EdgeWithPropertyValueQuery),
+    /// Gets edges with a property value in a given range.
+    EdgeWithPropertyRange(EdgeWithPropertyRangeQuery),
+
+    /// Gets the shortest path between two vertices.
+    ShortestPath(ShortestPathQuery),
 
     /// Gets the vertices associated with edges, or edges associated with
     /// vertices.
@@ -94,6 +116,15 @@ pub enum Query {
     PipeWithPropertyPresence(PipeWithPropertyPresenceQuery),
     /// Gets vertices or edges with a property equal to a given value.
     PipeWithPropertyValue(PipeWithPropertyValueQuery),
+    /// Gets vertices or edges with a property value in a given range.
+    PipeWithPropertyRange(PipeWithPropertyRangeQuery),
+    /// Gets the vertices reachable from a set of vertices within a range of
+    /// depths.
+    Traverse(TraverseQuery),
+    /// Removes duplicate vertices or edges.
+    Dedup(DedupQuery),
+    /// Sorts vertices or edges by a property.
+    OrderBy(OrderByQuery),
 
     /// Includes the results of a query in output.
     Include(IncludeQuery),
@@ -112,15 +143,24 @@ impl Query {
             | Query::SpecificVertex(_)
             | Query::VertexWithPropertyPresence(_)
             | Query::VertexWithPropertyValue(_)
+            | Query::VertexWithPropertyRange(_)
+            | Query::VertexWithCompositeProperty(_)
             | Query::AllEdge
+            | Query::RangeEdge(_)
             | Query::SpecificEdge(_)
             | Query::EdgeWithPropertyPresence(_)
             | Query::EdgeWithPropertyValue(_)
+            | Query::EdgeWithPropertyRange(_)
+            | Query::ShortestPath(_)
             | Query::Count(_) => 1,
             Query::Pipe(q) => q.inner.output_len(),
             Query::PipeProperty(q) => q.inner.output_len(),
             Query::PipeWithPropertyPresence(q) => q.inner.output_len(),
             Query::PipeWithPropertyValue(q) => q.inner.output_len(),
+            Query::PipeWithPropertyRange(q) => q.inner.output_len(),
+            Query::Traverse(q) => q.inner.output_len(),
+            Query::Dedup(q) => q.inner.output_len(),
+            Query::OrderBy(q) => q.inner.output_len(),
             Query::Include(q) => 1 + q.inner.output_len(),
         }
     }
@@ -134,12 +174,18 @@ impl Query {
             | Query::RangeVertex(_)
             | Query::SpecificVertex(_)
             | Query::VertexWithPropertyPresence(_)
-            | Query::VertexWithPropertyValue(_) => Ok(QueryOutputValue::Vertices(Vec::default())),
+            | Query::VertexWithPropertyValue(_)
+            | Query::VertexWithPropertyRange(_)
+            | Query::VertexWithCompositeProperty(_) => Ok(QueryOutputValue::Vertices(Vec::default())),
             Query::AllEdge
+            | Query::RangeEdge(_)
             | Query::SpecificEdge(_)
             | Query::EdgeWithPropertyPresence(_)
-            | Query::EdgeWithPropertyValue(_) => Ok(QueryOutputValue::Edges(Vec::default())),
+            | Query::EdgeWithPropertyValue(_)
+            | Query::EdgeWithPropertyRange(_) => Ok(QueryOutputValue::Edges(Vec::default())),
+            Query::ShortestPath(_) => Ok(QueryOutputValue::Paths(Vec::default())),
             Query::Count(_) => Ok(QueryOutputValue::Count(0)),
+            Query::Pipe(q) if q.paths => Ok(QueryOutputValue::PipePaths(Vec::default())),
             Query::Pipe(q) => q.inner.output_type(),
             Query::PipeProperty(q) => match q.inner.output_type()? {
                 QueryOutputValue::Vertices(_) => Ok(QueryOutputValue::VertexProperties(Vec::default())),
@@ -148,11 +194,18 @@ impl Query {
             },
             Query::PipeWithPropertyPresence(q) => q.inner.output_type(),
             Query::PipeWithPropertyValue(q) => q.inner.output_type(),
+            Query::PipeWithPropertyRange(q) => q.inner.output_type(),
+            Query::Traverse(q) => q.inner.output_type(),
+            Query::Dedup(q) => q.inner.output_type(),
+            Query::OrderBy(q) => q.inner.output_type(),
             Query::Include(q) => q.inner.output_type(),
         }
     }
 }
 
+impl QueryExt for Query {}
+impl CountQueryExt for Query {}
+
 /// Extension trait containing common functions for all query structs.
 pub trait QueryExt: Into<Query> {
     /// Gets the outbound vertices or edges associated with this query.
@@ -207,6 +260,43 @@ pub trait QueryExt: Into<Query> {
         PipeWithPropertyValueQuery::new(Box::new(self.into()), name, value, false)
     }
 
+    /// Gets values with a property value in a given range.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property.
+    /// * `range`: The range of property values.
+    fn with_property_in_range<T: Into<Identifier>>(
+        self,
+        name: T,
+        range: JsonRange,
+    ) -> errors::ValidationResult<PipeWithPropertyRangeQuery> {
+        PipeWithPropertyRangeQuery::new(Box::new(self.into()), name, range)
+    }
+
+    /// Gets the vertices reachable from this query's vertices by following
+    /// up to `max_depth` edges in a direction.
+    ///
+    /// # Arguments
+    /// * `direction`: Which direction to follow edges in.
+    /// * `max_depth`: The maximum number of edges to follow.
+    fn traverse(self, direction: EdgeDirection, max_depth: u32) -> errors::ValidationResult<TraverseQuery> {
+        TraverseQuery::new(Box::new(self.into()), direction, max_depth)
+    }
+
+    /// Removes duplicates from the query results, keeping the first of each
+    /// vertex or edge.
+    fn dedup(self) -> errors::ValidationResult<DedupQuery> {
+        DedupQuery::new(Box::new(self.into()))
+    }
+
+    /// Sorts the query results by a property, in ascending order.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to sort by.
+    fn order_by<T: Into<Identifier>>(self, name: T) -> errors::ValidationResult<OrderByQuery> {
+        OrderByQuery::new(Box::new(self.into()), name.into())
+    }
+
     /// Gets the properties associated with the query results.
     fn properties(self) -> errors::ValidationResult<PipePropertyQuery> {
         PipePropertyQuery::new(Box::new(self.into()))
@@ -225,8 +315,107 @@ pub trait CountQueryExt: Into<Query> {
     }
 }
 
+/// A range of JSON values, as ordered by `Json::total_cmp`.
+///
+/// # Examples
+/// ```
+/// use indradb::{ijson, JsonRange};
+/// let range = JsonRange::between(ijson!(3), ijson!(5));
+/// assert!(range.contains(&ijson!(4.5)));
+/// assert!(!range.contains(&ijson!(6)));
+/// ```
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct JsonRange {
+    /// The lower bound of the range.
+    pub start: Bound<Json>,
+    /// The upper bound of the range.
+    pub end: Bound<Json>,
+}
+
+impl JsonRange {
+    /// Creates a new range.
+    ///
+    /// # Arguments
+    /// * `start`: The lower bound of the range.
+    /// * `end`: The upper bound of the range.
+    pub fn new(start: Bound<Json>, end: Bound<Json>) -> Self {
+        Self { start, end }
+    }
+
+    /// Creates a range of values greater than the given one.
+    ///
+    /// # Arguments
+    /// * `value`: The exclusive lower bound.
+    pub fn gt(value: Json) -> Self {
+        Self::new(Bound::Excluded(value), Bound::Unbounded)
+    }
+
+    /// Creates a range of values greater than or equal to the given one.
+    ///
+    /// # Arguments
+    /// * `value`: The inclusive lower bound.
+    pub fn gte(value: Json) -> Self {
+        Self::new(Bound::Included(value), Bound::Unbounded)
+    }
+
+    /// Creates a range of values less than the given one.
+    ///
+    /// # Arguments
+    /// * `value`: The exclusive upper bound.
+    pub fn lt(value: Json) -> Self {
+        Self::new(Bound::Unbounded, Bound::Excluded(value))
+    }
+
+    /// Creates a range of values less than or equal to the given one.
+    ///
+    /// # Arguments
+    /// * `value`: The inclusive upper bound.
+    pub fn lte(value: Json) -> Self {
+        Self::new(Bound::Unbounded, Bound::Included(value))
+    }
+
+    /// Creates a range of values between the given ones, inclusive.
+    ///
+    /// # Arguments
+    /// * `start`: The inclusive lower bound.
+    /// * `end`: The inclusive upper bound.
+    pub fn between(start: Json, end: Json) -> Self {
+        Self::new(Bound::Included(start), Bound::Included(end))
+    }
+
+    /// Checks whether a value is in the range.
+    ///
+    /// # Arguments
+    /// * `value`: The value to check.
+    pub fn contains(&self, value: &Json) -> bool {
+        let after_start = match self.start {
+            Bound::Included(ref start) => value.total_cmp(start) != Ordering::Less,
+            Bound::Excluded(ref start) => value.total_cmp(start) == Ordering::Greater,
+            Bound::Unbounded => true,
+        };
+        let before_end = match self.end {
+            Bound::Included(ref end) => value.total_cmp(end) != Ordering::Greater,
+            Bound::Excluded(ref end) => value.total_cmp(end) == Ordering::Less,
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+
+    /// Checks whether the range can't contain any values, because its lower
+    /// bound is above its upper bound.
+    pub fn is_empty(&self) -> bool {
+        match (&self.start, &self.end) {
+            (Bound::Included(start), Bound::Included(end)) => start.total_cmp(end) == Ordering::Greater,
+            (Bound::Included(start), Bound::Excluded(end))
+            | (Bound::Excluded(start), Bound::Included(end))
+            | (Bound::Excluded(start), Bound::Excluded(end)) => start.total_cmp(end) != Ordering::Less,
+            _ => false,
+        }
+    }
+}
+
 /// Gets all vertices.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct AllVertexQuery;
 
 impl QueryExt for AllVertexQuery {}
@@ -241,7 +430,7 @@ impl Into<Query> for AllVertexQuery {
 }
 
 /// Gets a range of vertices.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct RangeVertexQuery {
     /// Limits the number of vertices to get.
     pub limit: u32,
@@ -309,7 +498,7 @@ impl RangeVertexQuery {
 }
 
 /// Gets a specific set of vertices.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct SpecificVertexQuery {
     /// The IDs of the vertices to get.
     pub ids: Vec<Uuid>,
@@ -337,7 +526,7 @@ impl SpecificVertexQuery {
 }
 
 /// Gets vertices with or without a given property.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct VertexWithPropertyPresenceQuery {
     /// The name of the property.
     pub name: Identifier,
@@ -356,7 +545,7 @@ impl VertexWithPropertyPresenceQuery {
 }
 
 /// Gets vertices with a property equal to a given value.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct VertexWithPropertyValueQuery {
     /// The name of the property.
     pub name: Identifier,
@@ -380,8 +569,67 @@ impl VertexWithPropertyValueQuery {
     }
 }
 
+/// Gets vertices with a property value in a given range.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct VertexWithPropertyRangeQuery {
+    /// The name of the property.
+    pub name: Identifier,
+    /// The range of property values.
+    pub range: JsonRange,
+}
+
+nestable_query!(VertexWithPropertyRangeQuery, VertexWithPropertyRange);
+
+impl VertexWithPropertyRangeQuery {
+    /// Creates a new vertex with property range query.
+    ///
+    /// # Arguments
+    /// * `name`: The property name.
+    /// * `range`: The range of property values.
+    pub fn new<T: Into<Identifier>>(name: T, range: JsonRange) -> Self {
+        Self {
+            name: name.into(),
+            range,
+        }
+    }
+}
+
+/// Gets vertices by the values of a prefix of a composite index.
+///
+/// The composite index must have been created over exactly `names`, in
+/// order. Vertices are matched on the first `values.len()` properties, so
+/// e.g. a query with only one value for an index over
+/// `(tenant_id, external_id)` gets all vertices for a tenant.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct VertexWithCompositePropertyQuery {
+    /// The names of the properties in the composite index.
+    pub names: Vec<Identifier>,
+    /// The values of a prefix of the properties.
+    pub values: Vec<Json>,
+}
+
+nestable_query!(VertexWithCompositePropertyQuery, VertexWithCompositeProperty);
+
+impl VertexWithCompositePropertyQuery {
+    /// Creates a new vertex with composite property query.
+    ///
+    /// # Arguments
+    /// * `names`: The names of the properties in the composite index.
+    /// * `values`: The values of a prefix of the properties.
+    ///
+    /// # Errors
+    /// Returns a `ValidationError::InvalidValue` if there are more values
+    /// than property names.
+    pub fn new(names: Vec<Identifier>, values: Vec<Json>) -> errors::ValidationResult<Self> {
+        if values.len() > names.len() {
+            return Err(errors::ValidationError::InvalidValue);
+        }
+        Ok(Self { names, values })
+    }
+}
+
 /// Gets all edges.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct AllEdgeQuery;
 
 impl QueryExt for AllEdgeQuery {}
@@ -395,8 +643,76 @@ impl Into<Query> for AllEdgeQuery {
     }
 }
 
+/// Gets a range of edges, ordered by outbound ID, type and then inbound ID.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct RangeEdgeQuery {
+    /// Limits the number of edges to get.
+    pub limit: u32,
+
+    /// Filters the type of edges returned.
+    pub t: Option<Identifier>,
+
+    /// Sets the lowest edge to return.
+    pub start_edge: Option<Edge>,
+}
+
+nestable_query!(RangeEdgeQuery, RangeEdge);
+
+impl Default for RangeEdgeQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RangeEdgeQuery {
+    /// Creates a new edge range query.
+    pub fn new() -> Self {
+        Self {
+            limit: u32::MAX,
+            t: None,
+            start_edge: None,
+        }
+    }
+
+    /// Sets the limit.
+    ///
+    /// # Arguments
+    /// * `limit`: Limits the number of returned results.
+    pub fn limit(self, limit: u32) -> Self {
+        Self {
+            limit,
+            t: self.t,
+            start_edge: self.start_edge,
+        }
+    }
+
+    /// Filter the type of edges returned.
+    ///
+    /// # Arguments
+    /// * `t`: Sets the type filter.
+    pub fn t(self, t: Identifier) -> Self {
+        Self {
+            limit: self.limit,
+            t: Some(t),
+            start_edge: self.start_edge,
+        }
+    }
+
+    /// Sets the lowest edge to return.
+    ///
+    /// # Arguments
+    /// * `start_edge`: The lowest edge to return.
+    pub fn start_edge(self, start_edge: Edge) -> Self {
+        Self {
+            limit: self.limit,
+            t: self.t,
+            start_edge: Some(start_edge),
+        }
+    }
+}
+
 /// Gets a specific set of edges.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct SpecificEdgeQuery {
     /// The edges to get.
     pub edges: Vec<Edge>,
@@ -423,7 +739,7 @@ impl SpecificEdgeQuery {
 }
 
 /// Gets edges with or without a given property.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct EdgeWithPropertyPresenceQuery {
     /// The name of the property.
     pub name: Identifier,
@@ -442,7 +758,7 @@ impl EdgeWithPropertyPresenceQuery {
 }
 
 /// Gets edges with a property equal to a given value.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct EdgeWithPropertyValueQuery {
     /// The name of the property.
     pub name: Identifier,
@@ -466,12 +782,155 @@ impl EdgeWithPropertyValueQuery {
     }
 }
 
+/// Gets edges with a property value in a given range.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct EdgeWithPropertyRangeQuery {
+    /// The name of the property.
+    pub name: Identifier,
+    /// The range of property values.
+    pub range: JsonRange,
+}
+
+nestable_query!(EdgeWithPropertyRangeQuery, EdgeWithPropertyRange);
+
+impl EdgeWithPropertyRangeQuery {
+    /// Creates a new edge with property range query.
+    ///
+    /// # Arguments
+    /// * `name`: The property name.
+    /// * `range`: The range of property values.
+    pub fn new<T: Into<Identifier>>(name: T, range: JsonRange) -> Self {
+        Self {
+            name: name.into(),
+            range,
+        }
+    }
+}
+
+/// Gets the shortest path from one vertex to another, following edges in
+/// their outbound direction. The output has a single path, or none if the
+/// target can't be reached.
+///
+/// By default, the path with the fewest edges is found. With a weight, the
+/// path whose edges have the lowest total weight is found instead, where
+/// each edge's weight is read from one of its properties.
+///
+/// # Examples
+/// ```
+/// use indradb::{Identifier, ShortestPathQuery};
+/// use uuid::Uuid;
+/// // A query to get how one person is connected to another, through up to 6
+/// // `follows` edges.
+/// let q = ShortestPathQuery::new(Uuid::from_u128(1), Uuid::from_u128(2))
+///     .t(Identifier::new("follows").unwrap())
+///     .max_depth(6);
+/// ```
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ShortestPathQuery {
+    /// The id of the vertex to start from.
+    pub source_id: Uuid,
+
+    /// The id of the vertex to end at.
+    pub target_id: Uuid,
+
+    /// Filters the type of edges followed.
+    pub t: Option<Identifier>,
+
+    /// The maximum number of edges in the path.
+    pub max_depth: u32,
+
+    /// Where to read the weight of each edge from, if the path is weighted.
+    pub weight: Option<EdgeWeight>,
+}
+
+into_query!(ShortestPathQuery, ShortestPath);
+impl CountQueryExt for ShortestPathQuery {}
+
+impl ShortestPathQuery {
+    /// Constructs a new shortest path query.
+    ///
+    /// # Arguments
+    /// * `source_id`: The id of the vertex to start from.
+    /// * `target_id`: The id of the vertex to end at.
+    pub fn new(source_id: Uuid, target_id: Uuid) -> Self {
+        Self {
+            source_id,
+            target_id,
+            t: None,
+            max_depth: u32::MAX,
+            weight: None,
+        }
+    }
+
+    /// Filter the type of edges followed.
+    ///
+    /// # Arguments
+    /// * `t`: Sets the type filter.
+    pub fn t(self, t: Identifier) -> Self {
+        Self { t: Some(t), ..self }
+    }
+
+    /// Sets the maximum depth.
+    ///
+    /// # Arguments
+    /// * `max_depth`: The maximum number of edges in the path.
+    pub fn max_depth(self, max_depth: u32) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Weights the path by an edge property.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the edge property holding each edge's weight.
+    /// * `invalid`: What to do with edges whose weight is missing, or isn't
+    ///   a non-negative number.
+    pub fn weighted_by(self, name: Identifier, invalid: InvalidWeight) -> Self {
+        Self {
+            weight: Some(EdgeWeight { name, invalid }),
+            ..self
+        }
+    }
+}
+
+/// Where the weight of each edge is read from, for weighted shortest paths.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct EdgeWeight {
+    /// The name of the edge property holding each edge's weight.
+    pub name: Identifier,
+
+    /// What to do with edges whose weight is missing, or isn't a
+    /// non-negative number.
+    pub invalid: InvalidWeight,
+}
+
+impl EdgeWeight {
+    // Reads a weight from a property value, which has to be a finite,
+    // non-negative number.
+    pub(crate) fn parse(value: &Json) -> Option<f64> {
+        value.as_f64().filter(|weight| weight.is_finite() && *weight >= 0.0)
+    }
+}
+
+/// What a weighted shortest path query does with edges whose weight is
+/// missing, or isn't a non-negative number.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidWeight {
+    /// Doesn't follow the edge.
+    Skip,
+    /// Fails the query with `Error::InvalidWeight`.
+    Fail,
+    /// Uses a default weight instead, which has to be a non-negative number
+    /// itself.
+    Default(Json),
+}
+
 /// Gets the vertices associated with edges, or edges associated with
 /// vertices.
 ///
 /// Generally, you shouldn't need to construct this directly, but rather call
 /// `.outbound()` or `.inbound()`.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct PipeQuery {
     /// The edge query to build off of.
     pub inner: Box<Query>,
@@ -484,6 +943,11 @@ pub struct PipeQuery {
 
     /// Filters the type of values returned.
     pub t: Option<Identifier>,
+
+    /// Whether to output the path to each value through this and any inner
+    /// pipes, rather than the values themselves.
+    #[serde(default)]
+    pub paths: bool,
 }
 
 nestable_query!(PipeQuery, Pipe);
@@ -505,6 +969,7 @@ impl PipeQuery {
             direction,
             limit: u32::max_value(),
             t: None,
+            paths: false,
         })
     }
 
@@ -518,6 +983,7 @@ impl PipeQuery {
             direction: self.direction,
             limit,
             t: self.t,
+            paths: self.paths,
         }
     }
 
@@ -531,12 +997,26 @@ impl PipeQuery {
             direction: self.direction,
             limit: self.limit,
             t: Some(t),
+            paths: self.paths,
+        }
+    }
+
+    /// Outputs paths rather than values. Each path goes from a value of the
+    /// innermost query that isn't a pipe or a property filter, through each
+    /// pipe, to an output value.
+    pub fn paths(self) -> Self {
+        Self {
+            inner: self.inner,
+            direction: self.direction,
+            limit: self.limit,
+            t: self.t,
+            paths: true,
         }
     }
 }
 
 /// Returns the properties associated with a vertex or edge.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct PipePropertyQuery {
     /// The inner query.
     pub inner: Box<Query>,
@@ -573,7 +1053,7 @@ impl PipePropertyQuery {
 }
 
 /// Gets vertices or edges with or without a property.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct PipeWithPropertyPresenceQuery {
     /// The query to filter.
     pub inner: Box<Query>,
@@ -606,7 +1086,7 @@ impl PipeWithPropertyPresenceQuery {
 }
 
 /// Gets vertices or edges with a property equal to a given value.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct PipeWithPropertyValueQuery {
     /// The query to filter.
     pub inner: Box<Query>,
@@ -647,6 +1127,293 @@ impl PipeWithPropertyValueQuery {
     }
 }
 
+/// Gets vertices or edges with a property value in a given range.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct PipeWithPropertyRangeQuery {
+    /// The query to filter.
+    pub inner: Box<Query>,
+    /// The name of the property.
+    pub name: Identifier,
+    /// The range of property values.
+    pub range: JsonRange,
+}
+
+nestable_query!(PipeWithPropertyRangeQuery, PipeWithPropertyRange);
+
+impl PipeWithPropertyRangeQuery {
+    /// Constructs a new pipe with property range query.
+    ///
+    /// # Arguments
+    /// * `inner`: The inner query.
+    /// * `name`: The property name to filter.
+    /// * `range`: The range of property values to filter.
+    pub fn new<T: Into<Identifier>>(inner: Box<Query>, name: T, range: JsonRange) -> errors::ValidationResult<Self> {
+        match inner.output_type()? {
+            QueryOutputValue::Vertices(_) | QueryOutputValue::Edges(_) => {}
+            _ => return Err(errors::ValidationError::InnerQuery),
+        }
+        Ok(Self {
+            inner,
+            name: name.into(),
+            range,
+        })
+    }
+}
+
+/// Gets the vertices reachable from a set of vertices within a range of
+/// depths, where the depth of a vertex is the number of edges followed to
+/// reach it.
+///
+/// Each vertex is output at most once, at the shallowest depth it's reached
+/// at, so cycles aren't followed. Vertices are output in order of depth.
+///
+/// # Examples
+/// ```
+/// use indradb::{EdgeDirection, Identifier, QueryExt, SpecificVertexQuery};
+/// use uuid::Uuid;
+/// // A query to get everyone within 3 hops of a person via `follows` edges,
+/// // not including the person themselves.
+/// let q = SpecificVertexQuery::single(Uuid::default())
+///     .traverse(EdgeDirection::Outbound, 3)
+///     .unwrap()
+///     .min_depth(1)
+///     .t(Identifier::new("follows").unwrap());
+/// ```
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TraverseQuery {
+    /// The vertex query to start from.
+    pub inner: Box<Query>,
+
+    /// Whether to follow outbound or inbound edges.
+    pub direction: EdgeDirection,
+
+    /// Filters the type of edges followed.
+    pub t: Option<Identifier>,
+
+    /// The minimum depth of vertices to get. The vertices from the inner
+    /// query are at a depth of 0.
+    pub min_depth: u32,
+
+    /// The maximum depth of vertices to get.
+    pub max_depth: u32,
+
+    /// Limits the number of vertices to get at each depth past 0.
+    pub limit: u32,
+}
+
+nestable_query!(TraverseQuery, Traverse);
+
+impl TraverseQuery {
+    /// Constructs a new traverse query.
+    ///
+    /// # Arguments
+    /// * `inner`: The vertex query to start from.
+    /// * `direction`: Which direction to follow edges in.
+    /// * `max_depth`: The maximum number of edges to follow.
+    pub fn new(inner: Box<Query>, direction: EdgeDirection, max_depth: u32) -> errors::ValidationResult<Self> {
+        match inner.output_type()? {
+            QueryOutputValue::Vertices(_) => {}
+            _ => return Err(errors::ValidationError::InnerQuery),
+        }
+
+        Ok(Self {
+            inner,
+            direction,
+            t: None,
+            min_depth: 0,
+            max_depth,
+            limit: u32::MAX,
+        })
+    }
+
+    /// Filter the type of edges followed.
+    ///
+    /// # Arguments
+    /// * `t`: Sets the type filter.
+    pub fn t(self, t: Identifier) -> Self {
+        Self { t: Some(t), ..self }
+    }
+
+    /// Sets the minimum depth.
+    ///
+    /// # Arguments
+    /// * `min_depth`: Vertices reached by following fewer edges than this
+    ///   aren't returned, although they're still traversed through.
+    pub fn min_depth(self, min_depth: u32) -> Self {
+        Self { min_depth, ..self }
+    }
+
+    /// Sets the limit.
+    ///
+    /// # Arguments
+    /// * `limit`: Limits the number of vertices returned at each depth past
+    ///   0. Only these vertices are traversed through to the next depth.
+    pub fn limit(self, limit: u32) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+/// Removes duplicate vertices or edges from the results of a query, keeping
+/// them in the order they're first seen.
+///
+/// # Examples
+/// ```
+/// use indradb::{QueryExt, SpecificVertexQuery};
+/// use uuid::Uuid;
+/// // A query to get the vertices that a vertex's neighbors link to, with
+/// // each one only returned once.
+/// let q = SpecificVertexQuery::single(Uuid::default())
+///     .outbound()
+///     .unwrap()
+///     .inbound()
+///     .unwrap()
+///     .outbound()
+///     .unwrap()
+///     .inbound()
+///     .unwrap()
+///     .dedup();
+/// ```
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct DedupQuery {
+    /// The vertex or edge query to remove duplicates from.
+    pub inner: Box<Query>,
+}
+
+nestable_query!(DedupQuery, Dedup);
+
+impl DedupQuery {
+    /// Constructs a new dedup query.
+    ///
+    /// # Arguments
+    /// * `inner`: The vertex or edge query to remove duplicates from.
+    pub fn new(inner: Box<Query>) -> errors::ValidationResult<Self> {
+        match inner.output_type()? {
+            QueryOutputValue::Vertices(_) | QueryOutputValue::Edges(_) => {}
+            _ => return Err(errors::ValidationError::InnerQuery),
+        }
+
+        Ok(Self { inner })
+    }
+}
+
+/// Sorts vertices or edges by the value of a property, using the total order
+/// of `Json::total_cmp`. Values that compare equal keep their order from the
+/// inner query.
+///
+/// # Examples
+/// ```
+/// use indradb::{AllVertexQuery, Identifier, QueryExt};
+/// // A query to get the ten vertices with the highest scores, after the
+/// // first ten. Vertices without a score come last.
+/// let q = AllVertexQuery
+///     .order_by(Identifier::new("score").unwrap())
+///     .unwrap()
+///     .descending()
+///     .offset(10)
+///     .limit(10);
+/// ```
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct OrderByQuery {
+    /// The vertex or edge query to sort.
+    pub inner: Box<Query>,
+
+    /// The name of the property to sort by.
+    pub name: Identifier,
+
+    /// Whether to sort in ascending or descending order.
+    pub order: SortOrder,
+
+    /// Where to put values without the property, or with a null value for
+    /// it.
+    pub nulls: NullsOrder,
+
+    /// The number of sorted values to skip.
+    pub offset: u32,
+
+    /// Limits the number of values to get, after the offset.
+    pub limit: u32,
+}
+
+nestable_query!(OrderByQuery, OrderBy);
+
+impl OrderByQuery {
+    /// Constructs a new order by query, which sorts in ascending order with
+    /// nulls last.
+    ///
+    /// # Arguments
+    /// * `inner`: The vertex or edge query to sort.
+    /// * `name`: The name of the property to sort by.
+    pub fn new(inner: Box<Query>, name: Identifier) -> errors::ValidationResult<Self> {
+        match inner.output_type()? {
+            QueryOutputValue::Vertices(_) | QueryOutputValue::Edges(_) => {}
+            _ => return Err(errors::ValidationError::InnerQuery),
+        }
+
+        Ok(Self {
+            inner,
+            name,
+            order: SortOrder::Ascending,
+            nulls: NullsOrder::Last,
+            offset: 0,
+            limit: u32::MAX,
+        })
+    }
+
+    /// Sorts in descending order.
+    pub fn descending(self) -> Self {
+        Self {
+            order: SortOrder::Descending,
+            ..self
+        }
+    }
+
+    /// Sets where nulls go.
+    ///
+    /// # Arguments
+    /// * `nulls`: Where to put values without the property, or with a null
+    ///   value for it.
+    pub fn nulls(self, nulls: NullsOrder) -> Self {
+        Self { nulls, ..self }
+    }
+
+    /// Sets the offset.
+    ///
+    /// # Arguments
+    /// * `offset`: The number of sorted values to skip.
+    pub fn offset(self, offset: u32) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// Sets the limit.
+    ///
+    /// # Arguments
+    /// * `limit`: Limits the number of values returned, after the offset.
+    pub fn limit(self, limit: u32) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+/// The order to sort values in.
+#[derive(Eq, PartialEq, Clone, Debug, Hash, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// From the lowest value to the highest.
+    Ascending,
+    /// From the highest value to the lowest.
+    Descending,
+}
+
+/// Where a sort puts values without the property it sorts by, or with a null
+/// value for it.
+#[derive(Eq, PartialEq, Clone, Debug, Hash, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NullsOrder {
+    /// Before the other values, whatever the sort order.
+    First,
+    /// After the other values, whatever the sort order.
+    Last,
+}
+
 /// Includes the results of a query in output.
 ///
 /// The outermost part of a query will always be explicitly included. This
@@ -662,7 +1429,7 @@ impl PipeWithPropertyValueQuery {
 /// // vertices are explicitly included as intermediate results.
 /// let q = AllVertexQuery.include().outbound();
 /// ```
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct IncludeQuery {
     /// The query to export.
     pub inner: Box<Query>,
@@ -688,7 +1455,7 @@ impl IncludeQuery {
 /// // A query to return the total number of vertices in the database.
 /// let q = AllVertexQuery.count();
 /// ```
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct CountQuery {
     /// The query to export.
     pub inner: Box<Query>,
@@ -706,7 +1473,9 @@ impl CountQuery {
             QueryOutputValue::Vertices(_)
             | QueryOutputValue::Edges(_)
             | QueryOutputValue::VertexProperties(_)
-            | QueryOutputValue::EdgeProperties(_) => {}
+            | QueryOutputValue::EdgeProperties(_)
+            | QueryOutputValue::Paths(_)
+            | QueryOutputValue::PipePaths(_) => {}
             _ => return Err(errors::ValidationError::InnerQuery),
         }
         Ok(Self { inner })
@@ -714,7 +1483,12 @@ impl CountQuery {
 }
 
 /// Value(s) returned from a query.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// With serde, output values are represented as maps with a `type` field
+/// naming the variant in snake case, and a `value` field holding its
+/// contents, e.g. `{"type": "count", "value": 3}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum QueryOutputValue {
     /// Vertices.
     Vertices(Vec<crate::Vertex>),
@@ -726,15 +1500,24 @@ pub enum QueryOutputValue {
     VertexProperties(Vec<crate::VertexProperties>),
     /// Edge properties.
     EdgeProperties(Vec<crate::EdgeProperties>),
+    /// Paths.
+    Paths(Vec<crate::Path>),
+    /// Paths through pipes.
+    PipePaths(Vec<crate::PipePath>),
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        ijson, AllVertexQuery, CountQuery, CountQueryExt, EdgeDirection, Identifier, PipePropertyQuery, PipeQuery,
-        PipeWithPropertyPresenceQuery, PipeWithPropertyValueQuery, Query, ValidationError,
+        ijson, AllEdgeQuery, AllVertexQuery, CountQuery, CountQueryExt, Edge, EdgeDirection, EdgeProperties,
+        EdgeProperty, EdgeWithPropertyRangeQuery, Identifier, JsonRange, NamedProperty, NullsOrder, Path,
+        PipePropertyQuery, PipeQuery, PipeWithPropertyPresenceQuery, PipeWithPropertyValueQuery, Query, QueryExt,
+        QueryOutputValue, RangeEdgeQuery, RangeVertexQuery, SpecificEdgeQuery, SpecificVertexQuery, TraverseQuery,
+        ValidationError, Vertex, VertexProperties, VertexWithCompositePropertyQuery,
     };
+    use std::ops::Bound;
     use std::str::FromStr;
+    use uuid::Uuid;
 
     fn expect_inner_query_err<T: core::fmt::Debug>(result: Result<T, ValidationError>) {
         match result {
@@ -764,6 +1547,7 @@ mod tests {
         expect_inner_query_err(CountQuery::new(Box::new(q.clone())));
         expect_inner_query_err(PipeQuery::new(Box::new(q.clone()), EdgeDirection::Outbound));
         expect_inner_query_err(PipePropertyQuery::new(Box::new(q.clone())));
+        expect_inner_query_err(TraverseQuery::new(Box::new(q.clone()), EdgeDirection::Outbound, 1));
         expect_inner_query_err(PipeWithPropertyPresenceQuery::new(
             Box::new(q.clone()),
             Identifier::new("foo").unwrap(),
@@ -776,4 +1560,125 @@ mod tests {
             true,
         ));
     }
+
+    #[test]
+    fn should_round_trip_queries_through_serde() {
+        let id = |s| Identifier::new(s).unwrap();
+        let vertex_id = Uuid::from_u128(1);
+        let edge = Edge::new(vertex_id, id("follows"), Uuid::from_u128(2));
+        let queries: Vec<Query> = vec![
+            AllVertexQuery.into(),
+            AllEdgeQuery.into(),
+            RangeVertexQuery::new()
+                .t(id("person"))
+                .start_id(vertex_id)
+                .limit(5)
+                .into(),
+            RangeEdgeQuery::new().start_edge(edge.clone()).into(),
+            SpecificEdgeQuery::new(vec![edge]).into(),
+            VertexWithCompositePropertyQuery::new(vec![id("a"), id("b")], vec![ijson!({"c": [1, 2.5, null]})])
+                .unwrap()
+                .into(),
+            EdgeWithPropertyRangeQuery::new(
+                id("weight"),
+                JsonRange::new(Bound::Excluded(ijson!(-1)), Bound::Unbounded),
+            )
+            .into(),
+            SpecificVertexQuery::single(vertex_id)
+                .include()
+                .inbound()
+                .unwrap()
+                .limit(2)
+                .dedup()
+                .unwrap()
+                .without_property(id("x"))
+                .unwrap()
+                .with_property_not_equal_to(id("y"), ijson!("z"))
+                .unwrap()
+                .properties()
+                .unwrap()
+                .name(id("w"))
+                .count()
+                .unwrap()
+                .into(),
+            AllVertexQuery
+                .order_by(id("score"))
+                .unwrap()
+                .descending()
+                .nulls(NullsOrder::First)
+                .limit(10)
+                .into(),
+        ];
+
+        for q in queries {
+            let json = serde_json::to_string(&q).unwrap();
+            assert_eq!(serde_json::from_str::<Query>(&json).unwrap(), q, "{json}");
+        }
+    }
+
+    #[test]
+    fn should_serialize_tagged_queries() {
+        let q: Query = AllVertexQuery.outbound().unwrap().limit(3).into();
+        assert_eq!(
+            serde_json::to_value(&q).unwrap(),
+            serde_json::json!({
+                "type": "pipe",
+                "inner": {"type": "all_vertex"},
+                "direction": "outbound",
+                "limit": 3,
+                "t": null,
+                "paths": false,
+            })
+        );
+
+        let json = serde_json::json!({
+            "type": "pipe",
+            "inner": {"type": "all_vertex"},
+            "direction": "outbound",
+            "limit": 3,
+            "t": null,
+        });
+        assert_eq!(serde_json::from_value::<Query>(json).unwrap(), q);
+    }
+
+    #[test]
+    fn should_round_trip_output_values_through_serde() {
+        let id = |s| Identifier::new(s).unwrap();
+        let vertex = Vertex::with_id(Uuid::from_u128(1), id("person"));
+        let edge = Edge::new(vertex.id, id("follows"), Uuid::from_u128(2));
+        let values = vec![
+            QueryOutputValue::Vertices(vec![vertex.clone()]),
+            QueryOutputValue::Edges(vec![edge.clone()]),
+            QueryOutputValue::Count(3),
+            QueryOutputValue::VertexProperties(vec![VertexProperties::new(
+                vertex.clone(),
+                vec![NamedProperty::new(id("name"), ijson!("a"))],
+            )]),
+            QueryOutputValue::Paths(vec![Path::new(
+                vec![vertex, Vertex::with_id(edge.inbound_id, id("person"))],
+                vec![edge.clone()],
+            )]),
+            QueryOutputValue::EdgeProperties(vec![EdgeProperties::new(
+                edge.clone(),
+                vec![NamedProperty::new(id("weight"), ijson!(1.5))],
+            )]),
+        ];
+
+        for value in values {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(
+                serde_json::from_str::<QueryOutputValue>(&json).unwrap(),
+                value,
+                "{json}"
+            );
+        }
+
+        assert_eq!(
+            serde_json::to_value(QueryOutputValue::Count(3)).unwrap(),
+            serde_json::json!({"type": "count", "value": 3})
+        );
+        let property = EdgeProperty::new(edge, ijson!(true));
+        let json = serde_json::to_string(&property).unwrap();
+        assert_eq!(serde_json::from_str::<EdgeProperty>(&json).unwrap(), property);
+    }
 }
@@ -0,0 +1,147 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::errors::{Error, Result};
+use crate::{Identifier, Json};
+
+use serde::{Deserialize, Serialize};
+
+/// The type of a JSON value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum JsonType {
+    /// A null value.
+    Null,
+    /// A boolean value.
+    Bool,
+    /// A number value.
+    Number,
+    /// A string value.
+    String,
+    /// An array value.
+    Array,
+    /// An object value.
+    Object,
+}
+
+impl JsonType {
+    /// Gets the type of a JSON value.
+    ///
+    /// # Arguments
+    /// * `value`: The JSON value.
+    pub fn of(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => JsonType::Null,
+            serde_json::Value::Bool(_) => JsonType::Bool,
+            serde_json::Value::Number(_) => JsonType::Number,
+            serde_json::Value::String(_) => JsonType::String,
+            serde_json::Value::Array(_) => JsonType::Array,
+            serde_json::Value::Object(_) => JsonType::Object,
+        }
+    }
+}
+
+/// Specifies whether a schema applies to vertices or edges.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum SchemaKind {
+    /// The schema applies to vertices of a type.
+    Vertex,
+    /// The schema applies to edges of a type.
+    Edge,
+}
+
+/// Declares which properties can be set on vertices or edges of a given
+/// type, and the JSON types of their values.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    /// The declared properties, mapped to the JSON types that their values
+    /// can have. An empty set of types allows values of any type.
+    pub properties: BTreeMap<Identifier, BTreeSet<JsonType>>,
+
+    /// Whether properties that aren't declared can be set.
+    pub allow_undeclared: bool,
+}
+
+impl Schema {
+    /// Creates a new schema that doesn't allow any properties.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a property.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property.
+    /// * `types`: The JSON types that the property's values can have. If
+    ///   empty, values of any type are allowed.
+    pub fn property<I: IntoIterator<Item = JsonType>>(mut self, name: Identifier, types: I) -> Self {
+        self.properties.insert(name, types.into_iter().collect());
+        self
+    }
+
+    /// Sets whether properties that aren't declared can be set.
+    ///
+    /// # Arguments
+    /// * `allow_undeclared`: Whether to allow undeclared properties.
+    pub fn allow_undeclared(self, allow_undeclared: bool) -> Self {
+        Self {
+            properties: self.properties,
+            allow_undeclared,
+        }
+    }
+
+    /// Checks whether a property value is allowed by the schema.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property.
+    /// * `value`: The value of the property.
+    pub fn allows(&self, name: Identifier, value: &Json) -> bool {
+        match self.properties.get(&name) {
+            Some(types) => types.is_empty() || types.contains(&JsonType::of(value)),
+            None => self.allow_undeclared,
+        }
+    }
+
+    /// Checks that a property value set on a vertex or edge of type `t` is
+    /// allowed by the schema.
+    ///
+    /// # Arguments
+    /// * `t`: The type of the vertex or edge.
+    /// * `name`: The name of the property.
+    /// * `value`: The value of the property.
+    ///
+    /// # Errors
+    /// Returns `Error::SchemaViolation` if the value isn't allowed.
+    pub fn validate(&self, t: Identifier, name: Identifier, value: &Json) -> Result<()> {
+        if self.allows(name, value) {
+            Ok(())
+        } else {
+            Err(Error::SchemaViolation { t, name })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonType, Schema};
+    use crate::{ijson, Identifier};
+
+    #[test]
+    fn should_allow_declared_properties() {
+        let name = Identifier::new("name").unwrap();
+        let any = Identifier::new("any").unwrap();
+        let schema = Schema::new()
+            .property(name, vec![JsonType::String, JsonType::Null])
+            .property(any, vec![]);
+        assert!(schema.allows(name, &ijson!("foo")));
+        assert!(schema.allows(name, &ijson!(null)));
+        assert!(!schema.allows(name, &ijson!(1)));
+        assert!(schema.allows(any, &ijson!({"foo": [1]})));
+    }
+
+    #[test]
+    fn should_allow_undeclared_properties() {
+        let other = Identifier::new("other").unwrap();
+        let schema = Schema::new();
+        assert!(!schema.allows(other, &ijson!(true)));
+        assert!(schema.clone().allow_undeclared(true).allows(other, &ijson!(true)));
+    }
+}
@@ -0,0 +1,903 @@
+use std::fmt;
+use std::ops::Bound;
+
+use crate::errors::{Error, Result, ValidationError};
+use crate::{
+    DynIter, Edge, EdgeDirection, EdgeWeight, Identifier, InvalidWeight, Json, JsonRange, NullsOrder, PipeQuery, Query,
+    SortOrder, Transaction,
+};
+
+use uuid::Uuid;
+
+/// What a property index is read for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexPredicate {
+    /// Values that have the property.
+    Presence,
+    /// Values whose property is equal to a given value.
+    Value(Json),
+    /// Values whose property is in a given range.
+    Range(JsonRange),
+}
+
+/// An operation in a query plan.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanOp {
+    /// Scans vertices in id order, starting at an id if one is given.
+    VertexScan {
+        /// The id to start at.
+        start_id: Option<Uuid>,
+    },
+    /// Scans vertices of a type in id order, through the type index.
+    VertexTypeScan {
+        /// The vertex type.
+        t: Identifier,
+        /// The id to start at.
+        start_id: Option<Uuid>,
+    },
+    /// Looks up vertices by id.
+    VertexLookup {
+        /// The vertex ids.
+        ids: Vec<Uuid>,
+    },
+    /// Gets vertices through a property index.
+    VertexIndexScan {
+        /// The property name.
+        name: Identifier,
+        /// What the index is read for.
+        predicate: IndexPredicate,
+    },
+    /// Gets vertices through a composite index.
+    VertexCompositeIndexScan {
+        /// The indexed property names.
+        names: Vec<Identifier>,
+        /// The values of a prefix of the properties.
+        values: Vec<Json>,
+    },
+    /// Scans edges in order, starting at an edge if one is given.
+    EdgeScan {
+        /// The edge to start at.
+        start: Option<Edge>,
+    },
+    /// Scans edges of a type in order, through the type index.
+    EdgeTypeScan {
+        /// The edge type.
+        t: Identifier,
+        /// The edge to start at.
+        start: Option<Edge>,
+    },
+    /// Looks up specific edges.
+    EdgeLookup {
+        /// The edges.
+        edges: Vec<Edge>,
+    },
+    /// Gets edges through a property index.
+    EdgeIndexScan {
+        /// The property name.
+        name: Identifier,
+        /// What the index is read for.
+        predicate: IndexPredicate,
+    },
+    /// Finds the shortest path between two vertices, searching from both
+    /// ends.
+    ShortestPath {
+        /// The id of the vertex to start from.
+        source_id: Uuid,
+        /// The id of the vertex to end at.
+        target_id: Uuid,
+        /// The edge type to filter on.
+        t: Option<Identifier>,
+        /// The maximum number of edges in the path.
+        max_depth: u32,
+        /// Where to read the weight of each edge from, if the path is
+        /// weighted.
+        weight: Option<EdgeWeight>,
+    },
+    /// Starts a path at each input vertex or edge, for pipes that output
+    /// paths. The nodes that consume the paths operate on the last value of
+    /// each one.
+    PathStart,
+    /// Gets the edges of the input vertices. With a type, each vertex's
+    /// edges are only scanned from where that type starts.
+    EdgeExpand {
+        /// Whether to get outbound or inbound edges.
+        direction: EdgeDirection,
+        /// The edge type to filter on.
+        t: Option<Identifier>,
+    },
+    /// Gets the vertices on one end of the input edges.
+    VertexExpand {
+        /// Whether to get the outbound or inbound vertices.
+        direction: EdgeDirection,
+        /// The vertex type to filter on.
+        t: Option<Identifier>,
+    },
+    /// Gets the vertices reachable from the input vertices, breadth-first.
+    Traverse {
+        /// Whether to follow outbound or inbound edges.
+        direction: EdgeDirection,
+        /// The edge type to filter on.
+        t: Option<Identifier>,
+        /// The minimum depth of vertices to output.
+        min_depth: u32,
+        /// The maximum depth of vertices to output.
+        max_depth: u32,
+        /// The maximum number of vertices to output at each depth past 0.
+        limit: u32,
+    },
+    /// Filters the input vertices or edges by reading the matching entries
+    /// of a property index into memory.
+    IndexFilter {
+        /// The property name.
+        name: Identifier,
+        /// What the index is read for.
+        predicate: IndexPredicate,
+        /// Whether to keep the inputs that don't match, rather than the ones
+        /// that do.
+        negated: bool,
+    },
+    /// Removes duplicates from the input vertices or edges, keeping the
+    /// first of each.
+    Dedup,
+    /// Sorts the input vertices or edges by a property, reading all of them
+    /// into memory.
+    OrderBy {
+        /// The property name.
+        name: Identifier,
+        /// Whether to sort in ascending or descending order.
+        order: SortOrder,
+        /// Where to put inputs without the property.
+        nulls: NullsOrder,
+        /// The number of sorted inputs to skip.
+        offset: u32,
+        /// The maximum number of sorted inputs to output, after the offset.
+        limit: u32,
+    },
+    /// Gets the properties of the input vertices or edges.
+    PropertyFetch {
+        /// The property name, or `None` for all properties.
+        name: Option<Identifier>,
+    },
+    /// Stops after a number of inputs.
+    Limit(u32),
+    /// Counts the inputs.
+    Count,
+    /// Gets the number of vertices from the datastore, without scanning them.
+    VertexCount,
+    /// Gets the number of edges from the datastore, without scanning them.
+    EdgeCount,
+}
+
+/// A physical plan for running a query, as returned by
+/// `Database::explain`. Each node of the plan consumes the output of its
+/// input node, if it has one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryPlan {
+    /// The operation.
+    pub op: PlanOp,
+    /// The node whose output this one consumes.
+    pub input: Option<Box<QueryPlan>>,
+    /// Whether the node's output is included in the query's output, in
+    /// addition to being consumed by the next node.
+    pub exported: bool,
+    /// An upper bound on the number of values the node outputs, estimated
+    /// from the vertex and edge counts, and the number of entries of the
+    /// type and property indexes the plan reads. This is only set on
+    /// explained plans.
+    pub estimated_rows: Option<u64>,
+}
+
+impl QueryPlan {
+    fn new(op: PlanOp, input: Option<QueryPlan>) -> Self {
+        Self {
+            op,
+            input: input.map(Box::new),
+            exported: false,
+            estimated_rows: None,
+        }
+    }
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut node = Some(self);
+        let mut depth = 0;
+        while let Some(plan) = node {
+            if depth > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:width$}{}", "", plan.op, width = depth * 2)?;
+            if plan.exported {
+                write!(f, " [exported]")?;
+            }
+            if let Some(rows) = plan.estimated_rows {
+                write!(f, " (rows <= {rows})")?;
+            }
+            node = plan.input.as_deref();
+            depth += 1;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PlanOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanOp::VertexScan { start_id } => {
+                write!(f, "VertexScan")?;
+                if let Some(start_id) = start_id {
+                    write!(f, " start_id={start_id}")?;
+                }
+                Ok(())
+            }
+            PlanOp::VertexTypeScan { t, start_id } => {
+                write!(f, "VertexTypeScan t={}", t.as_str())?;
+                if let Some(start_id) = start_id {
+                    write!(f, " start_id={start_id}")?;
+                }
+                Ok(())
+            }
+            PlanOp::VertexLookup { ids } => write!(f, "VertexLookup ids={}", ids.len()),
+            PlanOp::VertexIndexScan { name, predicate } => {
+                write!(f, "VertexIndexScan name={} {predicate}", name.as_str())
+            }
+            PlanOp::VertexCompositeIndexScan { names, values } => {
+                write!(f, "VertexCompositeIndexScan names=")?;
+                write_list(f, names.iter().map(|name| name.as_str()))?;
+                write!(f, " values=")?;
+                write_list(f, values.iter().map(|value| &**value))
+            }
+            PlanOp::EdgeScan { start } => {
+                write!(f, "EdgeScan")?;
+                if let Some(start) = start {
+                    write!(f, " start=")?;
+                    write_edge(f, start)?;
+                }
+                Ok(())
+            }
+            PlanOp::EdgeTypeScan { t, start } => {
+                write!(f, "EdgeTypeScan t={}", t.as_str())?;
+                if let Some(start) = start {
+                    write!(f, " start=")?;
+                    write_edge(f, start)?;
+                }
+                Ok(())
+            }
+            PlanOp::EdgeLookup { edges } => write!(f, "EdgeLookup edges={}", edges.len()),
+            PlanOp::EdgeIndexScan { name, predicate } => {
+                write!(f, "EdgeIndexScan name={} {predicate}", name.as_str())
+            }
+            PlanOp::ShortestPath {
+                source_id,
+                target_id,
+                t,
+                max_depth,
+                weight,
+            } => {
+                write!(f, "ShortestPath source_id={source_id} target_id={target_id}")?;
+                if let Some(t) = t {
+                    write!(f, " t={}", t.as_str())?;
+                }
+                if *max_depth != u32::MAX {
+                    write!(f, " max_depth={max_depth}")?;
+                }
+                if let Some(weight) = weight {
+                    write!(f, " weight={}", weight.name.as_str())?;
+                }
+                Ok(())
+            }
+            PlanOp::PathStart => write!(f, "PathStart"),
+            PlanOp::EdgeExpand { direction, t } => {
+                write!(f, "EdgeExpand direction={}", String::from(*direction))?;
+                if let Some(t) = t {
+                    write!(f, " t={}", t.as_str())?;
+                }
+                Ok(())
+            }
+            PlanOp::VertexExpand { direction, t } => {
+                write!(f, "VertexExpand direction={}", String::from(*direction))?;
+                if let Some(t) = t {
+                    write!(f, " t={}", t.as_str())?;
+                }
+                Ok(())
+            }
+            PlanOp::Traverse {
+                direction,
+                t,
+                min_depth,
+                max_depth,
+                limit,
+            } => {
+                write!(
+                    f,
+                    "Traverse direction={} depth={min_depth}..={max_depth}",
+                    String::from(*direction)
+                )?;
+                if let Some(t) = t {
+                    write!(f, " t={}", t.as_str())?;
+                }
+                if *limit != u32::MAX {
+                    write!(f, " limit={limit}")?;
+                }
+                Ok(())
+            }
+            PlanOp::IndexFilter {
+                name,
+                predicate,
+                negated,
+            } => {
+                write!(f, "IndexFilter name={} {predicate}", name.as_str())?;
+                if *negated {
+                    write!(f, " negated")?;
+                }
+                Ok(())
+            }
+            PlanOp::Dedup => write!(f, "Dedup"),
+            PlanOp::OrderBy {
+                name,
+                order,
+                nulls,
+                offset,
+                limit,
+            } => {
+                let order = match order {
+                    SortOrder::Ascending => "asc",
+                    SortOrder::Descending => "desc",
+                };
+                let nulls = match nulls {
+                    NullsOrder::First => "first",
+                    NullsOrder::Last => "last",
+                };
+                write!(f, "OrderBy name={} {order} nulls={nulls}", name.as_str())?;
+                if *offset != 0 {
+                    write!(f, " offset={offset}")?;
+                }
+                if *limit != u32::MAX {
+                    write!(f, " limit={limit}")?;
+                }
+                Ok(())
+            }
+            PlanOp::PropertyFetch { name } => match name {
+                Some(name) => write!(f, "PropertyFetch name={}", name.as_str()),
+                None => write!(f, "PropertyFetch all"),
+            },
+            PlanOp::Limit(limit) => write!(f, "Limit {limit}"),
+            PlanOp::Count => write!(f, "Count"),
+            PlanOp::VertexCount => write!(f, "VertexCount"),
+            PlanOp::EdgeCount => write!(f, "EdgeCount"),
+        }
+    }
+}
+
+impl fmt::Display for IndexPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexPredicate::Presence => write!(f, "presence"),
+            IndexPredicate::Value(value) => write!(f, "value={}", **value),
+            IndexPredicate::Range(range) => {
+                write!(f, "range=")?;
+                match range.start {
+                    Bound::Included(ref value) => write!(f, "[{}", **value)?,
+                    Bound::Excluded(ref value) => write!(f, "({}", **value)?,
+                    Bound::Unbounded => write!(f, "(")?,
+                }
+                write!(f, "..")?;
+                match range.end {
+                    Bound::Included(ref value) => write!(f, "{}]", **value),
+                    Bound::Excluded(ref value) => write!(f, "{})", **value),
+                    Bound::Unbounded => write!(f, ")"),
+                }
+            }
+        }
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: impl Iterator<Item = T>) -> fmt::Result {
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+fn write_edge(f: &mut fmt::Formatter, edge: &Edge) -> fmt::Result {
+    write!(f, "({}, {}, {})", edge.outbound_id, edge.t.as_str(), edge.inbound_id)
+}
+
+// The kind of values a plan node outputs.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Vertices,
+    Edges,
+    Properties,
+    Paths,
+    VertexPaths,
+    EdgePaths,
+    Count,
+}
+
+// Plans a query. Count queries are answered from the vertex and edge counts
+// where possible, which is only correct if nothing has expired.
+pub(crate) fn build(q: &Query, has_expired: bool) -> Result<QueryPlan> {
+    Ok(build_node(q, has_expired, true)?.0)
+}
+
+// Plans a query, along with the kind of values it outputs. `ordered` is
+// false when nothing depends on the order of the output, e.g. when it's
+// counted, which lets the planner swap scans for index reads.
+fn build_node(q: &Query, has_expired: bool, ordered: bool) -> Result<(QueryPlan, Output)> {
+    let plan = match q {
+        Query::AllVertex => (
+            QueryPlan::new(PlanOp::VertexScan { start_id: None }, None),
+            Output::Vertices,
+        ),
+        Query::RangeVertex(q) => {
+            let op = match q.t {
+                Some(t) => PlanOp::VertexTypeScan {
+                    t,
+                    start_id: q.start_id,
+                },
+                None => PlanOp::VertexScan { start_id: q.start_id },
+            };
+            (limited(QueryPlan::new(op, None), q.limit), Output::Vertices)
+        }
+        Query::SpecificVertex(q) => (
+            QueryPlan::new(PlanOp::VertexLookup { ids: q.ids.clone() }, None),
+            Output::Vertices,
+        ),
+        Query::VertexWithPropertyPresence(q) => (vertex_index_scan(q.name, IndexPredicate::Presence), Output::Vertices),
+        Query::VertexWithPropertyValue(q) => (
+            vertex_index_scan(q.name, IndexPredicate::Value(q.value.clone())),
+            Output::Vertices,
+        ),
+        Query::VertexWithPropertyRange(q) => (
+            vertex_index_scan(q.name, IndexPredicate::Range(q.range.clone())),
+            Output::Vertices,
+        ),
+        Query::VertexWithCompositeProperty(q) => {
+            let op = PlanOp::VertexCompositeIndexScan {
+                names: q.names.clone(),
+                values: q.values.clone(),
+            };
+            (QueryPlan::new(op, None), Output::Vertices)
+        }
+        Query::AllEdge => (QueryPlan::new(PlanOp::EdgeScan { start: None }, None), Output::Edges),
+        Query::RangeEdge(q) => {
+            let op = match q.t {
+                Some(t) => PlanOp::EdgeTypeScan {
+                    t,
+                    start: q.start_edge.clone(),
+                },
+                None => PlanOp::EdgeScan {
+                    start: q.start_edge.clone(),
+                },
+            };
+            (limited(QueryPlan::new(op, None), q.limit), Output::Edges)
+        }
+        Query::SpecificEdge(q) => (
+            QueryPlan::new(PlanOp::EdgeLookup { edges: q.edges.clone() }, None),
+            Output::Edges,
+        ),
+        Query::EdgeWithPropertyPresence(q) => (edge_index_scan(q.name, IndexPredicate::Presence), Output::Edges),
+        Query::EdgeWithPropertyValue(q) => (
+            edge_index_scan(q.name, IndexPredicate::Value(q.value.clone())),
+            Output::Edges,
+        ),
+        Query::EdgeWithPropertyRange(q) => (
+            edge_index_scan(q.name, IndexPredicate::Range(q.range.clone())),
+            Output::Edges,
+        ),
+        Query::ShortestPath(q) => {
+            if let Some(EdgeWeight {
+                invalid: InvalidWeight::Default(ref value),
+                ..
+            }) = q.weight
+            {
+                if EdgeWeight::parse(value).is_none() {
+                    return Err(Error::Invalid(ValidationError::InvalidValue));
+                }
+            }
+            let op = PlanOp::ShortestPath {
+                source_id: q.source_id,
+                target_id: q.target_id,
+                t: q.t,
+                max_depth: q.max_depth,
+                weight: q.weight.clone(),
+            };
+            (QueryPlan::new(op, None), Output::Paths)
+        }
+        Query::Pipe(pipe) if pipe.paths => build_path_node(q, has_expired, ordered)?,
+        Query::Pipe(q) => {
+            let (input, output) = build_node(&q.inner, has_expired, limits_ordered(ordered, q.limit))?;
+            let (op, output) = pipe_op(q, output)?;
+            (limited(QueryPlan::new(op, Some(input)), q.limit), output)
+        }
+        Query::Traverse(q) => {
+            let (input, output) = build_node(&q.inner, has_expired, limits_ordered(ordered, q.limit))?;
+            if output != Output::Vertices {
+                return Err(Error::OperationOnQuery);
+            }
+            let op = PlanOp::Traverse {
+                direction: q.direction,
+                t: q.t,
+                min_depth: q.min_depth,
+                max_depth: q.max_depth,
+                limit: q.limit,
+            };
+            (QueryPlan::new(op, Some(input)), Output::Vertices)
+        }
+        Query::Dedup(q) => {
+            let (input, output) = build_node(&q.inner, has_expired, ordered)?;
+            if !matches!(output, Output::Vertices | Output::Edges) {
+                return Err(Error::OperationOnQuery);
+            }
+            (QueryPlan::new(PlanOp::Dedup, Some(input)), output)
+        }
+        Query::OrderBy(q) => {
+            // Ties keep their inner order, which only matters if the sorted
+            // order does, or if some of them are skipped
+            let inner_ordered = limits_ordered(ordered, q.limit) || q.offset > 0;
+            let (input, output) = build_node(&q.inner, has_expired, inner_ordered)?;
+            if !matches!(output, Output::Vertices | Output::Edges) {
+                return Err(Error::OperationOnQuery);
+            }
+            let op = PlanOp::OrderBy {
+                name: q.name,
+                order: q.order,
+                nulls: q.nulls,
+                offset: q.offset,
+                limit: q.limit,
+            };
+            (QueryPlan::new(op, Some(input)), output)
+        }
+        Query::PipeProperty(q) => {
+            let (input, output) = build_node(&q.inner, has_expired, ordered)?;
+            if !matches!(output, Output::Vertices | Output::Edges) {
+                return Err(Error::OperationOnQuery);
+            }
+            (
+                QueryPlan::new(PlanOp::PropertyFetch { name: q.name }, Some(input)),
+                Output::Properties,
+            )
+        }
+        Query::PipeWithPropertyPresence(q) => index_filter(
+            &q.inner,
+            q.name,
+            IndexPredicate::Presence,
+            !q.exists,
+            has_expired,
+            ordered,
+        )?,
+        Query::PipeWithPropertyValue(q) => index_filter(
+            &q.inner,
+            q.name,
+            IndexPredicate::Value(q.value.clone()),
+            !q.equal,
+            has_expired,
+            ordered,
+        )?,
+        Query::PipeWithPropertyRange(q) => index_filter(
+            &q.inner,
+            q.name,
+            IndexPredicate::Range(q.range.clone()),
+            false,
+            has_expired,
+            ordered,
+        )?,
+        Query::Include(q) => {
+            let (mut plan, output) = build_node(&q.inner, has_expired, true)?;
+            plan.exported = true;
+            (plan, output)
+        }
+        Query::Count(q) => {
+            let plan = match *q.inner {
+                Query::AllVertex if !has_expired => QueryPlan::new(PlanOp::VertexCount, None),
+                Query::AllEdge if !has_expired => QueryPlan::new(PlanOp::EdgeCount, None),
+                ref inner => {
+                    let (input, output) = build_node(inner, has_expired, false)?;
+                    if output == Output::Count {
+                        return Err(Error::OperationOnQuery);
+                    }
+                    QueryPlan::new(PlanOp::Count, Some(input))
+                }
+            };
+            (plan, Output::Count)
+        }
+    };
+
+    Ok(plan)
+}
+
+// Plans a query whose values are tracked as paths, for pipes that output
+// paths. Paths start at the values of the innermost query that isn't a pipe
+// or a property filter.
+fn build_path_node(q: &Query, has_expired: bool, ordered: bool) -> Result<(QueryPlan, Output)> {
+    let (inner, name, predicate, negated) = match q {
+        Query::Pipe(q) => {
+            let (input, output) = build_path_node(&q.inner, has_expired, limits_ordered(ordered, q.limit))?;
+            let (op, output) = pipe_op(q, output)?;
+            return Ok((limited(QueryPlan::new(op, Some(input)), q.limit), output));
+        }
+        Query::PipeWithPropertyPresence(q) => (&q.inner, q.name, IndexPredicate::Presence, !q.exists),
+        Query::PipeWithPropertyValue(q) => (&q.inner, q.name, IndexPredicate::Value(q.value.clone()), !q.equal),
+        Query::PipeWithPropertyRange(q) => (&q.inner, q.name, IndexPredicate::Range(q.range.clone()), false),
+        // Paths already include every value along the way
+        Query::Include(_) => return Err(Error::OperationOnQuery),
+        _ => {
+            let (input, output) = build_node(q, has_expired, ordered)?;
+            let output = match output {
+                Output::Vertices => Output::VertexPaths,
+                Output::Edges => Output::EdgePaths,
+                _ => return Err(Error::OperationOnQuery),
+            };
+            return Ok((QueryPlan::new(PlanOp::PathStart, Some(input)), output));
+        }
+    };
+
+    let (input, output) = build_path_node(inner, has_expired, ordered)?;
+    let op = PlanOp::IndexFilter {
+        name,
+        predicate,
+        negated,
+    };
+    Ok((QueryPlan::new(op, Some(input)), output))
+}
+
+// Picks the operation for a pipe from what it's piping, along with what it
+// outputs.
+fn pipe_op(q: &PipeQuery, input: Output) -> Result<(PlanOp, Output)> {
+    let edge_expand = PlanOp::EdgeExpand {
+        direction: q.direction,
+        t: q.t,
+    };
+    let vertex_expand = PlanOp::VertexExpand {
+        direction: q.direction,
+        t: q.t,
+    };
+    Ok(match input {
+        Output::Vertices => (edge_expand, Output::Edges),
+        Output::Edges => (vertex_expand, Output::Vertices),
+        Output::VertexPaths => (edge_expand, Output::EdgePaths),
+        Output::EdgePaths => (vertex_expand, Output::VertexPaths),
+        _ => return Err(Error::OperationOnQuery),
+    })
+}
+
+fn vertex_index_scan(name: Identifier, predicate: IndexPredicate) -> QueryPlan {
+    QueryPlan::new(PlanOp::VertexIndexScan { name, predicate }, None)
+}
+
+fn edge_index_scan(name: Identifier, predicate: IndexPredicate) -> QueryPlan {
+    QueryPlan::new(PlanOp::EdgeIndexScan { name, predicate }, None)
+}
+
+// Whether the input of a node with a limit needs to be planned in order.
+// Which values a limit keeps depends on the order they come in, even if
+// nothing depends on the order of the node's own output.
+fn limits_ordered(ordered: bool, limit: u32) -> bool {
+    ordered || limit != u32::MAX
+}
+
+// Limits are left out of the plan when they're the default of `u32::MAX`,
+// which is effectively unlimited.
+fn limited(plan: QueryPlan, limit: u32) -> QueryPlan {
+    if limit == u32::MAX {
+        plan
+    } else {
+        QueryPlan::new(PlanOp::Limit(limit), Some(plan))
+    }
+}
+
+fn index_filter(
+    inner: &Query,
+    name: Identifier,
+    predicate: IndexPredicate,
+    negated: bool,
+    has_expired: bool,
+    ordered: bool,
+) -> Result<(QueryPlan, Output)> {
+    // Filtering every vertex or edge is the same as reading the index
+    // directly, which skips the scan. Index reads don't return values in id
+    // order though, so this is only done when the order doesn't matter.
+    if !ordered && !negated {
+        match inner {
+            Query::AllVertex => return Ok((vertex_index_scan(name, predicate), Output::Vertices)),
+            Query::AllEdge => return Ok((edge_index_scan(name, predicate), Output::Edges)),
+            _ => {}
+        }
+    }
+
+    let (input, output) = build_node(inner, has_expired, ordered)?;
+    if !matches!(output, Output::Vertices | Output::Edges) {
+        return Err(Error::OperationOnQuery);
+    }
+    let op = PlanOp::IndexFilter {
+        name,
+        predicate,
+        negated,
+    };
+    Ok((QueryPlan::new(op, Some(input)), output))
+}
+
+// Counts the values of an iterator, or returns `total` if there's no
+// iterator, e.g. because the property isn't indexed.
+fn count<T>(iter: Option<DynIter<'_, T>>, total: u64) -> Result<u64> {
+    match iter {
+        Some(iter) => {
+            let mut rows = 0;
+            for item in iter {
+                item?;
+                rows += 1;
+            }
+            Ok(rows)
+        }
+        None => Ok(total),
+    }
+}
+
+// Counts the entries of a property index that match a predicate.
+fn count_index<'a, T: Transaction<'a>>(
+    txn: &'a T,
+    output_edges: bool,
+    name: Identifier,
+    predicate: &IndexPredicate,
+) -> Result<u64> {
+    if output_edges {
+        let iter = match predicate {
+            IndexPredicate::Presence => txn.edges_with_property(name)?,
+            IndexPredicate::Value(value) => txn.edges_with_property_value(name, value)?,
+            IndexPredicate::Range(range) => txn.edges_with_property_range(name, range)?,
+        };
+        count(iter, txn.edge_count())
+    } else {
+        let iter = match predicate {
+            IndexPredicate::Presence => txn.vertex_ids_with_property(name)?,
+            IndexPredicate::Value(value) => txn.vertex_ids_with_property_value(name, value)?,
+            IndexPredicate::Range(range) => txn.vertex_ids_with_property_range(name, range)?,
+        };
+        count(iter, txn.vertex_count())
+    }
+}
+
+// Whether a node outputs edges, rather than vertices.
+fn outputs_edges(plan: &QueryPlan) -> bool {
+    match plan.op {
+        PlanOp::EdgeScan { .. }
+        | PlanOp::EdgeTypeScan { .. }
+        | PlanOp::EdgeLookup { .. }
+        | PlanOp::EdgeIndexScan { .. }
+        | PlanOp::EdgeExpand { .. } => true,
+        PlanOp::PathStart | PlanOp::IndexFilter { .. } | PlanOp::Dedup | PlanOp::OrderBy { .. } | PlanOp::Limit(_) => {
+            plan.input.as_deref().is_some_and(outputs_edges)
+        }
+        _ => false,
+    }
+}
+
+// Fills in the estimated number of rows for each node of a plan, returning
+// the estimate for the root. Scans are bounded by the number of entries of
+// the type or property index they read, which are counted, and expansions by
+// the number of edges of the type they filter on.
+pub(crate) fn estimate<'a, T: Transaction<'a>>(txn: &'a T, plan: &mut QueryPlan) -> Result<u64> {
+    let input_rows = match plan.input {
+        Some(ref mut input) => Some(estimate(txn, input)?),
+        None => None,
+    };
+    let input_edges = plan.input.as_deref().is_some_and(outputs_edges);
+
+    let rows = match plan.op {
+        PlanOp::VertexScan { .. } => txn.vertex_count(),
+        PlanOp::VertexTypeScan { t, start_id } => {
+            count(Some(txn.range_vertices_of_type(t, start_id.unwrap_or_default())?), 0)?
+        }
+        PlanOp::VertexIndexScan { name, ref predicate } => count_index(txn, false, name, predicate)?,
+        PlanOp::VertexCompositeIndexScan { ref names, ref values } => count(
+            txn.vertex_ids_with_composite_property_values(names, values)?,
+            txn.vertex_count(),
+        )?,
+        PlanOp::VertexLookup { ref ids } => (ids.len() as u64).min(txn.vertex_count()),
+        PlanOp::EdgeScan { .. } => txn.edge_count(),
+        PlanOp::EdgeTypeScan { t, ref start } => count(Some(txn.range_edges_of_type(t, start.clone())?), 0)?,
+        PlanOp::EdgeIndexScan { name, ref predicate } => count_index(txn, true, name, predicate)?,
+        PlanOp::EdgeLookup { ref edges } => (edges.len() as u64).min(txn.edge_count()),
+        PlanOp::EdgeExpand { t, .. } => match (input_rows, t) {
+            (Some(0), _) => 0,
+            (_, Some(t)) => count(Some(txn.range_edges_of_type(t, None)?), 0)?,
+            (_, None) => txn.edge_count(),
+        },
+        PlanOp::Traverse {
+            min_depth,
+            max_depth,
+            limit,
+            ..
+        } => {
+            // Each vertex is output once, and at most `limit` of them at
+            // each depth past 0.
+            let input_rows = input_rows.unwrap_or_default();
+            let first_depth = min_depth.max(1);
+            let depths = if max_depth >= first_depth {
+                (max_depth - first_depth) as u64 + 1
+            } else {
+                0
+            };
+            let rows = (limit as u64).saturating_mul(depths);
+            let rows = if min_depth == 0 {
+                rows.saturating_add(input_rows)
+            } else if input_rows == 0 {
+                0
+            } else {
+                rows
+            };
+            rows.min(txn.vertex_count())
+        }
+        PlanOp::VertexExpand { .. } => input_rows.unwrap_or_default().min(txn.vertex_count()),
+        PlanOp::IndexFilter {
+            name,
+            ref predicate,
+            negated: false,
+        } => input_rows
+            .unwrap_or_default()
+            .min(count_index(txn, input_edges, name, predicate)?),
+        PlanOp::PathStart | PlanOp::IndexFilter { .. } | PlanOp::Dedup | PlanOp::PropertyFetch { .. } => {
+            input_rows.unwrap_or_default()
+        }
+        PlanOp::OrderBy { offset, limit, .. } => input_rows
+            .unwrap_or_default()
+            .saturating_sub(offset as u64)
+            .min(limit as u64),
+        PlanOp::Limit(limit) => input_rows.unwrap_or_default().min(limit as u64),
+        PlanOp::ShortestPath { .. } | PlanOp::Count | PlanOp::VertexCount | PlanOp::EdgeCount => 1,
+    };
+
+    plan.estimated_rows = Some(rows);
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build;
+    use crate::{ijson, AllVertexQuery, CountQueryExt, EdgeDirection, Identifier, QueryExt, RangeVertexQuery};
+
+    #[test]
+    fn should_display_plan() {
+        let name = Identifier::new("foo").unwrap();
+        let q = RangeVertexQuery::new()
+            .limit(10)
+            .outbound()
+            .unwrap()
+            .include()
+            .with_property_equal_to(name, ijson!("bar"))
+            .unwrap();
+        let mut plan = build(&q.into(), false).unwrap();
+        plan.estimated_rows = Some(4);
+        assert_eq!(
+            plan.to_string(),
+            "IndexFilter name=foo value=\"bar\" (rows <= 4)\n  EdgeExpand direction=outbound [exported]\n    Limit 10\n      VertexScan"
+        );
+    }
+
+    #[test]
+    fn should_only_read_indexes_for_unordered_counts() {
+        let name = Identifier::new("foo").unwrap();
+        let q = AllVertexQuery.with_property(name).unwrap().count().unwrap();
+        let plan = build(&q.into(), false).unwrap();
+        assert_eq!(plan.to_string(), "Count\n  VertexIndexScan name=foo presence");
+
+        // The per-depth limit keeps the first vertices in id order
+        let q = AllVertexQuery
+            .with_property(name)
+            .unwrap()
+            .traverse(EdgeDirection::Outbound, 2)
+            .unwrap()
+            .limit(10)
+            .count()
+            .unwrap();
+        let plan = build(&q.into(), false).unwrap();
+        assert_eq!(
+            plan.to_string(),
+            "Count\n  Traverse direction=outbound depth=0..=2 limit=10\n    IndexFilter name=foo presence\n      VertexScan"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::ops::Bound;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use crate::errors::Result;
+
+use rocksdb::{ColumnFamily, DBIterator, Direction, IteratorMode, Snapshot, WriteBatch, DB};
+
+pub(crate) type KeyValue = (Box<[u8]>, Box<[u8]>);
+type PendingWrite = (Box<[u8]>, Option<Box<[u8]>>);
+type PendingWrites = BTreeMap<Box<[u8]>, Option<Box<[u8]>>>;
+
+/// A batch of writes to apply to rocksdb.
+///
+/// Non-buffered batches are written out at the end of each transaction
+/// operation. Buffered batches accumulate writes until the transaction is
+/// committed, and additionally index them by key, so that reads made through
+/// the transaction in the meantime can observe them.
+///
+/// Reads made through the batch can also be pinned to a snapshot, in which
+/// case stored values are read as of when the snapshot was taken.
+pub(crate) struct Batch<'a> {
+    inner: WriteBatch,
+    // Keyed by column family, then by key. A value of `None` represents a
+    // pending delete. The writes for a column family are shared with the
+    // iterators over it, and copied on write while any are alive.
+    pending: Option<HashMap<usize, Arc<PendingWrites>>>,
+    snapshot: Option<Snapshot<'a>>,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new() -> Self {
+        Batch {
+            inner: WriteBatch::default(),
+            pending: None,
+            snapshot: None,
+        }
+    }
+
+    pub fn buffered() -> Self {
+        Batch {
+            inner: WriteBatch::default(),
+            pending: Some(HashMap::default()),
+            snapshot: None,
+        }
+    }
+
+    /// Pins subsequent reads to a new snapshot of the database.
+    pub fn snapshot(&mut self, db: &'a DB) {
+        self.snapshot = Some(db.snapshot());
+    }
+
+    pub fn is_buffered(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // Column family handles live as long as the database, so their addresses
+    // identify them.
+    fn cf_key(cf: &ColumnFamily) -> usize {
+        cf as *const ColumnFamily as usize
+    }
+
+    fn pending_for_cf(&self, cf: &ColumnFamily) -> Option<&Arc<PendingWrites>> {
+        self.pending.as_ref()?.get(&Self::cf_key(cf))
+    }
+
+    pub fn put_cf<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, cf: &ColumnFamily, key: K, value: V) {
+        if let Some(ref mut pending) = self.pending {
+            Arc::make_mut(pending.entry(Self::cf_key(cf)).or_default())
+                .insert(key.as_ref().into(), Some(value.as_ref().into()));
+        }
+        self.inner.put_cf(cf, key, value);
+    }
+
+    pub fn delete_cf<K: AsRef<[u8]>>(&mut self, cf: &ColumnFamily, key: K) {
+        if let Some(ref mut pending) = self.pending {
+            Arc::make_mut(pending.entry(Self::cf_key(cf)).or_default()).insert(key.as_ref().into(), None);
+        }
+        self.inner.delete_cf(cf, key);
+    }
+
+    /// Gets a value, taking pending writes into account.
+    pub fn get_cf<K: AsRef<[u8]>>(&self, db: &DB, cf: &ColumnFamily, key: K) -> Result<Option<Vec<u8>>> {
+        if let Some(pending) = self.pending_for_cf(cf) {
+            if let Some(value) = pending.get(key.as_ref()) {
+                return Ok(value.as_ref().map(|value| value.to_vec()));
+            }
+        }
+        match self.snapshot {
+            Some(ref snapshot) => Ok(snapshot.get_cf(cf, key)?),
+            None => Ok(db.get_cf(cf, key)?),
+        }
+    }
+
+    /// Iterates forward over a column family starting at `from`, taking
+    /// pending writes into account. Pending writes are read as of when the
+    /// iterator is created, so writes made while iterating are not observed.
+    pub fn iterator_cf<'b>(
+        &self,
+        db: &'b DB,
+        cf: &'b ColumnFamily,
+        from: &[u8],
+    ) -> impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'b {
+        let pending = self.pending_for_cf(cf).cloned();
+
+        let mode = IteratorMode::From(from, Direction::Forward);
+        let stored = match self.snapshot {
+            Some(ref snapshot) => {
+                // rocksdb ties snapshot iterators to the borrow of the
+                // snapshot, but callers need to be able to write to the batch
+                // while iterating. This is safe because the snapshot is only
+                // replaced or released through `&mut` access to the owning
+                // transaction, which can't happen while any of its iterators
+                // are alive.
+                let snapshot: &'b Snapshot<'b> = unsafe { &*(snapshot as *const Snapshot as *const Snapshot<'b>) };
+                snapshot.iterator_cf(cf, mode)
+            }
+            None => db.iterator_cf(cf, mode),
+        };
+
+        MergedIterator {
+            stored,
+            pending,
+            pending_from: Bound::Included(from.into()),
+            next_stored: None,
+            next_pending: None,
+        }
+    }
+
+    /// Writes the batch out, unless it's buffered, in which case this is
+    /// deferred until `commit` is called.
+    pub fn write(&mut self, db: &DB) -> Result<()> {
+        if self.is_buffered() {
+            Ok(())
+        } else {
+            self.commit(db)
+        }
+    }
+
+    /// Writes the batch out, and resets it.
+    pub fn commit(&mut self, db: &DB) -> Result<()> {
+        if let Some(ref mut pending) = self.pending {
+            pending.clear();
+        }
+        db.write(mem::take(&mut self.inner))?;
+        Ok(())
+    }
+
+    /// Discards all writes in the batch.
+    pub fn clear(&mut self) {
+        if let Some(ref mut pending) = self.pending {
+            pending.clear();
+        }
+        self.inner.clear();
+    }
+}
+
+// Merges a rocksdb iterator with pending writes, both of which are sorted by
+// key. Pending writes take precedence over stored values.
+struct MergedIterator<'a> {
+    stored: DBIterator<'a>,
+    pending: Option<Arc<PendingWrites>>,
+    // Where to resume reading pending writes from. They're read one at a
+    // time, rather than copied up front.
+    pending_from: Bound<Box<[u8]>>,
+    next_stored: Option<KeyValue>,
+    next_pending: Option<PendingWrite>,
+}
+
+impl<'a> MergedIterator<'a> {
+    fn fill_next_pending(&mut self) {
+        if self.next_pending.is_some() {
+            return;
+        }
+        if let Some(ref pending) = self.pending {
+            let bounds = (self.pending_from.as_ref().map(|key| &key[..]), Bound::Unbounded);
+            if let Some((key, value)) = pending.range::<[u8], _>(bounds).next() {
+                self.pending_from = Bound::Excluded(key.clone());
+                self.next_pending = Some((key.clone(), value.clone()));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for MergedIterator<'a> {
+    type Item = StdResult<KeyValue, rocksdb::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.next_stored.is_none() {
+                match self.stored.next() {
+                    Some(Ok(item)) => self.next_stored = Some(item),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => {}
+                }
+            }
+
+            self.fill_next_pending();
+            let ordering = match (&self.next_stored, &self.next_pending) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((stored_key, _)), Some((pending_key, _))) => stored_key.cmp(pending_key),
+            };
+
+            let take_pending = match ordering {
+                Ordering::Less => false,
+                Ordering::Greater => true,
+                Ordering::Equal => {
+                    // The pending write replaces the stored value
+                    self.next_stored = None;
+                    true
+                }
+            };
+
+            if take_pending {
+                if let (key, Some(value)) = self.next_pending.take().unwrap() {
+                    return Some(Ok((key, value)));
+                }
+            } else {
+                return self.next_stored.take().map(Ok);
+            }
+        }
+    }
+}
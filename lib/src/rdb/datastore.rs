@@ -1,30 +1,104 @@
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::i32;
 use std::path::Path;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::SystemTime;
 use std::u64;
 
+use super::batch::Batch;
 use super::managers::*;
-use crate::errors::Result;
-use crate::{BulkInsertItem, Database, Datastore, DynIter, Edge, Identifier, Json, Transaction, Vertex};
-
-use rocksdb::{DBCompactionStyle, Options, WriteBatch, DB};
+use crate::errors::{Error, Result};
+use crate::util;
+use crate::{
+    BulkInsertItem, Database, Datastore, DynIter, Edge, Identifier, Json, JsonRange, Schema, SchemaKind, Transaction,
+    Vertex,
+};
+
+use rocksdb::{DBCompactionStyle, Options, DB};
 use uuid::Uuid;
 
-const CF_NAMES: [&str; 8] = [
+const CF_NAMES: [&str; 15] = [
     "vertices:v2",
+    "vertices_by_type:v2",
     "edge_ranges:v2",
     "reversed_edge_ranges:v2",
+    "edges_by_type:v2",
     "vertex_properties:v2",
     "edge_properties:v2",
-    "vertex_property_values:v2",
-    "edge_property_values:v2",
+    "vertex_property_values:v3",
+    "edge_property_values:v3",
+    "vertex_composite_property_values:v2",
+    "vertex_expiries:v2",
+    "vertices_by_expiry:v2",
+    "edge_expiries:v2",
+    "edges_by_expiry:v2",
     "metadata:v2",
 ];
 
+// Column families from older on-disk formats. They still have to be opened
+// if they exist, and are dropped once the indexes have been rebuilt.
+const LEGACY_CF_NAMES: [&str; 2] = ["vertex_property_values:v2", "edge_property_values:v2"];
+
+// The version of the on-disk format of the indexes. Databases with an older
+// version, or none, have their indexes rebuilt when they're opened.
+//
+// 1: Property values are keyed by an order-preserving encoding, rather than
+//    a hash, in the `:v3` column families.
+// 2: Vertices and edges are indexed by type.
+const INDEX_VERSION: u32 = 2;
+
+// Rebuilds the indexes that are derived from vertices, edges and their
+// properties.
+fn rebuild_indexes(db: &DB, indexes: &Indexes) -> Result<()> {
+    let mut batch = Batch::new();
+    let vertex_manager = VertexManager::new(db);
+    let edge_manager = EdgeManager::new(db);
+    let edge_range_manager = EdgeRangeManager::new(db);
+    let vertex_property_manager = VertexPropertyManager::new(db);
+    let edge_property_manager = EdgePropertyManager::new(db);
+    let vertex_property_value_manager = VertexPropertyValueManager::new(db);
+    let edge_property_value_manager = EdgePropertyValueManager::new(db);
+
+    for item in vertex_manager.iterate_for_range(&batch, Uuid::default()) {
+        let vertex = item?;
+        vertex_manager.index_type(&mut batch, &vertex);
+        for item in vertex_property_manager.iterate_for_owner(&batch, vertex.id)? {
+            let (id, name, value) = item?;
+            if indexes.properties.contains(&name) {
+                vertex_property_value_manager.set(&mut batch, id, name, &value);
+            }
+        }
+    }
+
+    for item in edge_range_manager.iterate_for_all(&batch) {
+        let edge = item?;
+        edge_manager.index_type(&mut batch, &edge);
+        for item in edge_property_manager.iterate_for_owner(&batch, &edge)? {
+            let (edge, name, value) = item?;
+            if indexes.properties.contains(&name) {
+                edge_property_value_manager.set(&mut batch, &edge, name, &value);
+            }
+        }
+    }
+
+    MetadataManager::new(db).set_index_version(&mut batch, INDEX_VERSION)?;
+    batch.write(db)
+}
+
 pub struct RocksdbTransaction<'a> {
     db: &'a DB,
-    indexed_properties: Arc<RwLock<HashSet<Identifier>>>,
+    // Held while writing, so that checks made before a write, e.g. for
+    // unique properties, still hold when it's written. Buffered transactions
+    // hold it until they're dropped, since they only write on commit.
+    writer: &'a Mutex<()>,
+    write_guard: Option<MutexGuard<'a, ()>>,
+    indexes: Arc<RwLock<Indexes>>,
+    // For buffered transactions, `indexes` is a private copy, and this is the
+    // datastore-wide set that it replaces on commit. Buffered transactions
+    // hold the write lock, so the datastore-wide set can't change meanwhile.
+    datastore_indexes: Option<Arc<RwLock<Indexes>>>,
+    batch: Batch<'a>,
     vertex_manager: VertexManager<'a>,
     edge_manager: EdgeManager<'a>,
     edge_range_manager: EdgeRangeManager<'a>,
@@ -33,16 +107,115 @@ pub struct RocksdbTransaction<'a> {
     edge_property_manager: EdgePropertyManager<'a>,
     vertex_property_value_manager: VertexPropertyValueManager<'a>,
     edge_property_value_manager: EdgePropertyValueManager<'a>,
+    vertex_composite_property_value_manager: VertexCompositePropertyValueManager<'a>,
+    vertex_expiry_manager: VertexExpiryManager<'a>,
+    edge_expiry_manager: EdgeExpiryManager<'a>,
     metadata_manager: MetadataManager<'a>,
 }
 
 impl<'a> RocksdbTransaction<'a> {
+    fn new(
+        db: &'a DB,
+        writer: &'a Mutex<()>,
+        write_guard: Option<MutexGuard<'a, ()>>,
+        indexes: Arc<RwLock<Indexes>>,
+        datastore_indexes: Option<Arc<RwLock<Indexes>>>,
+        batch: Batch<'a>,
+    ) -> Self {
+        RocksdbTransaction {
+            db,
+            writer,
+            write_guard,
+            indexes,
+            datastore_indexes,
+            batch,
+            vertex_manager: VertexManager::new(db),
+            edge_manager: EdgeManager::new(db),
+            edge_range_manager: EdgeRangeManager::new(db),
+            reversed_edge_range_manager: EdgeRangeManager::new_reversed(db),
+            vertex_property_manager: VertexPropertyManager::new(db),
+            edge_property_manager: EdgePropertyManager::new(db),
+            vertex_property_value_manager: VertexPropertyValueManager::new(db),
+            edge_property_value_manager: EdgePropertyValueManager::new(db),
+            vertex_composite_property_value_manager: VertexCompositePropertyValueManager::new(db),
+            vertex_expiry_manager: VertexExpiryManager::new(db),
+            edge_expiry_manager: EdgeExpiryManager::new(db),
+            metadata_manager: MetadataManager::new(db),
+        }
+    }
+
+    // Runs an operation that stages writes in the batch, then writes them
+    // out. An unbuffered batch is shared by every operation in the
+    // transaction, so if the operation fails, its staged writes are
+    // discarded rather than left for the next operation to write out.
+    fn write_staged<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        // Operations can be nested, in which case the lock is already held.
+        let acquired = self.write_guard.is_none();
+        if acquired {
+            self.write_guard = Some(self.writer.lock().unwrap());
+        }
+        let result = match f(self) {
+            Ok(value) => self.batch.write(self.db).map(|_| value),
+            Err(err) => {
+                if !self.batch.is_buffered() {
+                    self.batch.clear();
+                }
+                Err(err)
+            }
+        };
+        if acquired {
+            self.write_guard = None;
+        }
+        result
+    }
+
+    // Checks `(type, name, value)` property writes against the schemas for
+    // their types.
+    fn validate_properties<'b, I>(&self, kind: SchemaKind, props: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (Identifier, Identifier, &'b Json)>,
+    {
+        let mut schemas: HashMap<Identifier, Option<Schema>> = HashMap::new();
+        for (t, name, value) in props {
+            let schema = match schemas.entry(t) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.metadata_manager.get_schema(&self.batch, kind, t)?),
+            };
+            if let Some(schema) = schema {
+                schema.validate(t, name, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_unique_vertex_properties<'b, I>(&self, unique_properties: &HashSet<Identifier>, writes: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (Uuid, Identifier, &'b Json)>,
+    {
+        util::check_unique_vertex_properties(unique_properties, writes, |name, value| {
+            let mut ids = Vec::new();
+            for item in self
+                .vertex_property_value_manager
+                .iterate_for_value(&self.batch, name, value)
+            {
+                let (_, _, id) = item?;
+                if self.vertex_manager.exists(&self.batch, id)? {
+                    ids.push(id);
+                }
+            }
+            Ok(ids)
+        })
+    }
+
     fn vertex_ids_from_property_value_iterator(
         &'a self,
         iter: impl Iterator<Item = Result<VertexPropertyValueKey>> + 'a,
     ) -> impl Iterator<Item = Result<Uuid>> + 'a {
         iter.filter_map(|item| match item {
-            Ok((_, _, id)) => match self.vertex_manager.exists(id) {
+            Ok((_, _, id)) => match self.vertex_manager.exists(&self.batch, id) {
                 Ok(true) => Some(Ok(id)),
                 Ok(false) => None,
                 Err(err) => Some(Err(err)),
@@ -54,33 +227,40 @@ impl<'a> RocksdbTransaction<'a> {
 
 impl<'a> Transaction<'a> for RocksdbTransaction<'a> {
     fn vertex_count(&self) -> u64 {
-        let iter = self.vertex_manager.iterate_for_range(Uuid::default());
+        let iter = self.vertex_manager.iterate_for_range(&self.batch, Uuid::default());
         iter.count() as u64
     }
 
     fn all_vertices(&'a self) -> Result<DynIter<'a, Vertex>> {
-        let iter = self.vertex_manager.iterate_for_range(Uuid::default());
+        let iter = self.vertex_manager.iterate_for_range(&self.batch, Uuid::default());
         Ok(Box::new(iter))
     }
 
     fn range_vertices(&'a self, offset: Uuid) -> Result<DynIter<'a, Vertex>> {
-        let iter = self.vertex_manager.iterate_for_range(offset);
+        let iter = self.vertex_manager.iterate_for_range(&self.batch, offset);
+        Ok(Box::new(iter))
+    }
+
+    fn range_vertices_of_type(&'a self, t: Identifier, offset: Uuid) -> Result<DynIter<'a, Vertex>> {
+        let iter = self.vertex_manager.iterate_for_type(&self.batch, t, offset);
         Ok(Box::new(iter))
     }
 
     fn specific_vertices(&'a self, ids: Vec<Uuid>) -> Result<DynIter<'a, Vertex>> {
-        let iter = ids.into_iter().filter_map(move |id| match self.vertex_manager.get(id) {
-            Ok(Some(t)) => Some(Ok(Vertex::with_id(id, t))),
-            Ok(None) => None,
-            Err(err) => Some(Err(err)),
-        });
+        let iter = ids
+            .into_iter()
+            .filter_map(move |id| match self.vertex_manager.get(&self.batch, id) {
+                Ok(Some(t)) => Some(Ok(Vertex::with_id(id, t))),
+                Ok(None) => None,
+                Err(err) => Some(Err(err)),
+            });
 
         Ok(Box::new(iter))
     }
 
     fn vertex_ids_with_property(&'a self, name: Identifier) -> Result<Option<DynIter<'a, Uuid>>> {
-        if self.indexed_properties.read().unwrap().contains(&name) {
-            let iter = self.vertex_property_value_manager.iterate_for_name(name);
+        if self.indexes.read().unwrap().properties.contains(&name) {
+            let iter = self.vertex_property_value_manager.iterate_for_name(&self.batch, name);
             let iter = self.vertex_ids_from_property_value_iterator(iter);
             Ok(Some(Box::new(iter)))
         } else {
@@ -89,8 +269,10 @@ impl<'a> Transaction<'a> for RocksdbTransaction<'a> {
     }
 
     fn vertex_ids_with_property_value(&'a self, name: Identifier, value: &Json) -> Result<Option<DynIter<'a, Uuid>>> {
-        if self.indexed_properties.read().unwrap().contains(&name) {
-            let iter = self.vertex_property_value_manager.iterate_for_value(name, value);
+        if self.indexes.read().unwrap().properties.contains(&name) {
+            let iter = self
+                .vertex_property_value_manager
+                .iterate_for_value(&self.batch, name, value);
             let iter = self.vertex_ids_from_property_value_iterator(iter);
             Ok(Some(Box::new(iter)))
         } else {
@@ -98,34 +280,73 @@ impl<'a> Transaction<'a> for RocksdbTransaction<'a> {
         }
     }
 
+    fn vertex_ids_with_property_range(
+        &'a self,
+        name: Identifier,
+        range: &JsonRange,
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        if self.indexes.read().unwrap().properties.contains(&name) {
+            let iter = self
+                .vertex_property_value_manager
+                .iterate_for_range(&self.batch, name, range);
+            let iter = self.vertex_ids_from_property_value_iterator(iter);
+            Ok(Some(Box::new(iter)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn vertex_ids_with_composite_property_values(
+        &'a self,
+        names: &[Identifier],
+        values: &[Json],
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        if self.indexes.read().unwrap().composites.contains(names) {
+            let iter = self
+                .vertex_composite_property_value_manager
+                .iterate_for_prefix(&self.batch, names, values);
+            Ok(Some(Box::new(iter)))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn edge_count(&self) -> u64 {
-        let iter = self.edge_range_manager.iterate_for_all();
+        let iter = self.edge_range_manager.iterate_for_all(&self.batch);
         iter.count() as u64
     }
 
     fn all_edges(&'a self) -> Result<DynIter<'a, Edge>> {
-        let iter = self.edge_range_manager.iterate_for_all();
+        let iter = self.edge_range_manager.iterate_for_all(&self.batch);
         Ok(Box::new(iter))
     }
 
     fn range_edges(&'a self, offset: Edge) -> Result<DynIter<'a, Edge>> {
-        let iter = self
-            .edge_range_manager
-            .iterate_for_range(offset.outbound_id, offset.t, offset.inbound_id)?;
+        let iter =
+            self.edge_range_manager
+                .iterate_for_range(&self.batch, offset.outbound_id, offset.t, offset.inbound_id)?;
         Ok(Box::new(iter))
     }
 
     fn range_reversed_edges(&'a self, offset: Edge) -> Result<DynIter<'a, Edge>> {
-        let iter =
-            self.reversed_edge_range_manager
-                .iterate_for_range(offset.outbound_id, offset.t, offset.inbound_id)?;
+        let iter = self.reversed_edge_range_manager.iterate_for_range(
+            &self.batch,
+            offset.outbound_id,
+            offset.t,
+            offset.inbound_id,
+        )?;
+        Ok(Box::new(iter))
+    }
+
+    fn range_edges_of_type(&'a self, t: Identifier, offset: Option<Edge>) -> Result<DynIter<'a, Edge>> {
+        let iter = self.edge_manager.iterate_for_type(&self.batch, t, offset);
         Ok(Box::new(iter))
     }
 
     fn specific_edges(&'a self, edges: Vec<Edge>) -> Result<DynIter<'a, Edge>> {
         let iter = edges
             .into_iter()
-            .filter_map(move |e| match self.edge_range_manager.contains(&e) {
+            .filter_map(move |e| match self.edge_range_manager.contains(&self.batch, &e) {
                 Ok(true) => Some(Ok(e)),
                 Ok(false) => None,
                 Err(err) => Some(Err(err)),
@@ -135,10 +356,10 @@ impl<'a> Transaction<'a> for RocksdbTransaction<'a> {
     }
 
     fn edges_with_property(&'a self, name: Identifier) -> Result<Option<DynIter<'a, Edge>>> {
-        if self.indexed_properties.read().unwrap().contains(&name) {
+        if self.indexes.read().unwrap().properties.contains(&name) {
             let iter = self
                 .edge_property_value_manager
-                .iterate_for_name(name)
+                .iterate_for_name(&self.batch, name)
                 .map(|r| match r {
                     Ok((_, _, e)) => Ok(e),
                     Err(err) => Err(err),
@@ -150,10 +371,25 @@ impl<'a> Transaction<'a> for RocksdbTransaction<'a> {
     }
 
     fn edges_with_property_value(&'a self, name: Identifier, value: &Json) -> Result<Option<DynIter<'a, Edge>>> {
-        if self.indexed_properties.read().unwrap().contains(&name) {
+        if self.indexes.read().unwrap().properties.contains(&name) {
+            let iter = self
+                .edge_property_value_manager
+                .iterate_for_value(&self.batch, name, value)
+                .map(|r| match r {
+                    Ok((_, _, e)) => Ok(e),
+                    Err(err) => Err(err),
+                });
+            Ok(Some(Box::new(iter)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn edges_with_property_range(&'a self, name: Identifier, range: &JsonRange) -> Result<Option<DynIter<'a, Edge>>> {
+        if self.indexes.read().unwrap().properties.contains(&name) {
             let iter = self
                 .edge_property_value_manager
-                .iterate_for_value(name, value)
+                .iterate_for_range(&self.batch, name, range)
                 .map(|r| match r {
                     Ok((_, _, e)) => Ok(e),
                     Err(err) => Err(err),
@@ -165,199 +401,497 @@ impl<'a> Transaction<'a> for RocksdbTransaction<'a> {
     }
 
     fn vertex_property(&self, vertex: &Vertex, name: Identifier) -> Result<Option<Json>> {
-        match self.vertex_property_manager.get(vertex.id, name)? {
+        match self.vertex_property_manager.get(&self.batch, vertex.id, name)? {
             None => Ok(None),
             Some(value) => Ok(Some(value)),
         }
     }
 
     fn all_vertex_properties_for_vertex(&'a self, vertex: &Vertex) -> Result<DynIter<'a, (Identifier, Json)>> {
-        let iter = self.vertex_property_manager.iterate_for_owner(vertex.id)?;
+        let iter = self.vertex_property_manager.iterate_for_owner(&self.batch, vertex.id)?;
         let props: Result<Vec<_>> = iter.collect();
         let iter = props?.into_iter().map(|(_, name, value)| Ok((name, value)));
         Ok(Box::new(iter))
     }
 
     fn edge_property(&self, edge: &Edge, name: Identifier) -> Result<Option<Json>> {
-        match self.edge_property_manager.get(edge, name)? {
+        match self.edge_property_manager.get(&self.batch, edge, name)? {
             None => Ok(None),
             Some(value) => Ok(Some(value)),
         }
     }
 
     fn all_edge_properties_for_edge(&'a self, edge: &Edge) -> Result<DynIter<'a, (Identifier, Json)>> {
-        let iter = self.edge_property_manager.iterate_for_owner(edge)?;
+        let iter = self.edge_property_manager.iterate_for_owner(&self.batch, edge)?;
         let props: Result<Vec<_>> = iter.collect();
         let iter = props?.into_iter().map(|(_, name, value)| Ok((name, value)));
         Ok(Box::new(iter))
     }
 
-    fn delete_vertices(&mut self, vertices: Vec<Vertex>) -> Result<()> {
-        let indexed_properties = self.indexed_properties.read().unwrap();
-        let mut batch = WriteBatch::default();
+    fn expired_vertices(&'a self, now: SystemTime) -> Result<DynIter<'a, Vertex>> {
+        let iter = self
+            .vertex_expiry_manager
+            .iterate_until(&self.batch, now)
+            .filter_map(move |item| {
+                let id = match item {
+                    Ok(id) => id,
+                    Err(err) => return Some(Err(err)),
+                };
+                match self.vertex_manager.get(&self.batch, id) {
+                    Ok(Some(t)) => Some(Ok(Vertex::with_id(id, t))),
+                    Ok(None) => None,
+                    Err(err) => Some(Err(err)),
+                }
+            });
+        Ok(Box::new(iter))
+    }
 
-        for vertex in vertices.into_iter() {
-            self.vertex_manager.delete(&mut batch, &indexed_properties, vertex.id)?;
-        }
+    fn expired_edges(&'a self, now: SystemTime) -> Result<DynIter<'a, Edge>> {
+        let iter = self.edge_expiry_manager.iterate_until(&self.batch, now);
+        Ok(Box::new(iter))
+    }
 
-        self.db.write(batch)?;
-        Ok(())
+    fn vertex_expiry(&self, id: Uuid) -> Result<Option<SystemTime>> {
+        self.vertex_expiry_manager.get(&self.batch, id)
+    }
+
+    fn edge_expiry(&self, edge: &Edge) -> Result<Option<SystemTime>> {
+        self.edge_expiry_manager.get(&self.batch, edge)
+    }
+
+    fn delete_vertices(&mut self, vertices: Vec<Vertex>) -> Result<()> {
+        self.write_staged(|txn| {
+            let indexes = txn.indexes.read().unwrap();
+
+            for vertex in vertices.into_iter() {
+                txn.vertex_composite_property_value_manager.delete(
+                    &mut txn.batch,
+                    &indexes.composites,
+                    vertex.id,
+                    None,
+                )?;
+                txn.vertex_manager
+                    .delete(&mut txn.batch, &indexes.properties, vertex.id)?;
+            }
+
+            Ok(())
+        })
     }
 
     fn delete_edges(&mut self, edges: Vec<Edge>) -> Result<()> {
-        let indexed_properties = self.indexed_properties.read().unwrap();
-        let mut batch = WriteBatch::default();
+        self.write_staged(|txn| {
+            let indexes = txn.indexes.read().unwrap();
 
-        for edge in edges.into_iter() {
-            if self.vertex_manager.get(edge.outbound_id)?.is_some() {
-                self.edge_manager.delete(&mut batch, &indexed_properties, &edge)?;
-            };
-        }
+            for edge in edges.into_iter() {
+                if txn.vertex_manager.get(&txn.batch, edge.outbound_id)?.is_some() {
+                    txn.edge_manager.delete(&mut txn.batch, &indexes.properties, &edge)?;
+                };
+            }
 
-        self.db.write(batch)?;
-        Ok(())
+            Ok(())
+        })
     }
 
     fn delete_vertex_properties(&mut self, props: Vec<(Uuid, Identifier)>) -> Result<()> {
-        let indexed_properties = self.indexed_properties.read().unwrap();
-        let mut batch = WriteBatch::default();
-
-        for (id, name) in props.into_iter() {
-            self.vertex_property_manager
-                .delete(&mut batch, &indexed_properties, id, name)?;
-        }
+        self.write_staged(|txn| {
+            let indexes = txn.indexes.read().unwrap();
+
+            for (id, name) in props.into_iter() {
+                txn.vertex_composite_property_value_manager.delete(
+                    &mut txn.batch,
+                    &indexes.composites,
+                    id,
+                    Some(name),
+                )?;
+                txn.vertex_property_manager
+                    .delete(&mut txn.batch, &indexes.properties, id, name)?;
+            }
 
-        self.db.write(batch)?;
-        Ok(())
+            Ok(())
+        })
     }
 
     fn delete_edge_properties(&mut self, props: Vec<(Edge, Identifier)>) -> Result<()> {
-        let indexed_properties = self.indexed_properties.read().unwrap();
-        let mut batch = WriteBatch::default();
+        self.write_staged(|txn| {
+            let indexes = txn.indexes.read().unwrap();
 
-        for (edge, name) in props.into_iter() {
-            self.edge_property_manager
-                .delete(&mut batch, &indexed_properties, &edge, name)?;
-        }
+            for (edge, name) in props.into_iter() {
+                txn.edge_property_manager
+                    .delete(&mut txn.batch, &indexes.properties, &edge, name)?;
+            }
 
-        self.db.write(batch)?;
-        Ok(())
+            Ok(())
+        })
     }
 
     fn sync(&self) -> Result<()> {
         self.vertex_manager.compact();
+        self.edge_manager.compact();
         self.edge_range_manager.compact();
         self.edge_range_manager.compact();
         self.vertex_property_manager.compact();
         self.edge_property_manager.compact();
         self.vertex_property_value_manager.compact();
         self.edge_property_value_manager.compact();
+        self.vertex_composite_property_value_manager.compact();
+        self.vertex_expiry_manager.compact();
+        self.edge_expiry_manager.compact();
         self.metadata_manager.compact();
         self.db.flush()?;
         Ok(())
     }
 
-    fn create_vertex(&mut self, vertex: &Vertex) -> Result<bool> {
-        if self.vertex_manager.exists(vertex.id)? {
-            Ok(false)
-        } else {
-            let mut batch = WriteBatch::default();
-            self.vertex_manager.create(&mut batch, vertex)?;
-            self.db.write(batch)?;
-            Ok(true)
+    fn commit(&mut self) -> Result<()> {
+        if let Some(ref datastore_indexes) = self.datastore_indexes {
+            let mut datastore_indexes = datastore_indexes.write().unwrap();
+            self.batch.commit(self.db)?;
+            *datastore_indexes = self.indexes.read().unwrap().clone();
         }
+        Ok(())
     }
 
-    fn create_edge(&mut self, edge: &Edge) -> Result<bool> {
-        if !self.vertex_manager.exists(edge.outbound_id)? || !self.vertex_manager.exists(edge.inbound_id)? {
-            Ok(false)
-        } else {
-            let mut batch = WriteBatch::default();
-            self.edge_manager.set(&mut batch, edge)?;
-            self.db.write(batch)?;
-            Ok(true)
+    fn rollback(&mut self) -> Result<()> {
+        match self.datastore_indexes {
+            Some(ref datastore_indexes) => {
+                self.batch.clear();
+                *self.indexes.write().unwrap() = datastore_indexes.read().unwrap().clone();
+                Ok(())
+            }
+            None => Err(Error::Unsupported),
         }
     }
 
+    fn snapshot(&mut self) -> Result<()> {
+        self.batch.snapshot(self.db);
+        Ok(())
+    }
+
+    fn create_vertex(&mut self, vertex: &Vertex) -> Result<bool> {
+        self.write_staged(|txn| {
+            if txn.vertex_manager.exists(&txn.batch, vertex.id)? {
+                Ok(false)
+            } else {
+                txn.vertex_manager.create(&mut txn.batch, vertex)?;
+                Ok(true)
+            }
+        })
+    }
+
+    fn create_edge(&mut self, edge: &Edge) -> Result<bool> {
+        self.write_staged(|txn| {
+            if !txn.vertex_manager.exists(&txn.batch, edge.outbound_id)?
+                || !txn.vertex_manager.exists(&txn.batch, edge.inbound_id)?
+            {
+                Ok(false)
+            } else {
+                txn.edge_manager.set(&mut txn.batch, edge)?;
+                Ok(true)
+            }
+        })
+    }
+
+    fn create_vertex_with_expiry(&mut self, vertex: &Vertex, expires_at: SystemTime) -> Result<bool> {
+        self.write_staged(|txn| {
+            if txn.vertex_manager.exists(&txn.batch, vertex.id)? {
+                Ok(false)
+            } else {
+                txn.vertex_manager.create(&mut txn.batch, vertex)?;
+                txn.vertex_expiry_manager.set(&mut txn.batch, vertex.id, expires_at)?;
+                Ok(true)
+            }
+        })
+    }
+
+    fn create_edge_with_expiry(&mut self, edge: &Edge, expires_at: SystemTime) -> Result<bool> {
+        self.write_staged(|txn| {
+            if !txn.vertex_manager.exists(&txn.batch, edge.outbound_id)?
+                || !txn.vertex_manager.exists(&txn.batch, edge.inbound_id)?
+            {
+                Ok(false)
+            } else {
+                txn.edge_manager.set(&mut txn.batch, edge)?;
+                txn.edge_expiry_manager.set(&mut txn.batch, edge, expires_at)?;
+                Ok(true)
+            }
+        })
+    }
+
     // We override the default `bulk_insert` implementation because further
     // optimization can be done by using `WriteBatch`s.
     fn bulk_insert(&mut self, items: Vec<BulkInsertItem>) -> Result<()> {
-        let indexed_properties = self.indexed_properties.read().unwrap();
-        let mut batch = WriteBatch::default();
-
-        for item in items {
-            match item {
-                BulkInsertItem::Vertex(ref vertex) => {
-                    self.vertex_manager.create(&mut batch, vertex)?;
-                }
-                BulkInsertItem::Edge(ref edge) => {
-                    self.edge_manager.set(&mut batch, edge)?;
+        self.write_staged(|txn| {
+            // Properties and expiries are validated before anything is written,
+            // so that a violation doesn't leave the insert half-applied.
+            let now = SystemTime::now();
+            let mut vertex_types = HashMap::new();
+            for item in &items {
+                if let BulkInsertItem::Vertex(vertex) | BulkInsertItem::ExpiringVertex(vertex, _) = item {
+                    vertex_types.insert(vertex.id, vertex.t);
                 }
-                BulkInsertItem::VertexProperty(id, ref name, ref value) => {
-                    self.vertex_property_manager
-                        .set(&mut batch, &indexed_properties, id, *name, value)?;
+            }
+            let mut vertex_props = Vec::new();
+            let mut edge_props = Vec::new();
+            for item in &items {
+                match item {
+                    BulkInsertItem::VertexProperty(id, name, value) => {
+                        let t = match vertex_types.get(id) {
+                            Some(t) => Some(*t),
+                            None => txn.vertex_manager.get(&txn.batch, *id)?,
+                        };
+                        if let Some(t) = t {
+                            vertex_props.push((t, *name, value));
+                        }
+                    }
+                    BulkInsertItem::EdgeProperty(edge, name, value) => {
+                        edge_props.push((edge.t, *name, value));
+                    }
+                    BulkInsertItem::ExpiringVertex(_, ttl) | BulkInsertItem::ExpiringEdge(_, ttl) => {
+                        util::expiry(now, *ttl)?;
+                    }
+                    _ => {}
                 }
-                BulkInsertItem::EdgeProperty(ref edge, ref name, ref value) => {
-                    self.edge_property_manager
-                        .set(&mut batch, &indexed_properties, edge, *name, value)?;
+            }
+            txn.validate_properties(SchemaKind::Vertex, vertex_props)?;
+            txn.validate_properties(SchemaKind::Edge, edge_props)?;
+
+            let indexes = txn.indexes.read().unwrap();
+            txn.check_unique_vertex_properties(
+                &indexes.unique_properties,
+                items.iter().filter_map(|item| match item {
+                    BulkInsertItem::VertexProperty(id, name, value) => Some((*id, *name, value)),
+                    _ => None,
+                }),
+            )?;
+            // An unbuffered batch can't read back the properties inserted
+            // below, so they're tracked to compute composite index keys.
+            let mut composite_changes: HashMap<Uuid, HashMap<Identifier, &Json>> = HashMap::new();
+
+            for item in &items {
+                match item {
+                    BulkInsertItem::Vertex(vertex) => {
+                        txn.vertex_manager.create(&mut txn.batch, vertex)?;
+                    }
+                    BulkInsertItem::Edge(edge) => {
+                        txn.edge_manager.set(&mut txn.batch, edge)?;
+                    }
+                    BulkInsertItem::VertexProperty(id, name, value) => {
+                        if indexes.composites.iter().any(|names| names.contains(name)) {
+                            txn.vertex_composite_property_value_manager.delete(
+                                &mut txn.batch,
+                                &indexes.composites,
+                                *id,
+                                Some(*name),
+                            )?;
+                            composite_changes.entry(*id).or_default().insert(*name, value);
+                        }
+                        txn.vertex_property_manager
+                            .set(&mut txn.batch, &indexes.properties, *id, *name, value)?;
+                    }
+                    BulkInsertItem::EdgeProperty(edge, name, value) => {
+                        txn.edge_property_manager
+                            .set(&mut txn.batch, &indexes.properties, edge, *name, value)?;
+                    }
+                    BulkInsertItem::ExpiringVertex(vertex, ttl) => {
+                        txn.vertex_manager.create(&mut txn.batch, vertex)?;
+                        txn.vertex_expiry_manager
+                            .set(&mut txn.batch, vertex.id, util::expiry(now, *ttl)?)?;
+                    }
+                    BulkInsertItem::ExpiringEdge(edge, ttl) => {
+                        txn.edge_manager.set(&mut txn.batch, edge)?;
+                        txn.edge_expiry_manager
+                            .set(&mut txn.batch, edge, util::expiry(now, *ttl)?)?;
+                    }
                 }
             }
-        }
 
-        self.db.write(batch)?;
-        Ok(())
+            for (id, changes) in composite_changes {
+                let changes: Vec<(Identifier, &Json)> = changes.into_iter().collect();
+                txn.vertex_composite_property_value_manager
+                    .set(&mut txn.batch, &indexes.composites, id, &changes)?;
+            }
+
+            Ok(())
+        })
     }
 
     fn index_property(&mut self, name: Identifier) -> Result<()> {
-        let mut indexed_properties = self.indexed_properties.write().unwrap();
-        if !indexed_properties.insert(name) {
-            return Ok(());
-        }
+        self.write_staged(|txn| {
+            let mut indexes = txn.indexes.write().unwrap();
+            if !indexes.properties.insert(name) {
+                return Ok(());
+            }
 
-        let mut batch = WriteBatch::default();
-        self.metadata_manager
-            .set_indexed_properties(&mut batch, &indexed_properties)?;
+            txn.metadata_manager
+                .set_indexed_properties(&mut txn.batch, &indexes.properties)?;
 
-        for item in self.vertex_manager.iterate_for_range(Uuid::default()) {
-            let vertex = item?;
-            if let Some(property_value) = self.vertex_property_manager.get(vertex.id, name)? {
-                self.vertex_property_value_manager
-                    .set(&mut batch, vertex.id, name, &property_value);
+            for item in txn.vertex_manager.iterate_for_range(&txn.batch, Uuid::default()) {
+                let vertex = item?;
+                if let Some(property_value) = txn.vertex_property_manager.get(&txn.batch, vertex.id, name)? {
+                    txn.vertex_property_value_manager
+                        .set(&mut txn.batch, vertex.id, name, &property_value);
+                }
             }
-        }
 
-        for item in self.edge_range_manager.iterate_for_all() {
-            let edge = item?;
-            if let Some(property_value) = self.edge_property_manager.get(&edge, name)? {
-                self.edge_property_value_manager
-                    .set(&mut batch, &edge, name, &property_value);
+            for item in txn.edge_range_manager.iterate_for_all(&txn.batch) {
+                let edge = item?;
+                if let Some(property_value) = txn.edge_property_manager.get(&txn.batch, &edge, name)? {
+                    txn.edge_property_value_manager
+                        .set(&mut txn.batch, &edge, name, &property_value);
+                }
             }
-        }
 
-        self.db.write(batch)?;
-        Ok(())
+            Ok(())
+        })
+    }
+
+    fn index_unique_property(&mut self, name: Identifier) -> Result<()> {
+        self.write_staged(|txn| {
+            if txn.indexes.read().unwrap().unique_properties.contains(&name) {
+                return Ok(());
+            }
+
+            // Values are compared by their encoding in the index, where e.g.
+            // `1` and `1.0` are the same value.
+            let mut values = HashSet::new();
+            for item in txn.vertex_manager.iterate_for_range(&txn.batch, Uuid::default()) {
+                let vertex = item?;
+                if let Some(value) = txn.vertex_property_manager.get(&txn.batch, vertex.id, name)? {
+                    if !values.insert(util::build(&[util::Component::Json(&value)])) {
+                        return Err(Error::UniqueViolation { name, value });
+                    }
+                }
+            }
+
+            txn.index_property(name)?;
+            let mut indexes = txn.indexes.write().unwrap();
+            indexes.unique_properties.insert(name);
+            txn.metadata_manager
+                .set_unique_properties(&mut txn.batch, &indexes.unique_properties)?;
+            Ok(())
+        })
+    }
+
+    fn unindex_property(&mut self, name: Identifier) -> Result<()> {
+        self.write_staged(|txn| {
+            let mut indexes = txn.indexes.write().unwrap();
+            if !indexes.properties.remove(&name) {
+                return Ok(());
+            }
+
+            txn.metadata_manager
+                .set_indexed_properties(&mut txn.batch, &indexes.properties)?;
+            if indexes.unique_properties.remove(&name) {
+                txn.metadata_manager
+                    .set_unique_properties(&mut txn.batch, &indexes.unique_properties)?;
+            }
+
+            let vertex_keys = txn
+                .vertex_property_value_manager
+                .iterate_for_name(&txn.batch, name)
+                .collect::<Result<Vec<_>>>()?;
+            for (name, value, vertex_id) in vertex_keys {
+                txn.vertex_property_value_manager
+                    .delete(&mut txn.batch, vertex_id, name, &value);
+            }
+
+            let edge_keys = txn
+                .edge_property_value_manager
+                .iterate_for_name(&txn.batch, name)
+                .collect::<Result<Vec<_>>>()?;
+            for (name, value, edge) in edge_keys {
+                txn.edge_property_value_manager
+                    .delete(&mut txn.batch, &edge, name, &value);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn indexed_properties(&self) -> Result<Vec<Identifier>> {
+        let mut names: Vec<Identifier> = self.indexes.read().unwrap().properties.iter().copied().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn set_schema(&mut self, kind: SchemaKind, t: Identifier, schema: Option<Schema>) -> Result<()> {
+        self.write_staged(|txn| {
+            txn.metadata_manager
+                .set_schema(&mut txn.batch, kind, t, schema.as_ref())?;
+            Ok(())
+        })
+    }
+
+    fn schema(&self, kind: SchemaKind, t: Identifier) -> Result<Option<Schema>> {
+        self.metadata_manager.get_schema(&self.batch, kind, t)
+    }
+
+    fn index_composite_property(&mut self, names: Vec<Identifier>) -> Result<()> {
+        self.write_staged(|txn| {
+            let mut indexes = txn.indexes.write().unwrap();
+            if !indexes.composites.insert(names.clone()) {
+                return Ok(());
+            }
+
+            txn.metadata_manager
+                .set_composite_indexes(&mut txn.batch, &indexes.composites)?;
+
+            let composites = HashSet::from([names]);
+            for item in txn.vertex_manager.iterate_for_range(&txn.batch, Uuid::default()) {
+                let vertex = item?;
+                txn.vertex_composite_property_value_manager
+                    .set(&mut txn.batch, &composites, vertex.id, &[])?;
+            }
+
+            Ok(())
+        })
     }
 
     fn set_vertex_properties(&mut self, vertices: Vec<Uuid>, name: Identifier, value: &Json) -> Result<()> {
-        let indexed_properties = self.indexed_properties.read().unwrap();
-        let mut batch = WriteBatch::default();
-        for id in vertices.into_iter() {
-            self.vertex_property_manager
-                .set(&mut batch, &indexed_properties, id, name, value)?;
-        }
-        self.db.write(batch)?;
-        Ok(())
+        self.write_staged(|txn| {
+            let mut props = Vec::with_capacity(vertices.len());
+            for id in &vertices {
+                if let Some(t) = txn.vertex_manager.get(&txn.batch, *id)? {
+                    props.push((t, name, value));
+                }
+            }
+            txn.validate_properties(SchemaKind::Vertex, props)?;
+
+            let indexes = txn.indexes.read().unwrap();
+            txn.check_unique_vertex_properties(
+                &indexes.unique_properties,
+                vertices.iter().map(|id| (*id, name, value)),
+            )?;
+            for id in vertices.into_iter() {
+                txn.vertex_composite_property_value_manager.delete(
+                    &mut txn.batch,
+                    &indexes.composites,
+                    id,
+                    Some(name),
+                )?;
+                txn.vertex_property_manager
+                    .set(&mut txn.batch, &indexes.properties, id, name, value)?;
+                txn.vertex_composite_property_value_manager.set(
+                    &mut txn.batch,
+                    &indexes.composites,
+                    id,
+                    &[(name, value)],
+                )?;
+            }
+            Ok(())
+        })
     }
 
     fn set_edge_properties(&mut self, edges: Vec<Edge>, name: Identifier, value: &Json) -> Result<()> {
-        let indexed_properties = self.indexed_properties.read().unwrap();
-        let mut batch = WriteBatch::default();
-        for edge in edges.into_iter() {
-            self.edge_property_manager
-                .set(&mut batch, &indexed_properties, &edge, name, value)?;
-        }
-        self.db.write(batch)?;
-        Ok(())
+        self.write_staged(|txn| {
+            txn.validate_properties(SchemaKind::Edge, edges.iter().map(|edge| (edge.t, name, value)))?;
+
+            let indexes = txn.indexes.read().unwrap();
+            for edge in edges.into_iter() {
+                txn.edge_property_manager
+                    .set(&mut txn.batch, &indexes.properties, &edge, name, value)?;
+            }
+            Ok(())
+        })
     }
 }
 
@@ -365,7 +899,8 @@ impl<'a> Transaction<'a> for RocksdbTransaction<'a> {
 #[derive(Debug)]
 pub struct RocksdbDatastore {
     db: Arc<DB>,
-    indexed_properties: Arc<RwLock<HashSet<Identifier>>>,
+    writer: Mutex<()>,
+    indexes: Arc<RwLock<Indexes>>,
 }
 
 impl RocksdbDatastore {
@@ -374,29 +909,7 @@ impl RocksdbDatastore {
     /// # Arguments
     /// * `path`: The file path to the rocksdb database.
     pub fn new_db<P: AsRef<Path>>(path: P) -> Result<Database<RocksdbDatastore>> {
-        let opts = RocksdbDatastore::get_options(None);
-        let path = path.as_ref();
-
-        let db = match DB::open_cf(&opts, path, CF_NAMES) {
-            Ok(db) => db,
-            Err(_) => {
-                let mut db = DB::open(&opts, path)?;
-
-                for cf_name in &CF_NAMES {
-                    db.create_cf(cf_name, &opts)?;
-                }
-
-                db
-            }
-        };
-
-        let metadata_manager = MetadataManager::new(&db);
-        let indexed_properties = metadata_manager.get_indexed_properties()?;
-
-        Ok(Database::new(RocksdbDatastore {
-            db: Arc::new(db),
-            indexed_properties: Arc::new(RwLock::new(indexed_properties)),
-        }))
+        Self::new_db_with_options(path, &RocksdbDatastore::get_options(None))
     }
 
     /// Creates a new rocksdb datastore with user-tuned rocksdb Option.
@@ -406,26 +919,36 @@ impl RocksdbDatastore {
     /// * `opts`: The user-tuned rocksdb options.
     pub fn new_db_with_options<P: AsRef<Path>>(path: P, opts: &Options) -> Result<Database<RocksdbDatastore>> {
         let path = path.as_ref();
-
-        let db = match DB::open_cf(opts, path, CF_NAMES) {
-            Ok(db) => db,
-            Err(_) => {
-                let mut db = DB::open(opts, path)?;
-
-                for cf_name in &CF_NAMES {
-                    db.create_cf(cf_name, opts)?;
+        let mut opts = opts.clone();
+        opts.create_missing_column_families(true);
+
+        // Every column family that exists has to be opened, including those
+        // from older versions.
+        let mut cf_names: Vec<String> = CF_NAMES.iter().map(|name| name.to_string()).collect();
+        if let Ok(existing_cf_names) = DB::list_cf(&opts, path) {
+            for name in existing_cf_names {
+                if !cf_names.contains(&name) {
+                    cf_names.push(name);
                 }
-
-                db
             }
-        };
+        }
+        let mut db = DB::open_cf(&opts, path, &cf_names)?;
 
         let metadata_manager = MetadataManager::new(&db);
-        let indexed_properties = metadata_manager.get_indexed_properties()?;
+        let indexes = metadata_manager.get_indexes()?;
+        if metadata_manager.get_index_version()? < INDEX_VERSION {
+            rebuild_indexes(&db, &indexes)?;
+        }
+        for name in LEGACY_CF_NAMES {
+            if db.cf_handle(name).is_some() {
+                db.drop_cf(name)?;
+            }
+        }
 
         Ok(Database::new(RocksdbDatastore {
             db: Arc::new(db),
-            indexed_properties: Arc::new(RwLock::new(indexed_properties)),
+            writer: Mutex::new(()),
+            indexes: Arc::new(RwLock::new(indexes)),
         }))
     }
 
@@ -451,6 +974,7 @@ impl RocksdbDatastore {
         // https://github.com/facebook/rocksdb/wiki/RocksDB-Tuning-Guide
         let mut opts = Options::default();
         opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
         opts.set_compaction_style(DBCompactionStyle::Level);
         opts.set_write_buffer_size(67_108_864); // 64mb
         opts.set_max_write_buffer_number(3);
@@ -471,20 +995,24 @@ impl RocksdbDatastore {
 }
 
 impl Datastore for RocksdbDatastore {
-    type Transaction<'a> = RocksdbTransaction<'a> where Self: 'a;
+    type Transaction<'a>
+        = RocksdbTransaction<'a>
+    where
+        Self: 'a;
     fn transaction(&'_ self) -> Self::Transaction<'_> {
-        RocksdbTransaction {
-            db: &self.db,
-            indexed_properties: self.indexed_properties.clone(),
-            vertex_manager: VertexManager::new(&self.db),
-            edge_manager: EdgeManager::new(&self.db),
-            edge_range_manager: EdgeRangeManager::new(&self.db),
-            reversed_edge_range_manager: EdgeRangeManager::new_reversed(&self.db),
-            vertex_property_manager: VertexPropertyManager::new(&self.db),
-            edge_property_manager: EdgePropertyManager::new(&self.db),
-            vertex_property_value_manager: VertexPropertyValueManager::new(&self.db),
-            edge_property_value_manager: EdgePropertyValueManager::new(&self.db),
-            metadata_manager: MetadataManager::new(&self.db),
-        }
+        RocksdbTransaction::new(&self.db, &self.writer, None, self.indexes.clone(), None, Batch::new())
+    }
+
+    fn begin(&'_ self) -> Result<Self::Transaction<'_>> {
+        let write_guard = self.writer.lock().unwrap();
+        let indexes = self.indexes.read().unwrap().clone();
+        Ok(RocksdbTransaction::new(
+            &self.db,
+            &self.writer,
+            Some(write_guard),
+            Arc::new(RwLock::new(indexes)),
+            Some(self.indexes.clone()),
+            Batch::buffered(),
+        ))
     }
 }
@@ -1,25 +1,27 @@
 use std::collections::HashSet;
 use std::io::Cursor;
-use std::ops::Deref;
+use std::ops::{Bound, Deref};
 use std::result::Result as StdResult;
+use std::time::SystemTime;
 use std::u8;
 
+use super::batch::{Batch, KeyValue};
 use crate::errors::Result;
 use crate::models;
 use crate::util;
 
-use rocksdb::{ColumnFamilyRef, DBIterator, Direction, IteratorMode, WriteBatch, DB};
+use rocksdb::{ColumnFamilyRef, DB};
 use uuid::Uuid;
 
 pub type OwnedPropertyItem = (Uuid, models::Identifier, models::Json);
 pub type EdgePropertyItem = (models::Edge, models::Identifier, models::Json);
-pub type VertexPropertyValueKey = (models::Identifier, u64, Uuid);
-pub type EdgePropertyValueKey = (models::Identifier, u64, models::Edge);
+pub type VertexPropertyValueKey = (models::Identifier, models::Json, Uuid);
+pub type EdgePropertyValueKey = (models::Identifier, models::Json, models::Edge);
 
-fn take_with_prefix(
-    iterator: DBIterator<'_>,
+fn take_with_prefix<'a>(
+    iterator: impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a,
     prefix: Vec<u8>,
-) -> impl Iterator<Item = StdResult<(Box<[u8]>, Box<[u8]>), rocksdb::Error>> + '_ {
+) -> impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a {
     iterator.take_while(move |item| -> bool {
         if let Ok((ref k, _)) = *item {
             k.starts_with(&prefix)
@@ -29,9 +31,61 @@ fn take_with_prefix(
     })
 }
 
+fn json_range_key(property_name: models::Identifier, bound: Bound<&models::Json>) -> Vec<u8> {
+    match bound {
+        Bound::Included(value) | Bound::Excluded(value) => {
+            util::build(&[util::Component::Identifier(property_name), util::Component::Json(value)])
+        }
+        Bound::Unbounded => util::build(&[util::Component::Identifier(property_name)]),
+    }
+}
+
+// Restricts an iterator over a property value index, which should start at
+// `json_range_key` for the range's lower bound, to keys for values within the
+// range. Because the JSON encoding is prefix-free, every key for a value `v`
+// starts with the key built from `v`, and sorts before keys for any larger
+// value.
+fn take_for_json_range<'a>(
+    iterator: impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a,
+    property_name: models::Identifier,
+    range: &models::JsonRange,
+) -> impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a {
+    let start = match range.start {
+        Bound::Excluded(ref value) => Some(json_range_key(property_name, Bound::Excluded(value))),
+        _ => None,
+    };
+    let end = match range.end {
+        Bound::Included(ref value) => Bound::Included(json_range_key(property_name, Bound::Included(value))),
+        Bound::Excluded(ref value) => Bound::Excluded(json_range_key(property_name, Bound::Excluded(value))),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+
+    let skipped = iterator.skip_while(move |item| -> bool {
+        match (item, &start) {
+            (Ok((ref k, _)), Some(start)) => k.starts_with(start),
+            _ => false,
+        }
+    });
+    let filtered = take_with_prefix(skipped, util::build(&[util::Component::Identifier(property_name)]));
+    filtered.take_while(move |item| -> bool {
+        if let Ok((ref k, _)) = *item {
+            match end {
+                Bound::Included(ref end) => k.as_ref() < end.as_slice() || k.starts_with(end),
+                Bound::Excluded(ref end) => k.as_ref() < end.as_slice(),
+                Bound::Unbounded => true,
+            }
+        } else {
+            true
+        }
+    })
+}
+
 pub(crate) struct VertexManager<'a> {
     db: &'a DB,
     cf: ColumnFamilyRef<'a>,
+    // Indexes vertices by type, so that they can be scanned without having
+    // to filter through vertices of other types.
+    type_cf: ColumnFamilyRef<'a>,
 }
 
 impl<'a> VertexManager<'a> {
@@ -39,6 +93,7 @@ impl<'a> VertexManager<'a> {
         VertexManager {
             db,
             cf: db.cf_handle("vertices:v2").unwrap(),
+            type_cf: db.cf_handle("vertices_by_type:v2").unwrap(),
         }
     }
 
@@ -46,12 +101,16 @@ impl<'a> VertexManager<'a> {
         util::build(&[util::Component::Uuid(id)])
     }
 
-    pub fn exists(&self, id: Uuid) -> Result<bool> {
-        Ok(self.db.get_cf(&self.cf, self.key(id))?.is_some())
+    fn type_key(&self, t: models::Identifier, id: Uuid) -> Vec<u8> {
+        util::build(&[util::Component::Identifier(t), util::Component::Uuid(id)])
     }
 
-    pub fn get(&self, id: Uuid) -> Result<Option<models::Identifier>> {
-        match self.db.get_cf(&self.cf, self.key(id))? {
+    pub fn exists(&self, batch: &Batch, id: Uuid) -> Result<bool> {
+        Ok(batch.get_cf(self.db, self.cf, self.key(id))?.is_some())
+    }
+
+    pub fn get(&self, batch: &Batch, id: Uuid) -> Result<Option<models::Identifier>> {
+        match batch.get_cf(self.db, self.cf, self.key(id))? {
             Some(value_bytes) => {
                 let mut cursor = Cursor::new(value_bytes.deref());
                 Ok(Some(util::read_identifier(&mut cursor)))
@@ -60,11 +119,9 @@ impl<'a> VertexManager<'a> {
         }
     }
 
-    pub fn iterate_for_range(&'a self, id: Uuid) -> impl Iterator<Item = Result<models::Vertex>> + 'a {
+    pub fn iterate_for_range(&'a self, batch: &Batch, id: Uuid) -> impl Iterator<Item = Result<models::Vertex>> + 'a {
         let low_key = util::build(&[util::Component::Uuid(id)]);
-        let iter = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&low_key, Direction::Forward));
+        let iter = batch.iterator_cf(self.db, self.cf, &low_key);
         iter.map(|item| -> Result<models::Vertex> {
             let (k, v) = item?;
 
@@ -80,22 +137,50 @@ impl<'a> VertexManager<'a> {
         })
     }
 
-    pub fn create(&self, batch: &mut WriteBatch, vertex: &models::Vertex) -> Result<()> {
+    pub fn iterate_for_type(
+        &'a self,
+        batch: &Batch,
+        t: models::Identifier,
+        id: Uuid,
+    ) -> impl Iterator<Item = Result<models::Vertex>> + 'a {
+        let prefix = util::build(&[util::Component::Identifier(t)]);
+        let low_key = self.type_key(t, id);
+        let iter = batch.iterator_cf(self.db, self.type_cf, &low_key);
+        take_with_prefix(iter, prefix).map(move |item| -> Result<models::Vertex> {
+            let (k, _) = item?;
+            let mut cursor = Cursor::new(k);
+            let t = util::read_identifier(&mut cursor);
+            let id = util::read_uuid(&mut cursor);
+            Ok(models::Vertex::with_id(id, t))
+        })
+    }
+
+    pub fn create(&self, batch: &mut Batch, vertex: &models::Vertex) -> Result<()> {
+        // Bulk inserts can overwrite an existing vertex with a different type
+        if let Some(t) = self.get(batch, vertex.id)? {
+            if t != vertex.t {
+                batch.delete_cf(self.type_cf, self.type_key(t, vertex.id));
+            }
+        }
         let key = self.key(vertex.id);
-        batch.put_cf(&self.cf, &key, &util::build(&[util::Component::Identifier(vertex.t)]));
+        batch.put_cf(self.cf, &key, &util::build(&[util::Component::Identifier(vertex.t)]));
+        self.index_type(batch, vertex);
         Ok(())
     }
 
-    pub fn delete(
-        &self,
-        batch: &mut WriteBatch,
-        indexed_properties: &HashSet<models::Identifier>,
-        id: Uuid,
-    ) -> Result<()> {
-        batch.delete_cf(&self.cf, self.key(id));
+    pub fn index_type(&self, batch: &mut Batch, vertex: &models::Vertex) {
+        batch.put_cf(self.type_cf, self.type_key(vertex.t, vertex.id), []);
+    }
+
+    pub fn delete(&self, batch: &mut Batch, indexed_properties: &HashSet<models::Identifier>, id: Uuid) -> Result<()> {
+        if let Some(t) = self.get(batch, id)? {
+            batch.delete_cf(self.type_cf, self.type_key(t, id));
+        }
+        batch.delete_cf(self.cf, self.key(id));
+        VertexExpiryManager::new(self.db).delete(batch, id)?;
 
         let vertex_property_manager = VertexPropertyManager::new(self.db);
-        for item in vertex_property_manager.iterate_for_owner(id)? {
+        for item in vertex_property_manager.iterate_for_owner(batch, id)? {
             let (vertex_property_owner_id, vertex_property_name, _) = item?;
             vertex_property_manager.delete(
                 batch,
@@ -109,7 +194,7 @@ impl<'a> VertexManager<'a> {
 
         {
             let edge_range_manager = EdgeRangeManager::new(self.db);
-            for item in edge_range_manager.iterate_for_root(id, None)? {
+            for item in edge_range_manager.iterate_for_root(batch, id, None)? {
                 let edge = item?;
                 debug_assert_eq!(edge.outbound_id, id);
                 edge_manager.delete(batch, indexed_properties, &edge)?;
@@ -118,7 +203,7 @@ impl<'a> VertexManager<'a> {
 
         {
             let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db);
-            for item in reversed_edge_range_manager.iterate_for_root(id, None)? {
+            for item in reversed_edge_range_manager.iterate_for_root(batch, id, None)? {
                 let edge = item?;
                 debug_assert_eq!(edge.outbound_id, id);
                 edge_manager.delete(batch, indexed_properties, &edge)?;
@@ -131,36 +216,90 @@ impl<'a> VertexManager<'a> {
     pub fn compact(&self) {
         self.db
             .compact_range_cf(&self.cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+        self.db
+            .compact_range_cf(&self.type_cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
     }
 }
 
 pub(crate) struct EdgeManager<'a> {
     db: &'a DB,
+    // Indexes edges by type, so that they can be scanned without having to
+    // filter through edges of other types.
+    type_cf: ColumnFamilyRef<'a>,
 }
 
 impl<'a> EdgeManager<'a> {
     pub fn new(db: &'a DB) -> Self {
-        EdgeManager { db }
+        EdgeManager {
+            db,
+            type_cf: db.cf_handle("edges_by_type:v2").unwrap(),
+        }
+    }
+
+    fn type_key(&self, edge: &models::Edge) -> Vec<u8> {
+        util::build(&[
+            util::Component::Identifier(edge.t),
+            util::Component::Uuid(edge.outbound_id),
+            util::Component::Uuid(edge.inbound_id),
+        ])
+    }
+
+    pub fn iterate_for_type(
+        &'a self,
+        batch: &Batch,
+        t: models::Identifier,
+        offset: Option<models::Edge>,
+    ) -> impl Iterator<Item = Result<models::Edge>> + 'a {
+        let prefix = util::build(&[util::Component::Identifier(t)]);
+        let low_key = match offset {
+            Some(ref offset) => util::build(&[
+                util::Component::Identifier(t),
+                util::Component::Uuid(offset.outbound_id),
+                util::Component::Uuid(Uuid::default()),
+            ]),
+            None => prefix.clone(),
+        };
+        let iter = batch.iterator_cf(self.db, self.type_cf, &low_key);
+        take_with_prefix(iter, prefix)
+            .map(move |item| -> Result<models::Edge> {
+                let (k, _) = item?;
+                let mut cursor = Cursor::new(k);
+                let t = util::read_identifier(&mut cursor);
+                let outbound_id = util::read_uuid(&mut cursor);
+                let inbound_id = util::read_uuid(&mut cursor);
+                Ok(models::Edge::new(outbound_id, t, inbound_id))
+            })
+            // The seek only positions on the offset's outbound ID, since the
+            // offset may be of a different type.
+            .skip_while(move |item| match (item, &offset) {
+                (Ok(edge), Some(offset)) => edge < offset,
+                _ => false,
+            })
     }
 
-    pub fn set(&self, batch: &mut WriteBatch, edge: &models::Edge) -> Result<()> {
+    pub fn set(&self, batch: &mut Batch, edge: &models::Edge) -> Result<()> {
         let edge_range_manager = EdgeRangeManager::new(self.db);
         let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db);
         let reversed_edge = edge.reversed();
 
-        if edge_range_manager.contains(edge)? {
+        if edge_range_manager.contains(batch, edge)? {
             edge_range_manager.delete(batch, edge)?;
             reversed_edge_range_manager.delete(batch, &reversed_edge)?;
         }
 
         edge_range_manager.set(batch, edge)?;
         reversed_edge_range_manager.set(batch, &reversed_edge)?;
+        self.index_type(batch, edge);
         Ok(())
     }
 
+    pub fn index_type(&self, batch: &mut Batch, edge: &models::Edge) {
+        batch.put_cf(self.type_cf, self.type_key(edge), []);
+    }
+
     pub fn delete(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         indexed_properties: &HashSet<models::Identifier>,
         edge: &models::Edge,
     ) -> Result<()> {
@@ -170,14 +309,22 @@ impl<'a> EdgeManager<'a> {
         let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db);
         reversed_edge_range_manager.delete(batch, &edge.reversed())?;
 
+        batch.delete_cf(self.type_cf, self.type_key(edge));
+        EdgeExpiryManager::new(self.db).delete(batch, edge)?;
+
         let edge_property_manager = EdgePropertyManager::new(self.db);
-        for item in edge_property_manager.iterate_for_owner(edge)? {
+        for item in edge_property_manager.iterate_for_owner(batch, edge)? {
             let (edge_property_edge, edge_property_name, _) = item?;
             edge_property_manager.delete(batch, indexed_properties, &edge_property_edge, edge_property_name)?;
         }
 
         Ok(())
     }
+
+    pub fn compact(&self) {
+        self.db
+            .compact_range_cf(&self.type_cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+    }
 }
 
 pub(crate) struct EdgeRangeManager<'a> {
@@ -210,7 +357,7 @@ impl<'a> EdgeRangeManager<'a> {
 
     fn iterate<I>(&'a self, iterator: I) -> impl Iterator<Item = Result<models::Edge>> + 'a
     where
-        I: Iterator<Item = StdResult<(Box<[u8]>, Box<[u8]>), rocksdb::Error>> + 'a,
+        I: Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a,
     {
         iterator.map(move |item| -> Result<models::Edge> {
             let (k, _) = item?;
@@ -222,12 +369,13 @@ impl<'a> EdgeRangeManager<'a> {
         })
     }
 
-    pub fn contains(&self, edge: &models::Edge) -> Result<bool> {
-        Ok(self.db.get_cf(&self.cf, self.key(edge))?.is_some())
+    pub fn contains(&self, batch: &Batch, edge: &models::Edge) -> Result<bool> {
+        Ok(batch.get_cf(self.db, self.cf, self.key(edge))?.is_some())
     }
 
     pub fn iterate_for_root(
         &'a self,
+        batch: &Batch,
         id: Uuid,
         t: Option<models::Identifier>,
     ) -> Result<Box<dyn Iterator<Item = Result<models::Edge>> + 'a>> {
@@ -235,16 +383,12 @@ impl<'a> EdgeRangeManager<'a> {
             Some(t) => {
                 let prefix = util::build(&[util::Component::Uuid(id), util::Component::Identifier(t)]);
                 let low_key = util::build(&[util::Component::Uuid(id), util::Component::Identifier(t)]);
-                let iter = self
-                    .db
-                    .iterator_cf(&self.cf, IteratorMode::From(&low_key, Direction::Forward));
+                let iter = batch.iterator_cf(self.db, self.cf, &low_key);
                 (prefix, iter)
             }
             None => {
                 let prefix = util::build(&[util::Component::Uuid(id)]);
-                let iter = self
-                    .db
-                    .iterator_cf(&self.cf, IteratorMode::From(&prefix, Direction::Forward));
+                let iter = batch.iterator_cf(self.db, self.cf, &prefix);
                 (prefix, iter)
             }
         };
@@ -255,6 +399,7 @@ impl<'a> EdgeRangeManager<'a> {
 
     pub fn iterate_for_range(
         &'a self,
+        batch: &Batch,
         first_id: Uuid,
         t: models::Identifier,
         second_id: Uuid,
@@ -264,25 +409,23 @@ impl<'a> EdgeRangeManager<'a> {
             util::Component::Identifier(t),
             util::Component::Uuid(second_id),
         ]);
-        let iter = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&low_key, Direction::Forward));
+        let iter = batch.iterator_cf(self.db, self.cf, &low_key);
         Ok(Box::new(self.iterate(iter)))
     }
 
-    pub fn iterate_for_all(&'a self) -> impl Iterator<Item = Result<models::Edge>> + 'a {
-        let iterator = self.db.iterator_cf(&self.cf, IteratorMode::Start);
+    pub fn iterate_for_all(&'a self, batch: &Batch) -> impl Iterator<Item = Result<models::Edge>> + 'a {
+        let iterator = batch.iterator_cf(self.db, self.cf, &[]);
         self.iterate(iterator)
     }
 
-    pub fn set(&self, batch: &mut WriteBatch, edge: &models::Edge) -> Result<()> {
+    pub fn set(&self, batch: &mut Batch, edge: &models::Edge) -> Result<()> {
         let key = self.key(edge);
-        batch.put_cf(&self.cf, &key, []);
+        batch.put_cf(self.cf, &key, []);
         Ok(())
     }
 
-    pub fn delete(&self, batch: &mut WriteBatch, edge: &models::Edge) -> Result<()> {
-        batch.delete_cf(&self.cf, self.key(edge));
+    pub fn delete(&self, batch: &mut Batch, edge: &models::Edge) -> Result<()> {
+        batch.delete_cf(self.cf, self.key(edge));
         Ok(())
     }
 
@@ -314,13 +457,12 @@ impl<'a> VertexPropertyManager<'a> {
 
     pub fn iterate_for_owner(
         &'a self,
+        batch: &Batch,
         vertex_id: Uuid,
     ) -> Result<impl Iterator<Item = Result<OwnedPropertyItem>> + 'a> {
         let prefix = util::build(&[util::Component::Uuid(vertex_id)]);
 
-        let iterator = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&prefix, Direction::Forward));
+        let iterator = batch.iterator_cf(self.db, self.cf, &prefix);
 
         let filtered = take_with_prefix(iterator, prefix);
 
@@ -336,8 +478,8 @@ impl<'a> VertexPropertyManager<'a> {
         }))
     }
 
-    pub fn get(&self, vertex_id: Uuid, name: models::Identifier) -> Result<Option<models::Json>> {
-        match self.db.get_cf(&self.cf, self.key(vertex_id, name))? {
+    pub fn get(&self, batch: &Batch, vertex_id: Uuid, name: models::Identifier) -> Result<Option<models::Json>> {
+        match batch.get_cf(self.db, self.cf, self.key(vertex_id, name))? {
             Some(value_bytes) => Ok(Some(serde_json::from_slice(&value_bytes)?)),
             None => Ok(None),
         }
@@ -345,7 +487,7 @@ impl<'a> VertexPropertyManager<'a> {
 
     pub fn set(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         indexed_properties: &HashSet<models::Identifier>,
         vertex_id: Uuid,
         name: models::Identifier,
@@ -357,7 +499,7 @@ impl<'a> VertexPropertyManager<'a> {
             self.delete(batch, indexed_properties, vertex_id, name)?;
         }
         let value_json = serde_json::to_vec(value)?;
-        batch.put_cf(&self.cf, &key, &value_json);
+        batch.put_cf(self.cf, &key, &value_json);
         if is_indexed {
             let vertex_property_value_manager = VertexPropertyValueManager::new(self.db);
             vertex_property_value_manager.set(batch, vertex_id, name, value);
@@ -367,18 +509,18 @@ impl<'a> VertexPropertyManager<'a> {
 
     pub fn delete(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         indexed_properties: &HashSet<models::Identifier>,
         vertex_id: Uuid,
         name: models::Identifier,
     ) -> Result<()> {
         if indexed_properties.contains(&name) {
-            if let Some(value) = self.get(vertex_id, name)? {
+            if let Some(value) = self.get(batch, vertex_id, name)? {
                 let vertex_property_value_manager = VertexPropertyValueManager::new(self.db);
                 vertex_property_value_manager.delete(batch, vertex_id, name, &value);
             }
         }
-        batch.delete_cf(&self.cf, self.key(vertex_id, name));
+        batch.delete_cf(self.cf, self.key(vertex_id, name));
         Ok(())
     }
 
@@ -412,6 +554,7 @@ impl<'a> EdgePropertyManager<'a> {
 
     pub fn iterate_for_owner(
         &'a self,
+        batch: &Batch,
         edge: &'a models::Edge,
     ) -> Result<Box<dyn Iterator<Item = Result<EdgePropertyItem>> + 'a>> {
         let prefix = util::build(&[
@@ -420,9 +563,7 @@ impl<'a> EdgePropertyManager<'a> {
             util::Component::Uuid(edge.inbound_id),
         ]);
 
-        let iterator = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&prefix, Direction::Forward));
+        let iterator = batch.iterator_cf(self.db, self.cf, &prefix);
 
         let filtered = take_with_prefix(iterator, prefix);
 
@@ -450,8 +591,8 @@ impl<'a> EdgePropertyManager<'a> {
         Ok(Box::new(mapped))
     }
 
-    pub fn get(&self, edge: &models::Edge, name: models::Identifier) -> Result<Option<models::Json>> {
-        match self.db.get_cf(&self.cf, self.key(edge, name))? {
+    pub fn get(&self, batch: &Batch, edge: &models::Edge, name: models::Identifier) -> Result<Option<models::Json>> {
+        match batch.get_cf(self.db, self.cf, self.key(edge, name))? {
             Some(value_bytes) => Ok(Some(serde_json::from_slice(&value_bytes)?)),
             None => Ok(None),
         }
@@ -459,7 +600,7 @@ impl<'a> EdgePropertyManager<'a> {
 
     pub fn set(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         indexed_properties: &HashSet<models::Identifier>,
         edge: &models::Edge,
         name: models::Identifier,
@@ -471,7 +612,7 @@ impl<'a> EdgePropertyManager<'a> {
             self.delete(batch, indexed_properties, edge, name)?;
         }
         let value_json = serde_json::to_vec(value)?;
-        batch.put_cf(&self.cf, &key, &value_json);
+        batch.put_cf(self.cf, &key, &value_json);
         if is_indexed {
             let edge_property_value_manager = EdgePropertyValueManager::new(self.db);
             edge_property_value_manager.set(batch, edge, name, value);
@@ -481,18 +622,18 @@ impl<'a> EdgePropertyManager<'a> {
 
     pub fn delete(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         indexed_properties: &HashSet<models::Identifier>,
         edge: &models::Edge,
         name: models::Identifier,
     ) -> Result<()> {
         if indexed_properties.contains(&name) {
-            if let Some(value) = self.get(edge, name)? {
+            if let Some(value) = self.get(batch, edge, name)? {
                 let edge_property_value_manager = EdgePropertyValueManager::new(self.db);
                 edge_property_value_manager.delete(batch, edge, name, &value);
             }
         }
-        batch.delete_cf(&self.cf, self.key(edge, name));
+        batch.delete_cf(self.cf, self.key(edge, name));
         Ok(())
     }
 
@@ -511,7 +652,7 @@ impl<'a> VertexPropertyValueManager<'a> {
     pub fn new(db: &'a DB) -> Self {
         VertexPropertyValueManager {
             db,
-            cf: db.cf_handle("vertex_property_values:v2").unwrap(),
+            cf: db.cf_handle("vertex_property_values:v3").unwrap(),
         }
     }
 
@@ -525,7 +666,7 @@ impl<'a> VertexPropertyValueManager<'a> {
 
     fn iterate(
         &'a self,
-        iterator: DBIterator<'a>,
+        iterator: impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a,
         prefix: Vec<u8>,
     ) -> impl Iterator<Item = Result<VertexPropertyValueKey>> + 'a {
         let filtered = take_with_prefix(iterator, prefix);
@@ -534,25 +675,25 @@ impl<'a> VertexPropertyValueManager<'a> {
             let (k, _) = item?;
             let mut cursor = Cursor::new(k);
             let name = util::read_identifier(&mut cursor);
-            let value_hash = util::read_u64(&mut cursor);
+            let value = util::read_json(&mut cursor);
             let vertex_id = util::read_uuid(&mut cursor);
-            Ok((name, value_hash, vertex_id))
+            Ok((name, value, vertex_id))
         })
     }
 
     pub fn iterate_for_name(
         &'a self,
+        batch: &Batch,
         property_name: models::Identifier,
     ) -> impl Iterator<Item = Result<VertexPropertyValueKey>> + 'a {
         let prefix = util::build(&[util::Component::Identifier(property_name)]);
-        let iter = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&prefix, Direction::Forward));
+        let iter = batch.iterator_cf(self.db, self.cf, &prefix);
         self.iterate(iter, prefix)
     }
 
     pub fn iterate_for_value(
         &'a self,
+        batch: &Batch,
         property_name: models::Identifier,
         property_value: &models::Json,
     ) -> impl Iterator<Item = Result<VertexPropertyValueKey>> + 'a {
@@ -560,32 +701,42 @@ impl<'a> VertexPropertyValueManager<'a> {
             util::Component::Identifier(property_name),
             util::Component::Json(property_value),
         ]);
-        let iter = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&prefix, Direction::Forward));
+        let iter = batch.iterator_cf(self.db, self.cf, &prefix);
         self.iterate(iter, prefix)
     }
 
+    pub fn iterate_for_range(
+        &'a self,
+        batch: &Batch,
+        property_name: models::Identifier,
+        range: &models::JsonRange,
+    ) -> impl Iterator<Item = Result<VertexPropertyValueKey>> + 'a {
+        let prefix = util::build(&[util::Component::Identifier(property_name)]);
+        let low_key = json_range_key(property_name, range.start.as_ref());
+        let iter = batch.iterator_cf(self.db, self.cf, &low_key);
+        self.iterate(take_for_json_range(iter, property_name, range), prefix)
+    }
+
     pub fn set(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         vertex_id: Uuid,
         property_name: models::Identifier,
         property_value: &models::Json,
     ) {
         let key = self.key(property_name, property_value, vertex_id);
-        batch.put_cf(&self.cf, key, []);
+        batch.put_cf(self.cf, key, []);
     }
 
     pub fn delete(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         vertex_id: Uuid,
         property_name: models::Identifier,
         property_value: &models::Json,
     ) {
         let key = self.key(property_name, property_value, vertex_id);
-        batch.delete_cf(&self.cf, key);
+        batch.delete_cf(self.cf, key);
     }
 
     pub fn compact(&self) {
@@ -603,7 +754,7 @@ impl<'a> EdgePropertyValueManager<'a> {
     pub fn new(db: &'a DB) -> Self {
         EdgePropertyValueManager {
             db,
-            cf: db.cf_handle("edge_property_values:v2").unwrap(),
+            cf: db.cf_handle("edge_property_values:v3").unwrap(),
         }
     }
 
@@ -619,7 +770,7 @@ impl<'a> EdgePropertyValueManager<'a> {
 
     fn iterate(
         &'a self,
-        iterator: DBIterator<'a>,
+        iterator: impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a,
         prefix: Vec<u8>,
     ) -> impl Iterator<Item = Result<EdgePropertyValueKey>> + 'a {
         let filtered = take_with_prefix(iterator, prefix);
@@ -628,27 +779,27 @@ impl<'a> EdgePropertyValueManager<'a> {
             let (k, _) = item?;
             let mut cursor = Cursor::new(k);
             let name = util::read_identifier(&mut cursor);
-            let value_hash = util::read_u64(&mut cursor);
+            let value = util::read_json(&mut cursor);
             let out_id = util::read_uuid(&mut cursor);
             let t = util::read_identifier(&mut cursor);
             let in_id = util::read_uuid(&mut cursor);
-            Ok((name, value_hash, models::Edge::new(out_id, t, in_id)))
+            Ok((name, value, models::Edge::new(out_id, t, in_id)))
         })
     }
 
     pub fn iterate_for_name(
         &'a self,
+        batch: &Batch,
         property_name: models::Identifier,
     ) -> impl Iterator<Item = Result<EdgePropertyValueKey>> + 'a {
         let prefix = util::build(&[util::Component::Identifier(property_name)]);
-        let iter = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&prefix, Direction::Forward));
+        let iter = batch.iterator_cf(self.db, self.cf, &prefix);
         self.iterate(iter, prefix)
     }
 
     pub fn iterate_for_value(
         &'a self,
+        batch: &Batch,
         property_name: models::Identifier,
         property_value: &models::Json,
     ) -> impl Iterator<Item = Result<EdgePropertyValueKey>> + 'a {
@@ -656,32 +807,42 @@ impl<'a> EdgePropertyValueManager<'a> {
             util::Component::Identifier(property_name),
             util::Component::Json(property_value),
         ]);
-        let iter = self
-            .db
-            .iterator_cf(&self.cf, IteratorMode::From(&prefix, Direction::Forward));
+        let iter = batch.iterator_cf(self.db, self.cf, &prefix);
         self.iterate(iter, prefix)
     }
 
+    pub fn iterate_for_range(
+        &'a self,
+        batch: &Batch,
+        property_name: models::Identifier,
+        range: &models::JsonRange,
+    ) -> impl Iterator<Item = Result<EdgePropertyValueKey>> + 'a {
+        let prefix = util::build(&[util::Component::Identifier(property_name)]);
+        let low_key = json_range_key(property_name, range.start.as_ref());
+        let iter = batch.iterator_cf(self.db, self.cf, &low_key);
+        self.iterate(take_for_json_range(iter, property_name, range), prefix)
+    }
+
     pub fn set(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         edge: &models::Edge,
         property_name: models::Identifier,
         property_value: &models::Json,
     ) {
         let key = self.key(property_name, property_value, edge);
-        batch.put_cf(&self.cf, key, []);
+        batch.put_cf(self.cf, key, []);
     }
 
     pub fn delete(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         edge: &models::Edge,
         property_name: models::Identifier,
         property_value: &models::Json,
     ) {
         let key = self.key(property_name, property_value, edge);
-        batch.delete_cf(&self.cf, key);
+        batch.delete_cf(self.cf, key);
     }
 
     pub fn compact(&self) {
@@ -690,6 +851,311 @@ impl<'a> EdgePropertyValueManager<'a> {
     }
 }
 
+pub(crate) struct VertexCompositePropertyValueManager<'a> {
+    db: &'a DB,
+    cf: ColumnFamilyRef<'a>,
+}
+
+impl<'a> VertexCompositePropertyValueManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        VertexCompositePropertyValueManager {
+            db,
+            cf: db.cf_handle("vertex_composite_property_values:v2").unwrap(),
+        }
+    }
+
+    // Keys start with the index's property names, encoded as a JSON array so
+    // that indexes can't be confused with one another, followed by each
+    // property value. Since encoded JSON values are prefix-free, the key for
+    // a prefix of the values is a prefix of the full key.
+    fn prefix(&self, property_names: &[models::Identifier], property_values: &[models::Json]) -> Vec<u8> {
+        let names = models::Json::new(serde_json::Value::Array(
+            property_names
+                .iter()
+                .map(|name| serde_json::Value::String(name.to_string()))
+                .collect(),
+        ));
+        let mut components = vec![util::Component::Json(&names)];
+        components.extend(property_values.iter().map(util::Component::Json));
+        util::build(&components)
+    }
+
+    fn key(&self, property_names: &[models::Identifier], property_values: &[models::Json], vertex_id: Uuid) -> Vec<u8> {
+        let mut key = self.prefix(property_names, property_values);
+        key.extend_from_slice(vertex_id.as_bytes());
+        key
+    }
+
+    // Gets a vertex's values for the index's properties, or `None` if the
+    // vertex doesn't have all of them. `changed` overrides the stored values
+    // of properties that have been written to the batch, but might not be
+    // readable through it yet.
+    fn values(
+        &self,
+        batch: &Batch,
+        property_names: &[models::Identifier],
+        vertex_id: Uuid,
+        changed: &[(models::Identifier, &models::Json)],
+    ) -> Result<Option<Vec<models::Json>>> {
+        let vertex_property_manager = VertexPropertyManager::new(self.db);
+        let mut values = Vec::with_capacity(property_names.len());
+        for name in property_names {
+            let value = match changed.iter().find(|(changed_name, _)| changed_name == name) {
+                Some((_, changed_value)) => Some((*changed_value).clone()),
+                None => vertex_property_manager.get(batch, vertex_id, *name)?,
+            };
+            match value {
+                Some(value) => values.push(value),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(values))
+    }
+
+    pub fn iterate_for_prefix(
+        &'a self,
+        batch: &Batch,
+        property_names: &[models::Identifier],
+        property_values: &[models::Json],
+    ) -> impl Iterator<Item = Result<Uuid>> + 'a {
+        let prefix = self.prefix(property_names, property_values);
+        let iter = batch.iterator_cf(self.db, self.cf, &prefix);
+        take_with_prefix(iter, prefix).map(|item| -> Result<Uuid> {
+            let (k, _) = item?;
+            let mut cursor = Cursor::new(&k[k.len() - 16..]);
+            Ok(util::read_uuid(&mut cursor))
+        })
+    }
+
+    /// Adds a vertex to the composite indexes that include any of the
+    /// changed properties, or all composite indexes if `changed` is empty.
+    pub fn set(
+        &self,
+        batch: &mut Batch,
+        composite_indexes: &HashSet<Vec<models::Identifier>>,
+        vertex_id: Uuid,
+        changed: &[(models::Identifier, &models::Json)],
+    ) -> Result<()> {
+        for property_names in composite_indexes {
+            if !changed.is_empty() && !changed.iter().any(|(name, _)| property_names.contains(name)) {
+                continue;
+            }
+            if let Some(values) = self.values(batch, property_names, vertex_id, changed)? {
+                batch.put_cf(self.cf, self.key(property_names, &values, vertex_id), []);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a vertex from the composite indexes that include `name`, or
+    /// all composite indexes if `name` is `None`.
+    pub fn delete(
+        &self,
+        batch: &mut Batch,
+        composite_indexes: &HashSet<Vec<models::Identifier>>,
+        vertex_id: Uuid,
+        name: Option<models::Identifier>,
+    ) -> Result<()> {
+        for property_names in composite_indexes {
+            if matches!(name, Some(name) if !property_names.contains(&name)) {
+                continue;
+            }
+            if let Some(values) = self.values(batch, property_names, vertex_id, &[])? {
+                batch.delete_cf(self.cf, self.key(property_names, &values, vertex_id));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn compact(&self) {
+        self.db
+            .compact_range_cf(&self.cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+    }
+}
+
+// Takes entries from an iterator over an expiry index, which is keyed by
+// timestamp first, up to and including those that expire at `now`.
+fn take_until<'a>(
+    iterator: impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a,
+    now: SystemTime,
+) -> impl Iterator<Item = StdResult<KeyValue, rocksdb::Error>> + 'a {
+    let end = util::build(&[util::Component::Timestamp(now)]);
+    iterator.take_while(move |item| -> bool {
+        if let Ok((ref k, _)) = *item {
+            k[..end.len()] <= end[..]
+        } else {
+            true
+        }
+    })
+}
+
+pub(crate) struct VertexExpiryManager<'a> {
+    db: &'a DB,
+    cf: ColumnFamilyRef<'a>,
+    // Indexes vertices by when they expire, so that expired vertices can be
+    // found without a full scan.
+    time_cf: ColumnFamilyRef<'a>,
+}
+
+impl<'a> VertexExpiryManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        VertexExpiryManager {
+            db,
+            cf: db.cf_handle("vertex_expiries:v2").unwrap(),
+            time_cf: db.cf_handle("vertices_by_expiry:v2").unwrap(),
+        }
+    }
+
+    fn key(&self, id: Uuid) -> Vec<u8> {
+        util::build(&[util::Component::Uuid(id)])
+    }
+
+    fn time_key(&self, expires_at: SystemTime, id: Uuid) -> Vec<u8> {
+        util::build(&[util::Component::Timestamp(expires_at), util::Component::Uuid(id)])
+    }
+
+    pub fn get(&self, batch: &Batch, id: Uuid) -> Result<Option<SystemTime>> {
+        match batch.get_cf(self.db, self.cf, self.key(id))? {
+            Some(value_bytes) => {
+                let mut cursor = Cursor::new(value_bytes.deref());
+                Ok(Some(util::read_timestamp(&mut cursor)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn iterate_until(&'a self, batch: &Batch, now: SystemTime) -> impl Iterator<Item = Result<Uuid>> + 'a {
+        let iter = batch.iterator_cf(self.db, self.time_cf, &[]);
+        take_until(iter, now).map(|item| -> Result<Uuid> {
+            let (k, _) = item?;
+            let mut cursor = Cursor::new(k);
+            util::read_timestamp(&mut cursor);
+            Ok(util::read_uuid(&mut cursor))
+        })
+    }
+
+    pub fn set(&self, batch: &mut Batch, id: Uuid, expires_at: SystemTime) -> Result<()> {
+        self.delete(batch, id)?;
+        batch.put_cf(
+            self.cf,
+            self.key(id),
+            util::build(&[util::Component::Timestamp(expires_at)]),
+        );
+        batch.put_cf(self.time_cf, self.time_key(expires_at, id), []);
+        Ok(())
+    }
+
+    pub fn delete(&self, batch: &mut Batch, id: Uuid) -> Result<()> {
+        if let Some(expires_at) = self.get(batch, id)? {
+            batch.delete_cf(self.cf, self.key(id));
+            batch.delete_cf(self.time_cf, self.time_key(expires_at, id));
+        }
+        Ok(())
+    }
+
+    pub fn compact(&self) {
+        self.db
+            .compact_range_cf(&self.cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+        self.db
+            .compact_range_cf(&self.time_cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+    }
+}
+
+pub(crate) struct EdgeExpiryManager<'a> {
+    db: &'a DB,
+    cf: ColumnFamilyRef<'a>,
+    // Indexes edges by when they expire, so that expired edges can be found
+    // without a full scan.
+    time_cf: ColumnFamilyRef<'a>,
+}
+
+impl<'a> EdgeExpiryManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        EdgeExpiryManager {
+            db,
+            cf: db.cf_handle("edge_expiries:v2").unwrap(),
+            time_cf: db.cf_handle("edges_by_expiry:v2").unwrap(),
+        }
+    }
+
+    fn key(&self, edge: &models::Edge) -> Vec<u8> {
+        util::build(&[
+            util::Component::Uuid(edge.outbound_id),
+            util::Component::Identifier(edge.t),
+            util::Component::Uuid(edge.inbound_id),
+        ])
+    }
+
+    fn time_key(&self, expires_at: SystemTime, edge: &models::Edge) -> Vec<u8> {
+        util::build(&[
+            util::Component::Timestamp(expires_at),
+            util::Component::Uuid(edge.outbound_id),
+            util::Component::Identifier(edge.t),
+            util::Component::Uuid(edge.inbound_id),
+        ])
+    }
+
+    pub fn get(&self, batch: &Batch, edge: &models::Edge) -> Result<Option<SystemTime>> {
+        match batch.get_cf(self.db, self.cf, self.key(edge))? {
+            Some(value_bytes) => {
+                let mut cursor = Cursor::new(value_bytes.deref());
+                Ok(Some(util::read_timestamp(&mut cursor)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn iterate_until(&'a self, batch: &Batch, now: SystemTime) -> impl Iterator<Item = Result<models::Edge>> + 'a {
+        let iter = batch.iterator_cf(self.db, self.time_cf, &[]);
+        take_until(iter, now).map(|item| -> Result<models::Edge> {
+            let (k, _) = item?;
+            let mut cursor = Cursor::new(k);
+            util::read_timestamp(&mut cursor);
+            let outbound_id = util::read_uuid(&mut cursor);
+            let t = util::read_identifier(&mut cursor);
+            let inbound_id = util::read_uuid(&mut cursor);
+            Ok(models::Edge::new(outbound_id, t, inbound_id))
+        })
+    }
+
+    pub fn set(&self, batch: &mut Batch, edge: &models::Edge, expires_at: SystemTime) -> Result<()> {
+        self.delete(batch, edge)?;
+        batch.put_cf(
+            self.cf,
+            self.key(edge),
+            util::build(&[util::Component::Timestamp(expires_at)]),
+        );
+        batch.put_cf(self.time_cf, self.time_key(expires_at, edge), []);
+        Ok(())
+    }
+
+    pub fn delete(&self, batch: &mut Batch, edge: &models::Edge) -> Result<()> {
+        if let Some(expires_at) = self.get(batch, edge)? {
+            batch.delete_cf(self.cf, self.key(edge));
+            batch.delete_cf(self.time_cf, self.time_key(expires_at, edge));
+        }
+        Ok(())
+    }
+
+    pub fn compact(&self) {
+        self.db
+            .compact_range_cf(&self.cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+        self.db
+            .compact_range_cf(&self.time_cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+    }
+}
+
+/// The indexes that have been enabled on a datastore.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Indexes {
+    /// Properties with single-property indexes.
+    pub properties: HashSet<models::Identifier>,
+    /// The subset of `properties` whose indexes are unique.
+    pub unique_properties: HashSet<models::Identifier>,
+    /// The ordered property names of composite indexes.
+    pub composites: HashSet<Vec<models::Identifier>>,
+}
+
 pub(crate) struct MetadataManager<'a> {
     db: &'a DB,
     cf: ColumnFamilyRef<'a>,
@@ -704,15 +1170,99 @@ impl<'a> MetadataManager<'a> {
     }
 
     pub fn get_indexed_properties(&self) -> Result<HashSet<models::Identifier>> {
-        match self.db.get_cf(&self.cf, "indexed_properties")? {
+        match self.db.get_cf(self.cf, "indexed_properties")? {
+            Some(value_bytes) => Ok(bincode::deserialize(&value_bytes)?),
+            None => Ok(HashSet::default()),
+        }
+    }
+
+    pub fn get_unique_properties(&self) -> Result<HashSet<models::Identifier>> {
+        match self.db.get_cf(self.cf, "unique_properties")? {
+            Some(value_bytes) => Ok(bincode::deserialize(&value_bytes)?),
+            None => Ok(HashSet::default()),
+        }
+    }
+
+    pub fn get_composite_indexes(&self) -> Result<HashSet<Vec<models::Identifier>>> {
+        match self.db.get_cf(self.cf, "composite_indexes")? {
             Some(value_bytes) => Ok(bincode::deserialize(&value_bytes)?),
             None => Ok(HashSet::default()),
         }
     }
 
-    pub fn set_indexed_properties(&self, batch: &mut WriteBatch, indices: &HashSet<models::Identifier>) -> Result<()> {
+    // Databases without a version predate versioning, so are version 0.
+    pub fn get_index_version(&self) -> Result<u32> {
+        match self.db.get_cf(self.cf, "index_version")? {
+            Some(value_bytes) => Ok(bincode::deserialize(&value_bytes)?),
+            None => Ok(0),
+        }
+    }
+
+    pub fn set_index_version(&self, batch: &mut Batch, version: u32) -> Result<()> {
+        let value_bytes = bincode::serialize(&version)?;
+        batch.put_cf(self.cf, "index_version", &value_bytes);
+        Ok(())
+    }
+
+    pub fn get_indexes(&self) -> Result<Indexes> {
+        Ok(Indexes {
+            properties: self.get_indexed_properties()?,
+            unique_properties: self.get_unique_properties()?,
+            composites: self.get_composite_indexes()?,
+        })
+    }
+
+    pub fn set_composite_indexes(&self, batch: &mut Batch, indices: &HashSet<Vec<models::Identifier>>) -> Result<()> {
+        let value_bytes = bincode::serialize(&indices)?;
+        batch.put_cf(self.cf, "composite_indexes", &value_bytes);
+        Ok(())
+    }
+
+    pub fn set_indexed_properties(&self, batch: &mut Batch, indices: &HashSet<models::Identifier>) -> Result<()> {
         let value_bytes = bincode::serialize(&indices)?;
-        batch.put_cf(&self.cf, "indexed_properties", &value_bytes);
+        batch.put_cf(self.cf, "indexed_properties", &value_bytes);
+        Ok(())
+    }
+
+    pub fn set_unique_properties(&self, batch: &mut Batch, properties: &HashSet<models::Identifier>) -> Result<()> {
+        let value_bytes = bincode::serialize(&properties)?;
+        batch.put_cf(self.cf, "unique_properties", &value_bytes);
+        Ok(())
+    }
+
+    fn schema_key(&self, kind: models::SchemaKind, t: models::Identifier) -> String {
+        match kind {
+            models::SchemaKind::Vertex => format!("vertex_schemas:{}", t.as_str()),
+            models::SchemaKind::Edge => format!("edge_schemas:{}", t.as_str()),
+        }
+    }
+
+    // Unlike indexes, schemas are read through the batch rather than cached,
+    // so that buffered transactions see their own schema changes.
+    pub fn get_schema(
+        &self,
+        batch: &Batch,
+        kind: models::SchemaKind,
+        t: models::Identifier,
+    ) -> Result<Option<models::Schema>> {
+        match batch.get_cf(self.db, self.cf, self.schema_key(kind, t))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_schema(
+        &self,
+        batch: &mut Batch,
+        kind: models::SchemaKind,
+        t: models::Identifier,
+        schema: Option<&models::Schema>,
+    ) -> Result<()> {
+        let key = self.schema_key(kind, t);
+        match schema {
+            Some(schema) => batch.put_cf(self.cf, key, bincode::serialize(schema)?),
+            None => batch.delete_cf(self.cf, key),
+        }
         Ok(())
     }
 
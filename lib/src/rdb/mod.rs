@@ -1,5 +1,6 @@
 //! The rocksdb datastore implementation.
 
+mod batch;
 mod datastore;
 mod managers;
 
@@ -73,4 +74,118 @@ mod tests {
             assert_eq!(edge.outbound_id, out_v.id);
         }
     }
+
+    #[test]
+    fn should_open_baseline_database() {
+        use crate::util::{self, Component};
+        use crate::{ijson, Edge, Identifier, RangeEdgeQuery, RangeVertexQuery, Vertex, VertexWithPropertyValueQuery};
+        use rocksdb::{Options, DB};
+        use std::collections::HashSet;
+        use uuid::Uuid;
+
+        const BASELINE_CF_NAMES: [&str; 8] = [
+            "vertices:v2",
+            "edge_ranges:v2",
+            "reversed_edge_ranges:v2",
+            "vertex_properties:v2",
+            "edge_properties:v2",
+            "vertex_property_values:v2",
+            "edge_property_values:v2",
+            "metadata:v2",
+        ];
+
+        let path = tempdir().unwrap().into_path();
+        let id = Uuid::from_u128(1);
+        let t = Identifier::new("person").unwrap();
+        let name = Identifier::new("name").unwrap();
+        let edge_t = Identifier::new("likes").unwrap();
+
+        {
+            let mut opts = Options::default();
+            opts.create_if_missing(true);
+            opts.create_missing_column_families(true);
+            let db = DB::open_cf(&opts, &path, BASELINE_CF_NAMES).unwrap();
+            let vertices_cf = db.cf_handle("vertices:v2").unwrap();
+            db.put_cf(vertices_cf, id.as_bytes(), util::build(&[Component::Identifier(t)]))
+                .unwrap();
+            let edge_key = util::build(&[Component::Uuid(id), Component::Identifier(edge_t), Component::Uuid(id)]);
+            for cf_name in ["edge_ranges:v2", "reversed_edge_ranges:v2"] {
+                db.put_cf(db.cf_handle(cf_name).unwrap(), &edge_key, []).unwrap();
+            }
+            let properties_cf = db.cf_handle("vertex_properties:v2").unwrap();
+            let property_key = util::build(&[Component::Uuid(id), Component::FixedLengthString("name")]);
+            db.put_cf(properties_cf, property_key, b"\"alice\"").unwrap();
+            // Property values used to be keyed by an 8 byte hash of the value
+            let property_values_cf = db.cf_handle("vertex_property_values:v2").unwrap();
+            let property_value_key = [
+                util::build(&[Component::Identifier(name)]),
+                vec![0; 8],
+                id.as_bytes().to_vec(),
+            ];
+            db.put_cf(property_values_cf, property_value_key.concat(), []).unwrap();
+            let metadata_cf = db.cf_handle("metadata:v2").unwrap();
+            let indexed_properties = bincode::serialize(&HashSet::from([name])).unwrap();
+            db.put_cf(metadata_cf, "indexed_properties", indexed_properties)
+                .unwrap();
+        }
+
+        for _ in 0..2 {
+            let db = super::RocksdbDatastore::new_db(&path).unwrap();
+            let q = VertexWithPropertyValueQuery::new(name, ijson!("alice"));
+            let vertices = util::extract_vertices(db.get(q).unwrap()).unwrap();
+            assert_eq!(vertices, vec![Vertex::with_id(id, t)]);
+            let vertices = util::extract_vertices(db.get(RangeVertexQuery::new().t(t)).unwrap()).unwrap();
+            assert_eq!(vertices, vec![Vertex::with_id(id, t)]);
+            let edges = util::extract_edges(db.get(RangeEdgeQuery::new().t(edge_t)).unwrap()).unwrap();
+            assert_eq!(edges, vec![Edge::new(id, edge_t, id)]);
+        }
+    }
+
+    #[test]
+    fn should_not_observe_writes_after_snapshot() {
+        use crate::{Datastore, Transaction};
+
+        let path = tempdir().unwrap().into_path();
+        let db: crate::Database<crate::RocksdbDatastore> = crate::RocksdbDatastore::new_db(&path).unwrap();
+        let t = crate::Identifier::new("test_vertex_type").unwrap();
+        db.create_vertex_from_type(t).unwrap();
+
+        let mut txn = db.datastore.transaction();
+        txn.snapshot().unwrap();
+        db.create_vertex_from_type(t).unwrap();
+        assert_eq!(txn.vertex_count(), 1);
+        assert_eq!(txn.all_vertices().unwrap().count(), 1);
+
+        let txn = db.datastore.transaction();
+        assert_eq!(txn.vertex_count(), 2);
+    }
+
+    #[test]
+    fn should_not_write_concurrently_with_buffered_transaction() {
+        use std::thread;
+        use std::time::Duration;
+
+        let path = tempdir().unwrap().into_path();
+        let db: crate::Database<crate::RocksdbDatastore> = crate::RocksdbDatastore::new_db(&path).unwrap();
+        let name = crate::Identifier::new("foo").unwrap();
+        let id = db
+            .create_vertex_from_type(crate::Identifier::new("test_vertex_type").unwrap())
+            .unwrap();
+
+        // The write has to wait for the transaction, or it would be missed by
+        // the index the transaction builds.
+        let mut txn = db.begin().unwrap();
+        txn.index_property(name).unwrap();
+        thread::scope(|s| {
+            let writer = s.spawn(|| db.set_properties(crate::SpecificVertexQuery::single(id), name, &crate::ijson!(1)));
+            thread::sleep(Duration::from_millis(50));
+            txn.commit().unwrap();
+            writer.join().unwrap().unwrap();
+        });
+
+        let q = crate::VertexWithPropertyValueQuery::new(name, crate::ijson!(1));
+        let vertices = crate::util::extract_vertices(db.get(q).unwrap()).unwrap();
+        assert_eq!(vertices.len(), 1);
+        assert_eq!(vertices[0].id, id);
+    }
 }
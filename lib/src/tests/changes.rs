@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use super::util;
+use crate::{
+    ijson, AllVertexQuery, BulkInsertItem, ChangeEvent, CountQueryExt, Database, Datastore, Edge, Error, Identifier,
+    QueryExt, RingBufferSink, Schema, SchemaKind, SpecificEdgeQuery, SpecificVertexQuery, Vertex,
+};
+
+fn events(sink: &RingBufferSink, seq: u64) -> Vec<ChangeEvent> {
+    let records = sink.read_from(seq);
+    for (i, record) in records.iter().enumerate() {
+        assert_eq!(record.seq, seq + i as u64);
+    }
+    records.into_iter().map(|record| record.event).collect()
+}
+
+pub fn should_publish_changes<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let sink = Arc::new(RingBufferSink::new(100));
+    db.add_change_sink(sink.clone());
+
+    let t = Identifier::new("test_vertex_type")?;
+    let outbound = Vertex::new(t);
+    let inbound = Vertex::new(t);
+    assert!(db.create_vertex(&outbound)?);
+    assert!(!db.create_vertex(&outbound)?);
+    db.create_vertex(&inbound)?;
+    let edge = Edge::new(outbound.id, Identifier::new("test_edge_type")?, inbound.id);
+    db.create_edge(&edge)?;
+    let name = Identifier::new("foo")?;
+    db.set_properties(SpecificVertexQuery::single(outbound.id), name, &ijson!(1))?;
+    db.set_properties(SpecificEdgeQuery::single(edge.clone()), name, &ijson!(2))?;
+    db.delete(SpecificEdgeQuery::single(edge.clone()).properties()?)?;
+    db.delete(SpecificVertexQuery::single(outbound.id).properties()?)?;
+    db.delete(SpecificEdgeQuery::single(edge.clone()))?;
+    db.delete(SpecificVertexQuery::single(inbound.id))?;
+
+    assert_eq!(
+        events(&sink, 0),
+        vec![
+            ChangeEvent::VertexCreated(outbound.clone()),
+            ChangeEvent::VertexCreated(inbound.clone()),
+            ChangeEvent::EdgeCreated(edge.clone()),
+            ChangeEvent::VertexPropertySet(outbound.id, name, ijson!(1)),
+            ChangeEvent::EdgePropertySet(edge.clone(), name, ijson!(2)),
+            ChangeEvent::EdgePropertyDeleted(edge.clone(), name),
+            ChangeEvent::VertexPropertyDeleted(outbound.id, name),
+            ChangeEvent::EdgeDeleted(edge),
+            ChangeEvent::VertexDeleted(inbound),
+        ]
+    );
+
+    // Failed writes aren't published
+    assert!(db.set_properties(AllVertexQuery.count()?, name, &ijson!(3)).is_err());
+    assert!(events(&sink, 9).is_empty());
+    Ok(())
+}
+
+pub fn should_publish_bulk_insert_changes<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let sink = Arc::new(RingBufferSink::new(100));
+    db.add_change_sink(sink.clone());
+
+    let t = Identifier::new("test_vertex_type")?;
+    let outbound = Vertex::new(t);
+    let inbound = Vertex::new(t);
+    let edge = Edge::new(outbound.id, Identifier::new("test_edge_type")?, inbound.id);
+    let name = Identifier::new("foo")?;
+    let items = vec![
+        BulkInsertItem::Vertex(outbound.clone()),
+        BulkInsertItem::Vertex(inbound.clone()),
+        BulkInsertItem::Edge(edge.clone()),
+        BulkInsertItem::VertexProperty(outbound.id, name, ijson!(true)),
+        BulkInsertItem::EdgeProperty(edge.clone(), name, ijson!(false)),
+    ];
+    db.bulk_insert(items.clone())?;
+    db.index_property(name)?;
+
+    let mut expected: Vec<ChangeEvent> = items.iter().map(ChangeEvent::from).collect();
+    expected.push(ChangeEvent::PropertyIndexed(name));
+    assert_eq!(events(&sink, 0), expected);
+
+    // Vertices and edges that already exist aren't created again
+    db.bulk_insert(items.clone())?;
+    assert_eq!(
+        events(&sink, expected.len() as u64),
+        items[3..].iter().map(ChangeEvent::from).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub fn should_publish_cascaded_delete_changes<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let sink = Arc::new(RingBufferSink::new(100));
+
+    let t = Identifier::new("test_vertex_type")?;
+    let deleted = Vertex::new(t);
+    let other = Vertex::new(t);
+    db.create_vertex(&deleted)?;
+    db.create_vertex(&other)?;
+    let edge_t = Identifier::new("test_edge_type")?;
+    let outbound_edge = Edge::new(deleted.id, edge_t, other.id);
+    let inbound_edge = Edge::new(other.id, edge_t, deleted.id);
+    db.create_edge(&outbound_edge)?;
+    db.create_edge(&inbound_edge)?;
+    let name = Identifier::new("foo")?;
+    db.set_properties(SpecificVertexQuery::single(deleted.id), name, &ijson!(1))?;
+    db.set_properties(SpecificEdgeQuery::single(inbound_edge.clone()), name, &ijson!(2))?;
+
+    db.add_change_sink(sink.clone());
+    db.delete(SpecificVertexQuery::single(deleted.id))?;
+    assert_eq!(
+        events(&sink, 0),
+        vec![
+            ChangeEvent::EdgeDeleted(outbound_edge),
+            ChangeEvent::EdgePropertyDeleted(inbound_edge.clone(), name),
+            ChangeEvent::EdgeDeleted(inbound_edge),
+            ChangeEvent::VertexPropertyDeleted(deleted.id, name),
+            ChangeEvent::VertexDeleted(deleted),
+        ]
+    );
+    Ok(())
+}
+
+pub fn should_publish_schema_changes<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let sink = Arc::new(RingBufferSink::new(100));
+    db.add_change_sink(sink.clone());
+
+    let t = Identifier::new("test_vertex_type")?;
+    match db.set_schema(SchemaKind::Vertex, t, Schema::new()) {
+        Ok(()) => {}
+        Err(Error::Unsupported) => {
+            assert!(events(&sink, 0).is_empty());
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    }
+    db.delete_schema(SchemaKind::Vertex, t)?;
+    assert_eq!(
+        events(&sink, 0),
+        vec![
+            ChangeEvent::SchemaChanged(SchemaKind::Vertex, t),
+            ChangeEvent::SchemaChanged(SchemaKind::Vertex, t),
+        ]
+    );
+    Ok(())
+}
+
+pub fn should_publish_transaction_changes_on_commit<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let mut txn = match db.begin() {
+        Ok(txn) => txn,
+        Err(Error::Unsupported) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let sink = Arc::new(RingBufferSink::new(100));
+    db.add_change_sink(sink.clone());
+
+    let t = Identifier::new("test_vertex_type")?;
+    let committed = Vertex::new(t);
+    txn.create_vertex(&committed)?;
+    assert!(events(&sink, 0).is_empty());
+    txn.commit()?;
+    assert_eq!(events(&sink, 0), vec![ChangeEvent::VertexCreated(committed.clone())]);
+
+    let mut txn = db.begin()?;
+    txn.create_vertex(&Vertex::new(t))?;
+    txn.rollback()?;
+    let mut txn = db.begin()?;
+    txn.create_vertex(&Vertex::new(t))?;
+    drop(txn);
+    assert_eq!(events(&sink, 0).len(), 1);
+    assert_eq!(util::get_vertices(db, AllVertexQuery)?, vec![committed]);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use crate::util::{extract_count, extract_edges, extract_vertices};
+use crate::{
+    errors, expect_err, CountQueryExt, Database, Datastore, DedupQuery, Edge, Error, Identifier, QueryExt,
+    SpecificVertexQuery, Vertex,
+};
+
+use uuid::Uuid;
+
+// Creates the vertices 1 through 3, connected by edges 1->2, 1->3 and 2->3.
+fn create_graph<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    for id in 1..=3 {
+        db.create_vertex(&Vertex::with_id(Uuid::from_u128(id), vertex_t))?;
+    }
+    let edge_t = Identifier::new("test_edge_type")?;
+    for (outbound_id, inbound_id) in [(1, 2), (1, 3), (2, 3)] {
+        db.create_edge(&Edge::new(
+            Uuid::from_u128(outbound_id),
+            edge_t,
+            Uuid::from_u128(inbound_id),
+        ))?;
+    }
+    Ok(())
+}
+
+fn vertices(ids: &[u128]) -> SpecificVertexQuery {
+    SpecificVertexQuery::new(ids.iter().map(|id| Uuid::from_u128(*id)).collect())
+}
+
+pub fn should_dedup_vertices<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+
+    // 3 is reached through both 1 and 2
+    let q = vertices(&[1, 2]).outbound()?.inbound()?;
+    let ids: Vec<u128> = extract_vertices(db.get(q.clone())?)
+        .unwrap()
+        .iter()
+        .map(|v| v.id.as_u128())
+        .collect();
+    assert_eq!(ids, vec![2, 3, 3]);
+
+    let q = q.dedup()?;
+    let ids: Vec<u128> = extract_vertices(db.get(q.clone())?)
+        .unwrap()
+        .iter()
+        .map(|v| v.id.as_u128())
+        .collect();
+    assert_eq!(ids, vec![2, 3]);
+    assert_eq!(extract_count(db.get(q.count()?)?), Some(2));
+
+    // The first of each vertex is kept
+    let ids: Vec<u128> = extract_vertices(db.get(vertices(&[3, 1, 3, 2, 1]).dedup()?)?)
+        .unwrap()
+        .iter()
+        .map(|v| v.id.as_u128())
+        .collect();
+    assert_eq!(ids, vec![3, 1, 2]);
+    Ok(())
+}
+
+pub fn should_dedup_edges<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let q = vertices(&[1, 2, 1]).outbound()?;
+    assert_eq!(extract_edges(db.get(q.clone())?).unwrap().len(), 5);
+
+    let edges = extract_edges(db.get(q.dedup()?)?).unwrap();
+    let ids: Vec<(u128, u128)> = edges
+        .iter()
+        .map(|e| (e.outbound_id.as_u128(), e.inbound_id.as_u128()))
+        .collect();
+    assert_eq!(ids, vec![(1, 2), (1, 3), (2, 3)]);
+    Ok(())
+}
+
+pub fn should_not_dedup_properties<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let result = DedupQuery::new(Box::new(vertices(&[1]).properties()?.into()));
+    expect_err!(result, errors::ValidationError::InnerQuery);
+    Ok(())
+}
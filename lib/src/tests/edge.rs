@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use super::util;
 use crate::{
     ijson, models, AllEdgeQuery, Database, Datastore, Edge, EdgeDirection, EdgeWithPropertyValueQuery, Error,
-    Identifier, QueryExt, SpecificEdgeQuery, SpecificVertexQuery,
+    Identifier, QueryExt, RangeEdgeQuery, SpecificEdgeQuery, SpecificVertexQuery,
 };
 
 use uuid::Uuid;
@@ -221,6 +221,51 @@ pub fn should_get_edges_piped<D: Datastore>(db: &Database<D>) -> Result<(), Erro
     Ok(())
 }
 
+pub fn should_get_range_edges<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let (outbound_id, _) = util::create_edges(db)?;
+    let mut edges = util::get_edges(db, AllEdgeQuery)?;
+    edges.sort();
+
+    let range = util::get_edges(db, RangeEdgeQuery::new())?;
+    assert_eq!(range, edges);
+
+    let range = util::get_edges(db, RangeEdgeQuery::new().start_edge(edges[2].clone()).limit(2))?;
+    assert_eq!(range, edges[2..4].to_vec());
+    check_edge_range(&range, outbound_id, 2)?;
+    Ok(())
+}
+
+pub fn should_get_range_edges_with_type_filter<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let (outbound_id, inbound_ids) = util::create_edges(db)?;
+    let other_t = models::Identifier::new("other_edge_type")?;
+    let other_edge = Edge::new(outbound_id, other_t, inbound_ids[0]);
+    db.create_edge(&other_edge)?;
+
+    let t = models::Identifier::new("test_edge_type")?;
+    let mut typed_edges = util::get_edges(db, RangeEdgeQuery::new().t(t))?;
+    check_edge_range(&typed_edges, outbound_id, 5)?;
+    typed_edges.sort();
+
+    let range = util::get_edges(db, RangeEdgeQuery::new().t(other_t))?;
+    assert_eq!(range, vec![other_edge.clone()]);
+
+    let range = util::get_edges(db, RangeEdgeQuery::new().t(t).start_edge(typed_edges[3].clone()))?;
+    assert_eq!(range, typed_edges[3..].to_vec());
+
+    // The start edge doesn't have to be of the filtered type
+    let start_edge = Edge::new(outbound_id, other_t, typed_edges[4].inbound_id);
+    let range = util::get_edges(db, RangeEdgeQuery::new().t(t).start_edge(start_edge).limit(1))?;
+    assert_eq!(range, vec![typed_edges[0].clone()]);
+    let start_edge = Edge::new(outbound_id, models::Identifier::new("zzz_edge_type")?, Uuid::default());
+    let range = util::get_edges(db, RangeEdgeQuery::new().t(t).start_edge(start_edge))?;
+    assert!(range.is_empty());
+
+    db.delete(SpecificEdgeQuery::single(other_edge))?;
+    let range = util::get_edges(db, RangeEdgeQuery::new().t(other_t))?;
+    assert!(range.is_empty());
+    Ok(())
+}
+
 /// Test for a regression, see
 /// https://github.com/indradb/indradb/issues/278#issuecomment-1515797381
 pub fn should_delete_indexed_edge_with_property_value<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
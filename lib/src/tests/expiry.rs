@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use super::util;
+use crate::{
+    ijson, AllEdgeQuery, AllVertexQuery, BulkInsertItem, CountQueryExt, Database, Datastore, Edge, Error, Identifier,
+    QueryExt, SpecificEdgeQuery, SpecificVertexQuery, Vertex,
+};
+
+const LONG_TTL: Duration = Duration::from_secs(3600);
+
+// Datastores aren't required to support expiry, in which case the tests are
+// skipped.
+fn create_vertex_with_ttl<D: Datastore>(db: &Database<D>, vertex: &Vertex, ttl: Duration) -> Result<bool, Error> {
+    match db.create_vertex_with_ttl(vertex, ttl) {
+        Ok(created) => {
+            assert!(created);
+            Ok(true)
+        }
+        Err(Error::Unsupported) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn get_count<D: Datastore, Q: CountQueryExt>(db: &Database<D>, q: Q) -> Result<u64, Error> {
+    Ok(crate::util::extract_count(db.get(q.count()?)?).unwrap())
+}
+
+pub fn should_hide_expired_items<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let t = Identifier::new("test_vertex_type")?;
+    let expired = Vertex::new(t);
+    if !create_vertex_with_ttl(db, &expired, Duration::ZERO)? {
+        return Ok(());
+    }
+    let expiring = Vertex::new(t);
+    create_vertex_with_ttl(db, &expiring, LONG_TTL)?;
+    let permanent = Vertex::new(t);
+    db.create_vertex(&permanent)?;
+
+    let edge_t = Identifier::new("test_edge_type")?;
+    let to_expired_vertex = Edge::new(permanent.id, edge_t, expired.id);
+    let expired_edge = Edge::new(permanent.id, edge_t, expiring.id);
+    let expiring_edge = Edge::new(expiring.id, edge_t, permanent.id);
+    db.create_edge(&to_expired_vertex)?;
+    assert!(db.create_edge_with_ttl(&expired_edge, Duration::ZERO)?);
+    assert!(db.create_edge_with_ttl(&expiring_edge, LONG_TTL)?);
+
+    let mut vertices = util::get_vertices(db, AllVertexQuery)?;
+    vertices.sort_by_key(|v| v.id);
+    let mut expected = vec![expiring.clone(), permanent.clone()];
+    expected.sort_by_key(|v| v.id);
+    assert_eq!(vertices, expected);
+    assert_eq!(get_count(db, AllVertexQuery)?, 2);
+    assert!(util::get_vertices(db, SpecificVertexQuery::single(expired.id))?.is_empty());
+
+    assert_eq!(util::get_edges(db, AllEdgeQuery)?, vec![expiring_edge.clone()]);
+    assert_eq!(get_count(db, AllEdgeQuery)?, 1);
+    let q = SpecificEdgeQuery::new(vec![to_expired_vertex, expired_edge]);
+    assert!(util::get_edges(db, q)?.is_empty());
+    assert!(util::get_edges(db, SpecificVertexQuery::single(permanent.id).outbound()?)?.is_empty());
+    let q = SpecificVertexQuery::single(permanent.id).inbound()?;
+    assert_eq!(util::get_edges(db, q)?, vec![expiring_edge]);
+    let q = SpecificVertexQuery::single(permanent.id).outbound()?.inbound()?;
+    assert!(util::get_vertices(db, q)?.is_empty());
+    Ok(())
+}
+
+pub fn should_expire_now<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let t = Identifier::new("test_vertex_type")?;
+    let expired = Vertex::new(t);
+    if !create_vertex_with_ttl(db, &expired, Duration::ZERO)? {
+        return Ok(());
+    }
+    let expiring = Vertex::new(t);
+    create_vertex_with_ttl(db, &expiring, LONG_TTL)?;
+    let permanent = Vertex::new(t);
+    db.create_vertex(&permanent)?;
+
+    let edge_t = Identifier::new("test_edge_type")?;
+    let to_expired_vertex = Edge::new(permanent.id, edge_t, expired.id);
+    let expired_edge = Edge::new(permanent.id, edge_t, expiring.id);
+    db.create_edge(&to_expired_vertex)?;
+    db.create_edge_with_ttl(&expired_edge, Duration::ZERO)?;
+    let name = Identifier::new("foo")?;
+    db.set_properties(SpecificVertexQuery::single(permanent.id), name, &ijson!(true))?;
+
+    assert_eq!(db.expire_now()?, 2);
+    assert_eq!(db.expire_now()?, 0);
+
+    // Counts skip expiry filtering once nothing has expired, so these also
+    // check that the cascaded edge is gone.
+    assert_eq!(util::get_vertex_count(db)?, 2);
+    assert_eq!(get_count(db, AllEdgeQuery)?, 0);
+    let props = util::get_all_vertex_properties(db, SpecificVertexQuery::single(permanent.id))?;
+    assert_eq!(props.len(), 1);
+    assert_eq!(props[0].props.len(), 1);
+    Ok(())
+}
+
+pub fn should_bulk_insert_expiring_items<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let t = Identifier::new("test_vertex_type")?;
+    let probe = Vertex::new(t);
+    if !create_vertex_with_ttl(db, &probe, LONG_TTL)? {
+        return Ok(());
+    }
+
+    let expired = Vertex::new(t);
+    let expiring = Vertex::new(t);
+    let edge_t = Identifier::new("test_edge_type")?;
+    let expired_edge = Edge::new(probe.id, edge_t, expiring.id);
+    let expiring_edge = Edge::new(expiring.id, edge_t, probe.id);
+    db.bulk_insert(vec![
+        BulkInsertItem::ExpiringVertex(expired.clone(), Duration::ZERO),
+        BulkInsertItem::ExpiringVertex(expiring.clone(), LONG_TTL),
+        BulkInsertItem::ExpiringEdge(expired_edge, Duration::ZERO),
+        BulkInsertItem::ExpiringEdge(expiring_edge.clone(), LONG_TTL),
+    ])?;
+
+    assert!(util::get_vertices(db, SpecificVertexQuery::single(expired.id))?.is_empty());
+    assert_eq!(
+        util::get_vertices(db, SpecificVertexQuery::single(expiring.id))?,
+        vec![expiring]
+    );
+    assert_eq!(util::get_edges(db, AllEdgeQuery)?, vec![expiring_edge]);
+    assert_eq!(db.expire_now()?, 2);
+    Ok(())
+}
@@ -119,6 +119,171 @@ pub fn should_index_existing_edge_property<D: Datastore>(db: &Database<D>) -> Re
     Ok(())
 }
 
+pub fn should_unindex_property<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let property_name = models::Identifier::new("unindexed-property")?;
+    let other_property_name = models::Identifier::new("other-indexed-property")?;
+    let id = setup_vertex_with_indexed_property(db, property_name)?;
+    let edge = setup_edge_with_indexed_property(db, property_name)?;
+    db.index_property(other_property_name)?;
+    assert_eq!(db.indexed_properties()?, vec![other_property_name, property_name]);
+
+    db.unindex_property(property_name)?;
+    assert_eq!(db.indexed_properties()?, vec![other_property_name]);
+    let result = util::get_vertices(db, models::VertexWithPropertyPresenceQuery::new(property_name));
+    expect_err!(result, Error::NotIndexed);
+    let result = util::get_edges(db, models::EdgeWithPropertyValueQuery::new(property_name, ijson!(true)));
+    expect_err!(result, Error::NotIndexed);
+
+    // Unindexing is idempotent
+    db.unindex_property(property_name)?;
+
+    // Properties set while unindexed are picked up when re-indexed
+    db.set_properties(models::SpecificVertexQuery::single(id), property_name, &ijson!(false))?;
+    db.index_property(property_name)?;
+    let result = util::get_vertices(
+        db,
+        models::VertexWithPropertyValueQuery::new(property_name, ijson!(false)),
+    )?;
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].id, id);
+    let result = util::get_vertices(
+        db,
+        models::VertexWithPropertyValueQuery::new(property_name, ijson!(true)),
+    )?;
+    assert!(result.is_empty());
+    let result = util::get_edges(db, models::EdgeWithPropertyPresenceQuery::new(property_name))?;
+    assert_eq!(result, vec![edge]);
+    Ok(())
+}
+
+// Datastores aren't required to support unique indexes, in which case the
+// tests are skipped.
+fn index_unique_property<D: Datastore>(db: &Database<D>, name: models::Identifier) -> Result<bool, Error> {
+    match db.index_unique_property(name) {
+        Ok(()) => Ok(true),
+        Err(Error::Unsupported) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn should_enforce_unique_property<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let email = models::Identifier::new("email")?;
+    if !index_unique_property(db, email)? {
+        return Ok(());
+    }
+    assert_eq!(db.indexed_properties()?, vec![email]);
+
+    let t = models::Identifier::new("test_vertex_type")?;
+    let a = db.create_vertex_from_type(t)?;
+    let b = db.create_vertex_from_type(t)?;
+    db.set_properties(models::SpecificVertexQuery::single(a), email, &ijson!("a@example.com"))?;
+    let result = db.set_properties(models::SpecificVertexQuery::single(b), email, &ijson!("a@example.com"));
+    match result {
+        Err(Error::UniqueViolation { name, ref value }) => {
+            assert_eq!(name, email);
+            assert_eq!(*value, ijson!("a@example.com"));
+        }
+        _ => panic!("unexpected result: {result:?}"),
+    }
+    db.set_properties(models::SpecificVertexQuery::single(b), email, &ijson!("b@example.com"))?;
+
+    // Rewriting a vertex's own value is fine, but not giving it to several
+    db.set_properties(models::SpecificVertexQuery::single(a), email, &ijson!("a@example.com"))?;
+    let result = db.set_properties(
+        models::SpecificVertexQuery::new(vec![a, b]),
+        email,
+        &ijson!("c@example.com"),
+    );
+    expect_err!(result, Error::UniqueViolation { .. });
+
+    // Values can be swapped in a single bulk insert, but not duplicated
+    db.bulk_insert(vec![
+        models::BulkInsertItem::VertexProperty(a, email, ijson!("b@example.com")),
+        models::BulkInsertItem::VertexProperty(b, email, ijson!("a@example.com")),
+    ])?;
+    let c = models::Vertex::new(t);
+    let result = db.bulk_insert(vec![
+        models::BulkInsertItem::Vertex(c.clone()),
+        models::BulkInsertItem::VertexProperty(c.id, email, ijson!("a@example.com")),
+    ]);
+    expect_err!(result, Error::UniqueViolation { .. });
+    assert!(util::get_vertices(db, models::SpecificVertexQuery::single(c.id))?.is_empty());
+    let result = util::get_vertices(
+        db,
+        models::VertexWithPropertyValueQuery::new(email, ijson!("a@example.com")),
+    )?;
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].id, b);
+
+    // Edge properties aren't constrained
+    let edge = models::Edge::new(a, models::Identifier::new("test_edge_type")?, b);
+    db.create_edge(&edge)?;
+    db.set_properties(models::SpecificEdgeQuery::single(edge), email, &ijson!("a@example.com"))?;
+
+    // Deleting a vertex frees up its value
+    db.delete(models::SpecificVertexQuery::single(b))?;
+    db.set_properties(models::SpecificVertexQuery::single(a), email, &ijson!("a@example.com"))?;
+
+    // Unindexing drops the constraint
+    db.unindex_property(email)?;
+    let b = db.create_vertex_from_type(t)?;
+    db.set_properties(models::SpecificVertexQuery::single(b), email, &ijson!("a@example.com"))?;
+    Ok(())
+}
+
+pub fn should_not_index_unique_property_with_duplicates<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let sku = models::Identifier::new("sku")?;
+    let t = models::Identifier::new("test_vertex_type")?;
+    let a = db.create_vertex_from_type(t)?;
+    let b = db.create_vertex_from_type(t)?;
+    db.set_properties(models::SpecificVertexQuery::new(vec![a, b]), sku, &ijson!(42))?;
+
+    let result = index_unique_property(db, sku);
+    match result {
+        Ok(false) => return Ok(()),
+        Err(Error::UniqueViolation { .. }) => (),
+        _ => panic!("unexpected result: {result:?}"),
+    }
+    assert!(db.indexed_properties()?.is_empty());
+
+    db.set_properties(models::SpecificVertexQuery::single(b), sku, &ijson!(43))?;
+    assert!(index_unique_property(db, sku)?);
+    assert_eq!(db.indexed_properties()?, vec![sku]);
+    Ok(())
+}
+
+pub fn should_compare_unique_property_values_as_indexed<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let sku = models::Identifier::new("sku")?;
+    let t = models::Identifier::new("test_vertex_type")?;
+    let a = db.create_vertex_from_type(t)?;
+    let b = db.create_vertex_from_type(t)?;
+    db.set_properties(models::SpecificVertexQuery::single(a), sku, &ijson!(1))?;
+    db.set_properties(models::SpecificVertexQuery::single(b), sku, &ijson!(1.0))?;
+
+    // `1` and `1.0` are the same value in the index
+    let result = index_unique_property(db, sku);
+    match result {
+        Ok(false) => return Ok(()),
+        Err(Error::UniqueViolation { .. }) => (),
+        _ => panic!("unexpected result: {result:?}"),
+    }
+    db.set_properties(models::SpecificVertexQuery::single(b), sku, &ijson!(2))?;
+    assert!(index_unique_property(db, sku)?);
+
+    let result = db.set_properties(models::SpecificVertexQuery::single(b), sku, &ijson!(1.0));
+    expect_err!(result, Error::UniqueViolation { .. });
+    let c = models::Vertex::new(t);
+    let d = models::Vertex::new(t);
+    let result = db.bulk_insert(vec![
+        models::BulkInsertItem::Vertex(c.clone()),
+        models::BulkInsertItem::Vertex(d.clone()),
+        models::BulkInsertItem::VertexProperty(c.id, sku, ijson!(3)),
+        models::BulkInsertItem::VertexProperty(d.id, sku, ijson!(3.0)),
+    ]);
+    expect_err!(result, Error::UniqueViolation { .. });
+    Ok(())
+}
+
 pub fn should_delete_indexed_vertex_property<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
     let property_name = models::Identifier::new("deletable-vertex-property")?;
     let id = setup_vertex_with_indexed_property(db, property_name)?;
@@ -274,3 +439,208 @@ pub fn should_pipe_not_indexed_errors<D: Datastore>(db: &Database<D>) -> Result<
 
     Ok(())
 }
+
+pub fn should_query_vertex_property_range<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let property_name = models::Identifier::new("ranged-vertex-property")?;
+    db.index_property(property_name)?;
+    let t = models::Identifier::new("test_vertex_type")?;
+    let values = vec![
+        ijson!(null),
+        ijson!(false),
+        ijson!(-3.5),
+        ijson!(1),
+        ijson!(2),
+        ijson!(10),
+        ijson!("a"),
+        ijson!("ab"),
+        ijson!([1, 2]),
+    ];
+    let mut ids = Vec::with_capacity(values.len());
+    for value in &values {
+        let id = db.create_vertex_from_type(t)?;
+        db.set_properties(models::SpecificVertexQuery::single(id), property_name, value)?;
+        ids.push(id);
+    }
+
+    let get_range = |range: models::JsonRange| -> Result<Vec<Uuid>, Error> {
+        let q = models::VertexWithPropertyRangeQuery::new(property_name, range);
+        Ok(util::get_vertices(db, q)?.into_iter().map(|v| v.id).collect())
+    };
+
+    // Results come back ordered by property value
+    assert_eq!(get_range(models::JsonRange::gt(ijson!(1)))?, ids[4..].to_vec());
+    assert_eq!(get_range(models::JsonRange::gte(ijson!(1)))?, ids[3..].to_vec());
+    assert_eq!(get_range(models::JsonRange::lt(ijson!(1)))?, ids[..3].to_vec());
+    assert_eq!(get_range(models::JsonRange::lte(ijson!(1)))?, ids[..4].to_vec());
+    assert_eq!(
+        get_range(models::JsonRange::between(ijson!(-10), ijson!(10.0)))?,
+        ids[2..6].to_vec()
+    );
+    assert_eq!(get_range(models::JsonRange::gt(ijson!("a")))?, ids[7..].to_vec());
+    assert!(get_range(models::JsonRange::between(ijson!(10), ijson!(1)))?.is_empty());
+    Ok(())
+}
+
+pub fn should_query_edge_property_range<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let property_name = models::Identifier::new("ranged-edge-property")?;
+    db.index_property(property_name)?;
+    let vertex_t = models::Identifier::new("test_vertex_type")?;
+    let edge_t = models::Identifier::new("test_edge_type")?;
+    let outbound_id = db.create_vertex_from_type(vertex_t)?;
+    let mut edges = Vec::new();
+    for i in 0..5 {
+        let inbound_id = db.create_vertex_from_type(vertex_t)?;
+        let edge = models::Edge::new(outbound_id, edge_t, inbound_id);
+        db.create_edge(&edge)?;
+        db.set_properties(
+            models::SpecificEdgeQuery::single(edge.clone()),
+            property_name,
+            &ijson!(i),
+        )?;
+        edges.push(edge);
+    }
+
+    let q = models::EdgeWithPropertyRangeQuery::new(property_name, models::JsonRange::gt(ijson!(1)));
+    assert_eq!(util::get_edges(db, q)?, edges[2..].to_vec());
+    let q = models::EdgeWithPropertyRangeQuery::new(property_name, models::JsonRange::lte(ijson!(1)));
+    assert_eq!(util::get_edges(db, q)?, edges[..2].to_vec());
+    let q = models::EdgeWithPropertyRangeQuery::new(property_name, models::JsonRange::between(ijson!(1), ijson!(3)));
+    assert_eq!(util::get_edges(db, q)?, edges[1..4].to_vec());
+    Ok(())
+}
+
+pub fn should_pipe_property_range<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let property_name = models::Identifier::new("ranged-pipe-property")?;
+    let id = setup_vertex_with_indexed_property(db, property_name)?;
+    let q = models::SpecificVertexQuery::single(id);
+    db.set_properties(q.clone(), property_name, &ijson!(5))?;
+
+    let result = util::get_vertices(
+        db,
+        q.clone()
+            .with_property_in_range(property_name, models::JsonRange::gte(ijson!(5)))?,
+    )?;
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].id, id);
+    let result = util::get_vertices(
+        db,
+        q.with_property_in_range(property_name, models::JsonRange::gt(ijson!(5)))?,
+    )?;
+    assert!(result.is_empty());
+
+    let edge = setup_edge_with_indexed_property(db, property_name)?;
+    let q = models::SpecificEdgeQuery::single(edge.clone());
+    let result = util::get_edges(
+        db,
+        q.with_property_in_range(property_name, models::JsonRange::lte(ijson!(true)))?,
+    )?;
+    assert_eq!(result, vec![edge]);
+    Ok(())
+}
+
+pub fn should_not_query_unindexed_property_range<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let property_name = models::Identifier::new("unindexed-range-property")?;
+    let result = util::get_vertices(
+        db,
+        models::VertexWithPropertyRangeQuery::new(property_name, models::JsonRange::gt(ijson!(1))),
+    );
+    expect_err!(result, Error::NotIndexed);
+    let result = util::get_edges(
+        db,
+        models::EdgeWithPropertyRangeQuery::new(property_name, models::JsonRange::gt(ijson!(1))),
+    );
+    expect_err!(result, Error::NotIndexed);
+    let result = util::get_vertices(
+        db,
+        models::AllVertexQuery.with_property_in_range(property_name, models::JsonRange::gt(ijson!(1)))?,
+    );
+    expect_err!(result, Error::NotIndexed);
+    Ok(())
+}
+
+pub fn should_query_composite_property<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let tenant_id = models::Identifier::new("tenant_id")?;
+    let external_id = models::Identifier::new("external_id")?;
+    let names = vec![tenant_id, external_id];
+    let t = models::Identifier::new("test_vertex_type")?;
+
+    // Vertices that exist before the index is created get indexed
+    let existing_id = db.create_vertex_from_type(t)?;
+    let q = models::SpecificVertexQuery::single(existing_id);
+    db.set_properties(q.clone(), tenant_id, &ijson!("acme"))?;
+    db.set_properties(q, external_id, &ijson!(1))?;
+    db.index_composite_property(names.clone())?;
+
+    // So do vertices inserted afterwards, including through a bulk insert
+    let mut bulk_ids = Vec::new();
+    let mut items = Vec::new();
+    for (tenant, external) in [("acme", 2), ("globex", 1)] {
+        let vertex = models::Vertex::new(t);
+        items.push(models::BulkInsertItem::Vertex(vertex.clone()));
+        items.push(models::BulkInsertItem::VertexProperty(
+            vertex.id,
+            tenant_id,
+            ijson!(tenant),
+        ));
+        items.push(models::BulkInsertItem::VertexProperty(
+            vertex.id,
+            external_id,
+            ijson!(external),
+        ));
+        bulk_ids.push(vertex.id);
+    }
+    db.bulk_insert(items)?;
+
+    // Vertices missing some of the properties aren't indexed
+    let partial_id = db.create_vertex_from_type(t)?;
+    db.set_properties(
+        models::SpecificVertexQuery::single(partial_id),
+        tenant_id,
+        &ijson!("acme"),
+    )?;
+
+    let get_ids = |values: Vec<models::Json>| -> Result<Vec<Uuid>, Error> {
+        let q = models::VertexWithCompositePropertyQuery::new(names.clone(), values)?;
+        Ok(util::get_vertices(db, q)?.into_iter().map(|v| v.id).collect())
+    };
+
+    assert_eq!(get_ids(vec![ijson!("acme"), ijson!(1)])?, vec![existing_id]);
+    assert_eq!(get_ids(vec![ijson!("acme"), ijson!(2)])?, vec![bulk_ids[0]]);
+    assert_eq!(get_ids(vec![ijson!("acme")])?, vec![existing_id, bulk_ids[0]]);
+    assert_eq!(get_ids(vec![ijson!("globex")])?, vec![bulk_ids[1]]);
+    assert_eq!(get_ids(vec![])?.len(), 3);
+    assert!(get_ids(vec![ijson!("initech")])?.is_empty());
+
+    // Updating, deleting and adding properties keeps the index up to date
+    let q = models::SpecificVertexQuery::single(existing_id);
+    db.set_properties(q.clone(), external_id, &ijson!(3))?;
+    assert!(get_ids(vec![ijson!("acme"), ijson!(1)])?.is_empty());
+    assert_eq!(get_ids(vec![ijson!("acme"), ijson!(3)])?, vec![existing_id]);
+    db.delete(q.clone().properties()?.name(tenant_id))?;
+    assert!(get_ids(vec![ijson!("acme"), ijson!(3)])?.is_empty());
+    db.set_properties(models::SpecificVertexQuery::single(partial_id), external_id, &ijson!(4))?;
+    assert_eq!(get_ids(vec![ijson!("acme")])?, vec![bulk_ids[0], partial_id]);
+    db.delete(models::SpecificVertexQuery::single(bulk_ids[0]))?;
+    assert_eq!(get_ids(vec![ijson!("acme")])?, vec![partial_id]);
+    Ok(())
+}
+
+pub fn should_not_query_unindexed_composite_property<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let first = models::Identifier::new("first")?;
+    let second = models::Identifier::new("second")?;
+    db.index_composite_property(vec![first, second])?;
+
+    // The order of the properties matters
+    let q = models::VertexWithCompositePropertyQuery::new(vec![second, first], vec![ijson!(1)])?;
+    let result = util::get_vertices(db, q);
+    expect_err!(result, Error::NotIndexed);
+    let q = models::VertexWithCompositePropertyQuery::new(vec![first], vec![ijson!(1)])?;
+    let result = util::get_vertices(db, q);
+    expect_err!(result, Error::NotIndexed);
+
+    let result = db.index_composite_property(vec![]);
+    expect_err!(result, Error::Invalid(_));
+    let result = db.index_composite_property(vec![first, first]);
+    expect_err!(result, Error::Invalid(_));
+    Ok(())
+}
@@ -40,6 +40,7 @@ macro_rules! full_test_impl {
         define_test!(should_get_no_vertices_with_zero_limit, $code);
         define_test!(should_get_range_vertices_out_of_range, $code);
         define_test!(should_get_no_vertices_with_type_filter, $code);
+        define_test!(should_get_vertices_with_type_filter, $code);
         define_test!(should_get_single_vertex, $code);
         define_test!(should_get_single_vertex_nonexisting, $code);
         define_test!(should_get_vertices, $code);
@@ -67,6 +68,8 @@ macro_rules! full_test_impl {
         define_test!(should_get_edge_range, $code);
         define_test!(should_get_edges, $code);
         define_test!(should_get_edges_piped, $code);
+        define_test!(should_get_range_edges, $code);
+        define_test!(should_get_range_edges_with_type_filter, $code);
         define_test!(should_delete_indexed_edge_with_property_value, $code);
 
         // Include queries
@@ -79,6 +82,10 @@ macro_rules! full_test_impl {
         define_test!(should_not_query_unindexed_edge_property, $code);
         define_test!(should_index_existing_vertex_property, $code);
         define_test!(should_index_existing_edge_property, $code);
+        define_test!(should_unindex_property, $code);
+        define_test!(should_enforce_unique_property, $code);
+        define_test!(should_not_index_unique_property_with_duplicates, $code);
+        define_test!(should_compare_unique_property_values_as_indexed, $code);
         define_test!(should_delete_indexed_vertex_property, $code);
         define_test!(should_delete_indexed_edge_property, $code);
         define_test!(should_update_indexed_vertex_property, $code);
@@ -87,6 +94,12 @@ macro_rules! full_test_impl {
         define_test!(should_query_indexed_edge_property_empty, $code);
         define_test!(should_get_vertex_with_property_value_empty, $code);
         define_test!(should_pipe_not_indexed_errors, $code);
+        define_test!(should_query_vertex_property_range, $code);
+        define_test!(should_query_edge_property_range, $code);
+        define_test!(should_pipe_property_range, $code);
+        define_test!(should_not_query_unindexed_property_range, $code);
+        define_test!(should_query_composite_property, $code);
+        define_test!(should_not_query_unindexed_composite_property, $code);
 
         // Properties
         define_test!(should_handle_vertex_properties, $code);
@@ -102,5 +115,69 @@ macro_rules! full_test_impl {
         define_test!(should_not_set_properties_on_count, $code);
         define_test!(should_not_pipe_properties_on_vertex_count, $code);
         define_test!(should_not_pipe_property_presence_on_vertex_count, $code);
+
+        // Schemas
+        define_test!(should_enforce_vertex_schema, $code);
+        define_test!(should_enforce_edge_schema, $code);
+        define_test!(should_enforce_schema_on_bulk_insert, $code);
+
+        // Expiry
+        define_test!(should_hide_expired_items, $code);
+        define_test!(should_expire_now, $code);
+        define_test!(should_bulk_insert_expiring_items, $code);
+
+        // Changes
+        define_test!(should_publish_changes, $code);
+        define_test!(should_publish_bulk_insert_changes, $code);
+        define_test!(should_publish_cascaded_delete_changes, $code);
+        define_test!(should_publish_schema_changes, $code);
+        define_test!(should_publish_transaction_changes_on_commit, $code);
+
+        // Transactions
+        define_test!(should_commit_transaction, $code);
+        define_test!(should_read_writes_in_transaction, $code);
+        define_test!(should_rollback_transaction, $code);
+        define_test!(should_discard_dropped_transaction, $code);
+        define_test!(should_get_with_snapshot, $code);
+
+        // Query iterators
+        define_test!(should_get_iter, $code);
+        define_test!(should_get_iter_lazily, $code);
+
+        // Query plans
+        define_test!(should_explain_index_scan_for_counts, $code);
+        define_test!(should_explain_pushed_down_filters, $code);
+        define_test!(should_not_explain_count_of_count, $code);
+
+        // Query budgets
+        define_test!(should_enforce_row_budget, $code);
+        define_test!(should_enforce_edge_scan_budget, $code);
+        define_test!(should_enforce_deadline, $code);
+
+        // Traversals
+        define_test!(should_traverse_within_depths, $code);
+        define_test!(should_traverse_inbound, $code);
+        define_test!(should_limit_traverse_per_depth, $code);
+
+        // Shortest paths
+        define_test!(should_get_shortest_path, $code);
+        define_test!(should_not_get_unreachable_shortest_path, $code);
+        define_test!(should_get_weighted_shortest_path, $code);
+
+        // Pipe paths
+        define_test!(should_get_pipe_paths, $code);
+        define_test!(should_filter_pipe_paths, $code);
+        define_test!(should_not_get_pipe_paths_with_include, $code);
+
+        // Dedup
+        define_test!(should_dedup_vertices, $code);
+        define_test!(should_dedup_edges, $code);
+        define_test!(should_not_dedup_properties, $code);
+
+        // Order by
+        define_test!(should_order_vertices_by_property, $code);
+        define_test!(should_order_vertices_with_offset_and_limit, $code);
+        define_test!(should_order_edges_by_property, $code);
+        define_test!(should_not_order_properties, $code);
     };
 }
@@ -5,21 +5,45 @@
 //! `full_test_impl`.
 
 mod bulk_insert;
+mod changes;
+mod dedup;
 mod edge;
+mod expiry;
 mod include_query;
 mod indexing;
 #[macro_use]
 mod macros;
+mod order_by;
+mod pipe_paths;
+mod plan;
 mod properties;
+mod query_budget;
+mod query_iter;
+mod schema;
+mod shortest_path;
 mod sync;
+mod transaction;
+mod traverse;
 mod util;
 mod vertex;
 
 pub use self::bulk_insert::*;
+pub use self::changes::*;
+pub use self::dedup::*;
 pub use self::edge::*;
+pub use self::expiry::*;
 pub use self::include_query::*;
 pub use self::indexing::*;
 pub use self::macros::*;
+pub use self::order_by::*;
+pub use self::pipe_paths::*;
+pub use self::plan::*;
 pub use self::properties::*;
+pub use self::query_budget::*;
+pub use self::query_iter::*;
+pub use self::schema::*;
+pub use self::shortest_path::*;
 pub use self::sync::*;
+pub use self::transaction::*;
+pub use self::traverse::*;
 pub use self::vertex::*;
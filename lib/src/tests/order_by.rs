@@ -0,0 +1,94 @@
+use crate::util::{extract_count, extract_edges, extract_vertices};
+use crate::{
+    errors, ijson, AllVertexQuery, CountQueryExt, Database, Datastore, Edge, Error, Identifier, NullsOrder,
+    OrderByQuery, Query, QueryExt, SpecificEdgeQuery, SpecificVertexQuery, Vertex,
+};
+
+use uuid::Uuid;
+
+// Creates the vertices 1 through 6 with the scores 3, 1, null, none, 2.5 and
+// 1, and edges from 1 to each of the others.
+fn create_graph<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    for id in 1..=6 {
+        db.create_vertex(&Vertex::with_id(Uuid::from_u128(id), vertex_t))?;
+    }
+    let score = Identifier::new("score")?;
+    for (id, value) in [
+        (1, ijson!(3)),
+        (2, ijson!(1)),
+        (3, ijson!(null)),
+        (5, ijson!(2.5)),
+        (6, ijson!(1)),
+    ] {
+        db.set_properties(SpecificVertexQuery::single(Uuid::from_u128(id)), score, &value)?;
+    }
+    let edge_t = Identifier::new("test_edge_type")?;
+    for inbound_id in 2..=6 {
+        db.create_edge(&Edge::new(Uuid::from_u128(1), edge_t, Uuid::from_u128(inbound_id)))?;
+    }
+    Ok(())
+}
+
+fn ordered_ids<D: Datastore>(db: &Database<D>, q: OrderByQuery) -> Result<Vec<u128>, Error> {
+    let vertices = extract_vertices(db.get(q)?).unwrap();
+    Ok(vertices.into_iter().map(|v| v.id.as_u128()).collect())
+}
+
+pub fn should_order_vertices_by_property<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let score = Identifier::new("score")?;
+
+    // Ties keep their order, and nulls and missing values sort together
+    let q = AllVertexQuery.order_by(score)?;
+    assert_eq!(ordered_ids(db, q.clone())?, vec![2, 6, 5, 1, 3, 4]);
+    assert_eq!(ordered_ids(db, q.clone().descending())?, vec![1, 5, 2, 6, 3, 4]);
+    assert_eq!(
+        ordered_ids(db, q.clone().nulls(NullsOrder::First))?,
+        vec![3, 4, 2, 6, 5, 1]
+    );
+
+    let ids: Vec<Uuid> = [6, 2, 4, 1].into_iter().map(Uuid::from_u128).collect();
+    let q = SpecificVertexQuery::new(ids).order_by(score)?;
+    assert_eq!(ordered_ids(db, q)?, vec![6, 2, 1, 4]);
+    Ok(())
+}
+
+pub fn should_order_vertices_with_offset_and_limit<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let q = AllVertexQuery
+        .order_by(Identifier::new("score")?)?
+        .descending()
+        .offset(1)
+        .limit(2);
+    assert_eq!(ordered_ids(db, q.clone())?, vec![5, 2]);
+    assert_eq!(extract_count(db.get(q.clone().count()?)?), Some(2));
+    assert_eq!(ordered_ids(db, q.offset(10))?, Vec::<u128>::new());
+    Ok(())
+}
+
+pub fn should_order_edges_by_property<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let weight = Identifier::new("weight")?;
+    let edge_t = Identifier::new("test_edge_type")?;
+    let edge = |inbound_id| Edge::new(Uuid::from_u128(1), edge_t, Uuid::from_u128(inbound_id));
+    // Values of different types sort by type first
+    for (inbound_id, value) in [(2, ijson!(2)), (3, ijson!("a")), (4, ijson!(1)), (5, ijson!(true))] {
+        db.set_properties(SpecificEdgeQuery::single(edge(inbound_id)), weight, &value)?;
+    }
+
+    let q = SpecificVertexQuery::single(Uuid::from_u128(1))
+        .outbound()?
+        .order_by(weight)?;
+    let edges = extract_edges(db.get(q)?).unwrap();
+    assert_eq!(edges, vec![edge(5), edge(4), edge(2), edge(3), edge(6)]);
+    Ok(())
+}
+
+pub fn should_not_order_properties<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let inner: Query = AllVertexQuery.properties()?.into();
+    let result = OrderByQuery::new(Box::new(inner), Identifier::new("score")?);
+    expect_err!(result, errors::ValidationError::InnerQuery);
+    Ok(())
+}
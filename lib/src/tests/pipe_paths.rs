@@ -0,0 +1,118 @@
+use crate::util::{extract_count, extract_pipe_paths};
+use crate::{
+    errors, ijson, CountQueryExt, Database, Datastore, Edge, Error, Identifier, PathHop, PipePath, QueryExt,
+    SpecificVertexQuery, Vertex,
+};
+
+use uuid::Uuid;
+
+// Creates the vertices 1 through 5, 10 and 11, where 1 follows 2, 3 and 4,
+// and 2, 3 and 5 like 10, and 3 also likes 11.
+fn create_graph<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    for id in [1, 2, 3, 4, 5, 10, 11] {
+        db.create_vertex(&Vertex::with_id(Uuid::from_u128(id), vertex_t))?;
+    }
+    for inbound_id in [2, 3, 4] {
+        db.create_edge(&edge(1, "follows", inbound_id))?;
+    }
+    for (outbound_id, inbound_id) in [(2, 10), (3, 10), (5, 10), (3, 11)] {
+        db.create_edge(&edge(outbound_id, "likes", inbound_id))?;
+    }
+    Ok(())
+}
+
+fn edge(outbound_id: u128, t: &str, inbound_id: u128) -> Edge {
+    Edge::new(
+        Uuid::from_u128(outbound_id),
+        Identifier::new(t).unwrap(),
+        Uuid::from_u128(inbound_id),
+    )
+}
+
+// Builds the path 1 -follows-> `friend_id` -likes-> `liked_id`.
+fn liked_path(friend_id: u128, liked_id: u128) -> PipePath {
+    PipePath::new(vec![
+        PathHop::Vertex(Uuid::from_u128(1)),
+        PathHop::Edge(edge(1, "follows", friend_id)),
+        PathHop::Vertex(Uuid::from_u128(friend_id)),
+        PathHop::Edge(edge(friend_id, "likes", liked_id)),
+        PathHop::Vertex(Uuid::from_u128(liked_id)),
+    ])
+}
+
+pub fn should_get_pipe_paths<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let follows = Identifier::new("follows")?;
+    let likes = Identifier::new("likes")?;
+
+    let q = SpecificVertexQuery::single(Uuid::from_u128(1))
+        .outbound()?
+        .t(follows)
+        .inbound()?
+        .outbound()?
+        .t(likes)
+        .inbound()?
+        .paths();
+    let paths = extract_pipe_paths(db.get(q.clone())?).unwrap();
+    assert_eq!(paths, vec![liked_path(2, 10), liked_path(3, 10), liked_path(3, 11)]);
+    assert_eq!(extract_count(db.get(q.clone().count()?)?), Some(3));
+
+    let paths = extract_pipe_paths(db.get(q.limit(1))?).unwrap();
+    assert_eq!(paths, vec![liked_path(2, 10)]);
+
+    // Inbound pipes start from the other end of each edge
+    let q = SpecificVertexQuery::single(Uuid::from_u128(10))
+        .inbound()?
+        .t(likes)
+        .outbound()?
+        .paths();
+    let paths = extract_pipe_paths(db.get(q)?).unwrap();
+    let liker_ids: Vec<PathHop> = paths.iter().map(|path| path.last().unwrap().clone()).collect();
+    assert_eq!(
+        liker_ids,
+        vec![
+            PathHop::Vertex(Uuid::from_u128(2)),
+            PathHop::Vertex(Uuid::from_u128(3)),
+            PathHop::Vertex(Uuid::from_u128(5)),
+        ]
+    );
+    assert_eq!(paths[0].hops[1], PathHop::Edge(edge(2, "likes", 10)));
+    Ok(())
+}
+
+pub fn should_filter_pipe_paths<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let verified = Identifier::new("verified")?;
+    db.index_property(verified)?;
+    db.set_properties(SpecificVertexQuery::single(Uuid::from_u128(3)), verified, &ijson!(true))?;
+
+    let q = SpecificVertexQuery::single(Uuid::from_u128(1))
+        .outbound()?
+        .inbound()?
+        .with_property(verified)?
+        .outbound()?
+        .inbound()?
+        .paths();
+    let paths = extract_pipe_paths(db.get(q)?).unwrap();
+    assert_eq!(paths, vec![liked_path(3, 10), liked_path(3, 11)]);
+    Ok(())
+}
+
+pub fn should_not_get_pipe_paths_with_include<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let q = SpecificVertexQuery::single(Uuid::from_u128(1))
+        .include()
+        .outbound()?
+        .paths();
+    let result = db.get(q);
+    expect_err!(result, Error::OperationOnQuery);
+
+    // Paths can't be piped any further
+    let result = SpecificVertexQuery::single(Uuid::from_u128(1))
+        .outbound()?
+        .paths()
+        .inbound();
+    expect_err!(result, errors::ValidationError::InnerQuery);
+    Ok(())
+}
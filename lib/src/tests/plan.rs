@@ -0,0 +1,101 @@
+use crate::util::extract_count;
+use crate::{
+    ijson, AllVertexQuery, CountQuery, CountQueryExt, Database, Datastore, EdgeDirection, Error, Identifier,
+    IndexPredicate, PlanOp, Query, QueryExt, QueryPlan, RangeVertexQuery, SpecificVertexQuery, Vertex,
+};
+
+fn input(plan: &QueryPlan) -> &QueryPlan {
+    plan.input.as_deref().unwrap()
+}
+
+pub fn should_explain_index_scan_for_counts<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let t = Identifier::new("test_vertex_type")?;
+    let name = Identifier::new("foo")?;
+    db.index_property(name)?;
+    for value in [1, 1, 2] {
+        let id = db.create_vertex_from_type(t)?;
+        db.set_properties(SpecificVertexQuery::single(id), name, &ijson!(value))?;
+    }
+
+    // Counting doesn't depend on order, so the scan is swapped for an index
+    // read
+    let q = AllVertexQuery.with_property_equal_to(name, ijson!(1))?.count()?;
+    let plan = db.explain(q.clone())?;
+    assert_eq!(plan.op, PlanOp::Count);
+    assert_eq!(plan.estimated_rows, Some(1));
+    assert_eq!(
+        input(&plan).op,
+        PlanOp::VertexIndexScan {
+            name,
+            predicate: IndexPredicate::Value(ijson!(1)),
+        }
+    );
+    // Index scans are estimated from the matching index entries
+    assert_eq!(input(&plan).estimated_rows, Some(2));
+    assert!(input(&plan).input.is_none());
+    assert_eq!(extract_count(db.get(q)?), Some(2));
+
+    // Otherwise, the scan is kept so that vertices come out in id order
+    let plan = db.explain(AllVertexQuery.with_property_equal_to(name, ijson!(1))?)?;
+    assert_eq!(
+        plan.op,
+        PlanOp::IndexFilter {
+            name,
+            predicate: IndexPredicate::Value(ijson!(1)),
+            negated: false,
+        }
+    );
+    assert_eq!(plan.estimated_rows, Some(2));
+    assert_eq!(input(&plan).op, PlanOp::VertexScan { start_id: None });
+    assert_eq!(input(&plan).estimated_rows, Some(3));
+    Ok(())
+}
+
+pub fn should_explain_pushed_down_filters<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    let edge_t = Identifier::new("test_edge_type")?;
+    for _ in 0..3 {
+        db.create_vertex(&Vertex::new(vertex_t))?;
+    }
+    db.create_vertex(&Vertex::new(Identifier::new("other_vertex_type")?))?;
+
+    let plan = db.explain(RangeVertexQuery::new().t(vertex_t).limit(2).outbound()?.t(edge_t))?;
+    assert_eq!(
+        plan.op,
+        PlanOp::EdgeExpand {
+            direction: EdgeDirection::Outbound,
+            t: Some(edge_t),
+        }
+    );
+    assert_eq!(plan.estimated_rows, Some(0));
+    let limit = input(&plan);
+    assert_eq!(limit.op, PlanOp::Limit(2));
+    assert_eq!(limit.estimated_rows, Some(2));
+    assert_eq!(
+        input(limit).op,
+        PlanOp::VertexTypeScan {
+            t: vertex_t,
+            start_id: None,
+        }
+    );
+    // Type scans are estimated from the vertices of the type
+    assert_eq!(input(limit).estimated_rows, Some(3));
+
+    let plan = db.explain(AllVertexQuery.count()?)?;
+    assert_eq!(plan.op, PlanOp::VertexCount);
+    assert!(plan.input.is_none());
+
+    let plan = db.explain(AllVertexQuery.include().count()?)?;
+    assert_eq!(plan.op, PlanOp::Count);
+    assert!(input(&plan).exported);
+    Ok(())
+}
+
+pub fn should_not_explain_count_of_count<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let q = CountQuery {
+        inner: Box::new(AllVertexQuery.count()?.into()),
+    };
+    let result = db.explain(Query::Count(q));
+    expect_err!(result, Error::OperationOnQuery);
+    Ok(())
+}
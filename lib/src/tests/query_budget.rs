@@ -0,0 +1,89 @@
+use super::util;
+use crate::util::{extract_edges, extract_vertices};
+use crate::{
+    AllVertexQuery, Database, Datastore, Error, GetOptions, Identifier, QueryBudget, QueryExt, QueryLimit,
+    SpecificVertexQuery, Vertex,
+};
+
+use std::time::Duration;
+
+fn budgeted(budget: QueryBudget) -> GetOptions {
+    GetOptions {
+        budget: Some(budget),
+        ..GetOptions::default()
+    }
+}
+
+pub fn should_enforce_row_budget<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let (outbound_id, _) = util::create_edges(db)?;
+    let q = SpecificVertexQuery::single(outbound_id).outbound()?;
+
+    // The vertex counts as one row, and each of its five edges as another
+    let result = db.get_with_options(
+        q.clone(),
+        budgeted(QueryBudget {
+            max_rows: Some(5),
+            ..QueryBudget::default()
+        }),
+    );
+    expect_err!(result, Error::QueryBudgetExceeded(QueryLimit::Rows));
+
+    let output = db.get_with_options(
+        q,
+        budgeted(QueryBudget {
+            max_rows: Some(6),
+            ..QueryBudget::default()
+        }),
+    )?;
+    assert_eq!(extract_edges(output).unwrap().len(), 5);
+    Ok(())
+}
+
+pub fn should_enforce_edge_scan_budget<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let (outbound_id, _) = util::create_edges(db)?;
+    let q = SpecificVertexQuery::single(outbound_id).outbound()?;
+
+    let result = db.get_with_options(
+        q.clone(),
+        budgeted(QueryBudget {
+            max_edges_scanned: Some(4),
+            ..QueryBudget::default()
+        }),
+    );
+    expect_err!(result, Error::QueryBudgetExceeded(QueryLimit::EdgesScanned));
+
+    // Lazily pulled values are metered too
+    let mut output = db.get_iter_with_options(
+        q,
+        budgeted(QueryBudget {
+            max_edges_scanned: Some(4),
+            ..QueryBudget::default()
+        }),
+    )?;
+    let result = output.pop().unwrap().into_value();
+    expect_err!(result, Error::QueryBudgetExceeded(QueryLimit::EdgesScanned));
+    Ok(())
+}
+
+pub fn should_enforce_deadline<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    db.create_vertex(&Vertex::new(Identifier::new("test_vertex_type")?))?;
+
+    let result = db.get_with_options(
+        AllVertexQuery,
+        budgeted(QueryBudget {
+            timeout: Some(Duration::ZERO),
+            ..QueryBudget::default()
+        }),
+    );
+    expect_err!(result, Error::QueryBudgetExceeded(QueryLimit::Deadline));
+
+    let result = db.get_with_options(
+        AllVertexQuery,
+        budgeted(QueryBudget {
+            timeout: Some(Duration::from_secs(60)),
+            ..QueryBudget::default()
+        }),
+    );
+    assert_eq!(extract_vertices(result?).unwrap().len(), 1);
+    Ok(())
+}
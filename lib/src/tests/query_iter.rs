@@ -0,0 +1,57 @@
+use super::util;
+use crate::{
+    ijson, AllVertexQuery, BulkInsertItem, CountQueryExt, Database, Datastore, Error, Identifier, Query, QueryExt,
+    QueryOutputIter, QueryOutputValue, SpecificVertexQuery, Vertex,
+};
+
+pub fn should_get_iter<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let (outbound_id, _) = util::create_edges(db)?;
+    let q = SpecificVertexQuery::single(outbound_id);
+    db.set_properties(q.clone(), Identifier::new("foo")?, &ijson!(true))?;
+
+    let queries: Vec<Query> = vec![
+        q.clone().into(),
+        q.clone().outbound()?.into(),
+        q.clone().outbound()?.inbound()?.into(),
+        q.clone().properties()?.into(),
+        q.clone().include().outbound()?.include().count()?.into(),
+        q.clone().outbound()?.limit(2).inbound()?.include().properties()?.into(),
+    ];
+
+    for q in queries {
+        let expected = db.get(q.clone())?;
+        let output = db
+            .get_iter(q)?
+            .into_iter()
+            .map(|value| value.into_value())
+            .collect::<Result<Vec<QueryOutputValue>, Error>>()?;
+        assert_eq!(output, expected);
+    }
+
+    Ok(())
+}
+
+pub fn should_get_iter_lazily<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    let items = (0..2500)
+        .map(|_| BulkInsertItem::Vertex(Vertex::new(vertex_t)))
+        .collect();
+    db.bulk_insert(items)?;
+
+    {
+        let mut output = db.get_iter(AllVertexQuery)?;
+        assert_eq!(output.len(), 1);
+        match output.pop().unwrap() {
+            QueryOutputIter::Vertices(iter) => {
+                let vertices = iter.take(10).collect::<Result<Vec<Vertex>, Error>>()?;
+                assert_eq!(vertices.len(), 10);
+            }
+            _ => panic!("unexpected output"),
+        }
+    }
+
+    // Large results are streamed by some datastores, so check that they're
+    // reassembled correctly
+    assert_eq!(util::get_vertices(db, AllVertexQuery)?.len(), 2500);
+    Ok(())
+}
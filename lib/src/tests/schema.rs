@@ -0,0 +1,118 @@
+use super::util;
+use crate::{
+    ijson, AllVertexQuery, BulkInsertItem, Database, Datastore, Edge, Error, Identifier, JsonType, Schema, SchemaKind,
+    SpecificEdgeQuery, SpecificVertexQuery, Vertex,
+};
+
+// Datastores aren't required to support schemas, in which case the tests are
+// skipped.
+fn set_schema<D: Datastore>(db: &Database<D>, kind: SchemaKind, t: Identifier, schema: Schema) -> Result<bool, Error> {
+    match db.set_schema(kind, t, schema) {
+        Ok(()) => Ok(true),
+        Err(Error::Unsupported) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn person_schema() -> Result<Schema, Error> {
+    Ok(Schema::new()
+        .property(Identifier::new("name")?, vec![JsonType::String])
+        .property(Identifier::new("age")?, vec![JsonType::Number, JsonType::Null]))
+}
+
+pub fn should_enforce_vertex_schema<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let person_t = Identifier::new("person")?;
+    if !set_schema(db, SchemaKind::Vertex, person_t, person_schema()?)? {
+        return Ok(());
+    }
+    assert_eq!(db.schema(SchemaKind::Vertex, person_t)?, Some(person_schema()?));
+    assert_eq!(db.schema(SchemaKind::Edge, person_t)?, None);
+
+    let name = Identifier::new("name")?;
+    let age = Identifier::new("age")?;
+    let id = db.create_vertex_from_type(person_t)?;
+    let other_id = db.create_vertex_from_type(Identifier::new("test_vertex_type")?)?;
+    let q = SpecificVertexQuery::new(vec![id, other_id]);
+    db.set_properties(q.clone(), name, &ijson!("alice"))?;
+    db.set_properties(q.clone(), age, &ijson!(null))?;
+
+    let result = db.set_properties(q.clone(), name, &ijson!(1));
+    expect_err!(result, Error::SchemaViolation { .. });
+    let result = db.set_properties(q.clone(), Identifier::new("email")?, &ijson!("alice@example.com"));
+    match result {
+        Err(Error::SchemaViolation { t, name }) => {
+            assert_eq!(t, person_t);
+            assert_eq!(name.as_str(), "email");
+        }
+        _ => panic!("unexpected result: {result:?}"),
+    }
+
+    // Nothing is written if any of the properties are rejected
+    let all_props = util::get_all_vertex_properties(db, q.clone())?;
+    assert_eq!(all_props.len(), 2);
+    for vertex_props in all_props {
+        assert_eq!(vertex_props.props.len(), 2);
+        assert!(vertex_props
+            .props
+            .iter()
+            .any(|p| p.name == name && p.value == ijson!("alice")));
+    }
+
+    db.delete_schema(SchemaKind::Vertex, person_t)?;
+    assert_eq!(db.schema(SchemaKind::Vertex, person_t)?, None);
+    db.set_properties(q, name, &ijson!(1))?;
+    Ok(())
+}
+
+pub fn should_enforce_edge_schema<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let edge_t = Identifier::new("test_edge_type")?;
+    let schema = Schema::new()
+        .property(Identifier::new("weight")?, vec![JsonType::Number])
+        .allow_undeclared(true);
+    if !set_schema(db, SchemaKind::Edge, edge_t, schema)? {
+        return Ok(());
+    }
+
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    let outbound_id = db.create_vertex_from_type(vertex_t)?;
+    let inbound_id = db.create_vertex_from_type(vertex_t)?;
+    let edge = Edge::new(outbound_id, edge_t, inbound_id);
+    db.create_edge(&edge)?;
+    let q = SpecificEdgeQuery::single(edge);
+
+    db.set_properties(q.clone(), Identifier::new("weight")?, &ijson!(1.5))?;
+    db.set_properties(q.clone(), Identifier::new("label")?, &ijson!({"anything": "goes"}))?;
+    let result = db.set_properties(q, Identifier::new("weight")?, &ijson!("heavy"));
+    expect_err!(result, Error::SchemaViolation { .. });
+    Ok(())
+}
+
+pub fn should_enforce_schema_on_bulk_insert<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let person_t = Identifier::new("person")?;
+    if !set_schema(db, SchemaKind::Vertex, person_t, person_schema()?)? {
+        return Ok(());
+    }
+
+    let alice = Vertex::new(person_t);
+    let bob = Vertex::new(person_t);
+    let result = db.bulk_insert(vec![
+        BulkInsertItem::Vertex(alice.clone()),
+        BulkInsertItem::VertexProperty(alice.id, Identifier::new("name")?, ijson!("alice")),
+        BulkInsertItem::Vertex(bob.clone()),
+        BulkInsertItem::VertexProperty(bob.id, Identifier::new("name")?, ijson!(false)),
+    ]);
+    expect_err!(result, Error::SchemaViolation { .. });
+    assert!(util::get_vertices(db, AllVertexQuery)?.is_empty());
+
+    db.bulk_insert(vec![
+        BulkInsertItem::Vertex(alice.clone()),
+        BulkInsertItem::VertexProperty(alice.id, Identifier::new("name")?, ijson!("alice")),
+    ])?;
+    let result = db.bulk_insert(vec![BulkInsertItem::VertexProperty(
+        alice.id,
+        Identifier::new("age")?,
+        ijson!("old"),
+    )]);
+    expect_err!(result, Error::SchemaViolation { .. });
+    Ok(())
+}
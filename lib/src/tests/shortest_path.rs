@@ -0,0 +1,133 @@
+use crate::util::{extract_count, extract_paths};
+use crate::{
+    errors, ijson, CountQueryExt, Database, Datastore, Edge, Error, Identifier, InvalidWeight, Path, ShortestPathQuery,
+    SpecificEdgeQuery, Vertex,
+};
+
+use uuid::Uuid;
+
+// Creates the vertices 1 through 6, connected by `follows` edges 1->2,
+// 2->3, 3->4, 1->5, 5->4 and 4->6, and a `likes` edge 1->6.
+fn create_graph<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    for id in 1..=6 {
+        db.create_vertex(&Vertex::with_id(Uuid::from_u128(id), vertex_t))?;
+    }
+    for (outbound_id, inbound_id) in [(1, 2), (2, 3), (3, 4), (1, 5), (5, 4), (4, 6)] {
+        db.create_edge(&edge(outbound_id, "follows", inbound_id))?;
+    }
+    db.create_edge(&edge(1, "likes", 6))?;
+    Ok(())
+}
+
+fn edge(outbound_id: u128, t: &str, inbound_id: u128) -> Edge {
+    Edge::new(
+        Uuid::from_u128(outbound_id),
+        Identifier::new(t).unwrap(),
+        Uuid::from_u128(inbound_id),
+    )
+}
+
+fn path_ids(path: &Path) -> Vec<u128> {
+    path.vertices.iter().map(|v| v.id.as_u128()).collect()
+}
+
+fn get_paths<D: Datastore>(db: &Database<D>, q: ShortestPathQuery) -> Result<Vec<Path>, Error> {
+    Ok(extract_paths(db.get(q)?).unwrap())
+}
+
+pub fn should_get_shortest_path<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let follows = Identifier::new("follows")?;
+
+    let paths = get_paths(
+        db,
+        ShortestPathQuery::new(Uuid::from_u128(1), Uuid::from_u128(6)).t(follows),
+    )?;
+    assert_eq!(paths.len(), 1);
+    assert_eq!(path_ids(&paths[0]), vec![1, 5, 4, 6]);
+    assert_eq!(
+        paths[0].edges,
+        vec![edge(1, "follows", 5), edge(5, "follows", 4), edge(4, "follows", 6)]
+    );
+    assert_eq!(paths[0].cost, 3.0);
+
+    let paths = get_paths(db, ShortestPathQuery::new(Uuid::from_u128(1), Uuid::from_u128(6)))?;
+    assert_eq!(paths[0].edges, vec![edge(1, "likes", 6)]);
+
+    let paths = get_paths(db, ShortestPathQuery::new(Uuid::from_u128(2), Uuid::from_u128(6)))?;
+    assert_eq!(path_ids(&paths[0]), vec![2, 3, 4, 6]);
+
+    let paths = get_paths(db, ShortestPathQuery::new(Uuid::from_u128(3), Uuid::from_u128(3)))?;
+    assert_eq!(path_ids(&paths[0]), vec![3]);
+    assert!(paths[0].is_empty());
+
+    let q = ShortestPathQuery::new(Uuid::from_u128(1), Uuid::from_u128(6)).t(follows);
+    assert_eq!(extract_count(db.get(q.count()?)?), Some(1));
+    Ok(())
+}
+
+pub fn should_not_get_unreachable_shortest_path<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let follows = Identifier::new("follows")?;
+
+    // Edges are only followed in their outbound direction
+    let paths = get_paths(db, ShortestPathQuery::new(Uuid::from_u128(6), Uuid::from_u128(1)))?;
+    assert_eq!(paths, vec![]);
+
+    let q = ShortestPathQuery::new(Uuid::from_u128(1), Uuid::from_u128(6))
+        .t(follows)
+        .max_depth(2);
+    assert_eq!(get_paths(db, q)?, vec![]);
+
+    let paths = get_paths(db, ShortestPathQuery::new(Uuid::from_u128(1), Uuid::from_u128(7)))?;
+    assert_eq!(paths, vec![]);
+    Ok(())
+}
+
+pub fn should_get_weighted_shortest_path<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let cost = Identifier::new("cost")?;
+    for (outbound_id, t, inbound_id, weight) in [
+        (1, "follows", 2, ijson!(1)),
+        (2, "follows", 3, ijson!(1)),
+        (3, "follows", 4, ijson!(1.5)),
+        (1, "follows", 5, ijson!(5)),
+        (5, "follows", 4, ijson!(1)),
+        (4, "follows", 6, ijson!(1)),
+        (1, "likes", 6, ijson!(10)),
+    ] {
+        let q = SpecificEdgeQuery::single(edge(outbound_id, t, inbound_id));
+        db.set_properties(q, cost, &weight)?;
+    }
+    let q = ShortestPathQuery::new(Uuid::from_u128(1), Uuid::from_u128(6));
+
+    let paths = get_paths(db, q.clone().weighted_by(cost, InvalidWeight::Fail))?;
+    assert_eq!(path_ids(&paths[0]), vec![1, 2, 3, 4, 6]);
+    assert_eq!(paths[0].cost, 4.5);
+
+    // 4 is reached more cheaply through 3, but that path is too long
+    let paths = get_paths(db, q.clone().max_depth(3).weighted_by(cost, InvalidWeight::Fail))?;
+    assert_eq!(path_ids(&paths[0]), vec![1, 5, 4, 6]);
+    assert_eq!(paths[0].cost, 7.0);
+
+    let paths = get_paths(db, q.clone().max_depth(1).weighted_by(cost, InvalidWeight::Fail))?;
+    assert_eq!(paths[0].edges, vec![edge(1, "likes", 6)]);
+    assert_eq!(paths[0].cost, 10.0);
+
+    db.set_properties(SpecificEdgeQuery::single(edge(2, "follows", 3)), cost, &ijson!("cheap"))?;
+
+    let result = db.get(q.clone().weighted_by(cost, InvalidWeight::Fail));
+    expect_err!(result, Error::InvalidWeight { .. });
+
+    let paths = get_paths(db, q.clone().weighted_by(cost, InvalidWeight::Skip))?;
+    assert_eq!(path_ids(&paths[0]), vec![1, 5, 4, 6]);
+
+    let paths = get_paths(db, q.clone().weighted_by(cost, InvalidWeight::Default(ijson!(0.5))))?;
+    assert_eq!(path_ids(&paths[0]), vec![1, 2, 3, 4, 6]);
+    assert_eq!(paths[0].cost, 4.0);
+
+    let result = db.get(q.weighted_by(cost, InvalidWeight::Default(ijson!(null))));
+    expect_err!(result, Error::Invalid(errors::ValidationError::InvalidValue));
+    Ok(())
+}
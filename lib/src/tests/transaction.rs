@@ -0,0 +1,142 @@
+use super::util;
+use crate::util::{extract_vertex_properties, extract_vertices};
+use crate::{
+    ijson, AllEdgeQuery, AllVertexQuery, Database, DatabaseTransaction, Datastore, Edge, Error, GetOptions, Identifier,
+    QueryExt, SpecificVertexQuery, VertexWithPropertyValueQuery,
+};
+
+// Datastores aren't required to support buffered transactions, in which case
+// the tests are skipped.
+fn begin<D: Datastore>(db: &Database<D>) -> Result<Option<DatabaseTransaction<'_, D>>, Error> {
+    match db.begin() {
+        Ok(txn) => Ok(Some(txn)),
+        Err(Error::Unsupported) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn should_commit_transaction<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let mut txn = match begin(db)? {
+        Some(txn) => txn,
+        None => return Ok(()),
+    };
+
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    let outbound_id = txn.create_vertex_from_type(vertex_t)?;
+    let inbound_id = txn.create_vertex_from_type(vertex_t)?;
+    let edge = Edge::new(outbound_id, Identifier::new("test_edge_type")?, inbound_id);
+    assert!(txn.create_edge(&edge)?);
+    txn.set_properties(
+        SpecificVertexQuery::single(outbound_id),
+        Identifier::new("foo")?,
+        &ijson!(true),
+    )?;
+    txn.commit()?;
+
+    let vertices = util::get_vertices(db, AllVertexQuery)?;
+    assert_eq!(vertices.len(), 2);
+    let edges = util::get_edges(db, AllEdgeQuery)?;
+    assert_eq!(edges, vec![edge]);
+    let props = util::get_all_vertex_properties(db, SpecificVertexQuery::single(outbound_id))?;
+    assert_eq!(props.len(), 1);
+    assert_eq!(props[0].props[0].value, ijson!(true));
+    Ok(())
+}
+
+pub fn should_read_writes_in_transaction<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let mut txn = match begin(db)? {
+        Some(txn) => txn,
+        None => return Ok(()),
+    };
+
+    let id = txn.create_vertex_from_type(Identifier::new("test_vertex_type")?)?;
+    let q = SpecificVertexQuery::single(id);
+    let vertices = extract_vertices(txn.get(q.clone())?).unwrap();
+    assert_eq!(vertices.len(), 1);
+    assert_eq!(vertices[0].id, id);
+
+    txn.set_properties(q.clone(), Identifier::new("foo")?, &ijson!(1))?;
+    txn.set_properties(q.clone(), Identifier::new("foo")?, &ijson!(2))?;
+    let props = extract_vertex_properties(txn.get(q.clone().properties()?)?).unwrap();
+    assert_eq!(props.len(), 1);
+    assert_eq!(props[0].props.len(), 1);
+    assert_eq!(props[0].props[0].value, ijson!(2));
+
+    txn.delete(q.clone())?;
+    let vertices = extract_vertices(txn.get(q)?).unwrap();
+    assert!(vertices.is_empty());
+    txn.commit()?;
+
+    assert_eq!(util::get_vertex_count(db)?, 0);
+    Ok(())
+}
+
+pub fn should_rollback_transaction<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    let property_name = Identifier::new("foo")?;
+    db.index_property(property_name)?;
+    let (outbound_id, inbound_ids) = util::create_edges(db)?;
+    db.set_properties(SpecificVertexQuery::single(outbound_id), property_name, &ijson!(1))?;
+
+    let mut txn = match begin(db)? {
+        Some(txn) => txn,
+        None => return Ok(()),
+    };
+
+    let created_id = txn.create_vertex_from_type(vertex_t)?;
+    txn.create_edge(&Edge::new(created_id, Identifier::new("test_edge_type")?, outbound_id))?;
+    txn.set_properties(SpecificVertexQuery::single(outbound_id), property_name, &ijson!(2))?;
+    txn.index_property(Identifier::new("bar")?)?;
+    txn.delete(SpecificVertexQuery::new(inbound_ids.to_vec()))?;
+    txn.rollback()?;
+
+    let vertices = util::get_vertices(db, AllVertexQuery)?;
+    assert_eq!(vertices.len(), 6);
+    assert!(vertices.iter().all(|v| v.id != created_id));
+    let edges = util::get_edges(db, AllEdgeQuery)?;
+    assert_eq!(edges.len(), 5);
+    assert!(edges.iter().all(|e| e.outbound_id == outbound_id));
+
+    let vertices = util::get_vertices(db, VertexWithPropertyValueQuery::new(property_name, ijson!(1)))?;
+    assert_eq!(vertices.len(), 1);
+    assert_eq!(vertices[0].id, outbound_id);
+    let vertices = util::get_vertices(db, VertexWithPropertyValueQuery::new(property_name, ijson!(2)))?;
+    assert!(vertices.is_empty());
+
+    let result = util::get_vertices(db, AllVertexQuery.with_property(Identifier::new("bar")?)?);
+    expect_err!(result, Error::NotIndexed);
+    Ok(())
+}
+
+pub fn should_discard_dropped_transaction<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    {
+        let mut txn = match begin(db)? {
+            Some(txn) => txn,
+            None => return Ok(()),
+        };
+        txn.create_vertex_from_type(Identifier::new("test_vertex_type")?)?;
+    }
+
+    assert_eq!(util::get_vertex_count(db)?, 0);
+    Ok(())
+}
+
+pub fn should_get_with_snapshot<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let (outbound_id, _) = util::create_edges(db)?;
+    let q = SpecificVertexQuery::single(outbound_id).outbound()?.outbound()?;
+    let options = GetOptions {
+        snapshot: true,
+        ..GetOptions::default()
+    };
+
+    match db.get_with_options(q, options) {
+        Ok(output) => {
+            let vertices = extract_vertices(output).unwrap();
+            assert_eq!(vertices.len(), 5);
+            assert!(vertices.iter().all(|v| v.id == outbound_id));
+            Ok(())
+        }
+        Err(Error::Unsupported) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,82 @@
+use crate::util::{extract_count, extract_vertices};
+use crate::{
+    CountQueryExt, Database, Datastore, Edge, EdgeDirection, Error, Identifier, QueryExt, SpecificVertexQuery, Vertex,
+};
+
+use uuid::Uuid;
+
+// Creates the vertices 1 through 5, connected by `follows` edges 1->2,
+// 1->3, 2->3, 3->4, 4->1 and 4->5, and a `likes` edge 1->5.
+fn create_graph<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    let vertex_t = Identifier::new("test_vertex_type")?;
+    for id in 1..=5 {
+        db.create_vertex(&Vertex::with_id(Uuid::from_u128(id), vertex_t))?;
+    }
+    let follows = Identifier::new("follows")?;
+    for (outbound_id, inbound_id) in [(1, 2), (1, 3), (2, 3), (3, 4), (4, 1), (4, 5)] {
+        db.create_edge(&Edge::new(
+            Uuid::from_u128(outbound_id),
+            follows,
+            Uuid::from_u128(inbound_id),
+        ))?;
+    }
+    db.create_edge(&Edge::new(
+        Uuid::from_u128(1),
+        Identifier::new("likes")?,
+        Uuid::from_u128(5),
+    ))?;
+    Ok(())
+}
+
+fn traversed_ids<D: Datastore, Q: Into<crate::Query>>(db: &Database<D>, q: Q) -> Result<Vec<u128>, Error> {
+    let vertices = extract_vertices(db.get(q)?).unwrap();
+    Ok(vertices.into_iter().map(|v| v.id.as_u128()).collect())
+}
+
+pub fn should_traverse_within_depths<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let follows = Identifier::new("follows")?;
+    let start = SpecificVertexQuery::single(Uuid::from_u128(1));
+
+    // 3 is reachable at depths 1 and 2, but is only output at 1
+    let q = start.clone().traverse(EdgeDirection::Outbound, 2)?.t(follows);
+    assert_eq!(traversed_ids(db, q.clone())?, vec![1, 2, 3, 4]);
+    assert_eq!(extract_count(db.get(q.count()?)?), Some(4));
+
+    // The cycle back to 1 isn't followed
+    let q = start
+        .clone()
+        .traverse(EdgeDirection::Outbound, 10)?
+        .min_depth(1)
+        .t(follows);
+    assert_eq!(traversed_ids(db, q)?, vec![2, 3, 4, 5]);
+
+    let q = start.clone().traverse(EdgeDirection::Outbound, 1)?;
+    assert_eq!(traversed_ids(db, q)?, vec![1, 2, 3, 5]);
+
+    let q = start.traverse(EdgeDirection::Outbound, 1)?.min_depth(2);
+    assert_eq!(traversed_ids(db, q)?, Vec::<u128>::new());
+    Ok(())
+}
+
+pub fn should_traverse_inbound<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    let q = SpecificVertexQuery::single(Uuid::from_u128(5))
+        .traverse(EdgeDirection::Inbound, 10)?
+        .t(Identifier::new("follows")?);
+    assert_eq!(traversed_ids(db, q)?, vec![5, 4, 3, 1, 2]);
+    Ok(())
+}
+
+pub fn should_limit_traverse_per_depth<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_graph(db)?;
+    // Only the first vertex at each depth is traversed through, so 3 is
+    // reached through 2
+    let q = SpecificVertexQuery::new(vec![Uuid::from_u128(1), Uuid::from_u128(1)])
+        .traverse(EdgeDirection::Outbound, 3)?
+        .t(Identifier::new("follows")?)
+        .limit(1);
+    assert_eq!(traversed_ids(db, q.clone())?, vec![1, 2, 3, 4]);
+    assert_eq!(traversed_ids(db, q.min_depth(3))?, vec![4]);
+    Ok(())
+}
@@ -55,6 +55,32 @@ pub fn should_get_no_vertices_with_type_filter<D: Datastore>(db: &Database<D>) -
     Ok(())
 }
 
+pub fn should_get_vertices_with_type_filter<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
+    create_vertices(db)?;
+    let t = models::Identifier::new("rare_vertex_type")?;
+    let mut ids = vec![
+        db.create_vertex_from_type(t)?,
+        db.create_vertex_from_type(t)?,
+        db.create_vertex_from_type(t)?,
+    ];
+    ids.sort();
+
+    let range = util::get_vertices(db, RangeVertexQuery::new().t(t))?;
+    assert_eq!(range.iter().map(|v| v.id).collect::<Vec<_>>(), ids);
+    assert!(range.iter().all(|v| v.t == t));
+
+    // The limit and start ID apply to vertices of the type only
+    let range = util::get_vertices(db, RangeVertexQuery::new().t(t).limit(1).start_id(ids[1]))?;
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].id, ids[1]);
+
+    // Deleted vertices are dropped from the type index
+    db.delete(SpecificVertexQuery::single(ids[0]))?;
+    let range = util::get_vertices(db, RangeVertexQuery::new().t(t))?;
+    assert_eq!(range.iter().map(|v| v.id).collect::<Vec<_>>(), ids[1..].to_vec());
+    Ok(())
+}
+
 pub fn should_get_single_vertex<D: Datastore>(db: &Database<D>) -> Result<(), Error> {
     let vertex_t = models::Identifier::new("test_vertex_type")?;
     let id = db.create_vertex_from_type(vertex_t)?;
@@ -201,6 +227,7 @@ pub fn should_not_pipe_on_vertex_count<D: Datastore>(db: &Database<D>) -> Result
         direction: models::EdgeDirection::Outbound,
         limit: 1,
         t: None,
+        paths: false,
     };
     let result = db.get(q);
     expect_err!(result, errors::Error::OperationOnQuery);
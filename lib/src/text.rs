@@ -0,0 +1,1236 @@
+//! A Gremlin-like text syntax for queries.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::ops::{Bound, Range};
+use std::str::FromStr;
+
+use crate::errors::ValidationResult;
+use crate::{
+    CountQueryExt, Edge, EdgeDirection, EdgeWithPropertyPresenceQuery, EdgeWithPropertyRangeQuery,
+    EdgeWithPropertyValueQuery, Identifier, InvalidWeight, Json, JsonRange, NullsOrder, Query, QueryExt,
+    RangeEdgeQuery, RangeVertexQuery, ShortestPathQuery, SortOrder, SpecificEdgeQuery, SpecificVertexQuery,
+    VertexWithCompositePropertyQuery, VertexWithPropertyPresenceQuery, VertexWithPropertyRangeQuery,
+    VertexWithPropertyValueQuery,
+};
+
+use serde_json::{Map, Number, Value};
+use uuid::Uuid;
+
+/// An error from parsing the text of a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryParseError {
+    /// What went wrong.
+    pub message: String,
+    /// The byte range of the text where it went wrong.
+    pub span: Range<usize>,
+}
+
+impl QueryParseError {
+    fn new<S: Into<String>>(message: S, span: Range<usize>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl StdError for QueryParseError {}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.message, self.span.start, self.span.end)
+    }
+}
+
+type ParseResult<T> = Result<T, QueryParseError>;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Num(Number),
+    Punct(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{word}`"),
+            Token::Str(_) => write!(f, "a string"),
+            Token::Num(num) => write!(f, "`{num}`"),
+            Token::Punct(punct) => write!(f, "`{punct}`"),
+            Token::End => write!(f, "the end of the query"),
+        }
+    }
+}
+
+// Two-character punctuation comes first, so that it's matched greedily.
+const PUNCTUATION: [&str; 15] = [
+    "!=", ">=", "<=", "(", ")", "[", "]", "{", "}", ",", ".", "=", ">", "<", ":",
+];
+
+fn is_word_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn tokenize(text: &str) -> ParseResult<Vec<(Token, Range<usize>)>> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while let Some(c) = text[pos..].chars().next() {
+        let start = pos;
+        if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        }
+
+        let token = if is_word_start(c) {
+            pos = text[pos..].find(|c| !is_word_char(c)).map_or(text.len(), |i| pos + i);
+            Token::Word(text[start..pos].to_string())
+        } else if c == '-' || c.is_ascii_digit() {
+            pos = text[pos + 1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '+' || c == '-'))
+                .map_or(text.len(), |i| pos + 1 + i);
+            let num = serde_json::from_str(&text[start..pos])
+                .map_err(|_| QueryParseError::new("invalid number", start..pos))?;
+            Token::Num(num)
+        } else if c == '"' {
+            let mut escaped = false;
+            let len = text[pos + 1..].find(|c| {
+                let end = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                end
+            });
+            pos = match len {
+                Some(len) => pos + len + 2,
+                None => return Err(QueryParseError::new("unterminated string", start..text.len())),
+            };
+            let s = serde_json::from_str(&text[start..pos])
+                .map_err(|_| QueryParseError::new("invalid string", start..pos))?;
+            Token::Str(s)
+        } else if let Some(punct) = PUNCTUATION.iter().find(|punct| text[pos..].starts_with(**punct)) {
+            pos += punct.len();
+            Token::Punct(punct)
+        } else {
+            return Err(QueryParseError::new(
+                format!("unexpected character `{c}`"),
+                start..start + c.len_utf8(),
+            ));
+        };
+
+        tokens.push((token, start..pos));
+    }
+
+    tokens.push((Token::End, text.len()..text.len()));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+// A single condition in a filter, e.g. `name>=1`.
+struct Condition {
+    name: Identifier,
+    op: Option<(Op, Json)>,
+    span: Range<usize>,
+}
+
+enum Filter {
+    Presence(Identifier),
+    Value(Identifier, Json, bool),
+    Range(Identifier, JsonRange),
+}
+
+// How deeply JSON arrays and objects can be nested, as with `serde_json`.
+// Values are parsed recursively, so this keeps deep nesting from
+// overflowing the stack.
+const MAX_JSON_DEPTH: usize = 128;
+
+struct Parser {
+    tokens: Vec<(Token, Range<usize>)>,
+    pos: usize,
+    // How many JSON arrays and objects are being parsed.
+    json_depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn peek_second(&self) -> &Token {
+        &self.tokens[(self.pos + 1).min(self.tokens.len() - 1)].0
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.tokens[self.pos].1.clone()
+    }
+
+    // The end of the last consumed token, for building the spans of
+    // multi-token constructs.
+    fn last_end(&self) -> usize {
+        match self.pos {
+            0 => 0,
+            pos => self.tokens[pos - 1].1.end,
+        }
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        // The end token is never consumed, so that there's always something
+        // to peek at.
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> QueryParseError {
+        QueryParseError::new(format!("expected {expected}, found {}", self.peek()), self.span())
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Token::Punct(p) if *p == punct) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> ParseResult<()> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{punct}`")))
+        }
+    }
+
+    fn word(&mut self, expected: &str) -> ParseResult<(String, Range<usize>)> {
+        let span = self.span();
+        match self.peek() {
+            Token::Word(word) => {
+                let word = word.clone();
+                self.next();
+                Ok((word, span))
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    // Parses a comma-separated list, up to and including the closing
+    // punctuation.
+    fn list<T, F>(&mut self, close: &str, mut item: F) -> ParseResult<Vec<T>>
+    where
+        F: FnMut(&mut Self) -> ParseResult<T>,
+    {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            if !self.eat(",") {
+                return Err(self.unexpected(&format!("`,` or `{close}`")));
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> ParseResult<Identifier> {
+        let span = self.span();
+        let s = match self.peek() {
+            Token::Word(s) | Token::Str(s) => s.clone(),
+            _ => return Err(self.unexpected("a name")),
+        };
+        self.next();
+        Identifier::new(s).map_err(|err| QueryParseError::new(format!("invalid name: {err}"), span))
+    }
+
+    fn uuid(&mut self) -> ParseResult<Uuid> {
+        let span = self.span();
+        match self.peek() {
+            Token::Str(s) => {
+                let id = Uuid::parse_str(s).map_err(|_| QueryParseError::new("invalid UUID", span))?;
+                self.next();
+                Ok(id)
+            }
+            _ => Err(self.unexpected("a UUID string")),
+        }
+    }
+
+    fn limit(&mut self) -> ParseResult<u32> {
+        let span = self.span();
+        match self.peek() {
+            Token::Num(num) => {
+                let limit = num
+                    .as_u64()
+                    .and_then(|limit| u32::try_from(limit).ok())
+                    .ok_or_else(|| QueryParseError::new("limits must be integers that fit in 32 bits", span))?;
+                self.next();
+                Ok(limit)
+            }
+            _ => Err(self.unexpected("a limit")),
+        }
+    }
+
+    fn value(&mut self) -> ParseResult<Value> {
+        let value = match self.peek() {
+            Token::Str(s) => Value::String(s.clone()),
+            Token::Num(num) => Value::Number(num.clone()),
+            Token::Word(word) if word == "true" => Value::Bool(true),
+            Token::Word(word) if word == "false" => Value::Bool(false),
+            Token::Word(word) if word == "null" => Value::Null,
+            Token::Punct("[") | Token::Punct("{") => {
+                if self.json_depth == MAX_JSON_DEPTH {
+                    return Err(QueryParseError::new(
+                        format!("JSON values can't be nested more than {MAX_JSON_DEPTH} deep"),
+                        self.span(),
+                    ));
+                }
+                self.json_depth += 1;
+                let value = self.collection();
+                self.json_depth -= 1;
+                return value;
+            }
+            _ => return Err(self.unexpected("a JSON value")),
+        };
+        self.next();
+        Ok(value)
+    }
+
+    // Parses a JSON array or object.
+    fn collection(&mut self) -> ParseResult<Value> {
+        match self.next() {
+            Token::Punct("[") => Ok(Value::Array(self.list("]", Self::value)?)),
+            _ => {
+                let entries = self.list("}", |p| {
+                    let key = match p.peek() {
+                        Token::Str(key) => key.clone(),
+                        _ => return Err(p.unexpected("a string key")),
+                    };
+                    p.next();
+                    p.expect(":")?;
+                    Ok((key, p.value()?))
+                })?;
+                Ok(Value::Object(entries.into_iter().collect::<Map<String, Value>>()))
+            }
+        }
+    }
+
+    fn edge(&mut self) -> ParseResult<Edge> {
+        self.expect("(")?;
+        let outbound_id = self.uuid()?;
+        self.expect(",")?;
+        let t = self.identifier()?;
+        self.expect(",")?;
+        let inbound_id = self.uuid()?;
+        self.expect(")")?;
+        Ok(Edge::new(outbound_id, t, inbound_id))
+    }
+
+    fn condition(&mut self) -> ParseResult<Condition> {
+        let start = self.span().start;
+        let name = self.identifier()?;
+        let op = match self.peek() {
+            Token::Punct("=") => Some(Op::Eq),
+            Token::Punct("!=") => Some(Op::Ne),
+            Token::Punct(">") => Some(Op::Gt),
+            Token::Punct(">=") => Some(Op::Gte),
+            Token::Punct("<") => Some(Op::Lt),
+            Token::Punct("<=") => Some(Op::Lte),
+            _ => None,
+        };
+        let op = match op {
+            Some(op) => {
+                self.next();
+                Some((op, Json::new(self.value()?)))
+            }
+            None => None,
+        };
+        Ok(Condition {
+            name,
+            op,
+            span: start..self.last_end(),
+        })
+    }
+
+    // Parses the conditions of a filter, after its opening parenthesis.
+    fn filter(&mut self) -> ParseResult<(Filter, Range<usize>)> {
+        let start = self.span().start;
+        let conditions = self.list(")", Self::condition)?;
+        let span = start..self.last_end() - 1;
+
+        let filter = match conditions.as_slice() {
+            [] => return Err(QueryParseError::new("expected a property filter", span)),
+            [Condition { name, op: None, .. }] => Filter::Presence(*name),
+            [Condition {
+                name,
+                op: Some((Op::Eq, value)),
+                ..
+            }] => Filter::Value(*name, value.clone(), true),
+            [Condition {
+                name,
+                op: Some((Op::Ne, value)),
+                ..
+            }] => Filter::Value(*name, value.clone(), false),
+            _ => {
+                let name = conditions[0].name;
+                let mut range = JsonRange::new(Bound::Unbounded, Bound::Unbounded);
+                for condition in conditions {
+                    let bound = match condition.op {
+                        Some((Op::Gt, _)) | Some((Op::Gte, _)) => &mut range.start,
+                        Some((Op::Lt, _)) | Some((Op::Lte, _)) => &mut range.end,
+                        _ => {
+                            return Err(QueryParseError::new(
+                                "only bounds can be combined in a filter",
+                                condition.span,
+                            ))
+                        }
+                    };
+                    if condition.name != name || *bound != Bound::Unbounded {
+                        return Err(QueryParseError::new(
+                            "a filter can only combine a lower and an upper bound on the same property",
+                            condition.span,
+                        ));
+                    }
+                    *bound = match condition.op {
+                        Some((Op::Gt, value)) | Some((Op::Lt, value)) => Bound::Excluded(value),
+                        Some((_, value)) => Bound::Included(value),
+                        None => unreachable!(),
+                    };
+                }
+                Filter::Range(name, range)
+            }
+        };
+
+        Ok((filter, span))
+    }
+
+    // Parses the names and values of a composite index filter, after its
+    // opening parenthesis.
+    fn composite(&mut self) -> ParseResult<(Vec<Identifier>, Vec<Json>, Range<usize>)> {
+        let start = self.span().start;
+        let conditions = self.list(")", Self::condition)?;
+        let mut names = Vec::with_capacity(conditions.len());
+        let mut values = Vec::with_capacity(conditions.len());
+        for condition in conditions {
+            match condition.op {
+                Some((Op::Eq, value)) if values.len() == names.len() => values.push(value),
+                Some((Op::Eq, _)) => {
+                    return Err(QueryParseError::new(
+                        "composite values must come before names without values",
+                        condition.span,
+                    ))
+                }
+                Some(_) => {
+                    return Err(QueryParseError::new(
+                        "composite filters only support `=`",
+                        condition.span,
+                    ))
+                }
+                None => {}
+            }
+            names.push(condition.name);
+        }
+        Ok((names, values, start..self.last_end() - 1))
+    }
+
+    // Parses the rest of a source after `V(` or `E(`.
+    fn source(&mut self, vertex: bool) -> ParseResult<Query> {
+        let lookahead = (self.peek().clone(), self.peek_second().clone());
+        match lookahead {
+            (Token::Punct(")"), _) => {
+                self.next();
+                Ok(if vertex { Query::AllVertex } else { Query::AllEdge })
+            }
+            (Token::Punct("["), _) => {
+                self.next();
+                let q = self.specific_source(vertex, "]")?;
+                self.expect(")")?;
+                Ok(q)
+            }
+            (Token::Str(_), _) if vertex => self.specific_source(vertex, ")"),
+            (Token::Punct("("), _) if !vertex => self.specific_source(vertex, ")"),
+            (Token::Word(word), Token::Punct("(")) if word == "has" => {
+                self.next();
+                self.next();
+                let (filter, span) = self.filter()?;
+                self.expect(")")?;
+                Ok(match (filter, vertex) {
+                    (Filter::Presence(name), true) => VertexWithPropertyPresenceQuery::new(name).into(),
+                    (Filter::Presence(name), false) => EdgeWithPropertyPresenceQuery::new(name).into(),
+                    (Filter::Value(name, value, true), true) => VertexWithPropertyValueQuery::new(name, value).into(),
+                    (Filter::Value(name, value, true), false) => EdgeWithPropertyValueQuery::new(name, value).into(),
+                    (Filter::Value(_, _, false), _) => {
+                        return Err(QueryParseError::new("`!=` is only supported in `has` steps", span))
+                    }
+                    (Filter::Range(name, range), true) => VertexWithPropertyRangeQuery::new(name, range).into(),
+                    (Filter::Range(name, range), false) => EdgeWithPropertyRangeQuery::new(name, range).into(),
+                })
+            }
+            (Token::Word(word), Token::Punct("(")) if word == "composite" => {
+                self.next();
+                self.next();
+                let (names, values, span) = self.composite()?;
+                self.expect(")")?;
+                if !vertex {
+                    return Err(QueryParseError::new(
+                        "composite filters are only supported for vertices",
+                        span,
+                    ));
+                }
+                Ok(validated(VertexWithCompositePropertyQuery::new(names, values), span)?.into())
+            }
+            _ => {
+                let mut vertex_q = RangeVertexQuery::new();
+                let mut edge_q = RangeEdgeQuery::new();
+                self.list(")", |p| {
+                    let (key, span) = p.word("an argument")?;
+                    p.expect("=")?;
+                    match key.as_str() {
+                        "type" => {
+                            let t = p.identifier()?;
+                            vertex_q.t = Some(t);
+                            edge_q.t = Some(t);
+                        }
+                        "start" if vertex => vertex_q.start_id = Some(p.uuid()?),
+                        "start" => edge_q.start_edge = Some(p.edge()?),
+                        "limit" => {
+                            let limit = p.limit()?;
+                            vertex_q.limit = limit;
+                            edge_q.limit = limit;
+                        }
+                        _ => return Err(QueryParseError::new(format!("unknown argument `{key}`"), span)),
+                    }
+                    Ok(())
+                })?;
+                Ok(if vertex { vertex_q.into() } else { edge_q.into() })
+            }
+        }
+    }
+
+    fn path_source(&mut self) -> ParseResult<Query> {
+        let source_id = self.uuid()?;
+        self.expect(",")?;
+        let mut q = ShortestPathQuery::new(source_id, self.uuid()?);
+        let mut weight = None;
+        let mut invalid = None;
+        while self.eat(",") {
+            let (key, span) = self.word("an argument")?;
+            self.expect("=")?;
+            match key.as_str() {
+                "type" => q.t = Some(self.identifier()?),
+                "max" => q.max_depth = self.limit()?,
+                "weight" => weight = Some(self.identifier()?),
+                "invalid" if weight.is_none() => {
+                    return Err(QueryParseError::new("`invalid` must come after `weight`", span))
+                }
+                "invalid" => invalid = Some(self.invalid_weight()?),
+                _ => return Err(QueryParseError::new(format!("unknown argument `{key}`"), span)),
+            }
+        }
+        self.expect(")")?;
+        if let Some(name) = weight {
+            q = q.weighted_by(name, invalid.unwrap_or(InvalidWeight::Fail));
+        }
+        Ok(q.into())
+    }
+
+    fn invalid_weight(&mut self) -> ParseResult<InvalidWeight> {
+        let invalid = match self.peek() {
+            Token::Word(word) if word == "skip" => InvalidWeight::Skip,
+            Token::Word(word) if word == "fail" => InvalidWeight::Fail,
+            _ => return Ok(InvalidWeight::Default(Json::new(self.value()?))),
+        };
+        self.next();
+        Ok(invalid)
+    }
+
+    fn specific_source(&mut self, vertex: bool, close: &str) -> ParseResult<Query> {
+        if vertex {
+            Ok(SpecificVertexQuery::new(self.list(close, Self::uuid)?).into())
+        } else {
+            Ok(SpecificEdgeQuery::new(self.list(close, Self::edge)?).into())
+        }
+    }
+
+    fn step(&mut self, q: Query) -> ParseResult<Query> {
+        let (name, span) = self.word("a step")?;
+        self.expect("(")?;
+        // Errors from building the query cover the whole step.
+        let step_span = |p: &Self| span.start..p.last_end();
+
+        match name.as_str() {
+            "out" | "in" => {
+                let t = if self.eat(")") {
+                    None
+                } else {
+                    let t = self.identifier()?;
+                    self.expect(")")?;
+                    Some(t)
+                };
+                let pipe = if name == "out" { q.outbound() } else { q.inbound() };
+                let pipe = validated(pipe, step_span(self))?;
+                Ok(match t {
+                    Some(t) => pipe.t(t).into(),
+                    None => pipe.into(),
+                })
+            }
+            "limit" => {
+                let limit = self.limit()?;
+                self.expect(")")?;
+                match q {
+                    Query::Pipe(q) => Ok(q.limit(limit).into()),
+                    Query::AllVertex => Ok(RangeVertexQuery::new().limit(limit).into()),
+                    Query::RangeVertex(q) => Ok(q.limit(limit).into()),
+                    Query::AllEdge => Ok(RangeEdgeQuery::new().limit(limit).into()),
+                    Query::RangeEdge(q) => Ok(q.limit(limit).into()),
+                    _ => Err(QueryParseError::new(
+                        "`limit` can only follow `out`, `in`, or a source that gets a range",
+                        step_span(self),
+                    )),
+                }
+            }
+            "has" => {
+                let (filter, _) = self.filter()?;
+                let span = step_span(self);
+                Ok(match filter {
+                    Filter::Presence(name) => validated(q.with_property(name), span)?.into(),
+                    Filter::Value(name, value, true) => validated(q.with_property_equal_to(name, value), span)?.into(),
+                    Filter::Value(name, value, false) => {
+                        validated(q.with_property_not_equal_to(name, value), span)?.into()
+                    }
+                    Filter::Range(name, range) => validated(q.with_property_in_range(name, range), span)?.into(),
+                })
+            }
+            "traverse" => {
+                let (word, word_span) = self.word("`out` or `in`")?;
+                let direction = match word.as_str() {
+                    "out" => EdgeDirection::Outbound,
+                    "in" => EdgeDirection::Inbound,
+                    _ => return Err(QueryParseError::new(format!("unknown direction `{word}`"), word_span)),
+                };
+                let mut max_depth = None;
+                let mut min_depth = 0;
+                let mut t = None;
+                let mut limit = u32::MAX;
+                while self.eat(",") {
+                    let (key, span) = self.word("an argument")?;
+                    self.expect("=")?;
+                    match key.as_str() {
+                        "max" => max_depth = Some(self.limit()?),
+                        "min" => min_depth = self.limit()?,
+                        "type" => t = Some(self.identifier()?),
+                        "limit" => limit = self.limit()?,
+                        _ => return Err(QueryParseError::new(format!("unknown argument `{key}`"), span)),
+                    }
+                }
+                self.expect(")")?;
+                let max_depth =
+                    max_depth.ok_or_else(|| QueryParseError::new("`traverse` needs a `max` depth", step_span(self)))?;
+                let traverse = validated(q.traverse(direction, max_depth), step_span(self))?
+                    .min_depth(min_depth)
+                    .limit(limit);
+                Ok(match t {
+                    Some(t) => traverse.t(t).into(),
+                    None => traverse.into(),
+                })
+            }
+            "hasNot" => {
+                let name = self.identifier()?;
+                self.expect(")")?;
+                Ok(validated(q.without_property(name), step_span(self))?.into())
+            }
+            "props" => {
+                let name = if self.eat(")") {
+                    None
+                } else {
+                    let name = self.identifier()?;
+                    self.expect(")")?;
+                    Some(name)
+                };
+                let props = validated(q.properties(), step_span(self))?;
+                Ok(match name {
+                    Some(name) => props.name(name).into(),
+                    None => props.into(),
+                })
+            }
+            "paths" => {
+                self.expect(")")?;
+                match q {
+                    Query::Pipe(q) => Ok(q.paths().into()),
+                    _ => Err(QueryParseError::new(
+                        "`paths` can only follow `out` or `in`",
+                        step_span(self),
+                    )),
+                }
+            }
+            "dedup" => {
+                self.expect(")")?;
+                Ok(validated(q.dedup(), step_span(self))?.into())
+            }
+            "orderBy" => {
+                let name = self.identifier()?;
+                let mut order = SortOrder::Ascending;
+                let mut nulls = NullsOrder::Last;
+                let mut offset = 0;
+                let mut limit = u32::MAX;
+                while self.eat(",") {
+                    let (key, span) = self.word("an argument")?;
+                    match key.as_str() {
+                        "asc" => order = SortOrder::Ascending,
+                        "desc" => order = SortOrder::Descending,
+                        "nulls" => {
+                            self.expect("=")?;
+                            let (word, word_span) = self.word("`first` or `last`")?;
+                            nulls = match word.as_str() {
+                                "first" => NullsOrder::First,
+                                "last" => NullsOrder::Last,
+                                _ => {
+                                    return Err(QueryParseError::new(
+                                        format!("unknown nulls order `{word}`"),
+                                        word_span,
+                                    ))
+                                }
+                            };
+                        }
+                        "offset" => {
+                            self.expect("=")?;
+                            offset = self.limit()?;
+                        }
+                        "limit" => {
+                            self.expect("=")?;
+                            limit = self.limit()?;
+                        }
+                        _ => return Err(QueryParseError::new(format!("unknown argument `{key}`"), span)),
+                    }
+                }
+                self.expect(")")?;
+                let mut order_by = validated(q.order_by(name), step_span(self))?
+                    .nulls(nulls)
+                    .offset(offset)
+                    .limit(limit);
+                if order == SortOrder::Descending {
+                    order_by = order_by.descending();
+                }
+                Ok(order_by.into())
+            }
+            "include" => {
+                self.expect(")")?;
+                Ok(q.include().into())
+            }
+            "count" => {
+                self.expect(")")?;
+                Ok(validated(q.count(), step_span(self))?.into())
+            }
+            _ => Err(QueryParseError::new(format!("unknown step `{name}`"), span)),
+        }
+    }
+}
+
+fn validated<T>(result: ValidationResult<T>, span: Range<usize>) -> ParseResult<T> {
+    result.map_err(|err| QueryParseError::new(err.to_string(), span))
+}
+
+/// Parses a query from a Gremlin-like text syntax, e.g.
+/// `V(type=person).has(name="x").out(follows).limit(10).props()`.
+///
+/// A query starts with a source, which is one of:
+/// * `V()`: all vertices.
+/// * `V("<uuid>", ...)`: specific vertices.
+/// * `V(type=<type>, start="<uuid>", limit=<n>)`: a range of vertices. Each
+///   argument is optional.
+/// * `V(has(<filter>))`: vertices with a property, through its index.
+/// * `V(composite(a=<value>, b=<value>, c))`: vertices by the values of a
+///   prefix of a composite index.
+/// * `E()`, `E(("<uuid>", <type>, "<uuid>"), ...)`,
+///   `E(type=<type>, start=(...), limit=<n>)` and `E(has(<filter>))`: the
+///   same for edges.
+/// * `Path("<uuid>", "<uuid>", type=<type>, max=<n>)`: the shortest path
+///   between two vertices. `type` and `max` are optional. Paths can be
+///   weighted by an edge property with `weight=<name>`, followed by an
+///   optional `invalid=skip`, `invalid=fail` (the default) or
+///   `invalid=<value>` for edges without a valid weight.
+///
+/// Sources are followed by any number of steps:
+/// * `.out()`, `.out(<type>)`, `.in()` and `.in(<type>)`: pipes to outbound
+///   or inbound vertices or edges.
+/// * `.limit(<n>)`: limits a pipe or a source.
+/// * `.paths()`: makes a pipe output the path to each value, from the start
+///   of its chain of pipes.
+/// * `.has(<filter>)` and `.hasNot(<name>)`: filters by a property.
+/// * `.traverse(out, max=<n>, min=<n>, type=<type>, limit=<n>)` and
+///   `.traverse(in, ...)`: gets the vertices within a range of depths. Only
+///   `max` is required, and `limit` applies to each depth.
+/// * `.dedup()`: removes duplicate vertices or edges.
+/// * `.orderBy(<name>, desc, nulls=first, offset=<n>, limit=<n>)`: sorts
+///   vertices or edges by a property. Only the name is required; values
+///   are sorted in ascending order with nulls last by default.
+/// * `.props()` and `.props(<name>)`: gets properties.
+/// * `.include()`: includes the values so far in the output.
+/// * `.count()`: counts the values so far.
+///
+/// Filters are either a property name, `name=<value>`, `name!=<value>`, or
+/// up to two bounds such as `name>=<value>, name<<value>`. Values are JSON,
+/// and names and types can be quoted if they aren't valid bare words.
+impl FromStr for Query {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            json_depth: 0,
+        };
+
+        let (source, span) = parser.word("`V`, `E` or `Path`")?;
+        parser.expect("(")?;
+        let mut q = match source.as_str() {
+            "V" => parser.source(true)?,
+            "E" => parser.source(false)?,
+            "Path" => parser.path_source()?,
+            _ => return Err(QueryParseError::new(format!("unknown source `{source}`"), span)),
+        };
+
+        while parser.eat(".") {
+            q = parser.step(q)?;
+        }
+        match parser.peek() {
+            Token::End => Ok(q),
+            _ => Err(parser.unexpected("`.` or the end of the query")),
+        }
+    }
+}
+
+struct Name<'a>(&'a Identifier);
+
+impl<'a> fmt::Display for Name<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.0.as_str();
+        let mut chars = s.chars();
+        if chars.next().is_some_and(is_word_start) && chars.all(is_word_char) {
+            write!(f, "{s}")
+        } else {
+            write!(f, "{}", Value::String(s.to_string()))
+        }
+    }
+}
+
+struct EdgeText<'a>(&'a Edge);
+
+impl<'a> fmt::Display for EdgeText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let edge = self.0;
+        write!(
+            f,
+            "(\"{}\", {}, \"{}\")",
+            edge.outbound_id,
+            Name(&edge.t),
+            edge.inbound_id
+        )
+    }
+}
+
+fn write_list<T, F>(f: &mut fmt::Formatter, items: &[T], mut item: F) -> fmt::Result
+where
+    F: FnMut(&mut fmt::Formatter, &T) -> fmt::Result,
+{
+    for (i, value) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        item(f, value)?;
+    }
+    Ok(())
+}
+
+fn write_range_args(f: &mut fmt::Formatter, t: &Option<Identifier>, start: Option<String>, limit: u32) -> fmt::Result {
+    let mut args = Vec::new();
+    if let Some(t) = t {
+        args.push(format!("type={}", Name(t)));
+    }
+    if let Some(start) = start {
+        args.push(format!("start={start}"));
+    }
+    // Something has to distinguish this from a query for all values
+    if limit != u32::MAX || args.is_empty() {
+        args.push(format!("limit={limit}"));
+    }
+    write!(f, "{}", args.join(", "))
+}
+
+// Ranges without any bounds are written as presence filters, which match the
+// same values.
+fn write_range(f: &mut fmt::Formatter, name: &Identifier, range: &JsonRange) -> fmt::Result {
+    let name = Name(name);
+    let mut bounds = Vec::new();
+    match range.start {
+        Bound::Included(ref value) => bounds.push(format!("{name}>={}", **value)),
+        Bound::Excluded(ref value) => bounds.push(format!("{name}>{}", **value)),
+        Bound::Unbounded => {}
+    }
+    match range.end {
+        Bound::Included(ref value) => bounds.push(format!("{name}<={}", **value)),
+        Bound::Excluded(ref value) => bounds.push(format!("{name}<{}", **value)),
+        Bound::Unbounded => {}
+    }
+    if bounds.is_empty() {
+        write!(f, "{name}")
+    } else {
+        write!(f, "{}", bounds.join(", "))
+    }
+}
+
+/// Writes the query in the text syntax parsed by `Query::from_str`. The
+/// output parses back into an equivalent query.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Query::AllVertex => write!(f, "V()"),
+            Query::RangeVertex(q) => {
+                write!(f, "V(")?;
+                write_range_args(f, &q.t, q.start_id.map(|id| format!("\"{id}\"")), q.limit)?;
+                write!(f, ")")
+            }
+            Query::SpecificVertex(q) if q.ids.is_empty() => write!(f, "V([])"),
+            Query::SpecificVertex(q) => {
+                write!(f, "V(")?;
+                write_list(f, &q.ids, |f, id| write!(f, "\"{id}\""))?;
+                write!(f, ")")
+            }
+            Query::VertexWithPropertyPresence(q) => write!(f, "V(has({}))", Name(&q.name)),
+            Query::VertexWithPropertyValue(q) => write!(f, "V(has({}={}))", Name(&q.name), *q.value),
+            Query::VertexWithPropertyRange(q) => {
+                write!(f, "V(has(")?;
+                write_range(f, &q.name, &q.range)?;
+                write!(f, "))")
+            }
+            Query::VertexWithCompositeProperty(q) => {
+                write!(f, "V(composite(")?;
+                for (i, name) in q.names.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match q.values.get(i) {
+                        Some(value) => write!(f, "{}={}", Name(name), **value)?,
+                        None => write!(f, "{}", Name(name))?,
+                    }
+                }
+                write!(f, "))")
+            }
+
+            Query::AllEdge => write!(f, "E()"),
+            Query::RangeEdge(q) => {
+                write!(f, "E(")?;
+                write_range_args(f, &q.t, q.start_edge.as_ref().map(|e| EdgeText(e).to_string()), q.limit)?;
+                write!(f, ")")
+            }
+            Query::SpecificEdge(q) if q.edges.is_empty() => write!(f, "E([])"),
+            Query::SpecificEdge(q) => {
+                write!(f, "E(")?;
+                write_list(f, &q.edges, |f, edge| write!(f, "{}", EdgeText(edge)))?;
+                write!(f, ")")
+            }
+            Query::EdgeWithPropertyPresence(q) => write!(f, "E(has({}))", Name(&q.name)),
+            Query::EdgeWithPropertyValue(q) => write!(f, "E(has({}={}))", Name(&q.name), *q.value),
+            Query::EdgeWithPropertyRange(q) => {
+                write!(f, "E(has(")?;
+                write_range(f, &q.name, &q.range)?;
+                write!(f, "))")
+            }
+
+            Query::ShortestPath(q) => {
+                write!(f, "Path(\"{}\", \"{}\"", q.source_id, q.target_id)?;
+                if let Some(ref t) = q.t {
+                    write!(f, ", type={}", Name(t))?;
+                }
+                if q.max_depth != u32::MAX {
+                    write!(f, ", max={}", q.max_depth)?;
+                }
+                if let Some(ref weight) = q.weight {
+                    write!(f, ", weight={}", Name(&weight.name))?;
+                    match weight.invalid {
+                        InvalidWeight::Skip => write!(f, ", invalid=skip")?,
+                        InvalidWeight::Fail => {}
+                        InvalidWeight::Default(ref value) => write!(f, ", invalid={}", **value)?,
+                    }
+                }
+                write!(f, ")")
+            }
+
+            Query::Pipe(q) => {
+                let step = match q.direction {
+                    crate::EdgeDirection::Outbound => "out",
+                    crate::EdgeDirection::Inbound => "in",
+                };
+                match q.t {
+                    Some(ref t) => write!(f, "{}.{step}({})", q.inner, Name(t))?,
+                    None => write!(f, "{}.{step}()", q.inner)?,
+                }
+                if q.limit != u32::MAX {
+                    write!(f, ".limit({})", q.limit)?;
+                }
+                if q.paths {
+                    write!(f, ".paths()")?;
+                }
+                Ok(())
+            }
+            Query::PipeProperty(q) => match q.name {
+                Some(ref name) => write!(f, "{}.props({})", q.inner, Name(name)),
+                None => write!(f, "{}.props()", q.inner),
+            },
+            Query::PipeWithPropertyPresence(q) if q.exists => write!(f, "{}.has({})", q.inner, Name(&q.name)),
+            Query::PipeWithPropertyPresence(q) => write!(f, "{}.hasNot({})", q.inner, Name(&q.name)),
+            Query::PipeWithPropertyValue(q) => {
+                let op = if q.equal { "=" } else { "!=" };
+                write!(f, "{}.has({}{op}{})", q.inner, Name(&q.name), *q.value)
+            }
+            Query::PipeWithPropertyRange(q) => {
+                write!(f, "{}.has(", q.inner)?;
+                write_range(f, &q.name, &q.range)?;
+                write!(f, ")")
+            }
+            Query::Traverse(q) => {
+                let direction = match q.direction {
+                    crate::EdgeDirection::Outbound => "out",
+                    crate::EdgeDirection::Inbound => "in",
+                };
+                write!(f, "{}.traverse({direction}, max={}", q.inner, q.max_depth)?;
+                if q.min_depth != 0 {
+                    write!(f, ", min={}", q.min_depth)?;
+                }
+                if let Some(ref t) = q.t {
+                    write!(f, ", type={}", Name(t))?;
+                }
+                if q.limit != u32::MAX {
+                    write!(f, ", limit={}", q.limit)?;
+                }
+                write!(f, ")")
+            }
+            Query::Dedup(q) => write!(f, "{}.dedup()", q.inner),
+            Query::OrderBy(q) => {
+                write!(f, "{}.orderBy({}", q.inner, Name(&q.name))?;
+                if q.order == SortOrder::Descending {
+                    write!(f, ", desc")?;
+                }
+                if q.nulls == NullsOrder::First {
+                    write!(f, ", nulls=first")?;
+                }
+                if q.offset != 0 {
+                    write!(f, ", offset={}", q.offset)?;
+                }
+                if q.limit != u32::MAX {
+                    write!(f, ", limit={}", q.limit)?;
+                }
+                write!(f, ")")
+            }
+            Query::Include(q) => write!(f, "{}.include()", q.inner),
+            Query::Count(q) => write!(f, "{}.count()", q.inner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryParseError;
+    use crate::{
+        ijson, AllEdgeQuery, AllVertexQuery, CountQueryExt, Edge, EdgeDirection, EdgeWithPropertyRangeQuery,
+        Identifier, InvalidWeight, JsonRange, NullsOrder, Query, QueryExt, RangeEdgeQuery, RangeVertexQuery,
+        ShortestPathQuery, SpecificEdgeQuery, SpecificVertexQuery, VertexWithCompositePropertyQuery,
+        VertexWithPropertyValueQuery,
+    };
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    fn id(s: &str) -> Identifier {
+        Identifier::new(s).unwrap()
+    }
+
+    fn parse_err(text: &str) -> QueryParseError {
+        Query::from_str(text).unwrap_err()
+    }
+
+    #[test]
+    fn should_parse_query() {
+        let q = Query::from_str(r#"V(type=person).has(name="x").out(follows).limit(10).props()"#).unwrap();
+        let expected: Query = RangeVertexQuery::new()
+            .t(id("person"))
+            .with_property_equal_to(id("name"), ijson!("x"))
+            .unwrap()
+            .outbound()
+            .unwrap()
+            .t(id("follows"))
+            .limit(10)
+            .properties()
+            .unwrap()
+            .into();
+        assert_eq!(q, expected);
+
+        let q = Query::from_str(" V ( ) . limit ( 3 ) . include ( ) . count ( ) ").unwrap();
+        let expected: Query = RangeVertexQuery::new().limit(3).include().count().unwrap().into();
+        assert_eq!(q, expected);
+    }
+
+    #[test]
+    fn should_round_trip_queries() {
+        let vertex_id = Uuid::from_u128(1);
+        let edge = Edge::new(vertex_id, id("follows"), Uuid::from_u128(2));
+        let queries: Vec<Query> = vec![
+            AllVertexQuery.into(),
+            RangeVertexQuery::new().into(),
+            RangeVertexQuery::new()
+                .t(id("person"))
+                .start_id(vertex_id)
+                .limit(5)
+                .into(),
+            SpecificVertexQuery::new(vec![]).into(),
+            SpecificVertexQuery::new(vec![vertex_id, vertex_id]).into(),
+            VertexWithPropertyValueQuery::new(id("name"), ijson!({"first": "a", "tags": [1, 2.5, null]})).into(),
+            VertexWithCompositePropertyQuery::new(vec![id("a"), id("b"), id("c")], vec![ijson!(1), ijson!(true)])
+                .unwrap()
+                .into(),
+            AllEdgeQuery.with_property(id("weight")).unwrap().into(),
+            RangeEdgeQuery::new().start_edge(edge.clone()).into(),
+            SpecificEdgeQuery::new(vec![edge.clone()]).into(),
+            EdgeWithPropertyRangeQuery::new(
+                id("weight"),
+                JsonRange::new(
+                    std::ops::Bound::Excluded(ijson!(-1)),
+                    std::ops::Bound::Included(ijson!(1e3)),
+                ),
+            )
+            .into(),
+            ShortestPathQuery::new(vertex_id, Uuid::from_u128(2)).into(),
+            ShortestPathQuery::new(vertex_id, Uuid::from_u128(2))
+                .weighted_by(id("weight"), InvalidWeight::Fail)
+                .into(),
+            ShortestPathQuery::new(vertex_id, Uuid::from_u128(2))
+                .max_depth(3)
+                .weighted_by(id("weight"), InvalidWeight::Skip)
+                .into(),
+            ShortestPathQuery::new(vertex_id, Uuid::from_u128(2))
+                .weighted_by(id("weight"), InvalidWeight::Default(ijson!(1.5)))
+                .into(),
+            ShortestPathQuery::new(vertex_id, vertex_id)
+                .t(id("follows"))
+                .max_depth(6)
+                .count()
+                .unwrap()
+                .into(),
+            AllVertexQuery
+                .traverse(EdgeDirection::Outbound, 3)
+                .unwrap()
+                .count()
+                .unwrap()
+                .into(),
+            SpecificVertexQuery::single(vertex_id)
+                .traverse(EdgeDirection::Inbound, 4)
+                .unwrap()
+                .min_depth(1)
+                .t(id("follows"))
+                .limit(10)
+                .into(),
+            SpecificVertexQuery::single(vertex_id)
+                .inbound()
+                .unwrap()
+                .t(id("likes"))
+                .outbound()
+                .unwrap()
+                .limit(5)
+                .paths()
+                .into(),
+            AllEdgeQuery.inbound().unwrap().dedup().unwrap().count().unwrap().into(),
+            AllVertexQuery.order_by(id("score")).unwrap().into(),
+            AllVertexQuery
+                .outbound()
+                .unwrap()
+                .order_by(id("weight"))
+                .unwrap()
+                .descending()
+                .nulls(NullsOrder::First)
+                .offset(10)
+                .limit(5)
+                .into(),
+            SpecificVertexQuery::single(vertex_id)
+                .include()
+                .inbound()
+                .unwrap()
+                .limit(2)
+                .without_property(id("2nd-name"))
+                .unwrap()
+                .with_property_not_equal_to(id("x"), ijson!("a \"b\""))
+                .unwrap()
+                .with_property_in_range(id("y"), JsonRange::lt(ijson!(0)))
+                .unwrap()
+                .properties()
+                .unwrap()
+                .name(id("z"))
+                .count()
+                .unwrap()
+                .into(),
+        ];
+
+        for q in queries {
+            let text = q.to_string();
+            assert_eq!(Query::from_str(&text), Ok(q), "round trip of `{text}` failed");
+        }
+    }
+
+    #[test]
+    fn should_report_error_spans() {
+        let err = parse_err("V().out(follows");
+        assert_eq!(err.message, "expected `)`, found the end of the query");
+        assert_eq!(err.span, 15..15);
+
+        let err = parse_err("V().sideways()");
+        assert_eq!(err.message, "unknown step `sideways`");
+        assert_eq!(err.span, 4..12);
+
+        let err = parse_err("V().count().out()");
+        assert_eq!(err.span, 12..17);
+
+        let err = parse_err("V().has(a>1, b<2)");
+        assert_eq!(err.span, 13..16);
+
+        let err = parse_err(r#"V("not-a-uuid")"#);
+        assert_eq!(err.message, "invalid UUID");
+        assert_eq!(err.span, 2..14);
+
+        let err = parse_err("V(has(a!=1))");
+        assert_eq!(err.span, 6..10);
+
+        let err = parse_err("V().traverse(out, min=1)");
+        assert_eq!(err.message, "`traverse` needs a `max` depth");
+        assert_eq!(err.span, 4..24);
+
+        let text =
+            r#"Path("00000000-0000-0000-0000-000000000001", "00000000-0000-0000-0000-000000000002", invalid=skip)"#;
+        assert_eq!(parse_err(text).message, "`invalid` must come after `weight`");
+
+        let err = parse_err("V() # comment");
+        assert_eq!(err.message, "unexpected character `#`");
+        assert_eq!(err.span, 4..5);
+
+        let err = parse_err(&format!("V().has(x={})", "[".repeat(100_000)));
+        assert_eq!(err.message, "JSON values can't be nested more than 128 deep");
+        assert_eq!(err.span, 138..139);
+        let nested = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(Query::from_str(&format!("V().has(x={nested})")).is_ok());
+    }
+}
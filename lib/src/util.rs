@@ -1,12 +1,13 @@
 //! Utility functions. These are public because they may be useful for crates
 //! that implement Datastore.
 
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::io::{Cursor, Error as IoError, Read, Write};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Error as IoError, ErrorKind as IoErrorKind, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{str, u8};
 
-use crate::errors::{ValidationError, ValidationResult};
+use crate::errors::{Error, ValidationError, ValidationResult};
 use crate::models;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
@@ -27,8 +28,14 @@ pub enum Component<'a> {
     FixedLengthString(&'a str),
     /// An identifier.
     Identifier(models::Identifier),
-    /// A JSON value.
+    /// A JSON value. This is encoded such that the byte order of encoded
+    /// values matches the order of `Json::total_cmp`, and such that no
+    /// encoded value is a prefix of another.
     Json(&'a models::Json),
+    /// A timestamp. This is encoded as nanoseconds since the Unix epoch, so
+    /// that encoded timestamps sort chronologically. Timestamps that don't
+    /// fit are clamped.
+    Timestamp(SystemTime),
 }
 
 impl<'a> Component<'a> {
@@ -39,7 +46,8 @@ impl<'a> Component<'a> {
             Component::Uuid(_) => 16,
             Component::FixedLengthString(s) => s.len(),
             Component::Identifier(t) => t.0.len() + 1,
-            Component::Json(_) => 8,
+            Component::Json(json) => json_byte_len(json),
+            Component::Timestamp(_) => 8,
         }
     }
 
@@ -52,12 +60,109 @@ impl<'a> Component<'a> {
                 cursor.write_all(&[i.0.len() as u8])?;
                 cursor.write_all(i.0.as_bytes())
             }
-            Component::Json(json) => {
-                let mut hasher = DefaultHasher::new();
-                json.hash(&mut hasher);
-                let hash = hasher.finish();
-                cursor.write_u64::<BigEndian>(hash)
+            Component::Json(json) => write_json(cursor, json),
+            Component::Timestamp(timestamp) => cursor.write_u64::<BigEndian>(timestamp_nanos(timestamp)),
+        }
+    }
+}
+
+fn timestamp_nanos(timestamp: SystemTime) -> u64 {
+    match timestamp.duration_since(UNIX_EPOCH) {
+        Ok(duration) => u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+        Err(_) => 0,
+    }
+}
+
+const JSON_END: u8 = 0;
+const JSON_ENTRY: u8 = 1;
+const JSON_NULL: u8 = 1;
+const JSON_BOOL: u8 = 2;
+const JSON_NUMBER: u8 = 3;
+const JSON_STRING: u8 = 4;
+const JSON_ARRAY: u8 = 5;
+const JSON_OBJECT: u8 = 6;
+
+/// Splits a number into a key that totally orders it: the nearest `f64`,
+/// and as a tiebreaker for large integers that can't be represented exactly
+/// as `f64`s, the integral value.
+pub(crate) fn number_key(value: &serde_json::Number) -> (f64, i128) {
+    if let Some(v) = value.as_i64() {
+        (v as f64, v as i128)
+    } else if let Some(v) = value.as_u64() {
+        (v as f64, v as i128)
+    } else {
+        let v = value.as_f64().unwrap();
+        // Normalize negative zero, so that it's equal to positive zero
+        let v = if v == 0.0 { 0.0 } else { v };
+        (v, v as i128)
+    }
+}
+
+fn escaped_string_byte_len(s: &str) -> usize {
+    s.len() + s.bytes().filter(|b| *b == 0).count() + 2
+}
+
+fn json_byte_len(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Null => 1,
+        serde_json::Value::Bool(_) => 2,
+        serde_json::Value::Number(_) => 25,
+        serde_json::Value::String(v) => 1 + escaped_string_byte_len(v),
+        serde_json::Value::Array(v) => 2 + v.iter().map(json_byte_len).sum::<usize>(),
+        serde_json::Value::Object(v) => {
+            2 + v
+                .iter()
+                .map(|(k, v)| 1 + escaped_string_byte_len(k) + json_byte_len(v))
+                .sum::<usize>()
+        }
+    }
+}
+
+// Strings are terminated by `[0, 1]`, with nulls escaped as `[0, 255]`, so
+// that shorter strings sort first.
+fn write_escaped_string(cursor: &mut Cursor<Vec<u8>>, s: &str) -> Result<(), IoError> {
+    for (i, part) in s.split('\0').enumerate() {
+        if i > 0 {
+            cursor.write_all(&[0, 255])?;
+        }
+        cursor.write_all(part.as_bytes())?;
+    }
+    cursor.write_all(&[0, 1])
+}
+
+fn write_json(cursor: &mut Cursor<Vec<u8>>, value: &serde_json::Value) -> Result<(), IoError> {
+    match value {
+        serde_json::Value::Null => cursor.write_all(&[JSON_NULL]),
+        serde_json::Value::Bool(v) => cursor.write_all(&[JSON_BOOL, *v as u8]),
+        serde_json::Value::Number(v) => {
+            let (float, int) = number_key(v);
+            // Flip the sign bit of positive floats, and all bits of negative
+            // ones, so that they sort correctly as unsigned integers
+            let bits = float.to_bits();
+            let bits = if bits >> 63 == 1 { !bits } else { bits | (1 << 63) };
+            cursor.write_all(&[JSON_NUMBER])?;
+            cursor.write_u64::<BigEndian>(bits)?;
+            cursor.write_u128::<BigEndian>((int as u128) ^ (1 << 127))
+        }
+        serde_json::Value::String(v) => {
+            cursor.write_all(&[JSON_STRING])?;
+            write_escaped_string(cursor, v)
+        }
+        serde_json::Value::Array(v) => {
+            cursor.write_all(&[JSON_ARRAY])?;
+            for item in v {
+                write_json(cursor, item)?;
             }
+            cursor.write_all(&[JSON_END])
+        }
+        serde_json::Value::Object(v) => {
+            cursor.write_all(&[JSON_OBJECT])?;
+            for (key, value) in v {
+                cursor.write_all(&[JSON_ENTRY])?;
+                write_escaped_string(cursor, key)?;
+                write_json(cursor, value)?;
+            }
+            cursor.write_all(&[JSON_END])
         }
     }
 }
@@ -127,6 +232,100 @@ pub fn read_u64<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> u64 {
     cursor.read_u64::<BigEndian>().unwrap()
 }
 
+/// Reads a timestamp from bytes.
+///
+/// # Arguments
+/// * `cursor`: The bytes to read from.
+pub fn read_timestamp<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(read_u64(cursor))
+}
+
+fn read_u8<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> Result<u8, IoError> {
+    let mut buf: [u8; 1] = [0; 1];
+    cursor.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_escaped_string<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> Result<String, IoError> {
+    let mut buf = Vec::new();
+    loop {
+        match read_u8(cursor)? {
+            0 => match read_u8(cursor)? {
+                1 => break,
+                255 => buf.push(0),
+                _ => return Err(IoError::from(IoErrorKind::InvalidData)),
+            },
+            b => buf.push(b),
+        }
+    }
+    String::from_utf8(buf).map_err(|_| IoError::from(IoErrorKind::InvalidData))
+}
+
+fn read_json_value<T: AsRef<[u8]>>(cursor: &mut Cursor<T>, tag: u8) -> Result<serde_json::Value, IoError> {
+    match tag {
+        JSON_NULL => Ok(serde_json::Value::Null),
+        JSON_BOOL => Ok(serde_json::Value::Bool(read_u8(cursor)? != 0)),
+        JSON_NUMBER => {
+            let bits = cursor.read_u64::<BigEndian>()?;
+            let bits = if bits >> 63 == 1 { bits & !(1 << 63) } else { !bits };
+            let float = f64::from_bits(bits);
+            let int = (cursor.read_u128::<BigEndian>()? ^ (1 << 127)) as i128;
+
+            // Integers and integral floats encode the same, so they're
+            // decoded as integers where possible
+            let number = if int as f64 == float {
+                if let Ok(int) = i64::try_from(int) {
+                    serde_json::Number::from(int)
+                } else if let Ok(int) = u64::try_from(int) {
+                    serde_json::Number::from(int)
+                } else {
+                    serde_json::Number::from_f64(float).ok_or_else(|| IoError::from(IoErrorKind::InvalidData))?
+                }
+            } else {
+                serde_json::Number::from_f64(float).ok_or_else(|| IoError::from(IoErrorKind::InvalidData))?
+            };
+
+            Ok(serde_json::Value::Number(number))
+        }
+        JSON_STRING => Ok(serde_json::Value::String(read_escaped_string(cursor)?)),
+        JSON_ARRAY => {
+            let mut items = Vec::new();
+            loop {
+                match read_u8(cursor)? {
+                    JSON_END => break,
+                    tag => items.push(read_json_value(cursor, tag)?),
+                }
+            }
+            Ok(serde_json::Value::Array(items))
+        }
+        JSON_OBJECT => {
+            let mut entries = serde_json::Map::new();
+            loop {
+                match read_u8(cursor)? {
+                    JSON_END => break,
+                    JSON_ENTRY => {
+                        let key = read_escaped_string(cursor)?;
+                        let tag = read_u8(cursor)?;
+                        entries.insert(key, read_json_value(cursor, tag)?);
+                    }
+                    _ => return Err(IoError::from(IoErrorKind::InvalidData)),
+                }
+            }
+            Ok(serde_json::Value::Object(entries))
+        }
+        _ => Err(IoError::from(IoErrorKind::InvalidData)),
+    }
+}
+
+/// Reads a JSON value from bytes.
+///
+/// # Arguments
+/// * `cursor`: The bytes to read from.
+pub fn read_json<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> models::Json {
+    let value = read_u8(cursor).and_then(|tag| read_json_value(cursor, tag)).unwrap();
+    models::Json::new(value)
+}
+
 /// Generates a UUID v1. This utility method uses a shared context and node ID
 /// to help ensure generated UUIDs are unique.
 pub fn generate_uuid_v1() -> Uuid {
@@ -156,6 +355,81 @@ pub fn next_uuid(uuid: Uuid) -> ValidationResult<Uuid> {
     Err(ValidationError::CannotIncrementUuid)
 }
 
+/// Gets when something with a time-to-live expires.
+///
+/// # Arguments
+/// * `now`: The time that the time-to-live starts from.
+/// * `ttl`: The time-to-live.
+///
+/// # Errors
+/// Returns `ValidationError::InvalidValue` if the expiry time can't be
+/// represented.
+pub fn expiry(now: SystemTime, ttl: Duration) -> ValidationResult<SystemTime> {
+    now.checked_add(ttl).ok_or(ValidationError::InvalidValue)
+}
+
+/// Checks that a set of vertex property writes wouldn't give two vertices the
+/// same value for a property with a unique index. Writes are treated as a
+/// single change, so e.g. two vertices can swap values.
+///
+/// # Arguments
+/// * `unique_properties`: The names of the properties with unique indexes.
+/// * `writes`: The `(vertex ID, property name, property value)` writes.
+/// * `holders`: Gets the IDs of the vertices that currently have a given
+///   property value.
+///
+/// # Errors
+/// Returns `Error::UniqueViolation` if a value would be duplicated, or any
+/// error returned by `holders`.
+pub fn check_unique_vertex_properties<'a, I, F>(
+    unique_properties: &HashSet<models::Identifier>,
+    writes: I,
+    mut holders: F,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = (Uuid, models::Identifier, &'a models::Json)>,
+    F: FnMut(models::Identifier, &models::Json) -> Result<Vec<Uuid>, Error>,
+{
+    // Later writes to the same vertex property win.
+    let mut final_values: HashMap<(Uuid, models::Identifier), &models::Json> = HashMap::new();
+    for (id, name, value) in writes {
+        if unique_properties.contains(&name) {
+            final_values.insert((id, name), value);
+        }
+    }
+
+    // Values are compared the way they're indexed, where e.g. `1` and `1.0`
+    // are the same value.
+    let mut written: Vec<(models::Identifier, &models::Json)> =
+        final_values.iter().map(|(&(_, name), &value)| (name, value)).collect();
+    written.sort_by(|(name1, value1), (name2, value2)| name1.cmp(name2).then_with(|| value1.total_cmp(value2)));
+    for pair in written.windows(2) {
+        let ((name1, value1), (name2, value2)) = (pair[0], pair[1]);
+        if name1 == name2 && value1.total_cmp(value2) == Ordering::Equal {
+            return Err(Error::UniqueViolation {
+                name: name2,
+                value: value2.clone(),
+            });
+        }
+    }
+
+    for (&(id, name), &value) in final_values.iter() {
+        for holder in holders(name, value)? {
+            // Holders that are being written keep the value only if they're
+            // being set to it again.
+            let rewritten = final_values.get(&(holder, name)).map(|v| v.total_cmp(value));
+            if holder != id && !matches!(rewritten, Some(ordering) if ordering != Ordering::Equal) {
+                return Err(Error::UniqueViolation {
+                    name,
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Extracts vertices from the last query output value, or `None`.
 ///
 /// # Arguments
@@ -216,13 +490,39 @@ pub fn extract_edge_properties(mut output: Vec<models::QueryOutputValue>) -> Opt
     }
 }
 
+/// Extracts paths from the last query output value, or `None`.
+///
+/// # Arguments
+/// * `output`: The query output.
+pub fn extract_paths(mut output: Vec<models::QueryOutputValue>) -> Option<Vec<models::Path>> {
+    if let Some(models::QueryOutputValue::Paths(paths)) = output.pop() {
+        Some(paths)
+    } else {
+        None
+    }
+}
+
+/// Extracts pipe paths from the last query output value, or `None`.
+///
+/// # Arguments
+/// * `output`: The query output.
+pub fn extract_pipe_paths(mut output: Vec<models::QueryOutputValue>) -> Option<Vec<models::PipePath>> {
+    if let Some(models::QueryOutputValue::PipePaths(paths)) = output.pop() {
+        Some(paths)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
-        extract_count, extract_edge_properties, extract_edges, extract_vertex_properties, extract_vertices,
-        generate_uuid_v1, next_uuid,
+        build, extract_count, extract_edge_properties, extract_edges, extract_paths, extract_pipe_paths,
+        extract_vertex_properties, extract_vertices, generate_uuid_v1, next_uuid, read_json, Component,
     };
+    use crate::ijson;
     use core::str::FromStr;
+    use std::io::Cursor;
     use uuid::Uuid;
 
     #[test]
@@ -269,4 +569,59 @@ mod tests {
     fn should_not_extract_edge_properties_on_empty() {
         assert_eq!(extract_edge_properties(vec![]), None);
     }
+
+    #[test]
+    fn should_not_extract_paths_on_empty() {
+        assert_eq!(extract_paths(vec![]), None);
+    }
+
+    #[test]
+    fn should_not_extract_pipe_paths_on_empty() {
+        assert_eq!(extract_pipe_paths(vec![]), None);
+    }
+
+    #[test]
+    fn should_encode_json_in_order() {
+        let values = vec![
+            ijson!(null),
+            ijson!(false),
+            ijson!(true),
+            ijson!(i64::MIN),
+            ijson!(-1.5),
+            ijson!(0),
+            ijson!(3),
+            ijson!(3.5),
+            ijson!(u64::MAX - 1),
+            ijson!(u64::MAX),
+            ijson!(""),
+            ijson!("\0"),
+            ijson!("foo"),
+            ijson!("foo\0bar"),
+            ijson!([]),
+            ijson!([1, "foo"]),
+            ijson!(["foo", 1]),
+            ijson!({}),
+            ijson!({"key": 1}),
+            ijson!({"key": "value"}),
+        ];
+
+        for window in values.windows(2) {
+            let first = build(&[Component::Json(&window[0])]);
+            let second = build(&[Component::Json(&window[1])]);
+            assert!(first < second, "{:?} vs {:?}", window[0], window[1]);
+        }
+
+        for value in values {
+            let bytes = build(&[Component::Json(&value)]);
+            assert_eq!(bytes.len(), Component::Json(&value).byte_len());
+            let mut cursor = Cursor::new(bytes);
+            assert_eq!(read_json(&mut cursor), value);
+            assert_eq!(cursor.position() as usize, cursor.get_ref().len());
+        }
+
+        assert_eq!(
+            build(&[Component::Json(&ijson!(4))]),
+            build(&[Component::Json(&ijson!(4.0))])
+        );
+    }
 }
@@ -7,9 +7,9 @@ use crate::ConversionError;
 
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
-use tokio_stream::StreamExt;
+use tokio_stream::{Stream, StreamExt};
 use tonic::transport::{Channel, Endpoint, Error as TonicTransportError};
-use tonic::{Request, Status};
+use tonic::{Request, Status, Streaming};
 use uuid::Uuid;
 
 const CHANNEL_CAPACITY: usize = 100;
@@ -147,13 +147,38 @@ impl Client {
     /// # Arguments
     /// * `q`: The query to run.
     pub async fn get<Q: Into<indradb::Query>>(&mut self, q: Q) -> Result<Vec<indradb::QueryOutputValue>, ClientError> {
+        self.get_with_budget(q, indradb::QueryBudget::default()).await
+    }
+
+    /// Gets values specified by a query, limiting the resources it can use
+    /// on the server. Limits that are unbounded in `budget` fall back to the
+    /// server's defaults.
+    ///
+    /// # Arguments
+    /// * `q`: The query to run.
+    /// * `budget`: The query budget.
+    pub async fn get_with_budget<Q: Into<indradb::Query>>(
+        &mut self,
+        q: Q,
+        budget: indradb::QueryBudget,
+    ) -> Result<Vec<indradb::QueryOutputValue>, ClientError> {
         let q: crate::Query = q.into().into();
-        let mut output = Vec::<indradb::QueryOutputValue>::new();
-        let mut res = self.0.get(q).await?.into_inner();
-        while let Some(res) = res.next().await {
-            output.push(res?.try_into()?);
-        }
-        Ok(output)
+        let mut request = Request::new(q);
+        crate::write_query_budget(request.metadata_mut(), &budget);
+        collect_output(self.0.get(request).await?.into_inner()).await
+    }
+
+    /// Gets values specified by a query written in the text syntax parsed by
+    /// `indradb::Query::from_str`. Queries that fail to parse return an
+    /// `INVALID_ARGUMENT` gRPC error.
+    ///
+    /// # Arguments
+    /// * `query`: The text of the query.
+    pub async fn get_text(&mut self, query: &str) -> Result<Vec<indradb::QueryOutputValue>, ClientError> {
+        let request = crate::GetTextRequest {
+            query: query.to_string(),
+        };
+        collect_output(self.0.get_text(request).await?.into_inner()).await
     }
 
     /// Deletes values specified by a query.
@@ -166,6 +191,17 @@ impl Client {
         Ok(())
     }
 
+    /// Plans a query without running it, returning the plan along with
+    /// estimates of how many values each step outputs.
+    ///
+    /// # Arguments
+    /// * `q`: The query to plan.
+    pub async fn explain<Q: Into<indradb::Query>>(&mut self, q: Q) -> Result<indradb::QueryPlan, ClientError> {
+        let q: crate::Query = q.into().into();
+        let res = self.0.explain(q).await?;
+        Ok(res.into_inner().try_into()?)
+    }
+
     /// Sets properties.
     ///
     /// # Arguments
@@ -236,6 +272,38 @@ impl Client {
         Ok(())
     }
 
+    /// Disables indexing on a specified property, and removes its index
+    /// entries.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the property to stop indexing.
+    pub async fn unindex_property(&mut self, name: indradb::Identifier) -> Result<(), ClientError> {
+        let request = Request::new(crate::UnindexPropertyRequest {
+            name: Some(name.into()),
+        });
+        self.0.unindex_property(request).await?;
+        Ok(())
+    }
+
+    /// Gets the names of the properties with single-property indexes, in
+    /// sorted order.
+    pub async fn indexed_properties(&mut self) -> Result<Vec<indradb::Identifier>, ClientError> {
+        let res = self.0.indexed_properties(Request::new(())).await?;
+        Ok(res.into_inner().try_into()?)
+    }
+
+    /// Enables a composite index over an ordered list of vertex properties.
+    ///
+    /// # Arguments
+    /// * `names`: The names of the properties to index, in order.
+    pub async fn index_composite_property(&mut self, names: Vec<indradb::Identifier>) -> Result<(), ClientError> {
+        let request = Request::new(crate::IndexCompositePropertyRequest {
+            names: names.into_iter().map(|name| name.into()).collect(),
+        });
+        self.0.index_composite_property(request).await?;
+        Ok(())
+    }
+
     pub async fn execute_plugin(&mut self, name: &str, arg: indradb::Json) -> Result<indradb::Json, ClientError> {
         let req = Request::new(crate::ExecutePluginRequest {
             name: name.to_string(),
@@ -247,4 +315,63 @@ impl Client {
             None => Ok(indradb::Json::new(serde_json::Value::Null)),
         }
     }
+
+    /// Watches for changes made through the server from now on. The
+    /// returned stream ends with an error if it falls too far behind the
+    /// server's changes.
+    ///
+    /// # Arguments
+    /// * `filter`: Which changes to receive.
+    #[allow(clippy::result_large_err)]
+    pub async fn watch(
+        &mut self,
+        filter: indradb::ChangeFilter,
+    ) -> Result<impl Stream<Item = Result<indradb::ChangeRecord, ClientError>>, ClientError> {
+        let request = Request::new(crate::WatchRequest::from(filter));
+        let res = self.0.watch(request).await?.into_inner();
+        Ok(res.map(|record| Ok(record?.try_into()?)))
+    }
+}
+
+// Collects the output streamed back from a query.
+async fn collect_output(
+    mut res: Streaming<crate::QueryOutputValue>,
+) -> Result<Vec<indradb::QueryOutputValue>, ClientError> {
+    let mut output = Vec::<indradb::QueryOutputValue>::new();
+    let mut partial = false;
+    while let Some(res) = res.next().await {
+        let res = res?;
+        let next_partial = res.partial;
+        let value: indradb::QueryOutputValue = res.try_into()?;
+
+        // Values split across messages are reassembled
+        match (partial, output.last_mut(), value) {
+            (true, Some(indradb::QueryOutputValue::Vertices(vertices)), indradb::QueryOutputValue::Vertices(chunk)) => {
+                vertices.extend(chunk)
+            }
+            (true, Some(indradb::QueryOutputValue::Edges(edges)), indradb::QueryOutputValue::Edges(chunk)) => {
+                edges.extend(chunk)
+            }
+            (
+                true,
+                Some(indradb::QueryOutputValue::VertexProperties(props)),
+                indradb::QueryOutputValue::VertexProperties(chunk),
+            ) => props.extend(chunk),
+            (
+                true,
+                Some(indradb::QueryOutputValue::EdgeProperties(props)),
+                indradb::QueryOutputValue::EdgeProperties(chunk),
+            ) => props.extend(chunk),
+            (true, Some(indradb::QueryOutputValue::Paths(paths)), indradb::QueryOutputValue::Paths(chunk)) => {
+                paths.extend(chunk)
+            }
+            (true, Some(indradb::QueryOutputValue::PipePaths(paths)), indradb::QueryOutputValue::PipePaths(chunk)) => {
+                paths.extend(chunk)
+            }
+            (_, _, value) => output.push(value),
+        }
+
+        partial = next_partial;
+    }
+    Ok(output)
 }
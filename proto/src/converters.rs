@@ -4,6 +4,8 @@
 use std::convert::TryInto;
 use std::error::Error as StdError;
 use std::fmt;
+use std::ops::Bound;
+use std::time::Duration;
 
 use indradb::ValidationError;
 use serde_json::Error as SerdeJsonError;
@@ -17,6 +19,7 @@ pub enum ConversionError {
     Uuid { inner: UuidError },
     Validation { inner: ValidationError },
     NoneField { name: String },
+    InvalidMetadata { key: String },
     UnexpectedResponseType,
 }
 
@@ -38,6 +41,7 @@ impl fmt::Display for ConversionError {
             ConversionError::Uuid { ref inner } => write!(f, "uuid conversion failed: {inner}"),
             ConversionError::Validation { ref inner } => write!(f, "validation conversion failed: {inner}"),
             ConversionError::NoneField { ref name } => write!(f, "proto field '{name}' should not be none"),
+            ConversionError::InvalidMetadata { ref key } => write!(f, "invalid value for metadata key '{key}'"),
             ConversionError::UnexpectedResponseType => write!(f, "unexpected response type"),
         }
     }
@@ -113,6 +117,55 @@ impl TryInto<indradb::Json> for crate::Json {
     }
 }
 
+impl From<Bound<indradb::Json>> for crate::JsonBound {
+    fn from(bound: Bound<indradb::Json>) -> Self {
+        crate::JsonBound {
+            bound: match bound {
+                Bound::Included(value) => Some(crate::JsonBoundVariant::Included(value.into())),
+                Bound::Excluded(value) => Some(crate::JsonBoundVariant::Excluded(value.into())),
+                Bound::Unbounded => None,
+            },
+        }
+    }
+}
+
+impl TryInto<Bound<indradb::Json>> for crate::JsonBound {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<Bound<indradb::Json>, Self::Error> {
+        Ok(match self.bound {
+            Some(crate::JsonBoundVariant::Included(value)) => Bound::Included(value.try_into()?),
+            Some(crate::JsonBoundVariant::Excluded(value)) => Bound::Excluded(value.try_into()?),
+            None => Bound::Unbounded,
+        })
+    }
+}
+
+impl From<indradb::JsonRange> for crate::JsonRange {
+    fn from(range: indradb::JsonRange) -> Self {
+        crate::JsonRange {
+            start: Some(range.start.into()),
+            end: Some(range.end.into()),
+        }
+    }
+}
+
+impl TryInto<indradb::JsonRange> for crate::JsonRange {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::JsonRange, Self::Error> {
+        let start = match self.start {
+            Some(start) => start.try_into()?,
+            None => Bound::Unbounded,
+        };
+        let end = match self.end {
+            Some(end) => end.try_into()?,
+            None => Bound::Unbounded,
+        };
+        Ok(indradb::JsonRange::new(start, end))
+    }
+}
+
 impl From<indradb::Edge> for crate::Edge {
     fn from(edge: indradb::Edge) -> Self {
         crate::Edge {
@@ -179,8 +232,26 @@ impl From<indradb::Query> for crate::Query {
                         value: Some(q.value.into()),
                     })
                 }
+                indradb::Query::VertexWithPropertyRange(q) => {
+                    crate::QueryVariant::VertexWithPropertyRange(crate::VertexWithPropertyRangeQuery {
+                        name: Some(q.name.into()),
+                        range: Some(q.range.into()),
+                    })
+                }
+
+                indradb::Query::VertexWithCompositeProperty(q) => {
+                    crate::QueryVariant::VertexWithCompositeProperty(crate::VertexWithCompositePropertyQuery {
+                        names: q.names.into_iter().map(|name| name.into()).collect(),
+                        values: q.values.into_iter().map(|value| value.into()).collect(),
+                    })
+                }
 
                 indradb::Query::AllEdge => crate::QueryVariant::AllEdge(()),
+                indradb::Query::RangeEdge(q) => crate::QueryVariant::RangeEdge(crate::RangeEdgeQuery {
+                    limit: q.limit,
+                    t: q.t.map(|t| t.into()),
+                    start_edge: q.start_edge.map(|start_edge| start_edge.into()),
+                }),
                 indradb::Query::SpecificEdge(q) => crate::QueryVariant::SpecificEdge(crate::SpecificEdgeQuery {
                     edges: q.edges.into_iter().map(|id| id.into()).collect(),
                 }),
@@ -195,6 +266,20 @@ impl From<indradb::Query> for crate::Query {
                         value: Some(q.value.into()),
                     })
                 }
+                indradb::Query::EdgeWithPropertyRange(q) => {
+                    crate::QueryVariant::EdgeWithPropertyRange(crate::EdgeWithPropertyRangeQuery {
+                        name: Some(q.name.into()),
+                        range: Some(q.range.into()),
+                    })
+                }
+
+                indradb::Query::ShortestPath(q) => crate::QueryVariant::ShortestPath(crate::ShortestPathQuery {
+                    source_id: Some(q.source_id.into()),
+                    target_id: Some(q.target_id.into()),
+                    t: q.t.map(|t| t.into()),
+                    max_depth: q.max_depth,
+                    weight: q.weight.map(|weight| weight.into()),
+                }),
 
                 indradb::Query::Pipe(q) => {
                     let mut proto_q = crate::PipeQuery {
@@ -202,6 +287,7 @@ impl From<indradb::Query> for crate::Query {
                         direction: 0,
                         limit: q.limit,
                         t: q.t.map(|t| t.into()),
+                        paths: q.paths,
                     };
                     proto_q.set_direction(q.direction.into());
                     crate::QueryVariant::Pipe(Box::new(proto_q))
@@ -230,6 +316,45 @@ impl From<indradb::Query> for crate::Query {
                     };
                     crate::QueryVariant::PipeWithPropertyValue(Box::new(proto_q))
                 }
+                indradb::Query::PipeWithPropertyRange(q) => {
+                    let proto_q = crate::PipeWithPropertyRangeQuery {
+                        inner: Some(Box::new((*q.inner).into())),
+                        name: Some(q.name.into()),
+                        range: Some(q.range.into()),
+                    };
+                    crate::QueryVariant::PipeWithPropertyRange(Box::new(proto_q))
+                }
+                indradb::Query::Traverse(q) => {
+                    let mut proto_q = crate::TraverseQuery {
+                        inner: Some(Box::new((*q.inner).into())),
+                        direction: 0,
+                        t: q.t.map(|t| t.into()),
+                        min_depth: q.min_depth,
+                        max_depth: q.max_depth,
+                        limit: q.limit,
+                    };
+                    proto_q.set_direction(q.direction.into());
+                    crate::QueryVariant::Traverse(Box::new(proto_q))
+                }
+                indradb::Query::Dedup(q) => {
+                    let proto_q = crate::DedupQuery {
+                        inner: Some(Box::new((*q.inner).into())),
+                    };
+                    crate::QueryVariant::Dedup(Box::new(proto_q))
+                }
+                indradb::Query::OrderBy(q) => {
+                    let mut proto_q = crate::OrderByQuery {
+                        inner: Some(Box::new((*q.inner).into())),
+                        name: Some(q.name.into()),
+                        order: 0,
+                        nulls: 0,
+                        offset: q.offset,
+                        limit: q.limit,
+                    };
+                    proto_q.set_order(q.order.into());
+                    proto_q.set_nulls(q.nulls.into());
+                    crate::QueryVariant::OrderBy(Box::new(proto_q))
+                }
 
                 indradb::Query::Include(q) => {
                     let proto_q = crate::IncludeQuery {
@@ -277,8 +402,31 @@ impl TryInto<indradb::Query> for crate::Query {
                     value: value.try_into()?,
                 })
             }
+            crate::QueryVariant::VertexWithPropertyRange(q) => {
+                let name = required_field("name", q.name)?;
+                let range = required_field("range", q.range)?;
+                indradb::Query::VertexWithPropertyRange(indradb::VertexWithPropertyRangeQuery {
+                    name: name.try_into()?,
+                    range: range.try_into()?,
+                })
+            }
+
+            crate::QueryVariant::VertexWithCompositeProperty(q) => {
+                let names: Result<Vec<indradb::Identifier>, ConversionError> =
+                    q.names.into_iter().map(|name| name.try_into()).collect();
+                let values: Result<Vec<indradb::Json>, ConversionError> =
+                    q.values.into_iter().map(|value| value.try_into()).collect();
+                indradb::Query::VertexWithCompositeProperty(indradb::VertexWithCompositePropertyQuery::new(
+                    names?, values?,
+                )?)
+            }
 
             crate::QueryVariant::AllEdge(_q) => indradb::Query::AllEdge,
+            crate::QueryVariant::RangeEdge(q) => indradb::Query::RangeEdge(indradb::RangeEdgeQuery {
+                limit: q.limit,
+                t: q.t.map(|t| t.try_into()).transpose()?,
+                start_edge: q.start_edge.map(|start_edge| start_edge.try_into()).transpose()?,
+            }),
             crate::QueryVariant::SpecificEdge(q) => {
                 let edges: Result<Vec<indradb::Edge>, ConversionError> =
                     q.edges.into_iter().map(|id| id.try_into()).collect();
@@ -298,16 +446,34 @@ impl TryInto<indradb::Query> for crate::Query {
                     value: value.try_into()?,
                 })
             }
+            crate::QueryVariant::EdgeWithPropertyRange(q) => {
+                let name = required_field("name", q.name)?;
+                let range = required_field("range", q.range)?;
+                indradb::Query::EdgeWithPropertyRange(indradb::EdgeWithPropertyRangeQuery {
+                    name: name.try_into()?,
+                    range: range.try_into()?,
+                })
+            }
+
+            crate::QueryVariant::ShortestPath(q) => indradb::Query::ShortestPath(indradb::ShortestPathQuery {
+                source_id: required_field("source_id", q.source_id)?.try_into()?,
+                target_id: required_field("target_id", q.target_id)?.try_into()?,
+                t: q.t.map(|t| t.try_into()).transpose()?,
+                max_depth: q.max_depth,
+                weight: q.weight.map(|weight| weight.try_into()).transpose()?,
+            }),
 
             crate::QueryVariant::Pipe(q) => {
                 let direction = q.direction().into();
                 let limit = q.limit;
                 let t = q.t.map(|t| t.try_into()).transpose()?;
+                let paths = q.paths;
                 let inner = required_field("inner", q.inner)?;
                 indradb::Query::Pipe(indradb::PipeQuery {
                     direction,
                     limit,
                     t,
+                    paths,
                     inner: Box::new((*inner).try_into()?),
                 })
             }
@@ -339,6 +505,48 @@ impl TryInto<indradb::Query> for crate::Query {
                     equal: q.equal,
                 })
             }
+            crate::QueryVariant::PipeWithPropertyRange(q) => {
+                let inner = required_field("inner", q.inner)?;
+                let name = required_field("name", q.name)?;
+                let range = required_field("range", q.range)?;
+                indradb::Query::PipeWithPropertyRange(indradb::PipeWithPropertyRangeQuery {
+                    inner: Box::new((*inner).try_into()?),
+                    name: name.try_into()?,
+                    range: range.try_into()?,
+                })
+            }
+            crate::QueryVariant::Traverse(q) => {
+                let direction = q.direction().into();
+                let t = q.t.map(|t| t.try_into()).transpose()?;
+                let inner = required_field("inner", q.inner)?;
+                indradb::Query::Traverse(indradb::TraverseQuery {
+                    inner: Box::new((*inner).try_into()?),
+                    direction,
+                    t,
+                    min_depth: q.min_depth,
+                    max_depth: q.max_depth,
+                    limit: q.limit,
+                })
+            }
+            crate::QueryVariant::Dedup(q) => {
+                let inner = required_field("inner", q.inner)?;
+                indradb::Query::Dedup(indradb::DedupQuery {
+                    inner: Box::new((*inner).try_into()?),
+                })
+            }
+            crate::QueryVariant::OrderBy(q) => {
+                let order = q.order().into();
+                let nulls = q.nulls().into();
+                let inner = required_field("inner", q.inner)?;
+                indradb::Query::OrderBy(indradb::OrderByQuery {
+                    inner: Box::new((*inner).try_into()?),
+                    name: required_field("name", q.name)?.try_into()?,
+                    order,
+                    nulls,
+                    offset: q.offset,
+                    limit: q.limit,
+                })
+            }
 
             crate::QueryVariant::Include(q) => {
                 let inner = required_field("inner", q.inner)?;
@@ -378,9 +586,20 @@ impl From<indradb::QueryOutputValue> for crate::QueryOutputValue {
                     edge_properties: edge_properties.into_iter().map(|ep| ep.into()).collect(),
                 })
             }
+            indradb::QueryOutputValue::Paths(paths) => crate::QueryOutputValueVariant::Paths(crate::QueryOutputPaths {
+                paths: paths.into_iter().map(|path| path.into()).collect(),
+            }),
+            indradb::QueryOutputValue::PipePaths(paths) => {
+                crate::QueryOutputValueVariant::PipePaths(crate::QueryOutputPipePaths {
+                    pipe_paths: paths.into_iter().map(|path| path.into()).collect(),
+                })
+            }
         };
 
-        crate::QueryOutputValue { value: Some(variant) }
+        crate::QueryOutputValue {
+            value: Some(variant),
+            partial: false,
+        }
     }
 }
 
@@ -416,6 +635,16 @@ impl TryInto<indradb::QueryOutputValue> for crate::QueryOutputValue {
                     .collect();
                 indradb::QueryOutputValue::EdgeProperties(edge_properties?)
             }
+            crate::QueryOutputValueVariant::Paths(paths) => {
+                let paths: Result<Vec<indradb::Path>, ConversionError> =
+                    paths.paths.into_iter().map(|path| path.try_into()).collect();
+                indradb::QueryOutputValue::Paths(paths?)
+            }
+            crate::QueryOutputValueVariant::PipePaths(paths) => {
+                let paths: Result<Vec<indradb::PipePath>, ConversionError> =
+                    paths.pipe_paths.into_iter().map(|path| path.try_into()).collect();
+                indradb::QueryOutputValue::PipePaths(paths?)
+            }
         })
     }
 }
@@ -438,6 +667,60 @@ impl From<crate::EdgeDirection> for indradb::EdgeDirection {
     }
 }
 
+impl From<indradb::SortOrder> for crate::SortOrder {
+    fn from(order: indradb::SortOrder) -> Self {
+        match order {
+            indradb::SortOrder::Ascending => crate::SortOrder::Ascending,
+            indradb::SortOrder::Descending => crate::SortOrder::Descending,
+        }
+    }
+}
+
+impl From<crate::SortOrder> for indradb::SortOrder {
+    fn from(order: crate::SortOrder) -> Self {
+        match order {
+            crate::SortOrder::Ascending => indradb::SortOrder::Ascending,
+            crate::SortOrder::Descending => indradb::SortOrder::Descending,
+        }
+    }
+}
+
+impl From<indradb::SchemaKind> for crate::SchemaKind {
+    fn from(kind: indradb::SchemaKind) -> Self {
+        match kind {
+            indradb::SchemaKind::Vertex => crate::SchemaKind::Vertex,
+            indradb::SchemaKind::Edge => crate::SchemaKind::Edge,
+        }
+    }
+}
+
+impl From<crate::SchemaKind> for indradb::SchemaKind {
+    fn from(kind: crate::SchemaKind) -> Self {
+        match kind {
+            crate::SchemaKind::Vertex => indradb::SchemaKind::Vertex,
+            crate::SchemaKind::Edge => indradb::SchemaKind::Edge,
+        }
+    }
+}
+
+impl From<indradb::NullsOrder> for crate::NullsOrder {
+    fn from(nulls: indradb::NullsOrder) -> Self {
+        match nulls {
+            indradb::NullsOrder::First => crate::NullsOrder::NullsFirst,
+            indradb::NullsOrder::Last => crate::NullsOrder::NullsLast,
+        }
+    }
+}
+
+impl From<crate::NullsOrder> for indradb::NullsOrder {
+    fn from(nulls: crate::NullsOrder) -> Self {
+        match nulls {
+            crate::NullsOrder::NullsFirst => indradb::NullsOrder::First,
+            crate::NullsOrder::NullsLast => indradb::NullsOrder::Last,
+        }
+    }
+}
+
 impl From<indradb::NamedProperty> for crate::NamedProperty {
     fn from(prop: indradb::NamedProperty) -> Self {
         crate::NamedProperty {
@@ -542,6 +825,110 @@ impl TryInto<indradb::EdgeProperties> for crate::EdgeProperties {
     }
 }
 
+impl From<indradb::Path> for crate::Path {
+    fn from(path: indradb::Path) -> Self {
+        crate::Path {
+            vertices: path.vertices.into_iter().map(|vertex| vertex.into()).collect(),
+            edges: path.edges.into_iter().map(|edge| edge.into()).collect(),
+            cost: path.cost,
+        }
+    }
+}
+
+impl TryInto<indradb::Path> for crate::Path {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::Path, Self::Error> {
+        Ok(indradb::Path::with_cost(
+            self.vertices
+                .into_iter()
+                .map(|vertex| vertex.try_into())
+                .collect::<Result<_, _>>()?,
+            self.edges
+                .into_iter()
+                .map(|edge| edge.try_into())
+                .collect::<Result<_, _>>()?,
+            self.cost,
+        ))
+    }
+}
+
+impl From<indradb::PipePath> for crate::PipePath {
+    fn from(path: indradb::PipePath) -> Self {
+        crate::PipePath {
+            hops: path
+                .hops
+                .into_iter()
+                .map(|hop| crate::PathHop {
+                    hop: Some(match hop {
+                        indradb::PathHop::Vertex(id) => crate::PathHopVariant::VertexId(id.into()),
+                        indradb::PathHop::Edge(edge) => crate::PathHopVariant::Edge(edge.into()),
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryInto<indradb::PipePath> for crate::PipePath {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::PipePath, Self::Error> {
+        let hops = self
+            .hops
+            .into_iter()
+            .map(|hop| {
+                Ok(match required_field("hop", hop.hop)? {
+                    crate::PathHopVariant::VertexId(id) => indradb::PathHop::Vertex(id.try_into()?),
+                    crate::PathHopVariant::Edge(edge) => indradb::PathHop::Edge(edge.try_into()?),
+                })
+            })
+            .collect::<Result<_, ConversionError>>()?;
+        Ok(indradb::PipePath::new(hops))
+    }
+}
+
+impl From<indradb::EdgeWeight> for crate::EdgeWeight {
+    fn from(weight: indradb::EdgeWeight) -> Self {
+        crate::EdgeWeight {
+            name: Some(weight.name.into()),
+            invalid: Some(match weight.invalid {
+                indradb::InvalidWeight::Skip => crate::InvalidWeightVariant::Skip(()),
+                indradb::InvalidWeight::Fail => crate::InvalidWeightVariant::Fail(()),
+                indradb::InvalidWeight::Default(value) => crate::InvalidWeightVariant::Default(value.into()),
+            }),
+        }
+    }
+}
+
+impl TryInto<indradb::EdgeWeight> for crate::EdgeWeight {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::EdgeWeight, Self::Error> {
+        Ok(indradb::EdgeWeight {
+            name: required_field("name", self.name)?.try_into()?,
+            invalid: match required_field("invalid", self.invalid)? {
+                crate::InvalidWeightVariant::Skip(_) => indradb::InvalidWeight::Skip,
+                crate::InvalidWeightVariant::Fail(_) => indradb::InvalidWeight::Fail,
+                crate::InvalidWeightVariant::Default(value) => indradb::InvalidWeight::Default(value.try_into()?),
+            },
+        })
+    }
+}
+
+// Durations too long for protobuf are clamped, rather than failing the
+// conversion.
+fn duration_to_proto(duration: Duration) -> prost_types::Duration {
+    prost_types::Duration::try_from(duration).unwrap_or(prost_types::Duration {
+        seconds: i64::MAX,
+        nanos: 999_999_999,
+    })
+}
+
+fn duration_from_proto(duration: prost_types::Duration) -> Result<Duration, ConversionError> {
+    Duration::try_from(duration).map_err(|_| ValidationError::InvalidValue.into())
+}
+
 impl From<indradb::BulkInsertItem> for crate::BulkInsertItem {
     fn from(item: indradb::BulkInsertItem) -> Self {
         crate::BulkInsertItem {
@@ -562,6 +949,18 @@ impl From<indradb::BulkInsertItem> for crate::BulkInsertItem {
                         value: Some(value.into()),
                     })
                 }
+                indradb::BulkInsertItem::ExpiringVertex(vertex, ttl) => {
+                    crate::BulkInsertItemVariant::ExpiringVertex(crate::ExpiringVertexBulkInsertItem {
+                        vertex: Some(vertex.into()),
+                        ttl: Some(duration_to_proto(ttl)),
+                    })
+                }
+                indradb::BulkInsertItem::ExpiringEdge(edge, ttl) => {
+                    crate::BulkInsertItemVariant::ExpiringEdge(crate::ExpiringEdgeBulkInsertItem {
+                        edge: Some(edge.into()),
+                        ttl: Some(duration_to_proto(ttl)),
+                    })
+                }
             }),
         }
     }
@@ -584,6 +983,14 @@ impl TryInto<indradb::BulkInsertItem> for crate::BulkInsertItem {
                 required_field("name", item.name)?.try_into()?,
                 required_field("value", item.value)?.try_into()?,
             ),
+            crate::BulkInsertItemVariant::ExpiringVertex(item) => indradb::BulkInsertItem::ExpiringVertex(
+                required_field("vertex", item.vertex)?.try_into()?,
+                duration_from_proto(required_field("ttl", item.ttl)?)?,
+            ),
+            crate::BulkInsertItemVariant::ExpiringEdge(item) => indradb::BulkInsertItem::ExpiringEdge(
+                required_field("edge", item.edge)?.try_into()?,
+                duration_from_proto(required_field("ttl", item.ttl)?)?,
+            ),
         })
     }
 }
@@ -597,6 +1004,39 @@ impl TryInto<indradb::Identifier> for crate::IndexPropertyRequest {
     }
 }
 
+impl TryInto<indradb::Identifier> for crate::UnindexPropertyRequest {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::Identifier, Self::Error> {
+        let name = required_field("name", self.name)?.try_into()?;
+        Ok(name)
+    }
+}
+
+impl From<Vec<indradb::Identifier>> for crate::IndexedPropertiesResponse {
+    fn from(names: Vec<indradb::Identifier>) -> Self {
+        crate::IndexedPropertiesResponse {
+            names: names.into_iter().map(|name| name.into()).collect(),
+        }
+    }
+}
+
+impl TryInto<Vec<indradb::Identifier>> for crate::IndexedPropertiesResponse {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<Vec<indradb::Identifier>, Self::Error> {
+        self.names.into_iter().map(|name| name.try_into()).collect()
+    }
+}
+
+impl TryInto<Vec<indradb::Identifier>> for crate::IndexCompositePropertyRequest {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<Vec<indradb::Identifier>, Self::Error> {
+        self.names.into_iter().map(|name| name.try_into()).collect()
+    }
+}
+
 impl TryInto<(indradb::Query, indradb::Identifier, indradb::Json)> for crate::SetPropertiesRequest {
     type Error = ConversionError;
 
@@ -617,3 +1057,440 @@ impl From<(indradb::Query, indradb::Identifier, indradb::Json)> for crate::SetPr
         }
     }
 }
+
+impl From<indradb::ChangeFilter> for crate::WatchRequest {
+    fn from(filter: indradb::ChangeFilter) -> Self {
+        crate::WatchRequest {
+            vertex_ids: filter.vertex_ids.into_iter().map(|id| id.into()).collect(),
+            vertex_types: filter.vertex_types.into_iter().map(|t| t.into()).collect(),
+            edge_types: filter.edge_types.into_iter().map(|t| t.into()).collect(),
+            property_names: filter.property_names.into_iter().map(|name| name.into()).collect(),
+        }
+    }
+}
+
+impl TryInto<indradb::ChangeFilter> for crate::WatchRequest {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::ChangeFilter, Self::Error> {
+        Ok(indradb::ChangeFilter {
+            vertex_ids: self
+                .vertex_ids
+                .into_iter()
+                .map(|id| id.try_into())
+                .collect::<Result<_, _>>()?,
+            vertex_types: self
+                .vertex_types
+                .into_iter()
+                .map(|t| t.try_into())
+                .collect::<Result<_, _>>()?,
+            edge_types: self
+                .edge_types
+                .into_iter()
+                .map(|t| t.try_into())
+                .collect::<Result<_, _>>()?,
+            property_names: self
+                .property_names
+                .into_iter()
+                .map(|name| name.try_into())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<indradb::ChangeEvent> for crate::ChangeEvent {
+    fn from(event: indradb::ChangeEvent) -> Self {
+        crate::ChangeEvent {
+            event: Some(match event {
+                indradb::ChangeEvent::VertexCreated(vertex) => crate::ChangeEventVariant::VertexCreated(vertex.into()),
+                indradb::ChangeEvent::VertexDeleted(vertex) => crate::ChangeEventVariant::VertexDeleted(vertex.into()),
+                indradb::ChangeEvent::EdgeCreated(edge) => crate::ChangeEventVariant::EdgeCreated(edge.into()),
+                indradb::ChangeEvent::EdgeDeleted(edge) => crate::ChangeEventVariant::EdgeDeleted(edge.into()),
+                indradb::ChangeEvent::VertexPropertySet(id, name, value) => {
+                    crate::ChangeEventVariant::VertexPropertySet(crate::VertexPropertyChange {
+                        id: Some(id.into()),
+                        name: Some(name.into()),
+                        value: Some(value.into()),
+                    })
+                }
+                indradb::ChangeEvent::VertexPropertyDeleted(id, name) => {
+                    crate::ChangeEventVariant::VertexPropertyDeleted(crate::VertexPropertyChange {
+                        id: Some(id.into()),
+                        name: Some(name.into()),
+                        value: None,
+                    })
+                }
+                indradb::ChangeEvent::EdgePropertySet(edge, name, value) => {
+                    crate::ChangeEventVariant::EdgePropertySet(crate::EdgePropertyChange {
+                        edge: Some(edge.into()),
+                        name: Some(name.into()),
+                        value: Some(value.into()),
+                    })
+                }
+                indradb::ChangeEvent::EdgePropertyDeleted(edge, name) => {
+                    crate::ChangeEventVariant::EdgePropertyDeleted(crate::EdgePropertyChange {
+                        edge: Some(edge.into()),
+                        name: Some(name.into()),
+                        value: None,
+                    })
+                }
+                indradb::ChangeEvent::PropertyIndexed(name) => crate::ChangeEventVariant::PropertyIndexed(name.into()),
+                indradb::ChangeEvent::UniquePropertyIndexed(name) => {
+                    crate::ChangeEventVariant::UniquePropertyIndexed(name.into())
+                }
+                indradb::ChangeEvent::CompositePropertyIndexed(names) => {
+                    crate::ChangeEventVariant::CompositePropertyIndexed(crate::CompositePropertyIndex {
+                        names: names.into_iter().map(|name| name.into()).collect(),
+                    })
+                }
+                indradb::ChangeEvent::PropertyUnindexed(name) => {
+                    crate::ChangeEventVariant::PropertyUnindexed(name.into())
+                }
+                indradb::ChangeEvent::SchemaChanged(kind, t) => {
+                    let mut change = crate::SchemaChange {
+                        kind: 0,
+                        t: Some(t.into()),
+                    };
+                    change.set_kind(kind.into());
+                    crate::ChangeEventVariant::SchemaChanged(change)
+                }
+            }),
+        }
+    }
+}
+
+impl TryInto<indradb::ChangeEvent> for crate::ChangeEvent {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::ChangeEvent, Self::Error> {
+        Ok(match required_field("event", self.event)? {
+            crate::ChangeEventVariant::VertexCreated(vertex) => indradb::ChangeEvent::VertexCreated(vertex.try_into()?),
+            crate::ChangeEventVariant::VertexDeleted(vertex) => indradb::ChangeEvent::VertexDeleted(vertex.try_into()?),
+            crate::ChangeEventVariant::EdgeCreated(edge) => indradb::ChangeEvent::EdgeCreated(edge.try_into()?),
+            crate::ChangeEventVariant::EdgeDeleted(edge) => indradb::ChangeEvent::EdgeDeleted(edge.try_into()?),
+            crate::ChangeEventVariant::VertexPropertySet(change) => indradb::ChangeEvent::VertexPropertySet(
+                required_field("id", change.id)?.try_into()?,
+                required_field("name", change.name)?.try_into()?,
+                required_field("value", change.value)?.try_into()?,
+            ),
+            crate::ChangeEventVariant::VertexPropertyDeleted(change) => indradb::ChangeEvent::VertexPropertyDeleted(
+                required_field("id", change.id)?.try_into()?,
+                required_field("name", change.name)?.try_into()?,
+            ),
+            crate::ChangeEventVariant::EdgePropertySet(change) => indradb::ChangeEvent::EdgePropertySet(
+                required_field("edge", change.edge)?.try_into()?,
+                required_field("name", change.name)?.try_into()?,
+                required_field("value", change.value)?.try_into()?,
+            ),
+            crate::ChangeEventVariant::EdgePropertyDeleted(change) => indradb::ChangeEvent::EdgePropertyDeleted(
+                required_field("edge", change.edge)?.try_into()?,
+                required_field("name", change.name)?.try_into()?,
+            ),
+            crate::ChangeEventVariant::PropertyIndexed(name) => indradb::ChangeEvent::PropertyIndexed(name.try_into()?),
+            crate::ChangeEventVariant::UniquePropertyIndexed(name) => {
+                indradb::ChangeEvent::UniquePropertyIndexed(name.try_into()?)
+            }
+            crate::ChangeEventVariant::CompositePropertyIndexed(index) => {
+                indradb::ChangeEvent::CompositePropertyIndexed(
+                    index
+                        .names
+                        .into_iter()
+                        .map(|name| name.try_into())
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            crate::ChangeEventVariant::PropertyUnindexed(name) => {
+                indradb::ChangeEvent::PropertyUnindexed(name.try_into()?)
+            }
+            crate::ChangeEventVariant::SchemaChanged(change) => {
+                indradb::ChangeEvent::SchemaChanged(change.kind().into(), required_field("t", change.t)?.try_into()?)
+            }
+        })
+    }
+}
+
+impl From<indradb::ChangeRecord> for crate::ChangeRecord {
+    fn from(record: indradb::ChangeRecord) -> Self {
+        crate::ChangeRecord {
+            seq: record.seq,
+            event: Some(record.event.into()),
+        }
+    }
+}
+
+impl TryInto<indradb::ChangeRecord> for crate::ChangeRecord {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::ChangeRecord, Self::Error> {
+        Ok(indradb::ChangeRecord {
+            seq: self.seq,
+            event: required_field("event", self.event)?.try_into()?,
+        })
+    }
+}
+
+impl From<indradb::IndexPredicate> for crate::IndexPredicate {
+    fn from(predicate: indradb::IndexPredicate) -> Self {
+        crate::IndexPredicate {
+            predicate: Some(match predicate {
+                indradb::IndexPredicate::Presence => crate::IndexPredicateVariant::Presence(()),
+                indradb::IndexPredicate::Value(value) => crate::IndexPredicateVariant::Value(value.into()),
+                indradb::IndexPredicate::Range(range) => crate::IndexPredicateVariant::Range(range.into()),
+            }),
+        }
+    }
+}
+
+impl TryInto<indradb::IndexPredicate> for crate::IndexPredicate {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::IndexPredicate, Self::Error> {
+        Ok(match required_field("predicate", self.predicate)? {
+            crate::IndexPredicateVariant::Presence(_) => indradb::IndexPredicate::Presence,
+            crate::IndexPredicateVariant::Value(value) => indradb::IndexPredicate::Value(value.try_into()?),
+            crate::IndexPredicateVariant::Range(range) => indradb::IndexPredicate::Range(range.try_into()?),
+        })
+    }
+}
+
+fn expand_to_proto(direction: indradb::EdgeDirection, t: Option<indradb::Identifier>) -> crate::Expand {
+    let mut expand = crate::Expand {
+        direction: 0,
+        t: t.map(|t| t.into()),
+    };
+    expand.set_direction(direction.into());
+    expand
+}
+
+impl From<indradb::QueryPlan> for crate::QueryPlan {
+    fn from(plan: indradb::QueryPlan) -> Self {
+        crate::QueryPlan {
+            op: Some(match plan.op {
+                indradb::PlanOp::VertexScan { start_id } => crate::PlanOpVariant::VertexScan(crate::VertexScan {
+                    start_id: start_id.map(|start_id| start_id.into()),
+                }),
+                indradb::PlanOp::VertexTypeScan { t, start_id } => {
+                    crate::PlanOpVariant::VertexTypeScan(crate::VertexTypeScan {
+                        t: Some(t.into()),
+                        start_id: start_id.map(|start_id| start_id.into()),
+                    })
+                }
+                indradb::PlanOp::VertexLookup { ids } => crate::PlanOpVariant::VertexLookup(crate::VertexLookup {
+                    ids: ids.into_iter().map(|id| id.into()).collect(),
+                }),
+                indradb::PlanOp::VertexIndexScan { name, predicate } => {
+                    crate::PlanOpVariant::VertexIndexScan(crate::IndexScan {
+                        name: Some(name.into()),
+                        predicate: Some(predicate.into()),
+                    })
+                }
+                indradb::PlanOp::VertexCompositeIndexScan { names, values } => {
+                    crate::PlanOpVariant::VertexCompositeIndexScan(crate::CompositeIndexScan {
+                        names: names.into_iter().map(|name| name.into()).collect(),
+                        values: values.into_iter().map(|value| value.into()).collect(),
+                    })
+                }
+                indradb::PlanOp::EdgeScan { start } => crate::PlanOpVariant::EdgeScan(crate::EdgeScan {
+                    start: start.map(|start| start.into()),
+                }),
+                indradb::PlanOp::EdgeTypeScan { t, start } => crate::PlanOpVariant::EdgeTypeScan(crate::EdgeTypeScan {
+                    t: Some(t.into()),
+                    start: start.map(|start| start.into()),
+                }),
+                indradb::PlanOp::EdgeLookup { edges } => crate::PlanOpVariant::EdgeLookup(crate::EdgeLookup {
+                    edges: edges.into_iter().map(|edge| edge.into()).collect(),
+                }),
+                indradb::PlanOp::EdgeIndexScan { name, predicate } => {
+                    crate::PlanOpVariant::EdgeIndexScan(crate::IndexScan {
+                        name: Some(name.into()),
+                        predicate: Some(predicate.into()),
+                    })
+                }
+                indradb::PlanOp::ShortestPath {
+                    source_id,
+                    target_id,
+                    t,
+                    max_depth,
+                    weight,
+                } => crate::PlanOpVariant::ShortestPath(crate::ShortestPath {
+                    source_id: Some(source_id.into()),
+                    target_id: Some(target_id.into()),
+                    t: t.map(|t| t.into()),
+                    max_depth,
+                    weight: weight.map(|weight| weight.into()),
+                }),
+                indradb::PlanOp::EdgeExpand { direction, t } => {
+                    crate::PlanOpVariant::EdgeExpand(expand_to_proto(direction, t))
+                }
+                indradb::PlanOp::VertexExpand { direction, t } => {
+                    crate::PlanOpVariant::VertexExpand(expand_to_proto(direction, t))
+                }
+                indradb::PlanOp::Traverse {
+                    direction,
+                    t,
+                    min_depth,
+                    max_depth,
+                    limit,
+                } => {
+                    let mut traverse = crate::Traverse {
+                        direction: 0,
+                        t: t.map(|t| t.into()),
+                        min_depth,
+                        max_depth,
+                        limit,
+                    };
+                    traverse.set_direction(direction.into());
+                    crate::PlanOpVariant::Traverse(traverse)
+                }
+                indradb::PlanOp::IndexFilter {
+                    name,
+                    predicate,
+                    negated,
+                } => crate::PlanOpVariant::IndexFilter(crate::IndexFilter {
+                    name: Some(name.into()),
+                    predicate: Some(predicate.into()),
+                    negated,
+                }),
+                indradb::PlanOp::PropertyFetch { name } => crate::PlanOpVariant::PropertyFetch(crate::PropertyFetch {
+                    name: name.map(|name| name.into()),
+                }),
+                indradb::PlanOp::OrderBy {
+                    name,
+                    order,
+                    nulls,
+                    offset,
+                    limit,
+                } => {
+                    let mut order_by = crate::OrderBy {
+                        name: Some(name.into()),
+                        order: 0,
+                        nulls: 0,
+                        offset,
+                        limit,
+                    };
+                    order_by.set_order(order.into());
+                    order_by.set_nulls(nulls.into());
+                    crate::PlanOpVariant::OrderBy(order_by)
+                }
+                indradb::PlanOp::Limit(limit) => crate::PlanOpVariant::Limit(limit),
+                indradb::PlanOp::PathStart => crate::PlanOpVariant::PathStart(()),
+                indradb::PlanOp::Dedup => crate::PlanOpVariant::Dedup(()),
+                indradb::PlanOp::Count => crate::PlanOpVariant::Count(()),
+                indradb::PlanOp::VertexCount => crate::PlanOpVariant::VertexCount(()),
+                indradb::PlanOp::EdgeCount => crate::PlanOpVariant::EdgeCount(()),
+            }),
+            input: plan.input.map(|input| Box::new((*input).into())),
+            exported: plan.exported,
+            estimated_rows: plan.estimated_rows.unwrap_or_default(),
+        }
+    }
+}
+
+impl TryInto<indradb::QueryPlan> for crate::QueryPlan {
+    type Error = ConversionError;
+
+    fn try_into(self) -> Result<indradb::QueryPlan, Self::Error> {
+        let op = match required_field("op", self.op)? {
+            crate::PlanOpVariant::VertexScan(scan) => indradb::PlanOp::VertexScan {
+                start_id: scan.start_id.map(|start_id| start_id.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::VertexTypeScan(scan) => indradb::PlanOp::VertexTypeScan {
+                t: required_field("t", scan.t)?.try_into()?,
+                start_id: scan.start_id.map(|start_id| start_id.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::VertexLookup(lookup) => indradb::PlanOp::VertexLookup {
+                ids: lookup
+                    .ids
+                    .into_iter()
+                    .map(|id| id.try_into())
+                    .collect::<Result<_, _>>()?,
+            },
+            crate::PlanOpVariant::VertexIndexScan(scan) => indradb::PlanOp::VertexIndexScan {
+                name: required_field("name", scan.name)?.try_into()?,
+                predicate: required_field("predicate", scan.predicate)?.try_into()?,
+            },
+            crate::PlanOpVariant::VertexCompositeIndexScan(scan) => indradb::PlanOp::VertexCompositeIndexScan {
+                names: scan
+                    .names
+                    .into_iter()
+                    .map(|name| name.try_into())
+                    .collect::<Result<_, _>>()?,
+                values: scan
+                    .values
+                    .into_iter()
+                    .map(|value| value.try_into())
+                    .collect::<Result<_, _>>()?,
+            },
+            crate::PlanOpVariant::EdgeScan(scan) => indradb::PlanOp::EdgeScan {
+                start: scan.start.map(|start| start.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::EdgeTypeScan(scan) => indradb::PlanOp::EdgeTypeScan {
+                t: required_field("t", scan.t)?.try_into()?,
+                start: scan.start.map(|start| start.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::EdgeLookup(lookup) => indradb::PlanOp::EdgeLookup {
+                edges: lookup
+                    .edges
+                    .into_iter()
+                    .map(|edge| edge.try_into())
+                    .collect::<Result<_, _>>()?,
+            },
+            crate::PlanOpVariant::EdgeIndexScan(scan) => indradb::PlanOp::EdgeIndexScan {
+                name: required_field("name", scan.name)?.try_into()?,
+                predicate: required_field("predicate", scan.predicate)?.try_into()?,
+            },
+            crate::PlanOpVariant::ShortestPath(path) => indradb::PlanOp::ShortestPath {
+                source_id: required_field("source_id", path.source_id)?.try_into()?,
+                target_id: required_field("target_id", path.target_id)?.try_into()?,
+                t: path.t.map(|t| t.try_into()).transpose()?,
+                max_depth: path.max_depth,
+                weight: path.weight.map(|weight| weight.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::EdgeExpand(expand) => indradb::PlanOp::EdgeExpand {
+                direction: expand.direction().into(),
+                t: expand.t.map(|t| t.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::VertexExpand(expand) => indradb::PlanOp::VertexExpand {
+                direction: expand.direction().into(),
+                t: expand.t.map(|t| t.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::Traverse(traverse) => indradb::PlanOp::Traverse {
+                direction: traverse.direction().into(),
+                t: traverse.t.map(|t| t.try_into()).transpose()?,
+                min_depth: traverse.min_depth,
+                max_depth: traverse.max_depth,
+                limit: traverse.limit,
+            },
+            crate::PlanOpVariant::IndexFilter(filter) => indradb::PlanOp::IndexFilter {
+                name: required_field("name", filter.name)?.try_into()?,
+                predicate: required_field("predicate", filter.predicate)?.try_into()?,
+                negated: filter.negated,
+            },
+            crate::PlanOpVariant::PropertyFetch(fetch) => indradb::PlanOp::PropertyFetch {
+                name: fetch.name.map(|name| name.try_into()).transpose()?,
+            },
+            crate::PlanOpVariant::OrderBy(order_by) => indradb::PlanOp::OrderBy {
+                order: order_by.order().into(),
+                nulls: order_by.nulls().into(),
+                name: required_field("name", order_by.name)?.try_into()?,
+                offset: order_by.offset,
+                limit: order_by.limit,
+            },
+            crate::PlanOpVariant::Limit(limit) => indradb::PlanOp::Limit(limit),
+            crate::PlanOpVariant::PathStart(_) => indradb::PlanOp::PathStart,
+            crate::PlanOpVariant::Dedup(_) => indradb::PlanOp::Dedup,
+            crate::PlanOpVariant::Count(_) => indradb::PlanOp::Count,
+            crate::PlanOpVariant::VertexCount(_) => indradb::PlanOp::VertexCount,
+            crate::PlanOpVariant::EdgeCount(_) => indradb::PlanOp::EdgeCount,
+        };
+
+        Ok(indradb::QueryPlan {
+            op,
+            input: self.input.map(|input| (*input).try_into().map(Box::new)).transpose()?,
+            exported: self.exported,
+            // Plans sent over gRPC are always explained, so they always
+            // carry estimates
+            estimated_rows: Some(self.estimated_rows),
+        })
+    }
+}
@@ -5,13 +5,22 @@ extern crate indradb;
 tonic::include_proto!("indradb");
 
 pub use bulk_insert_item::Item as BulkInsertItemVariant;
+pub use change_event::Event as ChangeEventVariant;
+pub use edge_weight::Invalid as InvalidWeightVariant;
+pub use index_predicate::Predicate as IndexPredicateVariant;
 pub use indra_db_client::IndraDbClient as ProtoClient;
+pub use json_bound::Bound as JsonBoundVariant;
+pub use path_hop::Hop as PathHopVariant;
 pub use query::Query as QueryVariant;
 pub use query_output_value::Value as QueryOutputValueVariant;
+pub use query_plan::Op as PlanOpVariant;
 
 mod converters;
 pub use converters::*;
 
+mod metadata;
+pub use metadata::*;
+
 #[cfg(feature = "client")]
 mod client;
 #[cfg(feature = "client")]
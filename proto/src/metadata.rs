@@ -0,0 +1,69 @@
+//! Reading and writing the gRPC request metadata that sets a query's budget.
+//! Each limit is set with its own key, as a decimal integer. Limits whose
+//! keys are absent fall back to a default budget.
+
+use std::time::Duration;
+
+use tonic::metadata::{MetadataMap, MetadataValue};
+
+use crate::ConversionError;
+
+/// The metadata key for a query's timeout, in milliseconds.
+pub const QUERY_TIMEOUT_MS_KEY: &str = "indradb-query-timeout-ms";
+/// The metadata key for the maximum number of intermediate rows a query can
+/// output.
+pub const QUERY_MAX_ROWS_KEY: &str = "indradb-query-max-rows";
+/// The metadata key for the maximum number of edges a query can scan.
+pub const QUERY_MAX_EDGES_SCANNED_KEY: &str = "indradb-query-max-edges-scanned";
+
+fn read_limit(metadata: &MetadataMap, key: &str) -> Result<Option<u64>, ConversionError> {
+    match metadata.get(key) {
+        Some(value) => {
+            let limit = value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| ConversionError::InvalidMetadata { key: key.to_string() })?;
+            Ok(Some(limit))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reads a query budget from request metadata.
+///
+/// # Arguments
+/// * `metadata`: The request metadata.
+/// * `default`: The budget to use for limits that aren't set in the
+///   metadata.
+pub fn read_query_budget(
+    metadata: &MetadataMap,
+    default: indradb::QueryBudget,
+) -> Result<indradb::QueryBudget, ConversionError> {
+    Ok(indradb::QueryBudget {
+        timeout: read_limit(metadata, QUERY_TIMEOUT_MS_KEY)?
+            .map(Duration::from_millis)
+            .or(default.timeout),
+        max_rows: read_limit(metadata, QUERY_MAX_ROWS_KEY)?.or(default.max_rows),
+        max_edges_scanned: read_limit(metadata, QUERY_MAX_EDGES_SCANNED_KEY)?.or(default.max_edges_scanned),
+    })
+}
+
+/// Writes a query budget to request metadata. Unbounded limits aren't
+/// written, so the server's defaults apply to them.
+///
+/// # Arguments
+/// * `metadata`: The request metadata.
+/// * `budget`: The query budget.
+pub fn write_query_budget(metadata: &mut MetadataMap, budget: &indradb::QueryBudget) {
+    if let Some(timeout) = budget.timeout {
+        let millis = u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+        metadata.insert(QUERY_TIMEOUT_MS_KEY, MetadataValue::from(millis));
+    }
+    if let Some(max_rows) = budget.max_rows {
+        metadata.insert(QUERY_MAX_ROWS_KEY, MetadataValue::from(max_rows));
+    }
+    if let Some(max_edges_scanned) = budget.max_edges_scanned {
+        metadata.insert(QUERY_MAX_EDGES_SCANNED_KEY, MetadataValue::from(max_edges_scanned));
+    }
+}
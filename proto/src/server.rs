@@ -2,28 +2,103 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use std::error::Error as StdError;
 use std::fmt;
+use std::iter;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
 
 use libloading::Library;
 use tokio::net::TcpListener;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
 use tokio_stream::{Stream, StreamExt};
+use tonic::metadata::MetadataMap;
 use tonic::transport::{Error as TonicTransportError, Server as TonicServer};
 use tonic::{Request, Response, Status, Streaming};
 
 const CHANNEL_CAPACITY: usize = 100;
 
-fn send(tx: &mpsc::Sender<Result<crate::QueryOutputValue, Status>>, result: Result<crate::QueryOutputValue, Status>) {
+type QueryOutputStream = Pin<Box<dyn Stream<Item = Result<crate::QueryOutputValue, Status>> + Send + Sync + 'static>>;
+
+const CHUNK_SIZE: usize = 1000;
+
+// How many changes can be buffered for watchers before the slowest ones fall
+// behind.
+const WATCH_CAPACITY: usize = 1000;
+
+fn send(
+    tx: &mpsc::Sender<Result<crate::QueryOutputValue, Status>>,
+    result: Result<crate::QueryOutputValue, Status>,
+) -> bool {
     if let Err(err) = tx.blocking_send(result) {
         eprintln!("could not send message to client: {err}");
+        false
+    } else {
+        true
+    }
+}
+
+// Pulls an output value in chunks of up to `CHUNK_SIZE` items, as they're
+// read. An error ends the chunks.
+fn chunked<'a, T: 'a>(
+    items: indradb::DynIter<'a, T>,
+    mapper: fn(Vec<T>) -> indradb::QueryOutputValue,
+) -> Box<dyn Iterator<Item = Result<crate::QueryOutputValue, Status>> + 'a> {
+    let mut items = items.peekable();
+    let mut done = false;
+
+    Box::new(iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let mut chunk = Vec::new();
+        while chunk.len() < CHUNK_SIZE {
+            match items.next() {
+                Some(Ok(item)) => chunk.push(item),
+                Some(Err(err)) => {
+                    done = true;
+                    return Some(Err(map_indradb_error(err)));
+                }
+                None => break,
+            }
+        }
+        let mut value: crate::QueryOutputValue = mapper(chunk).into();
+        value.partial = items.peek().is_some();
+        done = !value.partial;
+        Some(Ok(value))
+    }))
+}
+
+fn output_chunks(
+    output: indradb::QueryOutputIter<'_>,
+) -> Box<dyn Iterator<Item = Result<crate::QueryOutputValue, Status>> + '_> {
+    match output {
+        indradb::QueryOutputIter::Vertices(vertices) => chunked(vertices, indradb::QueryOutputValue::Vertices),
+        indradb::QueryOutputIter::Edges(edges) => chunked(edges, indradb::QueryOutputValue::Edges),
+        indradb::QueryOutputIter::Count(count) => {
+            Box::new(iter::once(Ok(indradb::QueryOutputValue::Count(count).into())))
+        }
+        indradb::QueryOutputIter::VertexProperties(props) => {
+            chunked(props, indradb::QueryOutputValue::VertexProperties)
+        }
+        indradb::QueryOutputIter::EdgeProperties(props) => chunked(props, indradb::QueryOutputValue::EdgeProperties),
+        indradb::QueryOutputIter::Paths(paths) => chunked(paths, indradb::QueryOutputValue::Paths),
+        indradb::QueryOutputIter::PipePaths(paths) => chunked(paths, indradb::QueryOutputValue::PipePaths),
+    }
+}
+
+fn map_indradb_error(err: indradb::Error) -> Status {
+    match err {
+        indradb::Error::QueryBudgetExceeded(indradb::QueryLimit::Deadline) => {
+            Status::deadline_exceeded(format!("{err}"))
+        }
+        indradb::Error::QueryBudgetExceeded(_) => Status::resource_exhausted(format!("{err}")),
+        _ => Status::internal(format!("{err}")),
     }
 }
 
 fn map_indradb_result<T>(res: Result<T, indradb::Error>) -> Result<T, Status> {
-    res.map_err(|err| Status::internal(format!("{err}")))
+    res.map_err(map_indradb_error)
 }
 
 fn map_conversion_result<T>(res: Result<T, crate::ConversionError>) -> Result<T, Status> {
@@ -115,6 +190,26 @@ impl From<glob::GlobError> for InitError {
     }
 }
 
+// Broadcasts the database's changes to watchers.
+struct WatchSink(broadcast::Sender<indradb::ChangeRecord>);
+
+impl WatchSink {
+    fn register<D: indradb::Datastore>(db: &indradb::Database<D>) -> broadcast::Sender<indradb::ChangeRecord> {
+        let (tx, _) = broadcast::channel(WATCH_CAPACITY);
+        db.add_change_sink(Arc::new(WatchSink(tx.clone())));
+        tx
+    }
+}
+
+impl indradb::ChangeSink for WatchSink {
+    fn receive(&self, records: &[indradb::ChangeRecord]) {
+        for record in records {
+            // This only fails if nothing is watching.
+            let _ = self.0.send(record.clone());
+        }
+    }
+}
+
 #[derive(Default)]
 struct Plugins {
     entries: HashMap<String, Box<dyn indradb_plugin_host::Plugin>>,
@@ -127,6 +222,7 @@ struct Plugins {
 pub struct Server<D: indradb::Datastore + Send + Sync + 'static> {
     db: Arc<indradb::Database<D>>,
     plugins: Arc<Plugins>,
+    changes: broadcast::Sender<indradb::ChangeRecord>,
 }
 
 impl<D: indradb::Datastore + Send + Sync + 'static> Server<D> {
@@ -136,6 +232,7 @@ impl<D: indradb::Datastore + Send + Sync + 'static> Server<D> {
     /// * `datastore`: The underlying datastore to use.
     pub fn new(db: Arc<indradb::Database<D>>) -> Self {
         Self {
+            changes: WatchSink::register(&db),
             db,
             plugins: Arc::new(Plugins::default()),
         }
@@ -182,6 +279,7 @@ impl<D: indradb::Datastore + Send + Sync + 'static> Server<D> {
         }
 
         Ok(Self {
+            changes: WatchSink::register(&db),
             db,
             plugins: Arc::new(Plugins {
                 _libraries: libraries,
@@ -189,6 +287,72 @@ impl<D: indradb::Datastore + Send + Sync + 'static> Server<D> {
             }),
         })
     }
+
+    // Gets the options for a query, with the budget set through the request
+    // metadata merged over the database's default.
+    fn get_options(&self, metadata: &MetadataMap) -> Result<indradb::GetOptions, crate::ConversionError> {
+        let budget = crate::read_query_budget(metadata, self.db.query_budget())?;
+        Ok(indradb::GetOptions {
+            budget: Some(budget),
+            ..indradb::GetOptions::default()
+        })
+    }
+
+    // Runs a query on a blocking thread, streaming its output back as it's
+    // pulled. The datastore may hold locks while the output is being read, so
+    // a slow client mustn't hold them too: once the client falls behind, the
+    // rest of the output is read into memory, and sent after the read is
+    // done.
+    fn stream_output(&self, q: indradb::Query, options: indradb::GetOptions) -> Response<QueryOutputStream> {
+        let db = self.db.clone();
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::task::spawn_blocking(move || {
+            let mut behind = Vec::new();
+            {
+                // Output that's pulled lazily is read from a snapshot where
+                // the datastore supports it, so that it's consistent.
+                let snapshot_options = indradb::GetOptions {
+                    snapshot: true,
+                    ..options.clone()
+                };
+                let output = match db.get_iter_with_options(q.clone(), snapshot_options) {
+                    Err(indradb::Error::Unsupported) => db.get_iter_with_options(q, options),
+                    result => result,
+                };
+                let output = match output {
+                    Ok(output) => output,
+                    Err(err) => {
+                        send(&tx, Err(map_indradb_error(err)));
+                        return;
+                    }
+                };
+
+                for message in output.into_iter().flat_map(output_chunks) {
+                    let failed = message.is_err();
+                    if behind.is_empty() {
+                        match tx.try_send(message) {
+                            Ok(()) => {}
+                            Err(mpsc::error::TrySendError::Full(message)) => behind.push(message),
+                            Err(mpsc::error::TrySendError::Closed(_)) => return,
+                        }
+                    } else {
+                        behind.push(message);
+                    }
+                    if failed {
+                        break;
+                    }
+                }
+            }
+
+            for message in behind {
+                if !send(&tx, message) {
+                    break;
+                }
+            }
+        });
+
+        Response::new(Box::pin(ReceiverStream::new(rx)))
+    }
 }
 
 #[tonic::async_trait]
@@ -227,21 +391,11 @@ impl<D: indradb::Datastore + Send + Sync + 'static> crate::indra_db_server::Indr
         Ok(Response::new(crate::CreateResponse { created: res }))
     }
 
-    type GetStream = Pin<Box<dyn Stream<Item = Result<crate::QueryOutputValue, Status>> + Send + Sync + 'static>>;
+    type GetStream = QueryOutputStream;
     async fn get(&self, request: Request<crate::Query>) -> Result<Response<Self::GetStream>, Status> {
-        let db = self.db.clone();
+        let options = map_conversion_result(self.get_options(request.metadata()))?;
         let q: indradb::Query = map_conversion_result(request.into_inner().try_into())?;
-        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
-        tokio::task::spawn_blocking(move || match map_indradb_result(db.get(q)) {
-            Ok(output) => {
-                for output in output.into_iter() {
-                    send(&tx, Ok(output.into()));
-                }
-            }
-            Err(err) => send(&tx, Err(err)),
-        });
-
-        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+        Ok(self.stream_output(q, options))
     }
 
     async fn delete(&self, request: Request<crate::Query>) -> Result<Response<()>, Status> {
@@ -284,6 +438,32 @@ impl<D: indradb::Datastore + Send + Sync + 'static> crate::indra_db_server::Indr
         Ok(Response::new(()))
     }
 
+    async fn unindex_property(&self, request: Request<crate::UnindexPropertyRequest>) -> Result<Response<()>, Status> {
+        let db = self.db.clone();
+
+        let name: indradb::Identifier = map_conversion_result(request.into_inner().try_into())?;
+        map_jh_indra_result(tokio::task::spawn_blocking(move || db.unindex_property(name)).await)?;
+        Ok(Response::new(()))
+    }
+
+    async fn indexed_properties(&self, _: Request<()>) -> Result<Response<crate::IndexedPropertiesResponse>, Status> {
+        let db = self.db.clone();
+
+        let names = map_jh_indra_result(tokio::task::spawn_blocking(move || db.indexed_properties()).await)?;
+        Ok(Response::new(names.into()))
+    }
+
+    async fn index_composite_property(
+        &self,
+        request: Request<crate::IndexCompositePropertyRequest>,
+    ) -> Result<Response<()>, Status> {
+        let db = self.db.clone();
+
+        let names: Vec<indradb::Identifier> = map_conversion_result(request.into_inner().try_into())?;
+        map_jh_indra_result(tokio::task::spawn_blocking(move || db.index_composite_property(names)).await)?;
+        Ok(Response::new(()))
+    }
+
     async fn execute_plugin(
         &self,
         request: Request<crate::ExecutePluginRequest>,
@@ -310,6 +490,49 @@ impl<D: indradb::Datastore + Send + Sync + 'static> crate::indra_db_server::Indr
             Err(Status::not_found("unknown plugin"))
         }
     }
+
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<crate::ChangeRecord, Status>> + Send + Sync + 'static>>;
+    async fn watch(&self, request: Request<crate::WatchRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let filter: indradb::ChangeFilter = map_conversion_result(request.into_inner().try_into())?;
+        let mut changes = self.changes.subscribe();
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            loop {
+                let result = match changes.recv().await {
+                    Ok(record) if filter.matches(&record.event) => Ok(record.into()),
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(count)) => Err(Status::data_loss(format!(
+                        "watcher fell behind, and {count} changes were dropped"
+                    ))),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let failed = result.is_err();
+                if tx.send(result).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn explain(&self, request: Request<crate::Query>) -> Result<Response<crate::QueryPlan>, Status> {
+        let db = self.db.clone();
+        let q: indradb::Query = map_conversion_result(request.into_inner().try_into())?;
+        let plan = map_jh_indra_result(tokio::task::spawn_blocking(move || db.explain(q)).await)?;
+        Ok(Response::new(plan.into()))
+    }
+
+    type GetTextStream = QueryOutputStream;
+    async fn get_text(&self, request: Request<crate::GetTextRequest>) -> Result<Response<Self::GetTextStream>, Status> {
+        let options = map_conversion_result(self.get_options(request.metadata()))?;
+        let q: indradb::Query = request
+            .into_inner()
+            .query
+            .parse()
+            .map_err(|err| Status::invalid_argument(format!("{err}")))?;
+        Ok(self.stream_output(q, options))
+    }
 }
 
 /// Runs the IndraDB server.
@@ -14,9 +14,10 @@ use std::time::Duration;
 
 use indradb::{
     util, AllEdgeQuery, AllVertexQuery, BulkInsertItem, CountQueryExt, Datastore, DynIter, Edge,
-    EdgeWithPropertyPresenceQuery, EdgeWithPropertyValueQuery, Error, Identifier, Json, Query, QueryExt,
-    QueryOutputValue, RangeVertexQuery, Result, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Vertex,
-    VertexWithPropertyPresenceQuery, VertexWithPropertyValueQuery,
+    EdgeWithPropertyPresenceQuery, EdgeWithPropertyRangeQuery, EdgeWithPropertyValueQuery, Error, Identifier, Json,
+    JsonRange, Query, QueryExt, QueryOutputValue, RangeEdgeQuery, RangeVertexQuery, Result, Schema, SchemaKind,
+    SpecificEdgeQuery, SpecificVertexQuery, Transaction, Vertex, VertexWithCompositePropertyQuery,
+    VertexWithPropertyPresenceQuery, VertexWithPropertyRangeQuery, VertexWithPropertyValueQuery,
 };
 
 use tokio::runtime::Runtime;
@@ -90,6 +91,10 @@ impl<'a> Transaction<'a> for ClientTransaction {
         self.get_vertices(RangeVertexQuery::default().start_id(offset))
     }
 
+    fn range_vertices_of_type(&'a self, t: Identifier, offset: Uuid) -> Result<DynIter<'a, Vertex>> {
+        self.get_vertices(RangeVertexQuery::default().t(t).start_id(offset))
+    }
+
     fn specific_vertices(&'a self, ids: Vec<Uuid>) -> Result<DynIter<'a, Vertex>> {
         self.get_vertices(SpecificVertexQuery::new(ids))
     }
@@ -106,6 +111,26 @@ impl<'a> Transaction<'a> for ClientTransaction {
         Ok(Some(Box::new(vertices.into_iter().map(|v| Ok(v.id)))))
     }
 
+    fn vertex_ids_with_property_range(
+        &'a self,
+        name: Identifier,
+        range: &JsonRange,
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        let q = VertexWithPropertyRangeQuery::new(name, range.clone());
+        let vertices = util::extract_vertices(self.get(q)?).unwrap();
+        Ok(Some(Box::new(vertices.into_iter().map(|v| Ok(v.id)))))
+    }
+
+    fn vertex_ids_with_composite_property_values(
+        &'a self,
+        names: &[Identifier],
+        values: &[Json],
+    ) -> Result<Option<DynIter<'a, Uuid>>> {
+        let q = VertexWithCompositePropertyQuery::new(names.to_vec(), values.to_vec())?;
+        let vertices = util::extract_vertices(self.get(q)?).unwrap();
+        Ok(Some(Box::new(vertices.into_iter().map(|v| Ok(v.id)))))
+    }
+
     fn edge_count(&self) -> u64 {
         self.get_count(AllEdgeQuery.count().unwrap())
     }
@@ -115,19 +140,27 @@ impl<'a> Transaction<'a> for ClientTransaction {
     }
 
     fn range_edges(&'a self, offset: Edge) -> Result<DynIter<'a, Edge>> {
-        let edges = util::extract_edges(self.get(AllEdgeQuery)?).unwrap();
-        let iter = edges.into_iter().filter(move |e| e >= &offset).map(Ok);
-        Ok(Box::new(iter))
+        self.get_edges(RangeEdgeQuery::default().start_edge(offset))
     }
 
     fn range_reversed_edges(&'a self, offset: Edge) -> Result<DynIter<'a, Edge>> {
-        let edges = util::extract_edges(self.get(AllEdgeQuery)?).unwrap();
-        let iter = edges
+        let mut edges: Vec<Edge> = util::extract_edges(self.get(AllEdgeQuery)?)
+            .unwrap()
             .into_iter()
             .map(|e| e.reversed())
-            .filter(move |e| e >= &offset)
-            .map(Ok);
-        Ok(Box::new(iter))
+            .filter(|e| e >= &offset)
+            .collect();
+        // Reversed edges are read in the order they're stored in
+        edges.sort();
+        Ok(Box::new(edges.into_iter().map(Ok)))
+    }
+
+    fn range_edges_of_type(&'a self, t: Identifier, offset: Option<Edge>) -> Result<DynIter<'a, Edge>> {
+        let q = RangeEdgeQuery::default().t(t);
+        match offset {
+            Some(offset) => self.get_edges(q.start_edge(offset)),
+            None => self.get_edges(q),
+        }
     }
 
     fn specific_edges(&'a self, edges: Vec<Edge>) -> Result<DynIter<'a, Edge>> {
@@ -146,6 +179,12 @@ impl<'a> Transaction<'a> for ClientTransaction {
         Ok(Some(Box::new(edges.into_iter().map(Ok))))
     }
 
+    fn edges_with_property_range(&'a self, name: Identifier, range: &JsonRange) -> Result<Option<DynIter<'a, Edge>>> {
+        let q = EdgeWithPropertyRangeQuery::new(name, range.clone());
+        let edges = util::extract_edges(self.get(q)?).unwrap();
+        Ok(Some(Box::new(edges.into_iter().map(Ok))))
+    }
+
     fn vertex_property(&self, vertex: &Vertex, name: Identifier) -> Result<Option<Json>> {
         let q = SpecificVertexQuery::single(vertex.id).properties().unwrap().name(name);
         let props = util::extract_vertex_properties(self.get(q)?).unwrap();
@@ -260,6 +299,45 @@ impl<'a> Transaction<'a> for ClientTransaction {
         )
     }
 
+    // Unique indexes aren't exposed over gRPC, so the unique index tests are
+    // skipped.
+    fn index_unique_property(&mut self, _name: Identifier) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    fn index_composite_property(&mut self, names: Vec<Identifier>) -> Result<()> {
+        map_client_result(
+            self.exec
+                .borrow_mut()
+                .block_on(self.client.borrow_mut().index_composite_property(names)),
+        )
+    }
+
+    fn unindex_property(&mut self, name: Identifier) -> Result<()> {
+        map_client_result(
+            self.exec
+                .borrow_mut()
+                .block_on(self.client.borrow_mut().unindex_property(name)),
+        )
+    }
+
+    fn indexed_properties(&self) -> Result<Vec<Identifier>> {
+        map_client_result(
+            self.exec
+                .borrow_mut()
+                .block_on(self.client.borrow_mut().indexed_properties()),
+        )
+    }
+
+    // Schemas aren't exposed over gRPC, so the schema tests are skipped.
+    fn set_schema(&mut self, _kind: SchemaKind, _t: Identifier, _schema: Option<Schema>) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    fn schema(&self, _kind: SchemaKind, _t: Identifier) -> Result<Option<Schema>> {
+        Err(Error::Unsupported)
+    }
+
     fn set_vertex_properties(&mut self, vertex_ids: Vec<Uuid>, name: Identifier, value: &Json) -> Result<()> {
         self.set_properties(SpecificVertexQuery::new(vertex_ids), name, value)
     }
@@ -305,14 +383,13 @@ impl Datastore for ClientDatastore {
     }
 }
 
-full_test_impl!({
-    use indradb::Database;
+// Starts a server backed by a new in-memory datastore, returning its port.
+#[cfg(test)]
+fn start_server(rt: &Runtime) -> u16 {
     use std::net::ToSocketAddrs;
     use std::sync::Arc;
     use tokio::net::TcpListener;
 
-    let rt = Runtime::new().unwrap();
-
     let addr = "127.0.0.1:0".to_socket_addrs().unwrap().next().unwrap();
     let listener = rt.block_on(TcpListener::bind(&addr)).unwrap();
     let port = listener.local_addr().unwrap().port();
@@ -320,6 +397,227 @@ full_test_impl!({
         Arc::new(indradb::MemoryDatastore::new_db()),
         listener,
     ));
+    port
+}
+
+full_test_impl!({
+    use indradb::Database;
 
-    Database::new(ClientDatastore::new(port as u16, rt))
+    let rt = Runtime::new().unwrap();
+    let port = start_server(&rt);
+    Database::new(ClientDatastore::new(port, rt))
 });
+
+#[test]
+fn should_watch_changes() {
+    use indradb::{ChangeEvent, ChangeFilter};
+    use tokio_stream::StreamExt;
+
+    let rt = Runtime::new().unwrap();
+    let port = start_server(&rt);
+    let endpoint: Endpoint = format!("http://127.0.0.1:{port}").try_into().unwrap();
+
+    rt.block_on(async move {
+        let mut client = crate::Client::new(endpoint).await.unwrap();
+        let t = Identifier::new("test_vertex_type").unwrap();
+        let outbound = Vertex::new(t);
+        let inbound = Vertex::new(t);
+        let edge = Edge::new(outbound.id, Identifier::new("test_edge_type").unwrap(), inbound.id);
+        let filter = ChangeFilter {
+            vertex_ids: [inbound.id].into_iter().collect(),
+            edge_types: [edge.t].into_iter().collect(),
+            ..ChangeFilter::default()
+        };
+        let mut changes = Box::pin(client.watch(filter).await.unwrap());
+
+        client.create_vertex(&outbound).await.unwrap();
+        client.create_vertex(&inbound).await.unwrap();
+        client.create_edge(&edge).await.unwrap();
+        let record = changes.next().await.unwrap().unwrap();
+        assert_eq!(record.seq, 2);
+        assert_eq!(record.event, ChangeEvent::EdgeCreated(edge));
+    });
+}
+
+#[test]
+fn should_explain_queries() {
+    use indradb::{IndexPredicate, PlanOp, QueryPlan};
+
+    let rt = Runtime::new().unwrap();
+    let port = start_server(&rt);
+    let endpoint: Endpoint = format!("http://127.0.0.1:{port}").try_into().unwrap();
+
+    rt.block_on(async move {
+        let mut client = crate::Client::new(endpoint).await.unwrap();
+        let name = Identifier::new("foo").unwrap();
+        client.index_property(name).await.unwrap();
+        let id = client
+            .create_vertex_from_type(Identifier::new("test_vertex_type").unwrap())
+            .await
+            .unwrap();
+        client
+            .set_properties(SpecificVertexQuery::single(id), name, &indradb::ijson!(1))
+            .await
+            .unwrap();
+        client
+            .create_vertex(&Vertex::new(Identifier::new("test_vertex_type").unwrap()))
+            .await
+            .unwrap();
+
+        let q = AllVertexQuery
+            .with_property_equal_to(name, indradb::ijson!(1))
+            .unwrap()
+            .count()
+            .unwrap();
+        let plan = client.explain(q).await.unwrap();
+        let scan = QueryPlan {
+            op: PlanOp::VertexIndexScan {
+                name,
+                predicate: IndexPredicate::Value(indradb::ijson!(1)),
+            },
+            input: None,
+            exported: false,
+            estimated_rows: Some(1),
+        };
+        assert_eq!(
+            plan,
+            QueryPlan {
+                op: PlanOp::Count,
+                input: Some(Box::new(scan)),
+                exported: false,
+                estimated_rows: Some(1),
+            }
+        );
+    });
+}
+
+#[test]
+fn should_enforce_query_budgets() {
+    use indradb::QueryBudget;
+    use tonic::Code;
+
+    let rt = Runtime::new().unwrap();
+    let port = start_server(&rt);
+    let endpoint: Endpoint = format!("http://127.0.0.1:{port}").try_into().unwrap();
+
+    rt.block_on(async move {
+        let mut client = crate::Client::new(endpoint).await.unwrap();
+        let t = Identifier::new("test_vertex_type").unwrap();
+        for _ in 0..2 {
+            client.create_vertex(&Vertex::new(t)).await.unwrap();
+        }
+
+        let budget = QueryBudget {
+            max_rows: Some(1),
+            ..QueryBudget::default()
+        };
+        match client.get_with_budget(AllVertexQuery, budget).await {
+            Err(crate::ClientError::Grpc { inner }) => assert_eq!(inner.code(), Code::ResourceExhausted),
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        let budget = QueryBudget {
+            timeout: Some(Duration::ZERO),
+            ..QueryBudget::default()
+        };
+        match client.get_with_budget(AllVertexQuery, budget).await {
+            Err(crate::ClientError::Grpc { inner }) => assert_eq!(inner.code(), Code::DeadlineExceeded),
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        let budget = QueryBudget {
+            max_rows: Some(2),
+            ..QueryBudget::default()
+        };
+        let output = client.get_with_budget(AllVertexQuery, budget).await.unwrap();
+        assert_eq!(util::extract_vertices(output).unwrap().len(), 2);
+    });
+}
+
+#[test]
+fn should_get_text_queries() {
+    use tonic::Code;
+
+    let rt = Runtime::new().unwrap();
+    let port = start_server(&rt);
+    let endpoint: Endpoint = format!("http://127.0.0.1:{port}").try_into().unwrap();
+
+    rt.block_on(async move {
+        let mut client = crate::Client::new(endpoint).await.unwrap();
+        let t = Identifier::new("test_vertex_type").unwrap();
+        for _ in 0..3 {
+            client.create_vertex(&Vertex::new(t)).await.unwrap();
+        }
+
+        let output = client.get_text("V(type=test_vertex_type).limit(2)").await.unwrap();
+        assert_eq!(util::extract_vertices(output).unwrap().len(), 2);
+        let output = client.get_text("V().include().count()").await.unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[1], QueryOutputValue::Count(3));
+
+        match client.get_text("V().count().out()").await {
+            Err(crate::ClientError::Grpc { inner }) => {
+                assert_eq!(inner.code(), Code::InvalidArgument);
+                assert!(inner.message().ends_with("at 12..17"), "{}", inner.message());
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+    });
+}
+
+#[test]
+fn should_not_block_writes_on_unread_output() {
+    let rt = Runtime::new().unwrap();
+    let port = start_server(&rt);
+    let endpoint: Endpoint = format!("http://127.0.0.1:{port}").try_into().unwrap();
+
+    rt.block_on(async move {
+        let mut client = crate::Client::new(endpoint.clone()).await.unwrap();
+        // Enough vertices, with long enough types, to fill the server's output
+        // channel and the transport's buffers
+        let t = Identifier::new("t".repeat(255)).unwrap();
+        let items = (0..150_000).map(|_| BulkInsertItem::Vertex(Vertex::new(t))).collect();
+        client.bulk_insert(items).await.unwrap();
+
+        // The output is never read
+        let mut proto_client = crate::ProtoClient::connect(endpoint).await.unwrap();
+        let q: crate::Query = Query::AllVertex.into();
+        let _output = proto_client.get(q).await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+
+        let vertex = Vertex::new(t);
+        let created = tokio::time::timeout(Duration::from_secs(10), client.create_vertex(&vertex)).await;
+        assert!(created.unwrap().unwrap());
+    });
+}
+
+#[test]
+fn should_send_all_output_to_slow_clients() {
+    let rt = Runtime::new().unwrap();
+    let port = start_server(&rt);
+    let endpoint: Endpoint = format!("http://127.0.0.1:{port}").try_into().unwrap();
+
+    rt.block_on(async move {
+        let mut client = crate::Client::new(endpoint.clone()).await.unwrap();
+        // Enough vertices to fill the server's output channel
+        let t = Identifier::new("test_vertex_type").unwrap();
+        let items = (0..150_000).map(|_| BulkInsertItem::Vertex(Vertex::new(t))).collect();
+        client.bulk_insert(items).await.unwrap();
+
+        // The output isn't read until the server has fallen behind
+        let mut proto_client = crate::ProtoClient::connect(endpoint).await.unwrap();
+        let q: crate::Query = Query::AllVertex.into();
+        let mut output = proto_client.get(q).await.unwrap().into_inner();
+        sleep(Duration::from_millis(500)).await;
+
+        let mut ids = Vec::new();
+        while let Some(value) = output.message().await.unwrap() {
+            match value.try_into().unwrap() {
+                QueryOutputValue::Vertices(vertices) => ids.extend(vertices.into_iter().map(|v| v.id)),
+                value => panic!("unexpected output: {value:?}"),
+            }
+        }
+        assert_eq!(ids.len(), 150_000);
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    });
+}
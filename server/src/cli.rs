@@ -1,10 +1,12 @@
-use clap::{value_t, App, Arg, SubCommand};
+use clap::{value_t, App, Arg, ArgMatches, SubCommand};
 use std::ffi::OsString;
+use std::time::Duration;
 
 pub struct CliArgs {
     pub addr: String,
     pub datastore_args: CliDatastoreArgs,
     pub plugin_path: Option<String>,
+    pub query_budget: indradb::QueryBudget,
 }
 
 pub enum CliDatastoreArgs {
@@ -20,11 +22,22 @@ pub enum CliDatastoreArgs {
 
 const ADDRESS: &str = "ADDRESS";
 const PLUGIN_PATH: &str = "PLUGIN_PATH";
+const QUERY_TIMEOUT_MS: &str = "QUERY_TIMEOUT_MS";
+const QUERY_MAX_ROWS: &str = "QUERY_MAX_ROWS";
+const QUERY_MAX_EDGES_SCANNED: &str = "QUERY_MAX_EDGES_SCANNED";
 const DATABASE_PATH: &str = "DATABASE_PATH";
 const ROCKSDB_MAX_OPEN_FILES: &str = "ROCKSDB_MAX_OPEN_FILES";
 const ROCKSDB_REPAIR: &str = "ROCKSDB_REPAIR";
 const MEMORY_PERSIST_PATH: &str = "MEMORY_PERSIST_PATH";
 
+fn optional_u64(matches: &ArgMatches, name: &str) -> Option<u64> {
+    if matches.is_present(name) {
+        Some(value_t!(matches, name, u64).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    }
+}
+
 pub fn parse_cli_args() -> CliArgs {
     let database_path_argument = Arg::with_name(DATABASE_PATH)
         .help("Database url")
@@ -46,6 +59,24 @@ pub fn parse_cli_args() -> CliArgs {
         .help("Path to plugins")
         .takes_value(true);
 
+    let query_timeout_ms = Arg::with_name(QUERY_TIMEOUT_MS)
+        .long("query-timeout-ms")
+        .value_name(QUERY_TIMEOUT_MS)
+        .help("The default query timeout in milliseconds. Clients can override this per request.")
+        .takes_value(true);
+
+    let query_max_rows = Arg::with_name(QUERY_MAX_ROWS)
+        .long("query-max-rows")
+        .value_name(QUERY_MAX_ROWS)
+        .help("The default maximum number of intermediate rows a query can output. Clients can override this per request.")
+        .takes_value(true);
+
+    let query_max_edges_scanned = Arg::with_name(QUERY_MAX_EDGES_SCANNED)
+        .long("query-max-edges-scanned")
+        .value_name(QUERY_MAX_EDGES_SCANNED)
+        .help("The default maximum number of edges a query can scan. Clients can override this per request.")
+        .takes_value(true);
+
     let memory_subcommand = SubCommand::with_name("memory")
         .about("Start an indradb instance backed by memory. This is the default, so including this subcommand is only useful if you want to set options.")
         .arg(
@@ -78,6 +109,9 @@ pub fn parse_cli_args() -> CliArgs {
     let matches = App::new("indradb-server")
         .arg(&addr)
         .arg(&plugin_path)
+        .arg(&query_timeout_ms)
+        .arg(&query_max_rows)
+        .arg(&query_max_edges_scanned)
         .subcommand(memory_subcommand)
         .subcommand(rocksdb_subcommand)
         .get_matches();
@@ -102,5 +136,10 @@ pub fn parse_cli_args() -> CliArgs {
             CliDatastoreArgs::Memory { path: None }
         },
         plugin_path: matches.value_of(PLUGIN_PATH).map(|s| s.to_string()),
+        query_budget: indradb::QueryBudget {
+            timeout: optional_u64(&matches, QUERY_TIMEOUT_MS).map(Duration::from_millis),
+            max_rows: optional_u64(&matches, QUERY_MAX_ROWS),
+            max_edges_scanned: optional_u64(&matches, QUERY_MAX_EDGES_SCANNED),
+        },
     }
 }
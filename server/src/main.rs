@@ -13,13 +13,15 @@ use indradb_proto as proto;
 use tokio::net::TcpListener;
 
 async fn run_server<D>(
-    datastore: indradb::Database<D>,
+    mut datastore: indradb::Database<D>,
     listener: TcpListener,
     plugin_path: &Option<String>,
+    query_budget: indradb::QueryBudget,
 ) -> Result<(), Box<dyn Error>>
 where
     D: indradb::Datastore + Send + Sync + 'static,
 {
+    datastore.set_query_budget(query_budget);
     let binding = listener.local_addr()?;
     println!("grpc://{binding}");
 
@@ -59,15 +61,22 @@ pub async fn main() -> Result<(), Box<dyn Error>> {
                 &indradb::RocksdbDatastore::get_options(Some(max_open_files)),
             )
             .expect("Expected to be able to create the RocksDB datastore");
-            run_server(datastore, listener, &args.plugin_path).await
+            run_server(datastore, listener, &args.plugin_path, args.query_budget).await
         }
         CliDatastoreArgs::Memory { path } => {
             let datastore = match path {
                 None => indradb::MemoryDatastore::new_db(),
                 Some(path) if Path::new(path.as_os_str()).exists() => indradb::MemoryDatastore::read_msgpack_db(path)?,
-                Some(path) => indradb::MemoryDatastore::create_msgpack_db(path),
+                Some(path) => {
+                    // An image is written right away, so that a restart reads
+                    // it back and replays the write-ahead log on top of it,
+                    // even if the datastore was never synced.
+                    let datastore = indradb::MemoryDatastore::create_msgpack_db(path);
+                    datastore.sync()?;
+                    datastore
+                }
             };
-            run_server(datastore, listener, &args.plugin_path).await
+            run_server(datastore, listener, &args.plugin_path, args.query_budget).await
         }
     }
 }